@@ -39,7 +39,7 @@ fn main() -> std::io::Result<()> {
     use windancer::export::ssg::StaticSiteGenerator;
     let start = Instant::now();
     let mut ssg = StaticSiteGenerator::default();
-    let html = ssg.generate_html(f_org);
+    let html = ssg.generate_html(f_org).expect("generate html");
     let _ = fs::write("tests/windancer_output.html", format!("{}", html));
     let duration = start.elapsed();
     tracing::info!("windancer:               : {:?}", duration);
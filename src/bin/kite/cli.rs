@@ -4,7 +4,7 @@ use clap::{Parser, Subcommand};
 use clap_complete::Shell;
 
 #[derive(Parser)]
-#[command(version, author, about)]
+#[command(name = "kite", version, author, about)]
 pub struct Cli {
     /// Root directory of project
     #[arg(short = 'r', long, default_value = ".")]
@@ -39,6 +39,32 @@ pub enum Command {
         port: u16,
     },
 
+    /// Format org files in place
+    Fmt {
+        /// Check only, list unformatted files and exit with error if any
+        #[arg(long)]
+        check: bool,
+
+        /// Files or directories to format, default to input directory of project
+        paths: Vec<PathBuf>,
+    },
+
+    /// Evaluate src blocks by interpreters of `[compiler.babel]`, write results back in place
+    Eval {
+        /// Files or directories to evaluate, default to input directory of project
+        paths: Vec<PathBuf>,
+    },
+
+    /// Write src blocks with `:tangle` to files
+    Tangle {
+        /// List files to be written without writing them
+        #[arg(long)]
+        dry_run: bool,
+
+        /// Files or directories to tangle, default to input directory of project
+        paths: Vec<PathBuf>,
+    },
+
     /// Generate shell completion
     Completion {
         /// Shell to generate completion for
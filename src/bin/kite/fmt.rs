@@ -0,0 +1,48 @@
+//! `kite fmt`: format org files in place
+use std::fs;
+use std::path::PathBuf;
+
+use walkdir::WalkDir;
+use windancer::export::org::formatter::OrgFormatter;
+
+/// Format org files under `paths`, return `false` if some file is not formatted (`check`) or
+/// can't be formatted.
+///
+/// With `check`, files are not written, unformatted files are listed instead.
+pub fn fmt(formatter: &OrgFormatter, paths: &[PathBuf], check: bool) -> std::io::Result<bool> {
+    let mut ok = true;
+    for path in paths {
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = entry?;
+            let f_org = entry.path();
+            if !entry.file_type().is_file() || f_org.extension().is_none_or(|e| e != "org") {
+                continue;
+            }
+
+            let input = fs::read_to_string(f_org)?;
+            let formatted = match formatter.format(f_org, &input) {
+                Ok(formatted) => formatted,
+                Err(diagnostics) => {
+                    for diagnostic in diagnostics {
+                        eprintln!("{diagnostic}\n");
+                    }
+                    eprintln!("skip {}: can't be parsed completely", f_org.display());
+                    ok = false;
+                    continue;
+                }
+            };
+            if formatted == input {
+                continue;
+            }
+
+            if check {
+                println!("{}", f_org.display());
+                ok = false;
+            } else {
+                fs::write(f_org, formatted)?;
+                tracing::info!("formatted {}", f_org.display());
+            }
+        }
+    }
+    Ok(ok)
+}
@@ -0,0 +1,60 @@
+//! `kite init`: scaffold a new project
+use std::fs;
+use std::path::Path;
+
+const CONFIG_TOML: &str = r#"[general]
+input_directory = "content"
+tracing_max_level = "info"
+
+[compiler]
+debug = false
+
+[compiler.parser]
+org_use_sub_superscripts = "brace"
+
+[compiler.parser.org_todo_keywords]
+requiring_action = ["TODO"]
+no_further_action = ["DONE"]
+
+[ssg]
+output_directory = "public"
+cache_file = ".cache/windancer.bin"
+
+[ssg.site]
+
+[ssg.renderer]
+highlight_theme = "github-light"
+math = "mathjax"
+"#;
+
+const CONTENT_ORG: &str = r#"#+TITLE: Home
+
+* Welcome
+Edit =content/content.org= and run =kite build=.
+"#;
+
+/// Create `content/`, `templates/` and `config.toml` in `d_project`.
+///
+/// Existing files are kept untouched.
+pub fn init<P: AsRef<Path>>(d_project: P) -> std::io::Result<()> {
+    let d_project = d_project.as_ref();
+    tracing::info!("init project in {}", d_project.display());
+
+    fs::create_dir_all(d_project.join("content"))?;
+    fs::create_dir_all(d_project.join("templates"))?;
+
+    write_if_absent(&d_project.join("config.toml"), CONFIG_TOML)?;
+    // content/content.org is the index page of site
+    write_if_absent(&d_project.join("content").join("content.org"), CONTENT_ORG)?;
+
+    Ok(())
+}
+
+fn write_if_absent(path: &Path, content: &str) -> std::io::Result<()> {
+    if path.exists() {
+        tracing::warn!("  {} exists, skipped", path.display());
+        return Ok(());
+    }
+    tracing::info!("  create {}", path.display());
+    fs::write(path, content)
+}
@@ -1,21 +1,150 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
-use cli::Cli;
+use clap::{CommandFactory, Parser};
+use cli::{Cli, Command};
+use tracing_subscriber::FmtSubscriber;
+use windancer::compiler::babel::Evaluator;
+use windancer::config::WindancerConfig;
+use windancer::export::org::formatter::OrgFormatter;
+use windancer::export::ssg::StaticSiteGenerator;
+use windancer::export::tangle::Tangler;
 
 mod cli;
+mod fmt;
+mod init;
+mod serve;
+mod tangle;
+
+fn load_config(f_config: &Path) -> WindancerConfig {
+    WindancerConfig::from_file(f_config).unwrap_or_else(|e| {
+        eprintln!("Could not load config {}: {}", f_config.display(), e);
+        std::process::exit(1);
+    })
+}
+
+fn set_tracing(max_level: tracing::Level) {
+    let subscriber = FmtSubscriber::builder().with_max_level(max_level).finish();
+    tracing::subscriber::set_global_default(subscriber).expect("set global subscripber failed");
+}
+
+fn build(config: WindancerConfig) -> std::io::Result<()> {
+    let input_directory = config.general.input_directory.clone();
+    let mut ssg = StaticSiteGenerator::new(config.compiler, config.ssg);
+    for diagnostic in ssg.generate(input_directory)? {
+        eprintln!("{diagnostic}\n");
+    }
+    Ok(())
+}
 
 fn main() {
     let cli = Cli::parse();
 
+    if let Command::Completion { shell } = cli.command {
+        let mut cmd = Cli::command();
+        let name = cmd.get_name().to_string();
+        clap_complete::generate(shell, &mut cmd, name, &mut std::io::stdout());
+        return;
+    }
+
+    if let Command::Init { name } = &cli.command {
+        set_tracing(tracing::Level::INFO);
+        let d_project = cli.root.join(name);
+        if let Err(e) = init::init(&d_project) {
+            tracing::error!("init {} failed: {}", d_project.display(), e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
     let cli_dir: PathBuf = cli.root.canonicalize().unwrap_or_else(|e| {
-        tracing::error!(
+        eprintln!(
             "Could not find canonical path of root dir: {}",
             cli.root.display()
         );
-        tracing::error!("{}", e);
+        eprintln!("{}", e);
         std::process::exit(1);
     });
 
+    // paths in config are relative to root dir, while paths in command line are relative to cwd
+    let cwd = std::env::current_dir().expect("current dir");
+    std::env::set_current_dir(&cli_dir).expect("change directory to root");
+    let mut config = load_config(&cli.config);
+    set_tracing(config.tracing_max_level());
     tracing::debug!("cli_dir={}", cli_dir.display());
+    tracing::debug!("config={:#?}", config);
+
+    match cli.command {
+        Command::Build { output_dir } => {
+            if let Some(output_dir) = output_dir {
+                config.update_output_directory(cwd.join(output_dir));
+            }
+            if let Err(e) = build(config) {
+                tracing::error!("build failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Serve { port } => {
+            if let Err(e) = serve::serve(&cli.config, port) {
+                tracing::error!("serve failed: {}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Fmt { check, paths } => {
+            let paths = if paths.is_empty() {
+                vec![config.general.input_directory.clone()]
+            } else {
+                paths.iter().map(|e| cwd.join(e)).collect()
+            };
+            let formatter = OrgFormatter::new(config.compiler.parser().clone());
+            match fmt::fmt(&formatter, &paths, check) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    tracing::error!("fmt failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Eval { paths } => {
+            let paths = if paths.is_empty() {
+                vec![config.general.input_directory.clone()]
+            } else {
+                paths.iter().map(|e| cwd.join(e)).collect()
+            };
+            let evaluator = Evaluator::new(
+                config.compiler.babel().clone(),
+                config.compiler.parser().clone(),
+            );
+            for path in paths.iter() {
+                match evaluator.evaluate_path(path) {
+                    Ok(diagnostics) => {
+                        for diagnostic in diagnostics {
+                            eprintln!("{diagnostic}\n");
+                        }
+                    }
+                    Err(e) => {
+                        tracing::error!("eval failed: {}", e);
+                        std::process::exit(1);
+                    }
+                }
+            }
+        }
+        Command::Tangle { dry_run, paths } => {
+            let paths = if paths.is_empty() {
+                vec![config.general.input_directory.clone()]
+            } else {
+                paths.iter().map(|e| cwd.join(e)).collect()
+            };
+            let tangler = Tangler::new(config.compiler.parser().clone());
+            match tangle::tangle(&tangler, &paths, dry_run) {
+                Ok(true) => {}
+                Ok(false) => std::process::exit(1),
+                Err(e) => {
+                    tracing::error!("tangle failed: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Command::Init { .. } | Command::Completion { .. } => unreachable!(),
+    }
 }
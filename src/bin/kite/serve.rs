@@ -0,0 +1,296 @@
+//! `kite serve`: local http server with live reload
+//!
+//! - serve files in `ssg.output_directory`
+//! - watch `content/`, `templates/`, `static/` and config file, rebuild site on change
+//! - push reload to browsers via server-sent events at [`LIVE_RELOAD_PATH`], the endpoint is
+//!   injected into pages by `RendererConfig::live_reload`
+//!
+//! Only std is used (no network access required): one thread per connection, polling for
+//! file changes.
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::panic::AssertUnwindSafe;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use walkdir::WalkDir;
+use windancer::config::WindancerConfig;
+use windancer::export::ssg::StaticSiteGenerator;
+
+/// server-sent events endpoint
+pub const LIVE_RELOAD_PATH: &str = "/__kite/live_reload";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(300);
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(15);
+
+/// senders of connected browsers
+type Clients = Arc<Mutex<Vec<Sender<()>>>>;
+
+pub fn serve(f_config: &Path, port: u16) -> io::Result<()> {
+    let config = WindancerConfig::from_file(f_config).map_err(io::Error::other)?;
+    let d_output = config.ssg.output_directory.clone();
+    let d_input = config.general.input_directory.clone();
+    let d_root = d_input.parent().unwrap_or(Path::new("")).to_path_buf();
+    let watched = vec![
+        d_input,
+        d_root.join("templates"),
+        d_root.join("static"),
+        f_config.to_path_buf(),
+    ];
+
+    if let Err(e) = build(f_config) {
+        tracing::error!("build failed: {}", e);
+    }
+
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    tracing::info!(
+        "serving {} at http://127.0.0.1:{}, Ctrl+C to stop",
+        d_output.display(),
+        port
+    );
+
+    let clients: Clients = Arc::new(Mutex::new(vec![]));
+    {
+        let clients = Arc::clone(&clients);
+        let f_config = f_config.to_path_buf();
+        thread::spawn(move || watch(&f_config, &watched, &clients));
+    }
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(e) => {
+                tracing::warn!("accept failed: {}", e);
+                continue;
+            }
+        };
+        let clients = Arc::clone(&clients);
+        let d_output = d_output.clone();
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &d_output, &clients) {
+                tracing::debug!("connection closed: {}", e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Reload config and rebuild site, a panic during build does not stop the server
+fn build(f_config: &Path) -> Result<(), String> {
+    let mut config = WindancerConfig::from_file(f_config).map_err(|e| e.to_string())?;
+    config.ssg.renderer.live_reload = Some(LIVE_RELOAD_PATH.to_string());
+    let d_input = config.general.input_directory.clone();
+    // rewriting results of src blocks whose code is not changed would trigger rebuild again
+    let mut ssg =
+        StaticSiteGenerator::new(config.compiler, config.ssg).with_babel_keep_results(true);
+
+    match std::panic::catch_unwind(AssertUnwindSafe(|| ssg.rebuild(&d_input))) {
+        Ok(Ok(diagnostics)) => {
+            for diagnostic in diagnostics {
+                eprintln!("{diagnostic}\n");
+            }
+            Ok(())
+        }
+        Ok(Err(e)) => Err(e.to_string()),
+        Err(_) => Err("panicked".to_string()),
+    }
+}
+
+fn snapshot(watched: &[PathBuf]) -> HashMap<PathBuf, SystemTime> {
+    let mut mtimes = HashMap::new();
+    for path in watched {
+        for entry in WalkDir::new(path).into_iter().filter_map(|e| e.ok()) {
+            // skip hidden files and editor backups, such as `.#foo.org` or `#foo.org#`
+            let file_name = entry.file_name().to_string_lossy();
+            if file_name.starts_with(['.', '#']) || file_name.ends_with('~') {
+                continue;
+            }
+            if let Ok(metadata) = entry.metadata() {
+                if metadata.is_file() {
+                    let mtime = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                    mtimes.insert(entry.path().to_path_buf(), mtime);
+                }
+            }
+        }
+    }
+    mtimes
+}
+
+fn watch(f_config: &Path, watched: &[PathBuf], clients: &Clients) {
+    let mut prev = snapshot(watched);
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        let mut current = snapshot(watched);
+        if current == prev {
+            continue;
+        }
+
+        // wait until editor finished writing
+        loop {
+            thread::sleep(POLL_INTERVAL);
+            let next = snapshot(watched);
+            if next == current {
+                break;
+            }
+            current = next;
+        }
+        prev = current;
+
+        tracing::info!("change detected, rebuild ...");
+        match build(f_config) {
+            Ok(()) => {
+                let mut clients = clients.lock().expect("lock clients");
+                clients.retain(|tx| tx.send(()).is_ok());
+                tracing::info!("reload {} page(s)", clients.len());
+            }
+            Err(e) => tracing::error!("rebuild failed: {}", e),
+        }
+    }
+}
+
+fn handle(mut stream: TcpStream, d_output: &Path, clients: &Clients) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or("");
+    let target = parts.next().unwrap_or("/");
+    tracing::debug!("{} {}", method, target);
+    if method != "GET" && method != "HEAD" {
+        return respond(
+            &mut stream,
+            "405 Method Not Allowed",
+            "text/plain",
+            b"",
+            true,
+        );
+    }
+
+    let path = target.split(['?', '#']).next().unwrap_or("/");
+    if path == LIVE_RELOAD_PATH {
+        return live_reload(stream, clients);
+    }
+
+    let with_body = method == "GET";
+    match resolve(d_output, &percent_decode(path)) {
+        Some(f) => {
+            let body = fs::read(&f)?;
+            respond(&mut stream, "200 OK", mime_type(&f), &body, with_body)
+        }
+        None => respond(
+            &mut stream,
+            "404 Not Found",
+            "text/plain; charset=utf-8",
+            format!("{path} not found").as_bytes(),
+            with_body,
+        ),
+    }
+}
+
+fn respond(
+    stream: &mut TcpStream,
+    status: &str,
+    content_type: &str,
+    body: &[u8],
+    with_body: bool,
+) -> io::Result<()> {
+    let header = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\nCache-Control: no-cache\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+    stream.write_all(header.as_bytes())?;
+    if with_body {
+        stream.write_all(body)?;
+    }
+    stream.flush()
+}
+
+/// keep the connection open, send `reload` event on rebuild
+fn live_reload(mut stream: TcpStream, clients: &Clients) -> io::Result<()> {
+    stream.write_all(
+        b"HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n",
+    )?;
+    stream.flush()?;
+
+    let (tx, rx) = mpsc::channel();
+    clients.lock().expect("lock clients").push(tx);
+    loop {
+        match rx.recv_timeout(HEARTBEAT_INTERVAL) {
+            Ok(()) => stream.write_all(b"data: reload\n\n")?,
+            // comment line, detects closed connections
+            Err(RecvTimeoutError::Timeout) => stream.write_all(b": ping\n\n")?,
+            Err(RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+        stream.flush()?;
+    }
+}
+
+/// url path -> file in output directory, `/foo/` -> `foo/index.html`
+fn resolve(d_output: &Path, path: &str) -> Option<PathBuf> {
+    let mut f = d_output.to_path_buf();
+    for component in path.split('/') {
+        match component {
+            "" | "." => {}
+            ".." => return None,
+            c => f.push(c),
+        }
+    }
+    if f.is_dir() {
+        f.push("index.html");
+    }
+    f.is_file().then_some(f)
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                decoded.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+fn mime_type(f: &Path) -> &'static str {
+    match f.extension().and_then(|e| e.to_str()).unwrap_or("") {
+        "html" | "htm" => "text/html; charset=utf-8",
+        "css" => "text/css; charset=utf-8",
+        "js" => "text/javascript; charset=utf-8",
+        "json" => "application/json",
+        "xml" => "application/xml",
+        "ics" => "text/calendar; charset=utf-8",
+        "txt" | "org" => "text/plain; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "webp" => "image/webp",
+        "ico" => "image/x-icon",
+        "pdf" => "application/pdf",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,39 @@
+//! `kite tangle`: write src blocks with `:tangle` to files
+use std::path::PathBuf;
+
+use windancer::export::tangle::Tangler;
+
+/// Tangle org files under `paths`, return `false` if some src block can't be tangled.
+///
+/// With `dry_run`, files are not written, files to be written are listed instead.
+pub fn tangle(tangler: &Tangler, paths: &[PathBuf], dry_run: bool) -> std::io::Result<bool> {
+    let mut ok = true;
+    for path in paths {
+        let (files, diagnostics) = tangler.tangle_path(path)?;
+        for diagnostic in diagnostics.iter() {
+            eprintln!("{diagnostic}\n");
+        }
+        ok &= diagnostics.is_empty();
+
+        for file in files {
+            if dry_run {
+                println!(
+                    "{} ({} blocks from {})",
+                    file.path.display(),
+                    file.n_blocks,
+                    file.f_org.display()
+                );
+            } else {
+                match file.write() {
+                    Ok(true) => tracing::info!("tangled {}", file.path.display()),
+                    Ok(false) => {}
+                    Err(e) => {
+                        tracing::error!("failed to write {}: {}", file.path.display(), e);
+                        ok = false;
+                    }
+                }
+            }
+        }
+    }
+    Ok(ok)
+}
@@ -1,23 +1,43 @@
 //! Compile the raw org file to `Document' containing AST, file_info and meta_data
 //! - one org file --parser--> GreenNode --SyntaxNode::new_root()--> SyntaxNode --ast_builder--> AST
 //! - one directory --> Section
+pub mod agenda;
 pub mod ast_builder;
+pub mod babel;
+pub mod bibliography;
+pub mod cache;
+pub mod clock;
 pub mod content;
+pub mod diagnostic;
+pub mod dynamic_block;
+pub mod link_resolver;
+pub mod math;
 pub mod org_roam;
 pub mod parser;
+pub mod spreadsheet;
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::ffi::OsStr;
 use std::fs;
-use std::path::Path;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
 
+use chrono::{DateTime, Local};
 use parser::object;
-use rowan::WalkEvent;
+use rayon::prelude::*;
+use rowan::{GreenNode, WalkEvent};
 use serde::Deserialize;
 use walkdir::WalkDir;
 
 use crate::compiler::ast_builder::AstBuilder;
+use crate::compiler::ast_builder::element::OrgFile;
+use crate::compiler::ast_builder::timestamp::Timestamp;
+use crate::compiler::babel::BabelConfig;
+use crate::compiler::bibliography::{BibEntry, Bibliography, CitationStyle};
+use crate::compiler::cache::CompileCache;
 use crate::compiler::content::{Document, DocumentMetadata, FileInfo, Section, SectionMetadata};
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::math::Equations;
 use crate::compiler::parser::config::{OrgParserConfig, OrgUseSubSuperscripts};
 use crate::compiler::parser::syntax::{OrgSyntaxKind, SyntaxNode};
 use crate::compiler::parser::{OrgParser, get_text};
@@ -26,60 +46,158 @@ pub struct Compiler {
     parser: OrgParser,
     ast_builder: AstBuilder,
     debug: bool,
+    /// report LaTeX math which can't be converted to MathML
+    check_math: bool,
+    /// number equations automatically, `\ref` to labels of unnumbered equations are undefined
+    equation_numbering: bool,
 }
 
-#[derive(Debug, Deserialize, Default)]
+#[derive(Debug, Deserialize)]
 #[serde(default)]
 pub struct CompilerConfig {
     parser: OrgParserConfig,
     debug: bool,
+    babel: BabelConfig,
+    /// recalculate tables with `#+TBLFM:` before rendering
+    recalc_tables: bool,
+    /// number equations automatically when checking math, given by the renderer
+    #[serde(skip)]
+    equation_numbering: bool,
+}
+
+impl Default for CompilerConfig {
+    fn default() -> Self {
+        Self {
+            parser: OrgParserConfig::default(),
+            debug: false,
+            babel: BabelConfig::default(),
+            recalc_tables: false,
+            equation_numbering: true,
+        }
+    }
+}
+
+impl CompilerConfig {
+    pub fn parser(&self) -> &OrgParserConfig {
+        &self.parser
+    }
+
+    pub fn babel(&self) -> &BabelConfig {
+        &self.babel
+    }
+
+    pub fn recalc_tables(&self) -> bool {
+        self.recalc_tables
+    }
+
+    /// Resolve relative paths against project root `d_root`, see [`BabelConfig::resolve_paths`]
+    pub(crate) fn resolve_paths(&mut self, d_root: &Path) {
+        self.babel.resolve_paths(d_root);
+    }
+
+    pub fn with_equation_numbering(mut self, equation_numbering: bool) -> Self {
+        self.equation_numbering = equation_numbering;
+        self
+    }
 }
 
 impl Compiler {
     pub fn new(config: CompilerConfig) -> Self {
         // let config =
         //     OrgParserConfig::default().with_use_sub_superscripts(OrgUseSubSuperscripts::Brace);
+        let ast_builder =
+            AstBuilder::new().with_link_abbrevs(config.parser.org_link_abbrev_alist.clone());
         let parser = OrgParser::new(config.parser);
         let debug = config.debug;
-        let ast_builder = AstBuilder::new();
         Self {
             parser,
             ast_builder,
             debug,
+            check_math: false,
+            equation_numbering: config.equation_numbering,
         }
     }
 
+    pub fn with_check_math(mut self, check_math: bool) -> Self {
+        self.check_math = check_math;
+        self
+    }
+
     /// Compile `f_org` into `Document`
     pub fn compile_file<P: AsRef<Path>>(
         &self,
         f_org: P,
     ) -> Result<Document, Box<dyn std::error::Error>> {
-        let f_org = f_org.as_ref();
-        let syntax_tree = self.parser.parse(f_org);
-        // tracing::trace!("syntax_tree:{:#?}", syntax_tree);
+        let compiled = self
+            .compile_file_inner(f_org.as_ref(), None)
+            .map_err(Self::into_error)?;
+        compiled.report();
+        Ok(compiled.into())
+    }
 
-        let ast = self.ast_builder.build(&syntax_tree, f_org).expect("build");
+    /// Compile `f_org`, restore it from `cache` if content is not changed.
+    ///
+    /// Only `Send` data is returned, so that files can be compiled in parallel. Diagnostics are
+    /// returned as error if the file can't be compiled, otherwise kept in `CompiledFile`.
+    fn compile_file_inner(
+        &self,
+        f_org: &Path,
+        cache: Option<&CompileCache>,
+    ) -> Result<CompiledFile, Vec<Diagnostic>> {
+        let content = fs::read(f_org)
+            .map_err(|e| vec![Diagnostic::error(f_org, format!("failed to read: {e}"))])?;
         let file_info = FileInfo::from(f_org);
-        let mut metadata = Self::get_metadata(&syntax_tree);
+        let hash = cache.map(|_| Self::hash_with_bibliography(f_org, &content));
+        if let (Some(cache), Some(hash)) = (cache, &hash)
+            && let Some((ast, metadata, diagnostics)) = cache.get(&file_info.full_path, hash)
+        {
+            tracing::trace!("compile_file@cached: {}", f_org.display());
+            return Ok(CompiledFile {
+                file_info,
+                metadata,
+                ast,
+                green: GreenNode::new(OrgSyntaxKind::Root.into(), vec![]),
+                hash: Some(hash.clone()),
+                cached: true,
+                diagnostics,
+            });
+        }
+
+        let mut diagnostics = vec![];
+        let Some(syntax_tree) = self.parser.parse(f_org, &mut diagnostics) else {
+            return Err(diagnostics);
+        };
+        // tracing::trace!("syntax_tree:{:#?}", syntax_tree);
+        let source = syntax_tree.text().to_string();
+
+        let mut ast = match self.ast_builder.build(&syntax_tree, f_org) {
+            Ok(ast) => ast,
+            Err(e) => {
+                let diagnostic = Diagnostic::error(f_org, e.to_string());
+                diagnostics.push(match e.position() {
+                    Some(range) => diagnostic.with_span(&source, range.start..range.end),
+                    None => diagnostic,
+                });
+                return Err(diagnostics);
+            }
+        };
+        ast.bibliography = Self::resolve_citations(&syntax_tree, f_org, &source, &mut diagnostics);
+        let mut metadata = Self::get_metadata(&syntax_tree, f_org, &mut diagnostics);
 
         // FIXME: property > keyword? remove keyword's date?
-        metadata.last_modified_ts = ast.properties.get("LAST_MODIFIED").map(|e| {
-            object::timestamp::FlexibleDateTimeParser::new()
-                .parse(e.as_str())
-                .expect("get ts")
+        metadata.last_modified_ts = ast.properties.get("LAST_MODIFIED").and_then(|e| {
+            let range = Self::node_property_range(&syntax_tree, "LAST_MODIFIED");
+            Self::parse_timestamp(e, f_org, &source, range, &mut diagnostics)
         });
-        metadata.created_ts = ast.properties.get("CREATED").map(|e| {
-            object::timestamp::FlexibleDateTimeParser::new()
-                .parse(e.as_str())
-                .expect("get ts")
+        metadata.created_ts = ast.properties.get("CREATED").and_then(|e| {
+            let range = Self::node_property_range(&syntax_tree, "CREATED");
+            Self::parse_timestamp(e, f_org, &source, range, &mut diagnostics)
         });
 
-        let doc = Document {
-            file_info,
-            ast,
-            metadata,
-            syntax_tree,
-        };
+        if self.check_math {
+            self.check_math(&syntax_tree, f_org, &source, &mut diagnostics);
+        }
+        Self::check_timestamps(&syntax_tree, f_org, &source, &mut diagnostics);
 
         if self.debug {
             let f_ast = f_org.parent().unwrap().join(
@@ -90,8 +208,13 @@ impl Compiler {
                     .to_string()
                     .replace(".org", "_ast.json"),
             );
-            fs::write(&f_ast, format!("{:#?}", doc.ast))?;
-            tracing::trace!("write f_ast: {}", f_ast.display());
+            match fs::write(&f_ast, format!("{:#?}", ast)) {
+                Ok(()) => tracing::trace!("write f_ast: {}", f_ast.display()),
+                Err(e) => diagnostics.push(Diagnostic::warning(
+                    f_org,
+                    format!("failed to write {}: {e}", f_ast.display()),
+                )),
+            }
 
             let f_syntax = f_org.parent().unwrap().join(
                 f_org
@@ -101,10 +224,250 @@ impl Compiler {
                     .to_string()
                     .replace(".org", "_syntax.json"),
             );
-            fs::write(&f_syntax, format!("{:#?}", doc.syntax_tree))?;
-            tracing::trace!("write to f_syntax: {}", f_syntax.display());
+            match fs::write(&f_syntax, format!("{:#?}", syntax_tree)) {
+                Ok(()) => tracing::trace!("write to f_syntax: {}", f_syntax.display()),
+                Err(e) => diagnostics.push(Diagnostic::warning(
+                    f_org,
+                    format!("failed to write {}: {e}", f_syntax.display()),
+                )),
+            }
+        }
+
+        Ok(CompiledFile {
+            file_info,
+            metadata,
+            ast,
+            green: syntax_tree.green().into_owned(),
+            hash,
+            cached: false,
+            diagnostics,
+        })
+    }
+
+    /// Parse timestamp of metadata, invalid timestamp is reported as warning and ignored
+    fn parse_timestamp(
+        value: &str,
+        f_org: &Path,
+        source: &str,
+        range: Option<rowan::TextRange>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<DateTime<Local>> {
+        match object::timestamp::FlexibleDateTimeParser::new().parse(value) {
+            Ok(ts) => Some(ts),
+            Err(_) => {
+                let diagnostic = Diagnostic::warning(f_org, format!("invalid timestamp `{value}`"));
+                diagnostics.push(match range {
+                    Some(range) => {
+                        diagnostic.with_span(source, range.start().into()..range.end().into())
+                    }
+                    None => diagnostic,
+                });
+                None
+            }
+        }
+    }
+
+    /// Hash of org file and its `#+bibliography:` files, so that changed `.bib` is detected
+    fn hash_with_bibliography(f_org: &Path, content: &[u8]) -> String {
+        let mut bytes = content.to_vec();
+        for f_bib in bibliography::bibliography_files(f_org, &String::from_utf8_lossy(content)) {
+            bytes.extend(fs::read(f_bib).unwrap_or_default());
+        }
+        CompileCache::hash(&bytes)
+    }
+
+    /// Load `#+bibliography:` files and resolve cited keys, `None` if there is no bibliography.
+    ///
+    /// Unreadable or invalid `.bib` and unknown keys are reported as warnings.
+    fn resolve_citations(
+        syntax_tree: &SyntaxNode,
+        f_org: &Path,
+        source: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<Bibliography> {
+        let mut entries: Vec<BibEntry> = vec![];
+        let mut style = CitationStyle::default();
+        let mut has_bibliography = false;
+        for node in syntax_tree
+            .descendants()
+            .filter(|e| e.kind() == OrgSyntaxKind::Keyword)
+        {
+            let child_text = |kind| {
+                node.children()
+                    .find(|e| e.kind() == kind)
+                    .map(|e| e.text().to_string())
+            };
+            let (Some(key), Some(value)) = (
+                child_text(OrgSyntaxKind::KeywordKey),
+                child_text(OrgSyntaxKind::KeywordValue),
+            ) else {
+                continue;
+            };
+            match key.to_ascii_uppercase().as_str() {
+                "CITE_EXPORT" => style = CitationStyle::from_cite_export(&value),
+                "BIBLIOGRAPHY" => {
+                    has_bibliography = true;
+                    let f_bib = f_org.parent().unwrap_or(Path::new("")).join(value.trim());
+                    let bib = match fs::read_to_string(&f_bib) {
+                        Ok(bib) => bib,
+                        Err(e) => {
+                            let range = node.text_range();
+                            diagnostics.push(
+                                Diagnostic::warning(
+                                    f_org,
+                                    format!("failed to read {}: {e}", f_bib.display()),
+                                )
+                                .with_span(source, range.start().into()..range.end().into()),
+                            );
+                            continue;
+                        }
+                    };
+                    match bibliography::parse_bibtex(&bib) {
+                        Ok(mut bib_entries) => entries.append(&mut bib_entries),
+                        Err(e) => diagnostics.push(
+                            Diagnostic::warning(&f_bib, format!("invalid bibtex: {}", e.message))
+                                .with_span(&bib, e.offset..e.offset + 1),
+                        ),
+                    }
+                }
+                _ => {}
+            }
         }
-        Ok(doc)
+        if !has_bibliography {
+            return None;
+        }
+
+        let mut cited: Vec<BibEntry> = vec![];
+        for token in syntax_tree
+            .descendants_with_tokens()
+            .filter_map(|e| e.into_token())
+            .filter(|e| e.kind() == OrgSyntaxKind::CitationReferenceKey)
+        {
+            let key = token.text();
+            let is_cited = |key: &str| cited.iter().any(|e| e.key == key);
+            if key == "*" {
+                let uncited = entries
+                    .iter()
+                    .filter(|e| !is_cited(&e.key))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                cited.extend(uncited);
+            } else if !is_cited(key) {
+                match entries.iter().find(|e| e.key == key) {
+                    Some(entry) => cited.push(entry.clone()),
+                    None => {
+                        let range = token.text_range();
+                        diagnostics.push(
+                            Diagnostic::warning(f_org, format!("unknown citation key `{key}`"))
+                                .with_span(source, range.start().into()..range.end().into()),
+                        );
+                    }
+                }
+            }
+        }
+        Some(Bibliography {
+            style,
+            entries: cited,
+        })
+    }
+
+    /// Report LaTeX math which can't be converted to MathML and `\ref` to undefined label
+    fn check_math(
+        &self,
+        syntax_tree: &SyntaxNode,
+        f_org: &Path,
+        source: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        let mut equations = Equations::new(self.equation_numbering);
+        let mut refs = vec![];
+        for node in syntax_tree.descendants() {
+            let start: usize = node.text_range().start().into();
+            let text = node.text().to_string();
+            let result = match node.kind() {
+                OrgSyntaxKind::LatexEnvironment => {
+                    math::environment_to_mathml(&text, &mut equations).map_err(|e| (0, e))
+                }
+                OrgSyntaxKind::LatexFragment => match math::split_fragment(&text) {
+                    Some((offset, content, display)) => {
+                        math::fragment_to_mathml(content, display).map_err(|e| (offset, e))
+                    }
+                    None => {
+                        if let Some((label, _)) = math::parse_ref(&text) {
+                            refs.push((label.to_string(), node.text_range()));
+                        }
+                        continue;
+                    }
+                },
+                _ => continue,
+            };
+            if let Err((offset, e)) = result {
+                let position = start + offset + e.offset;
+                let end = source[position..]
+                    .chars()
+                    .next()
+                    .map_or(position, |c| position + c.len_utf8());
+                diagnostics.push(
+                    Diagnostic::warning(f_org, format!("invalid math: {}", e.message))
+                        .with_span(source, position..end),
+                );
+            }
+        }
+
+        for (label, range) in refs {
+            if !equations.has_label(&label) {
+                diagnostics.push(
+                    Diagnostic::warning(f_org, format!("undefined label `{label}`"))
+                        .with_span(source, range.start().into()..range.end().into()),
+                );
+            }
+        }
+    }
+
+    /// Report timestamps of invalid date or time, such as `<2024-02-30 Fri>`, which are kept as text
+    fn check_timestamps(
+        syntax_tree: &SyntaxNode,
+        f_org: &Path,
+        source: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) {
+        for node in syntax_tree
+            .descendants()
+            .filter(|node| node.kind() == OrgSyntaxKind::Timestamp)
+        {
+            if Timestamp::from_syntax_node(&node).is_none() {
+                let range = node.text_range();
+                diagnostics.push(
+                    Diagnostic::warning(f_org, format!("invalid timestamp `{}`", node.text()))
+                        .with_span(source, range.start().into()..range.end().into()),
+                );
+            }
+        }
+    }
+
+    /// Range of node property `key` in the syntax tree
+    fn node_property_range(syntax_tree: &SyntaxNode, key: &str) -> Option<rowan::TextRange> {
+        let prefix = format!(":{key}:");
+        syntax_tree
+            .descendants()
+            .filter(|node| node.kind() == OrgSyntaxKind::NodeProperty)
+            .find(|node| {
+                get_text(node)
+                    .trim_start()
+                    .to_ascii_uppercase()
+                    .starts_with(&prefix)
+            })
+            .map(|node| node.text_range())
+    }
+
+    /// Render diagnostics into one error
+    fn into_error(diagnostics: Vec<Diagnostic>) -> Box<dyn std::error::Error> {
+        diagnostics
+            .iter()
+            .map(|d| d.to_string())
+            .collect::<Vec<_>>()
+            .join("\n\n")
+            .into()
     }
 
     // todo
@@ -114,8 +477,14 @@ impl Compiler {
     // // FIXME: keyword? in ast_builder? keywords/properties
     // 有一个SyntaxNode (rowan)
     // 如何收集RoamNode，构建一张基于RoamNode图, 并构建RoamNode的父子关系及ID引用关系。(a，a的儿子b,a的孙子c均是RoamNode, c引用了RoamNode x, a和x, b和x的关系该如何处理？)
-    fn get_metadata(syntax_tree: &SyntaxNode) -> DocumentMetadata {
+    fn get_metadata(
+        syntax_tree: &SyntaxNode,
+        f_org: &Path,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> DocumentMetadata {
         let mut keyword = std::collections::HashMap::<String, Vec<String>>::new();
+        // range of the last keyword value, for diagnostics
+        let mut keyword_range = HashMap::<String, rowan::TextRange>::new();
         let mut preorder = syntax_tree.preorder();
         while let Some(event) = preorder.next() {
             match event {
@@ -129,9 +498,10 @@ impl Compiler {
                             .collect::<String>()
                             .to_ascii_uppercase();
 
-                        let value = element
+                        let value_node = element
                             .first_child_by_kind(&|e| e == OrgSyntaxKind::KeywordValue)
-                            .expect("must have KeywordValue")
+                            .expect("must have KeywordValue");
+                        let value = value_node
                             .children_with_tokens()
                             .map(|e| {
                                 if let Some(node) = e.as_node() {
@@ -145,6 +515,7 @@ impl Compiler {
                             .to_string();
 
                         if (key != "MACRO") && (!value.is_empty()) {
+                            keyword_range.insert(key.clone(), value_node.text_range());
                             if keyword.contains_key(&key) {
                                 keyword.get_mut(&key).expect("has value").push(value);
                             } else {
@@ -180,19 +551,15 @@ impl Compiler {
                     .contains("NIL")
             })
             .unwrap_or(true);
-        let created_ts = keyword.remove("DATE").map(|e| e.join("")).map(|e| {
-            object::timestamp::FlexibleDateTimeParser::new()
-                .parse(e.as_str())
-                .expect("get ts")
-        });
-        let last_modified_ts = keyword
-            .remove("LAST_MODIFIED")
-            .map(|e| e.join(""))
-            .map(|e| {
-                object::timestamp::FlexibleDateTimeParser::new()
-                    .parse(e.as_str())
-                    .expect("get ts")
-            });
+        let source = syntax_tree.text().to_string();
+        let mut parse_keyword_ts = |key: &str| {
+            keyword.remove(key).map(|e| e.join("")).and_then(|e| {
+                let range = keyword_range.get(key).copied();
+                Self::parse_timestamp(e.as_str(), f_org, &source, range, diagnostics)
+            })
+        };
+        let created_ts = parse_keyword_ts("DATE");
+        let last_modified_ts = parse_keyword_ts("LAST_MODIFIED");
 
         DocumentMetadata {
             title,
@@ -218,14 +585,89 @@ impl Compiler {
         false
     }
 
+    /// Compile `f_org` into `Document`, restore it from `cache` if content is not changed
+    pub fn compile_file_cached<P: AsRef<Path>>(
+        &self,
+        f_org: P,
+        cache: &mut CompileCache,
+    ) -> Result<Document, Box<dyn std::error::Error>> {
+        let compiled = self
+            .compile_file_inner(f_org.as_ref(), Some(cache))
+            .map_err(Self::into_error)?;
+        compiled.update_cache(cache);
+        compiled.report();
+        Ok(compiled.into())
+    }
+
+    /// Fingerprint of config, compiled documents in cache are invalid if it changed
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "{};debug={};check_math={};equation_numbering={}",
+            self.parser.config.fingerprint(),
+            self.debug,
+            self.check_math,
+            self.equation_numbering
+        )
+    }
+
     pub fn compile_section<P: AsRef<Path>>(
         &self,
         d_org: P,
     ) -> Result<Section, Box<dyn std::error::Error>> {
-        let mut documents = vec![];
-        let mut subsections = vec![];
+        self.compile_section_inner(d_org, None)
+    }
 
-        let file_info = FileInfo::from(&d_org);
+    /// Compile directory `d_org` into `Section`, unchanged files are restored from `cache`
+    pub fn compile_section_cached<P: AsRef<Path>>(
+        &self,
+        d_org: P,
+        cache: &mut CompileCache,
+    ) -> Result<Section, Box<dyn std::error::Error>> {
+        self.compile_section_inner(d_org, Some(cache))
+    }
+
+    /// Scan directory tree first, then compile all org files in parallel.
+    ///
+    /// Files failed to compile are skipped, their diagnostics are kept in `Section::diagnostics`.
+    fn compile_section_inner<P: AsRef<Path>>(
+        &self,
+        d_org: P,
+        cache: Option<&mut CompileCache>,
+    ) -> Result<Section, Box<dyn std::error::Error>> {
+        let scanned = Self::scan_section(d_org.as_ref())?;
+        let mut files = vec![];
+        scanned.collect_files(&mut files);
+
+        let compiled = {
+            let cache = cache.as_deref();
+            files
+                .par_iter()
+                .map(|f_org| {
+                    // a panic in one file should not abort the whole section
+                    panic::catch_unwind(AssertUnwindSafe(|| self.compile_file_inner(f_org, cache)))
+                        .unwrap_or_else(|_| {
+                            Err(vec![Diagnostic::error(
+                                f_org,
+                                "internal error: compiler panicked",
+                            )])
+                        })
+                })
+                .collect::<Vec<_>>()
+        };
+
+        if let Some(cache) = cache {
+            for compiled_file in compiled.iter().flatten() {
+                compiled_file.update_cache(cache);
+            }
+        }
+
+        let mut compiled = files.into_iter().zip(compiled).collect::<HashMap<_, _>>();
+        Ok(Self::assemble_section(scanned, &mut compiled))
+    }
+
+    fn scan_section(d_org: &Path) -> std::io::Result<ScannedSection> {
+        let mut files = vec![];
+        let mut subsections = vec![];
 
         for entry in fs::read_dir(d_org)? {
             let entry = entry?;
@@ -236,13 +678,38 @@ impl Compiler {
             if path.is_dir() && (!filename.starts_with(&['.', '#'])) {
                 if Self::has_org_file(&path) {
                     tracing::debug!("compile_section@dir: {}", path.display());
-                    subsections.push(self.compile_section(path)?);
+                    subsections.push(Self::scan_section(&path)?);
                 }
             } else if path.extension() == Some(OsStr::new("org"))
                 && (!filename.starts_with(&['.', '#']))
             {
                 tracing::debug!("compile_section@org: {}", path.display());
-                documents.push(self.compile_file(path)?);
+                files.push(path);
+            }
+        }
+
+        Ok(ScannedSection {
+            directory: d_org.to_path_buf(),
+            files,
+            subsections,
+        })
+    }
+
+    fn assemble_section(
+        scanned: ScannedSection,
+        compiled: &mut HashMap<PathBuf, Result<CompiledFile, Vec<Diagnostic>>>,
+    ) -> Section {
+        let file_info = FileInfo::from(&scanned.directory);
+
+        let mut documents = vec![];
+        let mut diagnostics = vec![];
+        for f_org in scanned.files.iter() {
+            match compiled.remove(f_org).expect("compiled") {
+                Ok(mut compiled_file) => {
+                    diagnostics.append(&mut compiled_file.diagnostics);
+                    documents.push(Document::from(compiled_file));
+                }
+                Err(mut file_diagnostics) => diagnostics.append(&mut file_diagnostics),
             }
         }
 
@@ -250,12 +717,83 @@ impl Compiler {
         // the index page should be placed at first place
         documents.sort_by_key(|doc| -(doc.file_info.maybe_index as i32));
 
-        Ok(Section {
+        let subsections = scanned
+            .subsections
+            .into_iter()
+            .map(|subsection| Self::assemble_section(subsection, compiled))
+            .collect();
+
+        Section {
             file_info,
             documents,
             subsections,
             metadata: SectionMetadata::default(),
-        })
+            diagnostics,
+        }
+    }
+}
+
+/// Directory tree of org files, scanned before compiling
+struct ScannedSection {
+    directory: PathBuf,
+    files: Vec<PathBuf>,
+    subsections: Vec<ScannedSection>,
+}
+
+impl ScannedSection {
+    fn collect_files(&self, files: &mut Vec<PathBuf>) {
+        files.extend(self.files.iter().cloned());
+        for subsection in self.subsections.iter() {
+            subsection.collect_files(files);
+        }
+    }
+}
+
+/// Compiled file, which is `Send` unlike `Document` (the red tree `SyntaxNode` is not `Send`)
+struct CompiledFile {
+    file_info: FileInfo,
+    metadata: DocumentMetadata,
+    ast: OrgFile,
+    green: GreenNode,
+    // content hash, only computed if compiled with cache
+    hash: Option<String>,
+    // restored from cache
+    cached: bool,
+    // warnings
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl CompiledFile {
+    fn update_cache(&self, cache: &mut CompileCache) {
+        let full_path = &self.file_info.full_path;
+        if self.cached {
+            cache.visit(full_path);
+        } else if let Some(hash) = &self.hash {
+            cache.insert(
+                full_path,
+                hash,
+                &self.ast,
+                &self.metadata,
+                &self.diagnostics,
+            );
+        }
+    }
+
+    fn report(&self) {
+        for diagnostic in self.diagnostics.iter() {
+            tracing::warn!("\n{}", diagnostic);
+        }
+    }
+}
+
+impl From<CompiledFile> for Document {
+    fn from(compiled: CompiledFile) -> Self {
+        Self {
+            file_info: compiled.file_info,
+            metadata: compiled.metadata,
+            ast: compiled.ast,
+            syntax_tree: SyntaxNode::new_root(compiled.green),
+        }
     }
 }
 
@@ -269,15 +807,63 @@ impl Default for Compiler {
             parser,
             ast_builder,
             debug: false,
+            check_math: false,
+            equation_numbering: true,
         }
     }
 }
 
+/// Compile org text in tests of other modules, which is written to a temporary directory first
+#[cfg(test)]
+pub(crate) mod testing {
+    use std::path::Path;
+
+    use super::Compiler;
+    use crate::compiler::content::{Document, Section};
+
+    /// Run `f` on directory `content` containing file `name` of `input`, the directory is removed
+    /// after. Each test thread has its own directory, since tests run in parallel.
+    fn with_content<T>(name: &str, input: &str, f: impl FnOnce(&Path) -> T) -> T {
+        let d_tmp = std::env::temp_dir().join(format!(
+            "windancer_compile_{}_{:?}",
+            std::process::id(),
+            std::thread::current().id()
+        ));
+        let d_content = d_tmp.join("content");
+        std::fs::create_dir_all(&d_content).expect("create dir");
+        std::fs::write(d_content.join(name), input).expect("write");
+        let output = f(&d_content);
+        std::fs::remove_dir_all(&d_tmp).expect("remove");
+        output
+    }
+
+    /// `Document` of file `name` containing `input`
+    pub(crate) fn compile_document(name: &str, input: &str) -> Document {
+        with_content(name, input, |d_content| {
+            Compiler::default()
+                .compile_file(d_content.join(name))
+                .expect("no Document compiled")
+        })
+    }
+
+    /// `Section` of a single file `content.org` containing `input`
+    pub(crate) fn compile_section(input: &str) -> Section {
+        with_content("content.org", input, |d_content| {
+            Compiler::default()
+                .compile_section(d_content)
+                .expect("no Section compiled")
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use tracing_subscriber::FmtSubscriber;
 
-    use super::Compiler;
+    use super::{Compiler, CompilerConfig};
+    use crate::compiler::ast_builder::element::Id;
+    use crate::compiler::bibliography::CitationStyle;
+    use crate::compiler::cache::CompileCache;
 
     #[test]
     fn test_compile_file() {
@@ -307,4 +893,181 @@ mod tests {
             println!("{i}:\n  {:#?}\n", sub.metadata);
         }
     }
+
+    #[test]
+    fn test_compile_file_cached() {
+        let f_org = "tests/test.org";
+        let compiler = Compiler::default();
+        let mut cache = CompileCache::default();
+
+        let doc = compiler
+            .compile_file_cached(f_org, &mut cache)
+            .expect("no Document compiled");
+        let (changed, removed) = cache.finish();
+        assert_eq!(changed.len(), 1);
+        assert!(removed.is_empty());
+
+        let cached_doc = compiler
+            .compile_file_cached(f_org, &mut cache)
+            .expect("no Document compiled");
+        let (changed, removed) = cache.finish();
+        assert!(changed.is_empty());
+        assert!(removed.is_empty());
+        assert_eq!(doc.ast.id(), cached_doc.ast.id());
+
+        let (_, removed) = cache.finish();
+        assert_eq!(removed.len(), 1);
+    }
+
+    #[test]
+    fn test_compile_section_with_diagnostics() {
+        let d_org = std::env::temp_dir().join(format!("windancer_diag_{}", std::process::id()));
+        std::fs::create_dir_all(&d_org).expect("create dir");
+        std::fs::write(
+            d_org.join("a.org"),
+            "#+DATE: not a date\n\n* A\n* B\nOn <2024-02-30 Fri>.\n",
+        )
+        .expect("write");
+
+        let compiler = Compiler::default();
+        let section = compiler.compile_section(&d_org).expect("compile section");
+        assert_eq!(section.documents.len(), 1);
+        let diagnostics = section.all_diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert!(diagnostics.iter().all(|e| !e.is_error()));
+        assert_eq!(diagnostics[0].span.as_ref().map(|s| s.line), Some(1));
+        assert_eq!(
+            diagnostics[1].message,
+            "invalid timestamp `<2024-02-30 Fri>`"
+        );
+        assert_eq!(diagnostics[1].span.as_ref().map(|s| s.line), Some(5));
+
+        let result = compiler.compile_file(d_org.join("missing.org"));
+        assert!(result.is_err());
+
+        std::fs::remove_dir_all(&d_org).expect("remove dir");
+    }
+
+    #[test]
+    fn test_compile_file_link_abbrevs() {
+        let d_org = std::env::temp_dir().join(format!("windancer_link_{}", std::process::id()));
+        std::fs::create_dir_all(&d_org).expect("create dir");
+        let f_org = d_org.join("a.org");
+        std::fs::write(
+            &f_org,
+            r"#+LINK: gh https://github.com/%s
+
+See gh:cnglen/windancer and [[gh:rust-lang/rust][rust]].
+",
+        )
+        .expect("write");
+
+        let doc = Compiler::default().compile_file(&f_org).expect("compile");
+        let links = doc
+            .ast
+            .extracted_links
+            .iter()
+            .map(|e| (e.link.protocol.as_str(), e.link.path.as_str()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            links,
+            vec![
+                ("https", "https://github.com/cnglen/windancer"),
+                ("https", "https://github.com/rust-lang/rust")
+            ]
+        );
+
+        std::fs::remove_dir_all(&d_org).expect("remove dir");
+    }
+
+    #[test]
+    fn test_compile_section_with_bibliography() {
+        let d_org = std::env::temp_dir().join(format!("windancer_bib_{}", std::process::id()));
+        std::fs::create_dir_all(&d_org).expect("create dir");
+        std::fs::write(
+            d_org.join("refs.bib"),
+            "@book{roe, title = {B}}\n@article{doe, title = {A}}\n@misc{poe, title = {C}}\n",
+        )
+        .expect("write");
+        std::fs::write(
+            d_org.join("a.org"),
+            "#+bibliography: refs.bib\n#+cite_export: basic numeric\n\n* A\nsee [cite:@doe;@nope] [cite:@roe;@doe]\n",
+        )
+        .expect("write");
+
+        let compiler = Compiler::default();
+        let section = compiler.compile_section(&d_org).expect("compile section");
+        let diagnostics = section.all_diagnostics();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].message, "unknown citation key `nope`");
+        assert_eq!(diagnostics[0].span.as_ref().map(|s| s.line), Some(5));
+
+        let bibliography = section.documents[0]
+            .ast
+            .bibliography
+            .as_ref()
+            .expect("bibliography");
+        assert_eq!(bibliography.style, CitationStyle::Numeric);
+        let keys = bibliography
+            .entries
+            .iter()
+            .map(|e| e.key.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(keys, vec!["doe", "roe"]);
+
+        std::fs::remove_dir_all(&d_org).expect("remove dir");
+    }
+
+    #[test]
+    fn test_compile_section_with_math_diagnostics() {
+        let d_org = std::env::temp_dir().join(format!("windancer_math_{}", std::process::id()));
+        std::fs::create_dir_all(&d_org).expect("create dir");
+        std::fs::write(
+            d_org.join("a.org"),
+            "* A\nsee \\(x + \\foo\\) and \\eqref{eq:x}\n",
+        )
+        .expect("write");
+
+        let compiler = Compiler::default().with_check_math(true);
+        let section = compiler.compile_section(&d_org).expect("compile section");
+        let diagnostics = section.all_diagnostics();
+        assert_eq!(diagnostics.len(), 2);
+        assert_eq!(
+            diagnostics[0].message,
+            "invalid math: unknown command `\\foo`"
+        );
+        assert_eq!(
+            diagnostics[0].span.as_ref().map(|s| (s.line, s.column)),
+            Some((2, 11))
+        );
+        assert_eq!(diagnostics[1].message, "undefined label `eq:x`");
+
+        // not checked by default
+        let section = Compiler::default()
+            .compile_section(&d_org)
+            .expect("compile section");
+        assert!(section.all_diagnostics().is_empty());
+
+        // label of equation without number
+        std::fs::write(
+            d_org.join("a.org"),
+            "\\begin{equation}\\label{eq:y}\ny\n\\end{equation}\n\nsee \\ref{eq:y}\n",
+        )
+        .expect("write");
+        let messages = |compiler: Compiler| {
+            compiler
+                .compile_section(&d_org)
+                .expect("compile section")
+                .all_diagnostics()
+                .iter()
+                .map(|e| e.message.clone())
+                .collect::<Vec<_>>()
+        };
+        assert!(messages(Compiler::default().with_check_math(true)).is_empty());
+        let compiler = Compiler::new(CompilerConfig::default().with_equation_numbering(false))
+            .with_check_math(true);
+        assert_eq!(messages(compiler), vec!["undefined label `eq:y`"]);
+
+        std::fs::remove_dir_all(&d_org).expect("remove dir");
+    }
 }
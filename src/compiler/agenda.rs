@@ -0,0 +1,399 @@
+//! Agenda of compiled [`Section`]: day/week views of `SCHEDULED`/`DEADLINE` headings and a global
+//! TODO list, similar to org agenda
+//!
+//! - a heading is listed on the days its timestamps occur, repeaters are expanded
+//! - today also lists open headings scheduled before today, and open headings whose deadline is
+//!   passed or within the warning period, i.e., warning delay of timestamp or
+//!   `deadline_warning_days`
+use std::collections::HashSet;
+
+use chrono::{Datelike, Local, NaiveDate, NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::ast_builder::element::HeadingSubtree;
+use crate::compiler::ast_builder::timestamp::{TimeUnit, Timestamp};
+use crate::compiler::content::{Document, Section};
+use crate::compiler::dynamic_block::plain_text;
+use crate::compiler::parser::config::OrgTodoKeywords;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AgendaConfig {
+    /// TODO states counted as open, `requiring_action` of `org_todo_keywords` if not given
+    pub open_states: Option<HashSet<String>>,
+    /// warning days before deadline without warning delay, like `org-deadline-warning-days`
+    pub deadline_warning_days: u32,
+    /// number of days of agenda page and feed, starting from today
+    pub span_days: u32,
+}
+
+impl Default for AgendaConfig {
+    fn default() -> Self {
+        Self {
+            open_states: None,
+            deadline_warning_days: 14,
+            span_days: 7,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgendaHeading {
+    /// url of heading, with `#anchor`
+    pub url: String,
+    pub title: String,
+    /// title of file
+    pub file: String,
+    pub keyword: Option<String>,
+    pub priority: Option<String>,
+    /// filetags and tags of heading and its ancestors
+    pub tags: Vec<String>,
+    pub scheduled: Option<Timestamp>,
+    pub deadline: Option<Timestamp>,
+    /// keyword is one of open states
+    pub is_open: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgendaItemType {
+    Scheduled,
+    Deadline,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgendaItem {
+    pub item_type: AgendaItemType,
+    /// time of day, if timestamp has time
+    pub time: Option<NaiveTime>,
+    /// days from timestamp to the day: positive if overdue, negative if deadline is upcoming
+    pub days: i64,
+    pub heading: AgendaHeading,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AgendaDay {
+    pub date: NaiveDate,
+    pub items: Vec<AgendaItem>,
+}
+
+/// Machine-readable agenda, see [`Agenda::feed()`]
+#[derive(Debug, Clone, Serialize)]
+pub struct AgendaFeed {
+    pub today: NaiveDate,
+    pub days: Vec<AgendaDay>,
+    pub todos: Vec<AgendaHeading>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Agenda {
+    /// headings with planning or TODO keyword, in document order
+    pub headings: Vec<AgendaHeading>,
+    today: NaiveDate,
+    deadline_warning_days: u32,
+}
+
+impl Agenda {
+    pub fn from_section(section: &Section, config: &AgendaConfig) -> Self {
+        let open_states = config
+            .open_states
+            .clone()
+            .unwrap_or_else(|| OrgTodoKeywords::default().requiring_action);
+        let mut agenda = Self {
+            headings: vec![],
+            today: Local::now().date_naive(),
+            deadline_warning_days: config.deadline_warning_days,
+        };
+        agenda.collect_section(section, &open_states);
+        agenda
+    }
+
+    /// Set the day of overdue and upcoming items, which is the local date by default
+    pub fn with_today(mut self, today: NaiveDate) -> Self {
+        self.today = today;
+        self
+    }
+
+    pub fn today(&self) -> NaiveDate {
+        self.today
+    }
+
+    fn collect_section(&mut self, section: &Section, open_states: &HashSet<String>) {
+        for document in section.documents.iter() {
+            self.collect_document(document, open_states);
+        }
+        for subsection in section.subsections.iter() {
+            self.collect_section(subsection, open_states);
+        }
+    }
+
+    fn collect_document(&mut self, document: &Document, open_states: &HashSet<String>) {
+        let url = format!("/{}", document.html_path());
+        let file = document
+            .metadata
+            .title
+            .clone()
+            .unwrap_or_else(|| document.file_info.name.clone());
+
+        fn collect_heading(
+            headings: &mut Vec<AgendaHeading>,
+            heading: &HeadingSubtree,
+            (url, file): (&str, &str),
+            tags: &[String],
+            open_states: &HashSet<String>,
+        ) {
+            if heading.is_commented {
+                return;
+            }
+            let mut tags = tags.to_vec();
+            for tag in heading.tags.iter() {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+
+            let planning = |keyword| {
+                heading
+                    .planning
+                    .as_ref()
+                    .and_then(|e| e.get(keyword))
+                    .cloned()
+            };
+            let (scheduled, deadline) = (planning("SCHEDULED"), planning("DEADLINE"));
+            if heading.keyword.is_some() || scheduled.is_some() || deadline.is_some() {
+                headings.push(AgendaHeading {
+                    url: format!("{url}#{}", heading.anchor),
+                    title: plain_text(&heading.title).trim().to_string(),
+                    file: file.to_string(),
+                    keyword: heading.keyword.clone(),
+                    priority: heading.priority.clone(),
+                    tags: tags.clone(),
+                    scheduled,
+                    deadline,
+                    is_open: heading
+                        .keyword
+                        .as_ref()
+                        .is_some_and(|e| open_states.contains(e)),
+                });
+            }
+
+            for subtree in heading.sub_heading_subtrees.iter() {
+                collect_heading(headings, subtree, (url, file), &tags, open_states);
+            }
+        }
+        for heading in document.ast.heading_subtrees.iter() {
+            collect_heading(
+                &mut self.headings,
+                heading,
+                (&url, &file),
+                &document.metadata.filetags,
+                open_states,
+            );
+        }
+    }
+
+    /// Items of `date`
+    pub fn day(&self, date: NaiveDate) -> AgendaDay {
+        let mut items = vec![];
+        for heading in self.headings.iter() {
+            if let Some(ts) = &heading.scheduled {
+                let days = match ts.occurrences(date, date).first() {
+                    Some(e) => Some((date - e.start.date()).num_days()),
+                    None if date == self.today && heading.is_open && ts.start.date() < date => {
+                        Some((date - ts.start.date()).num_days())
+                    }
+                    None => None,
+                };
+                if let Some(days) = days {
+                    items.push(item(AgendaItemType::Scheduled, ts, days, heading));
+                }
+            }
+
+            if let Some(ts) = &heading.deadline {
+                let warning_days = ts.delay.map_or(i64::from(self.deadline_warning_days), |e| {
+                    days_of(e.unit, e.value)
+                });
+                let days = match ts.occurrences(date, date).first() {
+                    Some(e) => Some((date - e.start.date()).num_days()),
+                    None if date == self.today && heading.is_open => {
+                        let days = (date - ts.start.date()).num_days();
+                        (days > 0 || -days <= warning_days).then_some(days)
+                    }
+                    None => None,
+                };
+                if let Some(days) = days {
+                    items.push(item(AgendaItemType::Deadline, ts, days, heading));
+                }
+            }
+        }
+
+        // items with time first, then by priority
+        items.sort_by_cached_key(|e| {
+            (
+                e.time.is_none(),
+                e.time,
+                e.heading
+                    .priority
+                    .clone()
+                    .unwrap_or_else(|| "B".to_string()),
+            )
+        });
+        AgendaDay { date, items }
+    }
+
+    /// Items of `n` days from `from`
+    pub fn days(&self, from: NaiveDate, n: u32) -> Vec<AgendaDay> {
+        from.iter_days()
+            .take(n as usize)
+            .map(|date| self.day(date))
+            .collect()
+    }
+
+    /// Items of the week, from Monday, containing `date`
+    pub fn week(&self, date: NaiveDate) -> Vec<AgendaDay> {
+        let monday = date - TimeDelta::days(i64::from(date.weekday().num_days_from_monday()));
+        self.days(monday, 7)
+    }
+
+    /// Open headings, by priority and then in document order
+    pub fn todos(&self) -> Vec<&AgendaHeading> {
+        let mut todos = self
+            .headings
+            .iter()
+            .filter(|e| e.is_open)
+            .collect::<Vec<_>>();
+        todos.sort_by_key(|e| e.priority.as_deref().unwrap_or("B"));
+        todos
+    }
+
+    /// Days from today and TODO list
+    pub fn feed(&self, n_days: u32) -> AgendaFeed {
+        AgendaFeed {
+            today: self.today,
+            days: self.days(self.today, n_days),
+            todos: self.todos().into_iter().cloned().collect(),
+        }
+    }
+}
+
+fn item(
+    item_type: AgendaItemType,
+    ts: &Timestamp,
+    days: i64,
+    heading: &AgendaHeading,
+) -> AgendaItem {
+    AgendaItem {
+        item_type,
+        time: ts.has_time.then(|| ts.start.time()),
+        days,
+        heading: heading.clone(),
+    }
+}
+
+/// Approximate days of `value` units
+fn days_of(unit: TimeUnit, value: u32) -> i64 {
+    let value = i64::from(value);
+    match unit {
+        TimeUnit::Hour => value / 24,
+        TimeUnit::Day => value,
+        TimeUnit::Week => value * 7,
+        TimeUnit::Month => value * 30,
+        TimeUnit::Year => value * 365,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::testing::compile_section;
+
+    fn agenda(input: &str, today: &str) -> Agenda {
+        let section = compile_section(input);
+        Agenda::from_section(&section, &AgendaConfig::default()).with_today(date(today))
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    fn titles(day: &AgendaDay) -> Vec<(String, AgendaItemType, i64)> {
+        day.items
+            .iter()
+            .map(|e| (e.heading.title.clone(), e.item_type, e.days))
+            .collect()
+    }
+
+    const INPUT: &str = r##"#+title: Tasks
+
+* TODO [#C] weekly
+SCHEDULED: <2024-01-01 Mon +1w>
+* TODO [#A] report :work:
+SCHEDULED: <2024-01-03 Wed 09:00> DEADLINE: <2024-01-12 Fri -3d>
+* DONE old
+SCHEDULED: <2024-01-02 Tue>
+* TODO call
+* TODO COMMENT hidden
+SCHEDULED: <2024-01-08 Mon>
+* note
+"##;
+
+    #[test]
+    fn test_day() {
+        let agenda = agenda(INPUT, "2024-01-09");
+        assert_eq!(agenda.headings.len(), 4);
+
+        // today: overdue scheduled and upcoming deadline
+        assert_eq!(
+            titles(&agenda.day(date("2024-01-09"))),
+            vec![
+                ("report".to_string(), AgendaItemType::Scheduled, 6),
+                ("report".to_string(), AgendaItemType::Deadline, -3),
+                ("weekly".to_string(), AgendaItemType::Scheduled, 8),
+            ]
+        );
+        // other days: occurrences only
+        assert_eq!(
+            titles(&agenda.day(date("2024-01-08"))),
+            vec![("weekly".to_string(), AgendaItemType::Scheduled, 0)]
+        );
+        assert_eq!(
+            titles(&agenda.day(date("2024-01-02"))),
+            vec![("old".to_string(), AgendaItemType::Scheduled, 0)]
+        );
+        assert_eq!(
+            agenda.day(date("2024-01-03")).items[0]
+                .time
+                .unwrap()
+                .to_string(),
+            "09:00:00"
+        );
+
+        let agenda = agenda.with_today(date("2024-01-05"));
+        assert!(
+            agenda
+                .day(date("2024-01-05"))
+                .items
+                .iter()
+                .all(|e| e.item_type == AgendaItemType::Scheduled)
+        );
+    }
+
+    #[test]
+    fn test_week_and_todos() {
+        let agenda = agenda(INPUT, "2024-01-09");
+        let week = agenda.week(date("2024-01-10"));
+        assert_eq!(week.len(), 7);
+        assert_eq!(week[0].date, date("2024-01-08"));
+        assert_eq!(week[4].items[0].item_type, AgendaItemType::Deadline);
+
+        assert_eq!(
+            agenda
+                .todos()
+                .iter()
+                .map(|e| e.title.as_str())
+                .collect::<Vec<_>>(),
+            vec!["report", "call", "weekly"]
+        );
+        assert_eq!(agenda.todos()[0].tags, vec!["work"]);
+    }
+}
@@ -16,35 +16,67 @@
 pub mod element;
 mod error;
 pub mod object;
+pub mod timestamp;
 
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::env;
 use std::path::Path;
 
 use element::{
-    AffiliatedKeyword, CenterBlock, Comment, CommentBlock, Drawer, Element, ExampleBlock,
-    ExportBlock, FixedWidth, FootnoteDefinition, HeadingSubtree, HorizontalRule, Item, Keyword,
+    AffiliatedKeyword, BabelCall, CachedResults, CenterBlock, Clock, Comment, CommentBlock,
+    DiarySexp, Drawer, DynamicBlock, Element, ExampleBlock, ExportBlock, FixedWidth,
+    FootnoteDefinition, HeadingSubtree, HorizontalRule, InlineTask, Item, Keyword,
     LatexEnvironment, List, ListType, NodeProperty, OrgFile, Paragraph, Planning, PropertyDrawer,
     QuoteBlock, Section, SpecialBlock, SrcBlock, Table, TableFormula, TableRow, TableRowType,
     VerseBlock, ZerothSectionPreamble, get_properties,
 };
-use error::AstError;
-use object::{CitationReference, GeneralLink, Object, TableCell, TableCellType};
+pub use error::{AstError, TextRange};
+use object::{
+    CitationReference, GeneralLink, Object, TableCell, TableCellType, expand_link_template,
+};
 use serde::{Deserialize, Serialize};
+use timestamp::Timestamp;
 
 use super::parser::syntax::{OrgSyntaxKind, SyntaxElement, SyntaxNode, SyntaxToken};
+use crate::compiler::babel::{parse_header_arguments, split_top_level};
+use crate::compiler::dynamic_block::plain_text;
 use crate::compiler::org_roam::{NodeType, RoamNode};
 use crate::export::ssg::renderer::Renderer;
+use crate::utils::anchor_slug;
 
-pub struct AstBuilder;
+pub struct AstBuilder {
+    /// site level link abbreviations, overridden by `#+LINK:` of file
+    link_abbrevs: BTreeMap<String, String>,
+}
 
 impl AstBuilder {
     pub fn new() -> Self {
-        AstBuilder {}
+        AstBuilder {
+            link_abbrevs: BTreeMap::new(),
+        }
+    }
+
+    pub fn with_link_abbrevs(mut self, link_abbrevs: BTreeMap<String, String>) -> Self {
+        self.link_abbrevs = link_abbrevs;
+
+        self
     }
 
     pub fn build<P: AsRef<Path>>(&self, root: &SyntaxNode, f_org: P) -> Result<OrgFile, AstError> {
-        Converter::new(f_org).convert(root)
+        let mut converter = Converter::new(f_org);
+        converter.link_abbrevs = self.link_abbrevs.clone();
+        converter.convert(root)
+    }
+
+    /// Src block of `node`, with header arguments parsed as in [`AstBuilder::build`]
+    pub(crate) fn build_src_block<P: AsRef<Path>>(
+        &self,
+        node: &SyntaxNode,
+        f_org: P,
+    ) -> Result<SrcBlock, AstError> {
+        let mut converter = Converter::new(f_org);
+        converter.link_abbrevs = self.link_abbrevs.clone();
+        converter.convert_src_block(node)
     }
 }
 
@@ -101,6 +133,8 @@ pub struct ExtractedLink {
     // full path from file root to link location
     pub source_path: Vec<SourcePathSegment>,
     pub link: GeneralLink,
+    /// plain text of the enclosing paragraph, if any
+    pub context: Option<String>,
 }
 
 impl ExtractedLink {
@@ -166,8 +200,9 @@ impl BuilderContext {
         }
     }
 
+    // file node with ID is kept in path, so that links under headings without ID belong to it
     fn leave_zeroth_section(&mut self) {
-        if let Some(SourcePathSegment::ZerothSection { .. }) = self.current_path.last() {
+        if let Some(SourcePathSegment::ZerothSection { id: None }) = self.current_path.last() {
             self.current_path.pop();
         }
     }
@@ -216,6 +251,9 @@ struct Converter {
     keywords: BTreeMap<String, Vec<Object>>,
     extracted_links: Vec<ExtractedLink>,
     roam_nodes: Vec<RoamNode>,
+    link_abbrevs: BTreeMap<String, String>,
+    // anchors of headings assigned so far, to keep them unique in file
+    heading_anchors: HashSet<String>,
 }
 
 impl Default for BuilderContext {
@@ -268,10 +306,59 @@ impl Converter {
             context: BuilderContext::new(f_org),
             extracted_links: vec![],
             roam_nodes: vec![],
+            link_abbrevs: BTreeMap::new(),
+            heading_anchors: HashSet::new(),
         }
     }
 
+    /// html id of heading: `ID`, `CUSTOM_ID` or slug of the titles from the top level heading to
+    /// it, which is suffixed by a number if the same slug is used by a previous heading
+    ///
+    /// It only depends on heading rows, so that it is stable when sections are changed, e.g., by
+    /// updating dynamic blocks after links are resolved.
+    fn heading_anchor(
+        &mut self,
+        title: &[Object],
+        properties: &BTreeMap<String, String>,
+    ) -> String {
+        let anchor = match ["ID", "CUSTOM_ID"]
+            .iter()
+            .filter_map(|e| properties.get(*e))
+            .map(|e| e.trim())
+            .find(|e| !e.is_empty())
+        {
+            Some(id) => id.to_string(),
+            None => {
+                let title_path = self
+                    .context
+                    .current_path
+                    .iter()
+                    .filter_map(|e| match e {
+                        SourcePathSegment::Heading { title, .. } => Some(plain_text(title)),
+                        _ => None,
+                    })
+                    .chain([plain_text(title)])
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let slug = match anchor_slug(&title_path) {
+                    slug if slug.is_empty() => "heading".to_string(),
+                    slug => slug,
+                };
+                (1..)
+                    .map(|n| match n {
+                        1 => slug.clone(),
+                        n => format!("{slug}-{n}"),
+                    })
+                    .find(|e| !self.heading_anchors.contains(e))
+                    .expect("unused anchor")
+            }
+        };
+        self.heading_anchors.insert(anchor.clone());
+        anchor
+    }
+
     fn convert(&mut self, root: &SyntaxNode) -> Result<OrgFile, AstError> {
+        self.link_abbrevs.extend(Self::collect_link_abbrevs(root));
         let ans = self.convert_document(root);
         ans
     }
@@ -385,6 +472,7 @@ impl Converter {
             extracted_links: self.extracted_links.clone(),
             roam_nodes: self.roam_nodes.clone(),
             properties: properties,
+            bibliography: None,
         })
     }
 
@@ -404,6 +492,7 @@ impl Converter {
         let mut planning = None;
         let mut property_drawer = None;
         let mut properties = BTreeMap::new();
+        let mut anchor = String::new();
         for child in node.children() {
             match child.kind() {
                 OrgSyntaxKind::Section => match self.convert_section(&child) {
@@ -485,6 +574,7 @@ impl Converter {
                     }
 
                     properties = get_properties(&property_drawer);
+                    anchor = self.heading_anchor(&title, &properties);
                     let id = properties.get("ID").cloned();
                     let parent_id = self.context.current_roam_node_path.last().cloned();
                     if let Some((id, aliases, refs, properties)) = Self::get_roam_info(&properties)
@@ -527,19 +617,92 @@ impl Converter {
             section,
             sub_heading_subtrees,
             properties,
+            anchor,
         })
     }
 
     fn convert_section(&mut self, node: &SyntaxNode) -> Result<Section, AstError> {
-        let mut elements = vec![];
+        let elements = self.convert_elements(node.children());
 
-        for child in node.children() {
-            if let Ok(element) = self.convert_element(&child) {
-                elements.push(element);
+        Ok(Section { elements: elements })
+    }
+
+    /// Convert sibling elements, linking `#+RESULTS:` to its preceding src block or babel call
+    ///
+    /// The results are an element with the affiliated keyword `#+RESULTS:`, or empty for a sole
+    /// `#+RESULTS:` keyword.
+    fn convert_elements(&mut self, nodes: impl Iterator<Item = SyntaxNode>) -> Vec<Element> {
+        let mut elements: Vec<Element> = vec![];
+        for node in nodes {
+            let last_results = match elements.last_mut() {
+                Some(Element::SrcBlock(SrcBlock {
+                    cached_results: cached_results @ None,
+                    ..
+                }))
+                | Some(Element::BabelCall(BabelCall {
+                    cached_results: cached_results @ None,
+                    ..
+                })) => Some(cached_results),
+                _ => None,
+            };
+            let maybe_keyword = Self::results_keyword_node(&node)
+                .and_then(|e| self.convert_affiliated_keyword(&e).ok());
+
+            match (last_results, maybe_keyword) {
+                (Some(cached_results), Some(keyword)) => {
+                    let element = if node.kind() == OrgSyntaxKind::Keyword {
+                        None
+                    } else {
+                        self.convert_element(&node).ok().map(|mut e| {
+                            Self::strip_results_keyword(&mut e);
+                            Box::new(e)
+                        })
+                    };
+                    *cached_results = Some(CachedResults { keyword, element });
+                }
+                _ => {
+                    if let Ok(element) = self.convert_element(&node) {
+                        elements.push(element);
+                    }
+                }
             }
         }
+        elements
+    }
 
-        Ok(Section { elements: elements })
+    /// Drop `#+RESULTS:` from affiliated keywords of `element`, which is kept in `CachedResults`
+    fn strip_results_keyword(element: &mut Element) {
+        let affiliated_keywords = match element {
+            Element::Paragraph(Paragraph {
+                affiliated_keywords,
+                ..
+            })
+            | Element::Drawer(Drawer {
+                affiliated_keywords,
+                ..
+            })
+            | Element::BabelCall(BabelCall {
+                affiliated_keywords,
+                ..
+            }) => affiliated_keywords,
+            _ => return,
+        };
+        affiliated_keywords.retain(|e| !e.key.eq_ignore_ascii_case("RESULTS"));
+    }
+
+    /// `#+RESULTS:` keyword, or affiliated keyword of the element `node`
+    fn results_keyword_node(node: &SyntaxNode) -> Option<SyntaxNode> {
+        let is_results = |e: &SyntaxNode| {
+            e.first_child_by_kind(&|c| c == OrgSyntaxKind::KeywordKey)
+                .is_some_and(|k| k.text().to_string().eq_ignore_ascii_case("RESULTS"))
+        };
+        match node.kind() {
+            OrgSyntaxKind::Keyword => Some(node.clone()).filter(is_results),
+            _ => node
+                .children()
+                .filter(|e| e.kind() == OrgSyntaxKind::AffiliatedKeyword)
+                .find(is_results),
+        }
     }
 
     fn convert_zeroth_section_preamble(
@@ -594,6 +757,10 @@ impl Converter {
                 Ok(Element::SpecialBlock(self.convert_special_block(&node)?))
             }
 
+            OrgSyntaxKind::DynamicBlock => {
+                Ok(Element::DynamicBlock(self.convert_dynamic_block(node)?))
+            }
+
             OrgSyntaxKind::ExampleBlock => {
                 Ok(Element::ExampleBlock(self.convert_example_block(&node)?))
             }
@@ -636,6 +803,14 @@ impl Converter {
 
             OrgSyntaxKind::Planning => Ok(Element::Planning(self.convert_planning(&node)?)),
 
+            OrgSyntaxKind::Clock => Ok(Element::Clock(self.convert_clock(node)?)),
+
+            OrgSyntaxKind::DiarySexp => Ok(Element::DiarySexp(self.convert_diary_sexp(node)?)),
+
+            OrgSyntaxKind::InlineTask => Ok(Element::InlineTask(self.convert_inline_task(node)?)),
+
+            OrgSyntaxKind::BabelCall => Ok(Element::BabelCall(self.convert_babel_call(node)?)),
+
             OrgSyntaxKind::ZerothSectionPreamble => Ok(Element::ZerothSectionPreamble(
                 self.convert_zeroth_section_preamble(&node)?,
             )),
@@ -645,7 +820,7 @@ impl Converter {
 
                 Err(AstError::UnknownNodeType {
                     kind: node.kind(),
-                    position: None,
+                    position: Some(node.text_range().into()),
                 })
             }
         };
@@ -755,7 +930,7 @@ impl Converter {
 
             _ => Err(AstError::UnknownNodeType {
                 kind: node_or_token.kind(),
-                position: None,
+                position: Some(node_or_token.text_range().into()),
             }),
         };
         self.context.kind_stack.pop();
@@ -764,6 +939,7 @@ impl Converter {
 
     // element.paragraph
     fn convert_paragraph(&mut self, node: &SyntaxNode) -> Result<Paragraph, AstError> {
+        let n_link = self.extracted_links.len();
         let mut objects = vec![];
         let mut affiliated_keywords: Vec<AffiliatedKeyword> = vec![];
         for child in node.children_with_tokens() {
@@ -790,6 +966,17 @@ impl Converter {
             }
         }
 
+        // links found in this paragraph share it as context, e.g. for backlinks
+        if self.extracted_links.len() > n_link {
+            let context = plain_text(&objects)
+                .split_whitespace()
+                .collect::<Vec<_>>()
+                .join(" ");
+            for link in self.extracted_links[n_link..].iter_mut() {
+                link.context.get_or_insert_with(|| context.clone());
+            }
+        }
+
         Ok(Paragraph {
             objects,
             affiliated_keywords,
@@ -822,11 +1009,7 @@ impl Converter {
                         .join("");
                 }
                 OrgSyntaxKind::DrawerContent => {
-                    for grandson in child.as_node().unwrap().children() {
-                        if let Ok(element) = self.convert_element(&grandson) {
-                            contents.push(element);
-                        }
-                    }
+                    contents = self.convert_elements(child.as_node().unwrap().children());
                 }
 
                 _ => {}
@@ -853,23 +1036,136 @@ impl Converter {
 
     // element.planning
     fn convert_planning(&mut self, node: &SyntaxNode) -> Result<Planning, AstError> {
-        let keyword = node
-            .first_child_or_token_by_kind(&|c| c == OrgSyntaxKind::PlanningKeyword)
-            .expect("planning must has one text")
-            .as_token()
-            .unwrap()
-            .text()
-            .to_string();
+        let mut items = vec![];
+        let mut keyword = None;
+        for child in node.children_with_tokens() {
+            match child.kind() {
+                OrgSyntaxKind::PlanningKeyword => {
+                    keyword = Some(child.as_token().unwrap().text().to_string());
+                }
+                OrgSyntaxKind::Timestamp => {
+                    let timestamp = self
+                        .convert_timestamp(child.as_node().unwrap())?
+                        .expect("timestamp");
+                    items.push((
+                        keyword.take().expect("planning keyword before timestamp"),
+                        timestamp,
+                    ));
+                }
+                _ => {}
+            }
+        }
+
+        Ok(Planning { items })
+    }
 
+    // element.clock
+    fn convert_clock(&mut self, node: &SyntaxNode) -> Result<Clock, AstError> {
         let timestamp = node
             .first_child_by_kind(&|c| c == OrgSyntaxKind::Timestamp)
-            .expect("planning must has one timestamp");
-        let timestamp = self
-            .convert_timestamp(&timestamp)
-            .expect("timestamp")
-            .expect("timestamp");
+            .expect("clock must has one timestamp");
+        let parsed =
+            Timestamp::from_syntax_node(&timestamp).ok_or_else(|| AstError::TextFormatError {
+                message: "invalid clock timestamp".to_string(),
+                text: timestamp.text().to_string(),
+                position: Some(timestamp.text_range().into()),
+            })?;
+        let (start, end) = (parsed.start, parsed.end);
+
+        let minutes = node
+            .first_child_or_token_by_kind(&|c| c == OrgSyntaxKind::ClockDuration)
+            .and_then(|e| {
+                let (h, m) = e.as_token()?.text().split_once(':')?;
+                Some(h.parse::<u64>().ok()? * 60 + m.parse::<u64>().ok()?)
+            })
+            .or_else(|| {
+                end.map(|end| u64::try_from((end - start).num_minutes()).unwrap_or_default())
+            });
 
-        Ok(Planning { keyword, timestamp })
+        Ok(Clock {
+            timestamp: Object::Timestamp(parsed),
+            start,
+            end,
+            minutes,
+        })
+    }
+
+    // element.inlinetask
+    fn convert_inline_task(&mut self, node: &SyntaxNode) -> Result<InlineTask, AstError> {
+        let mut inline_task = InlineTask {
+            level: 0,
+            keyword: None,
+            priority: None,
+            title: vec![],
+            tags: vec![],
+            planning: None,
+            property_drawer: None,
+            contents: None,
+        };
+        for child in node.children() {
+            match child.kind() {
+                OrgSyntaxKind::HeadingRow => {
+                    for c in child.children_with_tokens() {
+                        match c.kind() {
+                            OrgSyntaxKind::HeadingRowStars => {
+                                inline_task.level = c.as_token().unwrap().text().len() as u8;
+                            }
+                            OrgSyntaxKind::HeadingRowKeywordTodo => {
+                                inline_task.keyword = Some("TODO".to_string())
+                            }
+                            OrgSyntaxKind::HeadingRowKeywordDone => {
+                                inline_task.keyword = Some("DONE".to_string())
+                            }
+                            OrgSyntaxKind::HeadingRowKeywordOther => {
+                                inline_task.keyword = Some(c.as_token().unwrap().text().to_string())
+                            }
+                            OrgSyntaxKind::HeadingRowPriority => {
+                                inline_task.priority = c
+                                    .as_node()
+                                    .unwrap()
+                                    .first_child_or_token_by_kind(&|c| c == OrgSyntaxKind::Text)
+                                    .map(|p| p.as_token().unwrap().text().to_string());
+                            }
+                            OrgSyntaxKind::HeadingRowTitle => {
+                                inline_task.title = c
+                                    .as_node()
+                                    .unwrap()
+                                    .children_with_tokens()
+                                    .filter_map(|e| self.convert_object(&e).ok().flatten())
+                                    .collect();
+                            }
+                            OrgSyntaxKind::HeadingRowTags => {
+                                inline_task.tags = c
+                                    .as_node()
+                                    .unwrap()
+                                    .children_with_tokens()
+                                    .filter(|e| e.kind() == OrgSyntaxKind::HeadingRowTag)
+                                    .map(|e| e.as_token().unwrap().text().to_string())
+                                    .collect();
+                            }
+                            OrgSyntaxKind::Planning => {
+                                inline_task.planning =
+                                    Some(self.convert_planning(c.as_node().unwrap())?);
+                            }
+                            OrgSyntaxKind::PropertyDrawer => {
+                                inline_task.property_drawer =
+                                    Some(self.convert_property_drawer(c.as_node().unwrap())?);
+                            }
+                            _ => {}
+                        }
+                    }
+                }
+                OrgSyntaxKind::Section => {
+                    inline_task.contents = Some(self.convert_section(&child)?.elements);
+                }
+                OrgSyntaxKind::InlineTaskEnd => {
+                    inline_task.contents.get_or_insert_with(Vec::new);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(inline_task)
     }
 
     // element.table
@@ -880,6 +1176,7 @@ impl Converter {
         let mut rows = vec![];
         let mut header = vec![];
         let mut formulas = vec![];
+        let mut rules = vec![];
 
         let idx_rule_row = node
             .children()
@@ -898,6 +1195,7 @@ impl Converter {
                 }
                 OrgSyntaxKind::TableRuleRow => {
                     // rows.push(self.convert_table_row(&row, TableRowType::Rule)?);
+                    rules.push(header.len() + rows.len());
                 }
 
                 OrgSyntaxKind::TableFormula => {
@@ -940,6 +1238,7 @@ impl Converter {
             separator,
             rows,
             formulas,
+            rules,
         })
     }
 
@@ -1075,13 +1374,12 @@ impl Converter {
 
     // object.timestamp
     fn convert_timestamp(&self, node: &SyntaxNode) -> Result<Option<Object>, AstError> {
-        let text = node
-            .children_with_tokens()
-            .filter(|e| e.kind() == OrgSyntaxKind::Text)
-            .map(|e| e.as_token().unwrap().text().to_string())
-            .collect::<String>();
-
-        Ok(Some(Object::Timestamp(text)))
+        // syntactically valid but not a real date or time, such as `<2024-02-30 Fri>`, is kept as
+        // text and reported by `Compiler::check_timestamps()`
+        Ok(Some(match Timestamp::from_syntax_node(node) {
+            Some(timestamp) => Object::Timestamp(timestamp),
+            None => Object::Text(node.text().to_string()),
+        }))
     }
 
     // object.text
@@ -1187,6 +1485,37 @@ impl Converter {
         Ok(Some(Object::Macro { name, arguments }))
     }
 
+    // `#+LINK: ABBREV TEMPLATE` keywords anywhere in the file
+    fn collect_link_abbrevs(root: &SyntaxNode) -> BTreeMap<String, String> {
+        root.descendants()
+            .filter(|e| e.kind() == OrgSyntaxKind::Keyword)
+            .filter(|e| {
+                e.first_child_by_kind(&|c| c == OrgSyntaxKind::KeywordKey)
+                    .is_some_and(|k| k.text().to_string().eq_ignore_ascii_case("LINK"))
+            })
+            .filter_map(|e| {
+                let value = e
+                    .first_child_by_kind(&|c| c == OrgSyntaxKind::KeywordValue)?
+                    .text()
+                    .to_string();
+                let (abbrev, template) = value.trim().split_once(char::is_whitespace)?;
+                Some((abbrev.to_string(), template.trim().to_string()))
+            })
+            .collect()
+    }
+
+    // `ABBREV:TAG` or `ABBREV::TAG` -> expanded link, see `org-link-expand-abbrev`
+    fn expand_link_abbrev(&self, link: String) -> String {
+        let (abbrev, tag) = match link.split_once(':') {
+            Some((abbrev, tag)) => (abbrev, tag.strip_prefix(':').unwrap_or(tag)),
+            None => (link.as_str(), ""),
+        };
+        match self.link_abbrevs.get(abbrev) {
+            Some(template) => expand_link_template(template, tag),
+            None => link,
+        }
+    }
+
     // object.link
     fn parse_pathreg(s: String) -> (String, String) {
         if s.starts_with("./") {
@@ -1256,7 +1585,7 @@ impl Converter {
             _ => (String::from("unreachable"), vec![]),
         };
 
-        let (protocol, path) = Self::parse_pathreg(pathreg);
+        let (protocol, path) = Self::parse_pathreg(self.expand_link_abbrev(pathreg));
 
         let is_image = (protocol == "file" || protocol == "https")
             && [".jpg", ".jpeg", ".png", ".gif", ".svg"]
@@ -1268,11 +1597,13 @@ impl Converter {
             path,
             description,
             is_image,
+            href: None,
         };
 
         self.extracted_links.push(ExtractedLink {
             source_path: self.context.current_path(),
             link: general_link.clone(),
+            context: None,
         });
 
         Ok(Some(Object::GeneralLink(general_link)))
@@ -1690,23 +2021,29 @@ impl Converter {
         }))
     }
 
+    // parameters of greater block or data of lesser block, e.g, `html` in `#+begin_export html`
+    fn block_data(node: &SyntaxNode) -> Option<String> {
+        node.first_child_by_kind(&|c| c == OrgSyntaxKind::BlockBegin)?
+            .children_with_tokens()
+            .filter(|e| e.kind() == OrgSyntaxKind::Text)
+            .nth(2)
+            .map(|e| e.as_token().unwrap().text().trim().to_string())
+            .filter(|e| !e.is_empty())
+    }
+
     // element.center_block
     fn convert_center_block(&mut self, node: &SyntaxNode) -> Result<CenterBlock, AstError> {
-        let parameters = None;
+        let parameters = Self::block_data(node);
         let mut contents = vec![];
 
         match node.kind() {
             OrgSyntaxKind::CenterBlock => {
                 let _q = node.first_child_or_token_by_kind(&|c| c == OrgSyntaxKind::BlockBegin);
-                for e in node
-                    .first_child_by_kind(&|c| c == OrgSyntaxKind::BlockContent)
-                    .unwrap()
-                    .children()
-                {
-                    if let Ok(element) = self.convert_element(&e) {
-                        contents.push(element);
-                    }
-                }
+                contents = self.convert_elements(
+                    node.first_child_by_kind(&|c| c == OrgSyntaxKind::BlockContent)
+                        .unwrap()
+                        .children(),
+                );
             }
 
             _ => {}
@@ -1720,21 +2057,17 @@ impl Converter {
 
     // element.quote_block
     fn convert_quote_block(&mut self, node: &SyntaxNode) -> Result<QuoteBlock, AstError> {
-        let parameters = None;
+        let parameters = Self::block_data(node);
         let mut contents = vec![];
 
         match node.kind() {
             OrgSyntaxKind::QuoteBlock => {
                 let _q = node.first_child_or_token_by_kind(&|c| c == OrgSyntaxKind::BlockBegin);
-                for e in node
-                    .first_child_by_kind(&|c| c == OrgSyntaxKind::BlockContent)
-                    .unwrap()
-                    .children()
-                {
-                    if let Ok(element) = self.convert_element(&e) {
-                        contents.push(element);
-                    }
-                }
+                contents = self.convert_elements(
+                    node.first_child_by_kind(&|c| c == OrgSyntaxKind::BlockContent)
+                        .unwrap()
+                        .children(),
+                );
             }
             _ => {}
         }
@@ -1747,7 +2080,7 @@ impl Converter {
 
     // element.special_block
     fn convert_special_block(&mut self, node: &SyntaxNode) -> Result<SpecialBlock, AstError> {
-        let parameters = None;
+        let parameters = Self::block_data(node);
         let mut contents = vec![];
         let mut name = String::new();
 
@@ -1768,15 +2101,11 @@ impl Converter {
                     .to_string()
                     .to_lowercase();
 
-                for e in node
-                    .first_child_by_kind(&|c| c == OrgSyntaxKind::BlockContent)
-                    .expect(format!("no block content found: {:#?}", node).as_str())
-                    .children()
-                {
-                    if let Ok(element) = self.convert_element(&e) {
-                        contents.push(element);
-                    }
-                }
+                contents = self.convert_elements(
+                    node.first_child_by_kind(&|c| c == OrgSyntaxKind::BlockContent)
+                        .expect(format!("no block content found: {:#?}", node).as_str())
+                        .children(),
+                );
             }
             _ => {}
         }
@@ -1788,9 +2117,82 @@ impl Converter {
         })
     }
 
+    // element.dynamic_block
+    fn convert_dynamic_block(&mut self, node: &SyntaxNode) -> Result<DynamicBlock, AstError> {
+        let parameters = Self::block_data(node);
+        let plist = parameters.as_deref().map_or(vec![], Self::parse_plist);
+        let name = node
+            .first_child_by_kind(&|c| c == OrgSyntaxKind::BlockBegin)
+            .expect("dynamic block has begin row")
+            .children_with_tokens()
+            .filter(|e| e.kind() == OrgSyntaxKind::Text)
+            .nth(1)
+            .expect("dynamic block begin row has name")
+            .as_token()
+            .unwrap()
+            .text()
+            .to_string();
+
+        let contents = node
+            .first_child_by_kind(&|c| c == OrgSyntaxKind::BlockContent)
+            .map_or(vec![], |e| self.convert_elements(e.children()));
+
+        Ok(DynamicBlock {
+            name,
+            parameters,
+            plist,
+            contents,
+        })
+    }
+
+    // `:key value ...` -> [(key, value)], a value is words until next key, quotes are removed
+    fn parse_plist(s: &str) -> Vec<(String, String)> {
+        // split by whitespace outside of quotes and parentheses
+        let mut words = vec![];
+        let mut word = String::new();
+        let (mut in_quote, mut depth) = (false, 0usize);
+        for c in s.chars() {
+            match c {
+                '"' => in_quote = !in_quote,
+                '(' if !in_quote => depth += 1,
+                ')' if !in_quote => depth = depth.saturating_sub(1),
+                c if c.is_whitespace() && !in_quote && depth == 0 => {
+                    if !word.is_empty() {
+                        words.push(std::mem::take(&mut word));
+                    }
+                    continue;
+                }
+                _ => {}
+            }
+            word.push(c);
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+
+        let mut plist: Vec<(String, String)> = vec![];
+        for word in words {
+            if let Some(key) = word.strip_prefix(':')
+                && !key.is_empty()
+            {
+                plist.push((key.to_string(), String::new()));
+            } else if let Some((_, value)) = plist.last_mut() {
+                let word = word
+                    .strip_prefix('"')
+                    .and_then(|e| e.strip_suffix('"'))
+                    .unwrap_or(&word);
+                if !value.is_empty() {
+                    value.push(' ');
+                }
+                value.push_str(word);
+            }
+        }
+        plist
+    }
+
     // element.example_block
     fn convert_example_block(&mut self, node: &SyntaxNode) -> Result<ExampleBlock, AstError> {
-        let data = None;
+        let data = Self::block_data(node);
         let mut contents = vec![];
 
         match node.kind() {
@@ -1817,7 +2219,7 @@ impl Converter {
 
     // element.comment_block
     fn convert_comment_block(&mut self, node: &SyntaxNode) -> Result<CommentBlock, AstError> {
-        let data = None;
+        let data = Self::block_data(node);
         let mut contents = vec![];
 
         match node.kind() {
@@ -1844,7 +2246,7 @@ impl Converter {
 
     // element.verse_block
     fn convert_verse_block(&mut self, node: &SyntaxNode) -> Result<VerseBlock, AstError> {
-        let data = None;
+        let data = Self::block_data(node);
         let mut contents = vec![];
 
         match node.kind() {
@@ -1872,6 +2274,7 @@ impl Converter {
     // // FIXME: parse argument data? in compiler stage?
     // element.src_block
     fn convert_src_block(&mut self, node: &SyntaxNode) -> Result<SrcBlock, AstError> {
+        let mut name = None;
         let mut language = String::new();
         let mut switches: Vec<String> = vec![];
         let mut results = None;
@@ -1910,31 +2313,39 @@ impl Converter {
                     .unwrap()
                     .first_child_or_token_by_kind(&|c| c == OrgSyntaxKind::SrcBlockHeaderArguments)
                     .map_or(String::from(""), |e| {
-                        e.as_token().unwrap().text().to_string().to_lowercase()
+                        e.as_token().unwrap().text().to_string()
                     });
 
-                let mut parts = raw_arguments.split_whitespace();
-
-                while let Some(part) = parts.next() {
-                    if part.starts_with(':') {
-                        let key = &part[1..]; // 去掉冒号
-                        if let Some(value) = parts.next() {
-                            match key {
-                                "results" => results = Some(value.to_string()),
-                                "exports" => exports = Some(value.to_string()),
-                                "var" => {
-                                    if let Some((var_name, var_value)) = value.split_once('=') {
-                                        vars.insert(var_name.to_string(), var_value.to_string());
-                                    }
-                                }
-                                _ => {
-                                    other_args.insert(key.to_string(), value.to_string());
+                for (key, value) in parse_header_arguments(&raw_arguments) {
+                    match key.as_str() {
+                        "results" => results = Some(value.to_lowercase()),
+                        "exports" => exports = Some(value.to_lowercase()),
+                        "var" => {
+                            for var in split_top_level(&value, ',') {
+                                if let Some((var_name, var_value)) = var.split_once('=') {
+                                    vars.insert(
+                                        var_name.trim().to_string(),
+                                        var_value.trim().to_string(),
+                                    );
                                 }
                             }
                         }
+                        _ => {
+                            other_args.insert(key, value);
+                        }
                     }
                 }
 
+                name = node
+                    .children()
+                    .filter(|e| e.kind() == OrgSyntaxKind::AffiliatedKeyword)
+                    .find(|e| {
+                        e.first_child_by_kind(&|c| c == OrgSyntaxKind::KeywordKey)
+                            .is_some_and(|k| k.text().to_string().eq_ignore_ascii_case("NAME"))
+                    })
+                    .and_then(|e| e.first_child_by_kind(&|c| c == OrgSyntaxKind::KeywordValue))
+                    .map(|e| e.text().to_string().trim().to_string());
+
                 if exports.is_none() {
                     match language.as_str() {
                         "ditaa" | "mermaid" | "dot" => {
@@ -1959,6 +2370,7 @@ impl Converter {
         }
 
         Ok(SrcBlock {
+            name,
             language,
             switches,
             results,
@@ -1967,12 +2379,13 @@ impl Converter {
             other_args,
 
             contents,
+            cached_results: None,
         })
     }
 
     // element.export_block
     fn convert_export_block(&mut self, node: &SyntaxNode) -> Result<ExportBlock, AstError> {
-        let data = None;
+        let data = Self::block_data(node);
         let mut contents = vec![];
 
         match node.kind() {
@@ -2065,11 +2478,7 @@ impl Converter {
                 // FIXME: ListItemparser
                 //
                 OrgSyntaxKind::ListItemContent => {
-                    for cc in child.as_node().unwrap().children() {
-                        if let Ok(element) = self.convert_element(&cc) {
-                            contents.push(element);
-                        }
-                    }
+                    contents = self.convert_elements(child.as_node().unwrap().children());
                 }
 
                 _ => {}
@@ -2127,12 +2536,7 @@ impl Converter {
             .text()
             .to_string();
 
-        let contents = node
-            .children()
-            .map(|e| self.convert_element(&e))
-            .filter(|e| e.is_ok())
-            .map(|e| e.unwrap())
-            .collect();
+        let contents = self.convert_elements(node.children());
 
         let rids = self.footnote_label_to_rids.get(&label).expect(&format!(
             "convert_footnote_defintion(): Can't get {label} from {:?}, {node:?}",
@@ -2219,6 +2623,18 @@ impl Converter {
         Ok(Comment { text: text })
     }
 
+    // element.diary_sexp
+    fn convert_diary_sexp(&self, node: &SyntaxNode) -> Result<DiarySexp, AstError> {
+        let value = node
+            .children_with_tokens()
+            .filter(|e| e.kind() == OrgSyntaxKind::Text)
+            .nth(1)
+            .map(|e| e.as_token().unwrap().text().to_string())
+            .unwrap_or_default();
+
+        Ok(DiarySexp { value })
+    }
+
     // element.node_property
     fn convert_node_property(&self, node: &SyntaxNode) -> Result<NodeProperty, AstError> {
         let text = node
@@ -2326,6 +2742,45 @@ impl Converter {
         })
     }
 
+    // element.babel_call
+    fn convert_babel_call(&mut self, node: &SyntaxNode) -> Result<BabelCall, AstError> {
+        let text_of = |kind: OrgSyntaxKind| {
+            node.children_with_tokens()
+                .find(|e| e.kind() == kind)
+                .map(|e| e.as_token().expect("babel call token").text().to_string())
+        };
+
+        let affiliated_keywords = node
+            .children()
+            .filter(|e| e.kind() == OrgSyntaxKind::AffiliatedKeyword)
+            .filter_map(|e| self.convert_affiliated_keyword(&e).ok())
+            .collect();
+        let arguments = if node
+            .children_with_tokens()
+            .any(|e| e.kind() == OrgSyntaxKind::LeftRoundBracket)
+        {
+            Some(text_of(OrgSyntaxKind::BabelCallArguments).unwrap_or_default())
+        } else {
+            None
+        };
+        let end_header = text_of(OrgSyntaxKind::BabelCallEndHeader).map(|e| {
+            let e = e.trim();
+            e.strip_prefix('[')
+                .and_then(|e| e.strip_suffix(']'))
+                .unwrap_or(e)
+                .to_string()
+        });
+
+        Ok(BabelCall {
+            name: text_of(OrgSyntaxKind::BabelCallName).unwrap_or_default(),
+            inside_header: text_of(OrgSyntaxKind::BabelCallInsideHeader),
+            arguments,
+            end_header,
+            affiliated_keywords,
+            cached_results: None,
+        })
+    }
+
     // element.latex_environment
     fn convert_latex_environment(&self, node: &SyntaxNode) -> Result<LatexEnvironment, AstError> {
         Ok(LatexEnvironment {
@@ -3,10 +3,13 @@ use std::collections::BTreeMap;
 use std::fmt;
 
 use bincode;
+use chrono::NaiveDateTime;
 use serde::{Deserialize, Serialize};
 
 use crate::compiler::ast_builder::ExtractedLink;
 use crate::compiler::ast_builder::object::Object;
+use crate::compiler::ast_builder::timestamp::Timestamp;
+use crate::compiler::bibliography::Bibliography;
 use crate::compiler::org_roam::RoamNode;
 
 pub(crate) trait Id {
@@ -21,7 +24,7 @@ pub(crate) trait Id {
     }
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct OrgFile {
     pub zeroth_section: Option<Section>,
     pub heading_subtrees: Vec<HeadingSubtree>,
@@ -38,6 +41,8 @@ pub struct OrgFile {
     pub extracted_links: Vec<ExtractedLink>,
     /// Roam Nodes
     pub roam_nodes: Vec<RoamNode>,
+    /// Cited entries of `#+bibliography:`, resolved by compiler
+    pub bibliography: Option<Bibliography>,
 }
 
 impl Id for OrgFile {}
@@ -54,6 +59,7 @@ properties: {:#?}
 keywords: {:#?},
 extracted_links: {:#?},
 roam_nodes: {:#?},
+bibliography: {:#?},
 }}"##,
             self.zeroth_section,
             self.heading_subtrees,
@@ -61,7 +67,8 @@ roam_nodes: {:#?},
             self.properties,
             self.keywords,
             self.extracted_links,
-            self.roam_nodes
+            self.roam_nodes,
+            self.bibliography
         )
     }
 }
@@ -81,6 +88,8 @@ pub struct HeadingSubtree {
     pub section: Option<Section>,
     pub sub_heading_subtrees: Vec<HeadingSubtree>,
     pub properties: BTreeMap<String, String>,
+    /// html id: `ID`, `CUSTOM_ID` or slug of the title path, unique in file
+    pub anchor: String,
 }
 
 impl Id for HeadingSubtree {}
@@ -112,6 +121,7 @@ impl fmt::Debug for HeadingSubtree {
     planning: {:#?},
     property_drawer: {:#?},
     properties: {:#?},
+    anchor: {:#?},
 
     section: {:#?},
     sub_heading_subtrees: {:#?}
@@ -125,6 +135,7 @@ impl fmt::Debug for HeadingSubtree {
             self.planning,
             self.property_drawer,
             self.properties,
+            self.anchor,
             self.section,
             self.sub_heading_subtrees
         )
@@ -158,10 +169,11 @@ pub enum Element {
     CenterBlock(CenterBlock),
     QuoteBlock(QuoteBlock),
     SpecialBlock(SpecialBlock),
-    // DynamicBlock(DynamicBlock),
+    DynamicBlock(DynamicBlock),
     List(List),
     Item(Item),
     FootnoteDefinition(FootnoteDefinition),
+    InlineTask(InlineTask),
 
     ZerothSectionPreamble(ZerothSectionPreamble), // todo
 
@@ -179,12 +191,43 @@ pub enum Element {
     FixedWidth(FixedWidth),
     NodeProperty(NodeProperty),
     Planning(Planning),
+    Clock(Clock),
+    DiarySexp(DiarySexp),
+    BabelCall(BabelCall),
 
     TableRow(TableRow),
-    // BabelCall(BabelCall),
     Comment(Comment),
 }
 
+impl Element {
+    /// `#+NAME:` of element, which is a target of internal links
+    pub fn name(&self) -> Option<String> {
+        let affiliated_keywords = match self {
+            Element::Table(e) => return e.name.as_ref().map(|e| e.trim().to_string()),
+            Element::SrcBlock(e) => return e.name.clone(),
+            Element::Paragraph(e) => &e.affiliated_keywords,
+            Element::Drawer(e) => &e.affiliated_keywords,
+            Element::BabelCall(e) => &e.affiliated_keywords,
+            _ => return None,
+        };
+        affiliated_keywords
+            .iter()
+            .find(|e| e.key.eq_ignore_ascii_case("NAME"))
+            .map(|e| {
+                e.value
+                    .iter()
+                    .filter_map(|e| match e {
+                        Object::Text(t) => Some(t.as_str()),
+                        _ => None,
+                    })
+                    .collect::<String>()
+                    .trim()
+                    .to_string()
+            })
+            .filter(|e| !e.is_empty())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ZerothSectionPreamble {
     pub comment: Option<Comment>,
@@ -198,6 +241,20 @@ pub struct Drawer {
     pub affiliated_keywords: Vec<AffiliatedKeyword>,
 }
 
+/// Heading with at least `org_inlinetask_min_level` stars, which does not start a subtree
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InlineTask {
+    pub level: u8,
+    pub keyword: Option<String>,
+    pub priority: Option<String>,
+    pub title: Vec<Object>,
+    pub tags: Vec<String>,
+    pub planning: Option<Planning>,
+    pub property_drawer: Option<PropertyDrawer>,
+    /// `None` for the single line form, i.e., without END row
+    pub contents: Option<Vec<Element>>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PropertyDrawer {
     pub contents: Vec<NodeProperty>,
@@ -211,8 +268,35 @@ pub struct NodeProperty {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Planning {
-    pub keyword: String,
+    /// (`SCHEDULED`/`DEADLINE`/`CLOSED`, timestamp) in the order of planning line
+    pub items: Vec<(String, Object)>,
+}
+
+impl Planning {
+    /// Timestamp of `keyword`, such as `SCHEDULED`
+    pub fn get(&self, keyword: &str) -> Option<&Timestamp> {
+        self.items.iter().find_map(|(k, v)| match v {
+            Object::Timestamp(timestamp) if k == keyword => Some(timestamp),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Clock {
+    /// e.g, `[2024-01-01 Mon 10:00]--[2024-01-01 Mon 11:30]`
     pub timestamp: Object,
+    pub start: NaiveDateTime,
+    /// `None` if the clock is still running
+    pub end: Option<NaiveDateTime>,
+    /// clocked minutes, given by `=> H:MM` or computed from start and end
+    pub minutes: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DiarySexp {
+    /// e.g, `(diary-float t 4 2)`
+    pub value: String,
 }
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -224,6 +308,8 @@ pub struct Table {
     pub separator: Option<TableRow>, // 分隔线行（可选）
     pub rows: Vec<TableRow>,         // 数据行
     pub formulas: Vec<TableFormula>, // 表格公式
+    /// rule rows (hlines), i.e., number of header and data rows before each of them
+    pub rules: Vec<usize>,
 }
 
 impl fmt::Debug for Table {
@@ -329,6 +415,26 @@ pub struct SpecialBlock {
     pub contents: Vec<Element>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DynamicBlock {
+    pub name: String,
+    /// raw parameters, e.g, `:scope file :maxlevel 2`
+    pub parameters: Option<String>,
+    /// parameters as property list, keys without leading colon
+    pub plist: Vec<(String, String)>,
+    pub contents: Vec<Element>,
+}
+
+impl DynamicBlock {
+    /// Value of `key` in plist, case insensitive
+    pub fn parameter(&self, key: &str) -> Option<&str> {
+        self.plist
+            .iter()
+            .find(|(k, _)| k.eq_ignore_ascii_case(key))
+            .map(|(_, v)| v.as_str())
+    }
+}
+
 // Lesser
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExampleBlock {
@@ -356,6 +462,8 @@ pub struct ExportBlock {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SrcBlock {
+    /// `#+NAME:`
+    pub name: Option<String>,
     pub language: String,
 
     pub switches: Vec<String>,
@@ -366,6 +474,51 @@ pub struct SrcBlock {
     pub other_args: BTreeMap<String, String>,
 
     pub contents: Vec<Object>,
+    pub cached_results: Option<CachedResults>,
+}
+
+/// `#+CALL: NAME[INSIDE-HEADER](ARGUMENTS) END-HEADER`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BabelCall {
+    pub name: String,
+    pub inside_header: Option<String>,
+    pub arguments: Option<String>,
+    /// without the optional brackets, i.e., `[:exports both]` gives `:exports both`
+    pub end_header: Option<String>,
+    pub affiliated_keywords: Vec<AffiliatedKeyword>,
+    pub cached_results: Option<CachedResults>,
+}
+
+impl BabelCall {
+    /// `:exports` in end header or inside header
+    pub fn exports(&self) -> Option<&str> {
+        [&self.end_header, &self.inside_header]
+            .into_iter()
+            .flatten()
+            .find_map(|e| header_argument(e, "exports"))
+    }
+}
+
+/// Element affiliated with `#+RESULTS:` right after a src block or babel call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CachedResults {
+    pub keyword: AffiliatedKeyword,
+    /// `None` for empty results, i.e., a sole `#+RESULTS:` line
+    pub element: Option<Box<Element>>,
+}
+
+/// Value of `:key` in header arguments such as `:results silent :exports both`
+pub fn header_argument<'a>(headers: &'a str, key: &str) -> Option<&'a str> {
+    let mut parts = headers.split_whitespace();
+    while let Some(part) = parts.next() {
+        if part
+            .strip_prefix(':')
+            .is_some_and(|e| e.eq_ignore_ascii_case(key))
+        {
+            return parts.next().filter(|e| !e.starts_with(':'));
+        }
+    }
+    None
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -64,6 +64,22 @@ pub enum AstError {
     },
 }
 
+impl AstError {
+    /// 错误位置, MultipleErrors 取第一个有位置的错误
+    pub fn position(&self) -> Option<TextRange> {
+        match self {
+            AstError::UnknownNodeType { position, .. }
+            | AstError::MissingRequiredChild { position, .. }
+            | AstError::TextFormatError { position, .. }
+            | AstError::InvalidHeadingLevel { position, .. }
+            | AstError::InvalidLinkFormat { position, .. }
+            | AstError::NestingError { position, .. } => *position,
+            AstError::MultipleErrors(errors) => errors.iter().find_map(|e| e.position()),
+            AstError::InternalError { .. } => None,
+        }
+    }
+}
+
 /// 文本范围位置信息
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct TextRange {
@@ -77,6 +93,12 @@ impl TextRange {
     }
 }
 
+impl From<rowan::TextRange> for TextRange {
+    fn from(range: rowan::TextRange) -> Self {
+        Self::new(range.start().into(), range.end().into())
+    }
+}
+
 impl fmt::Display for TextRange {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}..{}", self.start, self.end)
@@ -2,6 +2,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::compiler::ast_builder::timestamp::Timestamp;
+
 // 内联元素（Inline-level elements）
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum Object {
@@ -39,7 +41,7 @@ pub enum Object {
 
     Target(String),
 
-    Timestamp(String),
+    Timestamp(Timestamp),
 
     // if definition if found (such as inline or anonymous footnote), a FootnoteDefinition object is auto generated in addition to the FootnoteReference object
     FootnoteReference {
@@ -125,4 +127,28 @@ pub struct GeneralLink {
     pub path: String,
     pub description: Vec<Object>,
     pub is_image: bool,
+    /// href of internal link resolved by `link_resolver`, e.g, `#anchor` or `/other.html#anchor`
+    pub href: Option<String>,
+}
+
+/// Expand link `template` with `tag`, like `org-link-abbrev-alist`:
+/// - `%s` is replaced by `tag`, `%h` by url-encoded `tag`
+/// - otherwise `tag` is appended
+pub fn expand_link_template(template: &str, tag: &str) -> String {
+    if template.contains("%s") {
+        template.replace("%s", tag)
+    } else if template.contains("%h") {
+        let hexified = tag
+            .bytes()
+            .map(|b| match b {
+                b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                    (b as char).to_string()
+                }
+                _ => format!("%{b:02X}"),
+            })
+            .collect::<String>();
+        template.replace("%h", &hexified)
+    } else {
+        format!("{template}{tag}")
+    }
 }
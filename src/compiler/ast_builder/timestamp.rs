@@ -0,0 +1,432 @@
+//! Typed timestamp of org-mode
+//!
+//! - `<2024-01-01 Mon>`, `[2024-01-01 Mon 10:00]`: active/inactive timestamp
+//! - `<2024-01-01 Mon 10:00-11:30>`: time span in the same day
+//! - `<2024-01-01 Mon>--<2024-01-03 Wed>`: range
+//! - `<2024-01-01 Mon +1w -2d>`: repeater (`+`, `++`, `.+`) and warning delay (`-`, `--`)
+use chrono::{Months, NaiveDate, NaiveDateTime, NaiveTime, TimeDelta};
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::parser::object::timestamp::parse_timestamp;
+use crate::compiler::parser::syntax::{OrgSyntaxKind, SyntaxNode};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TimeUnit {
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl TimeUnit {
+    fn from_char(c: char) -> Option<Self> {
+        match c {
+            'h' => Some(Self::Hour),
+            'd' => Some(Self::Day),
+            'w' => Some(Self::Week),
+            'm' => Some(Self::Month),
+            'y' => Some(Self::Year),
+            _ => None,
+        }
+    }
+
+    fn as_char(&self) -> char {
+        match self {
+            Self::Hour => 'h',
+            Self::Day => 'd',
+            Self::Week => 'w',
+            Self::Month => 'm',
+            Self::Year => 'y',
+        }
+    }
+
+    /// Fixed length of unit, `None` for month and year
+    fn delta(&self) -> Option<TimeDelta> {
+        match self {
+            Self::Hour => Some(TimeDelta::hours(1)),
+            Self::Day => Some(TimeDelta::days(1)),
+            Self::Week => Some(TimeDelta::weeks(1)),
+            Self::Month | Self::Year => None,
+        }
+    }
+
+    /// `datetime` shifted by `n` units, month and year are clamped to the last day of month
+    pub fn add(&self, datetime: NaiveDateTime, n: u32) -> Option<NaiveDateTime> {
+        match self {
+            Self::Month => datetime.checked_add_months(Months::new(n)),
+            Self::Year => datetime.checked_add_months(Months::new(n.checked_mul(12)?)),
+            _ => datetime.checked_add_signed(self.delta()?.checked_mul(i32::try_from(n).ok()?)?),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RepeaterType {
+    /// `+`: shifted by interval once
+    Cumulate,
+    /// `++`: shifted by interval until in the future
+    CatchUp,
+    /// `.+`: shifted by interval from today
+    Restart,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Repeater {
+    pub repeater_type: RepeaterType,
+    pub value: u32,
+    pub unit: TimeUnit,
+    /// deadline of habit, e.g., `(3, Day)` of `.+2d/3d`
+    pub deadline: Option<(u32, TimeUnit)>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DelayType {
+    /// `-`: warn before every occurrence
+    All,
+    /// `--`: warn before the first occurrence only
+    First,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Delay {
+    pub delay_type: DelayType,
+    pub value: u32,
+    pub unit: TimeUnit,
+}
+
+/// An occurrence of timestamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Occurrence {
+    pub start: NaiveDateTime,
+    pub end: Option<NaiveDateTime>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Timestamp {
+    /// original text, kept for export
+    pub raw: String,
+    pub active: bool,
+    pub start: NaiveDateTime,
+    /// end of range or time span
+    pub end: Option<NaiveDateTime>,
+    /// false for date only timestamp, whose time is 00:00
+    pub has_time: bool,
+    pub repeater: Option<Repeater>,
+    pub delay: Option<Delay>,
+}
+
+/// One bracketed part of timestamp
+struct Part {
+    active: bool,
+    date: Option<NaiveDate>,
+    times: Vec<NaiveTime>,
+    repeater: Option<Repeater>,
+    delay: Option<Delay>,
+}
+
+impl Part {
+    fn start(&self) -> Option<NaiveDateTime> {
+        Some(
+            self.date?
+                .and_time(self.times.first().copied().unwrap_or(NaiveTime::MIN)),
+        )
+    }
+}
+
+/// `1w` -> (1, Week)
+fn parse_interval(s: &str) -> Option<(u32, TimeUnit)> {
+    let unit = TimeUnit::from_char(s.chars().last()?)?;
+    let value = s[..s.len() - 1].parse().ok()?;
+    Some((value, unit))
+}
+
+/// Text of `TimestampRepeater`, e.g., `++1w` or `.+2d/3d`
+fn parse_repeater(s: &str) -> Option<Repeater> {
+    let (repeater_type, rest) = if let Some(rest) = s.strip_prefix("++") {
+        (RepeaterType::CatchUp, rest)
+    } else if let Some(rest) = s.strip_prefix(".+") {
+        (RepeaterType::Restart, rest)
+    } else {
+        (RepeaterType::Cumulate, s.strip_prefix('+')?)
+    };
+    let (interval, deadline) = match rest.split_once('/') {
+        Some((interval, deadline)) => (interval, Some(parse_interval(deadline)?)),
+        None => (rest, None),
+    };
+    let (value, unit) = parse_interval(interval)?;
+    Some(Repeater {
+        repeater_type,
+        value,
+        unit,
+        deadline,
+    })
+}
+
+/// Text of `TimestampDelay`, e.g., `-2d` or `--2d`
+fn parse_delay(s: &str) -> Option<Delay> {
+    let (delay_type, rest) = match s.strip_prefix("--") {
+        Some(rest) => (DelayType::First, rest),
+        None => (DelayType::All, s.strip_prefix('-')?),
+    };
+    let (value, unit) = parse_interval(rest)?;
+    Some(Delay {
+        delay_type,
+        value,
+        unit,
+    })
+}
+
+impl Timestamp {
+    /// Build from `Timestamp` node of syntax tree, `None` if the date or time is invalid, such
+    /// as `<2024-02-30 Fri>`
+    pub(crate) fn from_syntax_node(node: &SyntaxNode) -> Option<Self> {
+        let mut parts: Vec<Part> = vec![];
+        for token in node.children_with_tokens().filter_map(|e| e.into_token()) {
+            let text = token.text();
+            match token.kind() {
+                OrgSyntaxKind::LeftAngleBracket | OrgSyntaxKind::LeftSquareBracket => {
+                    parts.push(Part {
+                        active: token.kind() == OrgSyntaxKind::LeftAngleBracket,
+                        date: None,
+                        times: vec![],
+                        repeater: None,
+                        delay: None,
+                    })
+                }
+                OrgSyntaxKind::TimestampDate => {
+                    parts.last_mut()?.date = Some(NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?)
+                }
+                OrgSyntaxKind::TimestampTime => parts
+                    .last_mut()?
+                    .times
+                    .push(NaiveTime::parse_from_str(text, "%H:%M").ok()?),
+                OrgSyntaxKind::TimestampRepeater => {
+                    parts.last_mut()?.repeater = Some(parse_repeater(text)?)
+                }
+                OrgSyntaxKind::TimestampDelay => parts.last_mut()?.delay = Some(parse_delay(text)?),
+                _ => {}
+            }
+        }
+
+        let start = parts.first()?;
+        let end = match parts.get(1) {
+            Some(end) => Some(end.start()?),
+            None => start
+                .date
+                .zip(start.times.get(1))
+                .map(|(date, time)| date.and_time(*time)),
+        };
+        Some(Self {
+            raw: node.text().to_string(),
+            active: start.active,
+            start: start.start()?,
+            end,
+            has_time: !start.times.is_empty(),
+            repeater: start.repeater,
+            delay: start.delay,
+        })
+    }
+
+    /// Parse text of timestamp, `None` if it's not a timestamp or the date or time is invalid
+    pub fn parse(text: &str) -> Option<Self> {
+        Self::from_syntax_node(&parse_timestamp(text.trim())?)
+    }
+
+    /// Occurrences overlapping with dates `[from, to]`, repeating timestamps are expanded
+    ///
+    /// All repeater types are expanded in the same way from `start`, as org agenda does.
+    pub fn occurrences(&self, from: NaiveDate, to: NaiveDate) -> Vec<Occurrence> {
+        let span = self.end.map(|end| end - self.start);
+        let occurrence = |start: NaiveDateTime| Occurrence {
+            start,
+            end: span.map(|span| start + span),
+        };
+        let overlaps = |e: &Occurrence| {
+            e.start.date() <= to && e.end.map_or(e.start, |end| end.max(e.start)).date() >= from
+        };
+
+        let repeater = match self.repeater {
+            Some(repeater) if repeater.value > 0 => repeater,
+            _ => {
+                return Some(occurrence(self.start))
+                    .filter(overlaps)
+                    .into_iter()
+                    .collect();
+            }
+        };
+
+        // skip occurrences ending before `from` for units of fixed length
+        let mut n = match repeater.unit.delta() {
+            Some(delta) => {
+                let interval = delta * i32::try_from(repeater.value).unwrap_or(i32::MAX);
+                let gap = from.and_time(NaiveTime::MIN) - self.start - span.unwrap_or_default();
+                u32::try_from(gap.num_seconds() / interval.num_seconds().max(1))
+                    .unwrap_or_default()
+                    .saturating_sub(1)
+            }
+            None => 0,
+        };
+
+        let mut occurrences = vec![];
+        while let Some(value) = repeater.value.checked_mul(n)
+            && let Some(start) = repeater.unit.add(self.start, value)
+            && start.date() <= to
+        {
+            let e = occurrence(start);
+            if overlaps(&e) {
+                occurrences.push(e);
+            }
+            n += 1;
+        }
+        occurrences
+    }
+
+    /// Text of start and end of range, e.g., `<2024-01-01 Mon>` and `<2024-01-03 Wed>`
+    pub fn raw_parts(&self) -> (&str, Option<&str>) {
+        // a part has no closing bracket inside, and parts of range are separated by `--`
+        let close = if self.active { '>' } else { ']' };
+        match self.raw.find(close) {
+            Some(idx) if idx + 1 < self.raw.len() => (&self.raw[..=idx], self.raw.get(idx + 3..)),
+            _ => (&self.raw, None),
+        }
+    }
+
+    /// Value of `datetime` attribute of html `<time>`: start, and end if any
+    pub fn html_datetime(&self) -> (String, Option<String>) {
+        let format = if self.has_time {
+            "%Y-%m-%dT%H:%M"
+        } else {
+            "%Y-%m-%d"
+        };
+        (
+            self.start.format(format).to_string(),
+            self.end.map(|e| e.format(format).to_string()),
+        )
+    }
+}
+
+impl std::fmt::Display for Repeater {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let prefix = match self.repeater_type {
+            RepeaterType::Cumulate => "+",
+            RepeaterType::CatchUp => "++",
+            RepeaterType::Restart => ".+",
+        };
+        write!(f, "{prefix}{}{}", self.value, self.unit.as_char())?;
+        if let Some((value, unit)) = self.deadline {
+            write!(f, "/{value}{}", unit.as_char())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Delay {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let prefix = match self.delay_type {
+            DelayType::All => "-",
+            DelayType::First => "--",
+        };
+        write!(f, "{prefix}{}{}", self.value, self.unit.as_char())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn datetime(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let ts = Timestamp::parse("<2030-10-05 Sat 10:00 +1m --3d>").unwrap();
+        assert!(ts.active);
+        assert!(ts.has_time);
+        assert_eq!(ts.start, datetime("2030-10-05 10:00"));
+        assert_eq!(ts.end, None);
+        assert_eq!(ts.repeater.unwrap().to_string(), "+1m");
+        assert_eq!(
+            ts.delay,
+            Some(Delay {
+                delay_type: DelayType::First,
+                value: 3,
+                unit: TimeUnit::Day
+            })
+        );
+
+        let ts = Timestamp::parse("[2004-08-24 Tue]--[2004-08-26 Thu]").unwrap();
+        assert!(!ts.active);
+        assert!(!ts.has_time);
+        assert_eq!(ts.end, Some(datetime("2004-08-26 00:00")));
+
+        let ts = Timestamp::parse("<2024-01-01 Mon 10:00-11:30 .+2w>").unwrap();
+        assert_eq!(ts.end, Some(datetime("2024-01-01 11:30")));
+        assert_eq!(ts.repeater.unwrap().repeater_type, RepeaterType::Restart);
+        assert_eq!(
+            ts.html_datetime(),
+            (
+                "2024-01-01T10:00".to_string(),
+                Some("2024-01-01T11:30".to_string())
+            )
+        );
+
+        let ts = Timestamp::parse("<2024-01-01 Mon .+2d/3d>").unwrap();
+        assert_eq!(
+            ts.repeater,
+            Some(Repeater {
+                repeater_type: RepeaterType::Restart,
+                value: 2,
+                unit: TimeUnit::Day,
+                deadline: Some((3, TimeUnit::Day)),
+            })
+        );
+        assert_eq!(ts.repeater.unwrap().to_string(), ".+2d/3d");
+
+        assert!(Timestamp::parse("<2024-02-30 Fri>").is_none());
+        assert!(Timestamp::parse("<2024-02-03 Sat 25:00>").is_none());
+    }
+
+    #[test]
+    fn test_occurrences() {
+        let starts = |ts: &str, from: &str, to: &str| {
+            Timestamp::parse(ts)
+                .unwrap()
+                .occurrences(date(from), date(to))
+                .iter()
+                .map(|e| e.start.format("%Y-%m-%d").to_string())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            starts("<2024-01-01 Mon +1w>", "2024-01-10", "2024-01-31"),
+            vec!["2024-01-15", "2024-01-22", "2024-01-29"]
+        );
+        assert_eq!(
+            starts("<2024-01-31 Wed ++1m>", "2024-01-01", "2024-04-30"),
+            vec!["2024-01-31", "2024-02-29", "2024-03-31", "2024-04-30"]
+        );
+        assert_eq!(
+            starts("<2024-01-01 Mon>", "2024-01-02", "2024-01-31"),
+            Vec::<String>::new()
+        );
+        // range overlapping with the window
+        assert_eq!(
+            starts(
+                "<2024-01-01 Mon>--<2024-01-03 Wed>",
+                "2024-01-02",
+                "2024-01-31"
+            ),
+            vec!["2024-01-01"]
+        );
+        assert_eq!(
+            starts("<2024-01-01 Mon +1d>", "2023-12-01", "2024-01-02"),
+            vec!["2024-01-01", "2024-01-02"]
+        );
+    }
+}
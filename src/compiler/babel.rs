@@ -0,0 +1,1058 @@
+//! Evaluate src blocks by local interpreters, and write outputs back as `#+RESULTS:`
+//!
+//! Evaluation is opt-in: files are evaluated by `kite eval`, or before each build if
+//! `[compiler.babel]` is `enabled`. Only blocks whose language has an interpreter are evaluated.
+//!
+//! - code is piped into the interpreter, which runs in the working directory (`:dir`, or the
+//!   directory of org file) with a cleared environment except `env`, and is killed after
+//!   `timeout_secs`. On unix, it leads a process group, which is killed as a whole, so that no
+//!   process spawned by it is left running
+//! - `:var NAME=VALUE`: VALUE is a number, a `"string"` or the name of another src block whose
+//!   output is used, it's assigned before code (python and shells) or substituted for `$NAME` (sql)
+//! - `:noweb yes`: `<<NAME>>` is replaced with the bodies of blocks named NAME or with
+//!   `:noweb-ref NAME`
+//! - `:results output|value silent|raw|drawer|table|verbatim`, `:file PATH`, `:eval no|never` and
+//!   `:cache yes` follow org-babel, e.g., python body is run as a function by default, and the
+//!   value of its `return` statement is the result, which is `None` without one
+//!
+//! Outputs are cached in `cache_directory`, keyed on blake3 hash of the interpreter, header
+//! arguments and expanded code, so that a block runs again only if changed, remove the directory
+//! to run all blocks again. `:cache yes` additionally writes the hash as `#+RESULTS[HASH]:`, and
+//! such a block is skipped if the hash is not changed. Only results are rewritten, the rest of
+//! file is kept as is.
+//!
+//! Evaluation is not sandboxed: interpreters run with the privileges of the user, and may read,
+//! write or delete any file and access the network, so only evaluate files you trust. The cleared
+//! environment and the timeout guard against accidents, not against malicious code. `:dir` is
+//! confined to the directory of org file, i.e., it must be relative and can't go up with `..`.
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::io::{Read, Write};
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use walkdir::WalkDir;
+
+use crate::compiler::ast_builder::element::SrcBlock;
+use crate::compiler::ast_builder::{AstBuilder, AstError};
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::parser::OrgParser;
+use crate::compiler::parser::config::OrgParserConfig;
+use crate::compiler::parser::syntax::{OrgSyntaxKind, SyntaxNode};
+
+/// Outputs with more lines are written as example block instead of fixed width
+const MIN_LINES_FOR_BLOCK_OUTPUT: usize = 10;
+
+/// Max depth of nested noweb references
+const MAX_NOWEB_DEPTH: usize = 16;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BabelConfig {
+    /// evaluate src blocks before build
+    pub enabled: bool,
+    /// command line of language, `{KEY}` is replaced with header argument `:KEY`
+    pub interpreters: BTreeMap<String, Vec<String>>,
+    /// seconds before an interpreter is killed
+    pub timeout_secs: u64,
+    /// working directory of interpreters relative to project root, directory of org file if not
+    /// given
+    pub working_directory: Option<PathBuf>,
+    /// environment variables passed to interpreters, others are cleared
+    pub env: Vec<String>,
+    /// directory of cached outputs relative to project root
+    pub cache_directory: PathBuf,
+}
+
+impl BabelConfig {
+    /// Resolve relative paths against project root `d_root`, i.e., the directory of config file
+    pub(crate) fn resolve_paths(&mut self, d_root: &Path) {
+        self.working_directory = self.working_directory.as_ref().map(|e| d_root.join(e));
+        self.cache_directory = d_root.join(&self.cache_directory);
+    }
+}
+
+impl Default for BabelConfig {
+    fn default() -> Self {
+        let interpreters = [
+            ("sh", vec!["sh"]),
+            ("shell", vec!["sh"]),
+            ("bash", vec!["bash"]),
+            ("python", vec!["python3"]),
+            ("sqlite", vec!["sqlite3", "{db}"]),
+        ]
+        .into_iter()
+        .map(|(k, v)| (k.to_string(), v.into_iter().map(String::from).collect()))
+        .collect();
+        Self {
+            enabled: false,
+            interpreters,
+            timeout_secs: 10,
+            working_directory: None,
+            env: ["PATH", "HOME", "LANG"].map(String::from).to_vec(),
+            cache_directory: ".cache/babel".into(),
+        }
+    }
+}
+
+/// How variables are passed to code
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Dialect {
+    Python,
+    Shell,
+    Sql,
+    Other,
+}
+
+impl Dialect {
+    fn of(language: &str) -> Self {
+        match language {
+            "python" | "python3" => Dialect::Python,
+            "sh" | "shell" | "bash" | "zsh" | "dash" | "ksh" => Dialect::Shell,
+            "sql" | "sqlite" | "postgresql" | "mysql" => Dialect::Sql,
+            _ => Dialect::Other,
+        }
+    }
+}
+
+/// Src block collected from syntax tree
+#[derive(Debug)]
+struct Block {
+    /// name, language and header arguments
+    src: SrcBlock,
+    /// code without common indentation and comma escapes
+    body: String,
+    /// range of `#+begin_src` line, used in diagnostics
+    range: Range<usize>,
+    /// indentation of `#+begin_src`
+    indent: String,
+    /// existing results to be replaced, or empty range where results are inserted
+    results_range: Range<usize>,
+    has_results: bool,
+    /// hash in `#+RESULTS[HASH]:`
+    results_hash: Option<String>,
+    /// a blank line is needed after inserted results
+    needs_blank_line: bool,
+}
+
+impl Block {
+    /// The last value of header argument `:key`, except `:var`
+    fn header(&self, key: &str) -> Option<&str> {
+        match key {
+            "results" => self.src.results.as_deref(),
+            "exports" => self.src.exports.as_deref(),
+            _ => self.src.other_args.get(key).map(String::as_str),
+        }
+    }
+
+    /// Words of `:results`
+    fn results(&self) -> Vec<&str> {
+        self.header("results")
+            .map_or(vec![], |e| e.split_whitespace().collect())
+    }
+}
+
+/// State of evaluating one file
+struct Evaluation<'a> {
+    f_org: &'a Path,
+    blocks: Vec<Block>,
+    outputs: HashMap<usize, Result<String, String>>,
+    running: HashSet<usize>,
+    /// blocks whose interpreter is run, i.e., output is not restored from cache
+    ran: HashSet<usize>,
+}
+
+pub struct Evaluator {
+    config: BabelConfig,
+    parser: OrgParser,
+    ast_builder: AstBuilder,
+    /// existing results are kept if output is restored from cache
+    keep_results: bool,
+}
+
+impl Evaluator {
+    pub fn new(config: BabelConfig, parser_config: OrgParserConfig) -> Self {
+        Self {
+            config,
+            parser: OrgParser::new(parser_config),
+            ast_builder: AstBuilder::new(),
+            keep_results: false,
+        }
+    }
+
+    /// Keep existing results of blocks whose code and header arguments are not changed, even if
+    /// the cached output differs from them.
+    ///
+    /// Used by `kite serve`, where a rewritten file triggers another rebuild.
+    pub fn with_keep_results(mut self, keep_results: bool) -> Self {
+        self.keep_results = keep_results;
+        self
+    }
+
+    /// Evaluate org files under `path`, which is a file or directory, and write back changed files.
+    pub fn evaluate_path<P: AsRef<Path>>(&self, path: P) -> std::io::Result<Vec<Diagnostic>> {
+        let mut diagnostics = vec![];
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = entry?;
+            let f_org = entry.path();
+            if !entry.file_type().is_file() || f_org.extension().is_none_or(|e| e != "org") {
+                continue;
+            }
+
+            let input = fs::read_to_string(f_org)?;
+            let (output, file_diagnostics) = self.evaluate(f_org, &input);
+            diagnostics.extend(file_diagnostics);
+            if output != input {
+                fs::write(f_org, output)?;
+                tracing::info!("evaluated {}", f_org.display());
+            }
+        }
+        Ok(diagnostics)
+    }
+
+    /// Evaluate src blocks of `input` read from `f_org`, return `input` with results updated.
+    ///
+    /// Blocks failed to evaluate are reported as diagnostics, their results are kept as is.
+    pub fn evaluate<P: AsRef<Path>>(&self, f_org: P, input: &str) -> (String, Vec<Diagnostic>) {
+        let f_org = f_org.as_ref();
+        let mut diagnostics = vec![];
+        if !input.to_ascii_lowercase().contains("#+begin_src") {
+            return (input.to_string(), diagnostics);
+        }
+        let syntax_tree = self.parser.parse_source(f_org, input, &mut diagnostics);
+        let root = match syntax_tree {
+            Some(root) if diagnostics.is_empty() && root.text() == input => root,
+            _ => {
+                diagnostics.push(Diagnostic::warning(
+                    f_org,
+                    "src blocks are not evaluated since file can't be parsed completely",
+                ));
+                return (input.to_string(), diagnostics);
+            }
+        };
+
+        let mut blocks = vec![];
+        for node in root
+            .descendants()
+            .filter(|e| e.kind() == OrgSyntaxKind::SrcBlock)
+        {
+            match collect_block(&self.ast_builder, f_org, &node) {
+                Ok(block) => blocks.push(block),
+                Err(e) => {
+                    let range = node.text_range();
+                    diagnostics.push(
+                        Diagnostic::warning(f_org, format!("failed to collect src block: {e}"))
+                            .with_span(input, range.start().into()..range.end().into()),
+                    );
+                }
+            }
+        }
+        let mut evaluation = Evaluation {
+            f_org,
+            blocks,
+            outputs: HashMap::new(),
+            running: HashSet::new(),
+            ran: HashSet::new(),
+        };
+
+        let mut edits = vec![];
+        for i in 0..evaluation.blocks.len() {
+            let block = &evaluation.blocks[i];
+            if !self.config.interpreters.contains_key(&block.src.language)
+                || matches!(block.header("eval"), Some("no" | "never"))
+                || block.results().contains(&"silent")
+            {
+                continue;
+            }
+
+            let result = self.prepare(&mut evaluation, i).and_then(|(code, hash)| {
+                let block = &evaluation.blocks[i];
+                let cached = block.header("cache") == Some("yes");
+                if cached && block.results_hash.as_deref() == Some(hash.as_str()) {
+                    return Ok(None);
+                }
+                let output = self.output(&mut evaluation, i, &code, &hash)?;
+                let block = &evaluation.blocks[i];
+                if self.keep_results && block.has_results && !evaluation.ran.contains(&i) {
+                    return Ok(None);
+                }
+                Ok(Some(results_text(block, &output, cached.then_some(&hash))))
+            });
+            let block = &evaluation.blocks[i];
+            match result {
+                Ok(Some(text)) => edits.push((block.results_range.clone(), text)),
+                Ok(None) => {}
+                Err(e) => diagnostics.push(
+                    Diagnostic::warning(f_org, format!("failed to evaluate src block: {e}"))
+                        .with_span(input, block.range.clone()),
+                ),
+            }
+        }
+
+        let mut output = input.to_string();
+        for (range, text) in edits.into_iter().rev() {
+            output.replace_range(range, &text);
+        }
+        (output, diagnostics)
+    }
+
+    /// Expanded code to run and its hash
+    fn prepare(&self, evaluation: &mut Evaluation, i: usize) -> Result<(String, String), String> {
+        let block = &evaluation.blocks[i];
+        let command = self.command(block)?;
+        let body = match block.header("noweb") {
+            Some("no") | Some("tangle") | None => block.body.clone(),
+            Some(_) => expand_noweb(&block.body, &|name| {
+                evaluation
+                    .blocks
+                    .iter()
+                    .filter(|e| {
+                        e.src.name.as_deref() == Some(name) || e.header("noweb-ref") == Some(name)
+                    })
+                    .map(|e| e.body.as_str())
+                    .collect()
+            })?,
+        };
+
+        let assignments = block.src.vars.clone();
+        let mut vars = vec![];
+        for (name, value) in assignments {
+            let value = self.var_value(evaluation, &value)?;
+            vars.push((name, value));
+        }
+
+        let block = &evaluation.blocks[i];
+        let is_value = !block.results().contains(&"output");
+        let code = build_code(&block.src.language, &body, &vars, is_value)?;
+
+        let mut headers = block
+            .src
+            .other_args
+            .iter()
+            .filter(|(k, _)| *k != "cache")
+            .map(|(k, v)| format!(":{k} {v}"))
+            .chain(block.src.results.iter().map(|v| format!(":results {v}")))
+            .chain(block.src.vars.iter().map(|(k, v)| format!(":var {k}={v}")))
+            .collect::<Vec<_>>();
+        headers.sort();
+        let hash = blake3::hash(
+            format!(
+                "{}\0{}\0{}\0{}",
+                block.src.language,
+                command.join(" "),
+                headers.join(" "),
+                code
+            )
+            .as_bytes(),
+        )
+        .to_hex()
+        .to_string();
+        Ok((code, hash))
+    }
+
+    /// Output of block `i`, restored from cache or by running its interpreter
+    fn output(
+        &self,
+        evaluation: &mut Evaluation,
+        i: usize,
+        code: &str,
+        hash: &str,
+    ) -> Result<String, String> {
+        if let Some(output) = evaluation.outputs.get(&i) {
+            return output.clone();
+        }
+
+        let f_cache = self.config.cache_directory.join(hash);
+        let output = match fs::read_to_string(&f_cache) {
+            Ok(output) => Ok(output),
+            Err(_) => {
+                let block = &evaluation.blocks[i];
+                let dir = self.working_directory(evaluation.f_org, block)?;
+                let output = self.run(&self.command(block)?, code, &dir);
+                evaluation.ran.insert(i);
+                if let Ok(output) = &output {
+                    let _ = fs::create_dir_all(&self.config.cache_directory);
+                    if let Err(e) = fs::write(&f_cache, output) {
+                        tracing::warn!("failed to cache output in {}: {}", f_cache.display(), e);
+                    }
+                }
+                output
+            }
+        };
+        evaluation.outputs.insert(i, output.clone());
+        output
+    }
+
+    /// Value of `:var`: number, string, or output of the named src block
+    fn var_value(&self, evaluation: &mut Evaluation, value: &str) -> Result<String, String> {
+        if let Some(s) = value.strip_prefix('"').and_then(|e| e.strip_suffix('"')) {
+            return Ok(s.replace("\\\"", "\"").replace("\\\\", "\\"));
+        }
+        if value.parse::<f64>().is_ok() {
+            return Ok(value.to_string());
+        }
+
+        let i = evaluation
+            .blocks
+            .iter()
+            .position(|e| e.src.name.as_deref() == Some(value))
+            .ok_or_else(|| format!("no src block named {value}"))?;
+        if !evaluation.running.insert(i) {
+            return Err(format!("circular reference of {value}"));
+        }
+        let output = self
+            .prepare(evaluation, i)
+            .and_then(|(code, hash)| self.output(evaluation, i, &code, &hash));
+        evaluation.running.remove(&i);
+        Ok(output?.trim_end().to_string())
+    }
+
+    /// Command line of interpreter, with placeholders replaced by header arguments
+    fn command(&self, block: &Block) -> Result<Vec<String>, String> {
+        let command = self
+            .config
+            .interpreters
+            .get(&block.src.language)
+            .ok_or_else(|| format!("no interpreter for {}", block.src.language))?;
+        command
+            .iter()
+            .map(
+                |arg| match arg.strip_prefix('{').and_then(|e| e.strip_suffix('}')) {
+                    Some(key) => block
+                        .header(key)
+                        .map(String::from)
+                        .ok_or_else(|| format!("missing header argument :{key}")),
+                    None => Ok(arg.clone()),
+                },
+            )
+            .collect()
+    }
+
+    /// `:dir` under the directory of org file, `working_directory`, or the directory of org file
+    fn working_directory(&self, f_org: &Path, block: &Block) -> Result<PathBuf, String> {
+        let d_org = f_org.parent().unwrap_or(Path::new("."));
+        match (block.header("dir"), &self.config.working_directory) {
+            (Some(dir), _) => {
+                let normalized = crate::utils::normalize(Path::new(dir));
+                match normalized.components().next() {
+                    Some(Component::Normal(_)) | None => Ok(d_org.join(normalized)),
+                    _ => Err(format!(":dir {dir} is out of the directory of org file")),
+                }
+            }
+            (None, Some(dir)) => Ok(dir.clone()),
+            (None, None) => Ok(d_org.to_path_buf()),
+        }
+    }
+
+    /// Run `command` with `code` as stdin, return stdout
+    fn run(&self, command: &[String], code: &str, dir: &Path) -> Result<String, String> {
+        let (program, args) = command.split_first().ok_or("empty interpreter")?;
+        let mut cmd = Command::new(program);
+        cmd.args(args)
+            .current_dir(if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            })
+            .env_clear()
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
+        for key in self.config.env.iter() {
+            if let Ok(value) = std::env::var(key) {
+                cmd.env(key, value);
+            }
+        }
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+        let mut child = cmd
+            .spawn()
+            .map_err(|e| format!("failed to run {program}: {e}"))?;
+
+        // pipes are served by threads, so that a chatty process never blocks
+        let mut stdin = child.stdin.take().expect("piped stdin");
+        let code = code.to_string();
+        thread::spawn(move || stdin.write_all(code.as_bytes()));
+        let read_to_string = |mut reader: Box<dyn Read + Send>| {
+            thread::spawn(move || {
+                let mut s = String::new();
+                reader.read_to_string(&mut s).map(|_| s)
+            })
+        };
+        let stdout = read_to_string(Box::new(child.stdout.take().expect("piped stdout")));
+        let stderr = read_to_string(Box::new(child.stderr.take().expect("piped stderr")));
+
+        let deadline = Instant::now() + Duration::from_secs(self.config.timeout_secs);
+        let status = loop {
+            match child.try_wait() {
+                Ok(Some(status)) => break status,
+                Ok(None) if Instant::now() < deadline => thread::sleep(Duration::from_millis(10)),
+                Ok(None) => {
+                    kill(&mut child);
+                    return Err(format!("killed after {}s", self.config.timeout_secs));
+                }
+                Err(e) => {
+                    kill(&mut child);
+                    return Err(e.to_string());
+                }
+            }
+        };
+        // processes left in background would keep stdout open
+        kill(&mut child);
+
+        let stdout = stdout
+            .join()
+            .map_err(|_| "failed to read stdout")?
+            .map_err(|e| e.to_string())?;
+        if !status.success() {
+            let stderr = stderr.join().ok().and_then(|e| e.ok()).unwrap_or_default();
+            return Err(format!(
+                "{program} exited with {status}: {}",
+                stderr.trim_end()
+            ));
+        }
+        Ok(stdout)
+    }
+}
+
+/// Kill `child` and processes spawned by it, i.e., its process group on unix
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    {
+        // SAFETY: kill(2) has no memory safety requirements. The child leads its own process
+        // group, whose id is the pid of child.
+        unsafe {
+            libc::kill(-(child.id() as libc::pid_t), libc::SIGKILL);
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+fn collect_block(
+    ast_builder: &AstBuilder,
+    f_org: &Path,
+    node: &SyntaxNode,
+) -> Result<Block, AstError> {
+    let begin = node
+        .first_child_by_kind(&|c| c == OrgSyntaxKind::BlockBegin)
+        .expect("src block has begin row");
+    let end = node.first_child_by_kind(&|c| c == OrgSyntaxKind::BlockEnd);
+    let body = node
+        .first_child_by_kind(&|c| c == OrgSyntaxKind::BlockContent)
+        .map_or(String::new(), |e| unindent(&e.text().to_string()));
+    let indent = begin
+        .first_token()
+        .filter(|e| e.kind() == OrgSyntaxKind::Whitespace)
+        .map_or(String::new(), |e| e.text().to_string());
+
+    let begin_range = begin.text_range();
+    let end_offset = end
+        .as_ref()
+        .map_or(begin_range.end(), |e| e.text_range().end());
+    let next = node.next_sibling().filter(is_results);
+    let (results_range, results_hash) = match &next {
+        Some(next) => {
+            let start = usize::from(next.text_range().start());
+            let keyword = match next.kind() {
+                OrgSyntaxKind::Keyword => Some(next.clone()),
+                _ => next
+                    .children()
+                    .filter(|e| e.kind() == OrgSyntaxKind::AffiliatedKeyword)
+                    .find(|e| keyword_key(e).eq_ignore_ascii_case("RESULTS")),
+            };
+            let hash = keyword
+                .and_then(|e| e.first_child_by_kind(&|c| c == OrgSyntaxKind::KeywordOptvalue))
+                .map(|e| e.text().to_string());
+            (start..start + content_end(&next.text().to_string()), hash)
+        }
+        None => {
+            let end = usize::from(end_offset);
+            (end..end, None)
+        }
+    };
+    // blank lines after `#+end_src` belong to src block
+    let has_blank_lines = usize::from(node.text_range().end()) > usize::from(end_offset);
+
+    Ok(Block {
+        src: ast_builder.build_src_block(node, f_org)?,
+        body,
+        range: usize::from(begin_range.start())..usize::from(begin_range.end()),
+        indent,
+        results_range,
+        has_results: next.is_some(),
+        results_hash,
+        needs_blank_line: next.is_none()
+            && !has_blank_lines
+            && node.next_sibling_or_token().is_some(),
+    })
+}
+
+fn keyword_key(node: &SyntaxNode) -> String {
+    node.first_child_by_kind(&|c| c == OrgSyntaxKind::KeywordKey)
+        .map_or(String::new(), |e| e.text().to_string())
+}
+
+/// `#+RESULTS:` keyword, or element with affiliated keyword `#+RESULTS:`
+fn is_results(node: &SyntaxNode) -> bool {
+    match node.kind() {
+        OrgSyntaxKind::Keyword => keyword_key(node).eq_ignore_ascii_case("RESULTS"),
+        _ => node
+            .children()
+            .filter(|e| e.kind() == OrgSyntaxKind::AffiliatedKeyword)
+            .any(|e| keyword_key(&e).eq_ignore_ascii_case("RESULTS")),
+    }
+}
+
+/// `:key value` pairs of header arguments in order, `:var` may occur more than once
+pub(crate) fn parse_header_arguments(s: &str) -> Vec<(String, String)> {
+    let mut headers: Vec<(String, String)> = vec![];
+    for word in split_top_level(s, ' ') {
+        match word.strip_prefix(':') {
+            Some(key) if !key.is_empty() => headers.push((key.to_lowercase(), String::new())),
+            _ => {
+                if let Some((_, value)) = headers.last_mut() {
+                    if !value.is_empty() {
+                        value.push(' ');
+                    }
+                    value.push_str(&word);
+                }
+            }
+        }
+    }
+    headers
+}
+
+/// Split `s` by `sep` outside of quotes and parentheses, empty parts are dropped
+pub(crate) fn split_top_level(s: &str, sep: char) -> Vec<String> {
+    let mut parts = vec![];
+    let mut part = String::new();
+    let mut in_quote = false;
+    let mut depth = 0usize;
+    for c in s.chars() {
+        match c {
+            '"' => in_quote = !in_quote,
+            '(' if !in_quote => depth += 1,
+            ')' if !in_quote => depth = depth.saturating_sub(1),
+            _ if c == sep && !in_quote && depth == 0 => {
+                if !part.trim().is_empty() {
+                    parts.push(part.trim().to_string());
+                }
+                part.clear();
+                continue;
+            }
+            _ => {}
+        }
+        part.push(c);
+    }
+    if !part.trim().is_empty() {
+        parts.push(part.trim().to_string());
+    }
+    parts
+}
+
+/// Remove common indentation and comma escapes, i.e., `,*` and `,#+`
+pub(crate) fn unindent(s: &str) -> String {
+    let n = s
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| line.len() - line.trim_start_matches([' ', '\t']).len())
+        .min()
+        .unwrap_or(0);
+    s.lines()
+        .map(|line| {
+            let line = line.get(n..).unwrap_or("");
+            match line.trim_start().strip_prefix(',') {
+                Some(rest) if rest.starts_with('*') || rest.starts_with("#+") => {
+                    line.replacen(',', "", 1)
+                }
+                _ => line.to_string(),
+            }
+        })
+        .map(|line| line + "\n")
+        .collect()
+}
+
+/// Replace `<<NAME>>` with bodies given by `bodies_of(NAME)`, the prefix and suffix of the
+/// reference are repeated for each line of bodies
+pub(crate) fn expand_noweb<'a>(
+    body: &str,
+    bodies_of: &dyn Fn(&str) -> Vec<&'a str>,
+) -> Result<String, String> {
+    expand_noweb_nested(body, bodies_of, 0)
+}
+
+fn expand_noweb_nested<'a>(
+    body: &str,
+    bodies_of: &dyn Fn(&str) -> Vec<&'a str>,
+    depth: usize,
+) -> Result<String, String> {
+    if depth > MAX_NOWEB_DEPTH {
+        return Err("noweb references are nested too deep".to_string());
+    }
+    let mut s = String::new();
+    for line in body.lines() {
+        let reference = line.split_once("<<").and_then(|(prefix, rest)| {
+            let (name, suffix) = rest.split_once(">>")?;
+            let is_name = !name.is_empty() && !name.contains(char::is_whitespace);
+            is_name.then_some((prefix, name, suffix))
+        });
+        let Some((prefix, name, suffix)) = reference else {
+            s.push_str(line);
+            s.push('\n');
+            continue;
+        };
+        let bodies = bodies_of(name)
+            .into_iter()
+            .map(|e| expand_noweb_nested(e, bodies_of, depth + 1))
+            .collect::<Result<Vec<_>, _>>()?;
+        if bodies.is_empty() {
+            return Err(format!("unresolved noweb reference <<{name}>>"));
+        }
+        for expanded in bodies.iter().flat_map(|e| e.lines()) {
+            s.push_str(&format!("{prefix}{expanded}{suffix}\n"));
+        }
+    }
+    Ok(s)
+}
+
+/// Code run by interpreter: variables assigned, and python body wrapped to print its value
+fn build_code(
+    language: &str,
+    body: &str,
+    vars: &[(String, String)],
+    is_value: bool,
+) -> Result<String, String> {
+    let is_number = |v: &str| v.parse::<f64>().is_ok();
+    match Dialect::of(language) {
+        Dialect::Python => {
+            let mut s = vars
+                .iter()
+                .map(|(k, v)| match is_number(v) {
+                    true => format!("{k} = {v}\n"),
+                    false => format!("{k} = \"{}\"\n", escape(v, &['\\', '"'])),
+                })
+                .collect::<String>();
+            if is_value {
+                s.push_str("def main():\n");
+                for line in body.lines() {
+                    s.push_str(&format!("    {line}\n"));
+                }
+                s.push_str("    pass\nprint(main())\n");
+            } else {
+                s.push_str(body);
+            }
+            Ok(s)
+        }
+        Dialect::Shell => {
+            let mut s = vars
+                .iter()
+                .map(|(k, v)| format!("{k}='{}'\n", v.replace('\'', "'\\''")))
+                .collect::<String>();
+            s.push_str(body);
+            Ok(s)
+        }
+        Dialect::Sql => Ok(vars
+            .iter()
+            .fold(body.to_string(), |s, (k, v)| s.replace(&format!("${k}"), v))),
+        Dialect::Other if vars.is_empty() => Ok(body.to_string()),
+        Dialect::Other => Err(format!(":var is not supported for {language}")),
+    }
+}
+
+fn escape(s: &str, chars: &[char]) -> String {
+    let mut escaped = String::new();
+    for c in s.chars() {
+        match c {
+            '\n' => escaped.push_str("\\n"),
+            _ if chars.contains(&c) => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// `#+RESULTS:` with formatted output, indented as the src block
+fn results_text(block: &Block, output: &str, hash: Option<&String>) -> String {
+    let output = output.trim_end_matches('\n');
+    let results = block.results();
+    let lines = if let Some(file) = block.header("file") {
+        vec![format!("[[file:{file}]]")]
+    } else if results.contains(&"raw") {
+        output.lines().map(String::from).collect()
+    } else if results.contains(&"drawer") {
+        let mut lines = vec![":results:".to_string()];
+        lines.extend(output.lines().map(String::from));
+        lines.push(":end:".to_string());
+        lines
+    } else if results.iter().any(|e| matches!(*e, "table" | "vector")) {
+        table_lines(output)
+    } else if output.is_empty() {
+        vec![]
+    } else if output.lines().count() > MIN_LINES_FOR_BLOCK_OUTPUT {
+        let mut lines = vec!["#+begin_example".to_string()];
+        lines.extend(output.lines().map(|line| {
+            match line.trim_start().starts_with('*') || line.trim_start().starts_with("#+") {
+                true => format!(",{line}"),
+                false => line.to_string(),
+            }
+        }));
+        lines.push("#+end_example".to_string());
+        lines
+    } else {
+        output
+            .lines()
+            .map(|line| match line.is_empty() {
+                true => ":".to_string(),
+                false => format!(": {line}"),
+            })
+            .collect()
+    };
+
+    let indent = &block.indent;
+    let hash = hash.map_or(String::new(), |e| format!("[{e}]"));
+    let name = block
+        .src
+        .name
+        .as_ref()
+        .map_or(String::new(), |e| format!(" {e}"));
+    let mut s = format!("{indent}#+RESULTS{hash}:{name}\n");
+    for line in lines {
+        match line.is_empty() {
+            true => s.push('\n'),
+            false => s.push_str(&format!("{indent}{line}\n")),
+        }
+    }
+    if !block.has_results {
+        s.insert(0, '\n');
+        if block.needs_blank_line {
+            s.push('\n');
+        }
+    }
+    s
+}
+
+/// Lines of aligned org table, cells are separated by tab or `|`
+fn table_lines(output: &str) -> Vec<String> {
+    let rows = output
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            line.split(['\t', '|'])
+                .map(|e| e.trim().to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    let n_column = rows.iter().map(|e| e.len()).max().unwrap_or(0);
+    let widths = (0..n_column)
+        .map(|j| {
+            rows.iter()
+                .filter_map(|row| row.get(j))
+                .map(|e| e.chars().count())
+                .max()
+                .unwrap_or(0)
+        })
+        .collect::<Vec<_>>();
+    rows.iter()
+        .map(|row| {
+            let cells = widths
+                .iter()
+                .enumerate()
+                .map(|(j, width)| {
+                    let cell = row.get(j).map_or("", |e| e.as_str());
+                    format!(" {cell}{} |", " ".repeat(width - cell.chars().count()))
+                })
+                .collect::<String>();
+            format!("|{cells}")
+        })
+        .collect()
+}
+
+/// End of the last non-blank line, i.e., trailing blank lines are excluded
+fn content_end(s: &str) -> usize {
+    match s.rfind(|c: char| !c.is_whitespace()) {
+        Some(i) => s[i..].find('\n').map_or(s.len(), |j| i + j + 1),
+        None => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn evaluator(name: &str, timeout_secs: u64) -> Evaluator {
+        let config = BabelConfig {
+            timeout_secs,
+            cache_directory: std::env::temp_dir()
+                .join(format!("windancer_babel_{}_{name}", std::process::id())),
+            ..BabelConfig::default()
+        };
+        Evaluator::new(config, OrgParserConfig::default())
+    }
+
+    #[test]
+    fn test_header_arguments() {
+        assert_eq!(
+            parse_header_arguments(r#":var x=1, s="a b" :results output table :noweb yes"#),
+            vec![
+                ("var".to_string(), r#"x=1, s="a b""#.to_string()),
+                ("results".to_string(), "output table".to_string()),
+                ("noweb".to_string(), "yes".to_string()),
+            ]
+        );
+        assert_eq!(
+            split_top_level(r#"x=1, s="a, b", t=f(1, 2)"#, ','),
+            vec!["x=1", r#"s="a, b""#, "t=f(1, 2)"]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_shell() {
+        let input = r#"* code
+#+NAME: greeting
+#+begin_src sh :var who="org" :results output
+echo "hello $who"
+#+end_src
+text
+
+#+begin_src sh :var x=greeting :noweb yes
+<<prefix>> "$x!"
+#+end_src
+
+#+RESULTS:
+: stale
+
+#+begin_src sh :noweb-ref prefix :eval no
+echo
+#+end_src
+"#;
+        let evaluator = evaluator("shell", 10);
+        let (output, diagnostics) = evaluator.evaluate("test.org", input);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        assert_eq!(
+            output,
+            r#"* code
+#+NAME: greeting
+#+begin_src sh :var who="org" :results output
+echo "hello $who"
+#+end_src
+
+#+RESULTS: greeting
+: hello org
+
+text
+
+#+begin_src sh :var x=greeting :noweb yes
+<<prefix>> "$x!"
+#+end_src
+
+#+RESULTS:
+: hello org!
+
+#+begin_src sh :noweb-ref prefix :eval no
+echo
+#+end_src
+"#
+        );
+
+        // results are replaced in place
+        let (output2, _) = evaluator.evaluate("test.org", &output);
+        assert_eq!(output, output2);
+        fs::remove_dir_all(&evaluator.config.cache_directory).expect("remove");
+    }
+
+    #[test]
+    fn test_evaluate_cached() {
+        let d_run =
+            std::env::temp_dir().join(format!("windancer_babel_{}_runs", std::process::id()));
+        fs::create_dir_all(&d_run).expect("create dir");
+        // interpreters run in the directory of org file
+        let f_org = d_run.join("test.org");
+        let input = "#+begin_src sh\necho run >> runs; wc -l < runs\n#+end_src\n";
+        let plain = evaluator("cached", 10);
+        let (output, _) = plain.evaluate(&f_org, input);
+        assert!(output.contains("#+RESULTS:\n: 1\n"), "{output}");
+        // restored from cache without `:cache yes`
+        let (output2, _) = plain.evaluate(&f_org, &output);
+        assert_eq!(output, output2);
+
+        // results edited by hand are restored, unless kept
+        let edited = output.replace(": 1\n", ": edited\n");
+        let (output2, _) = plain.evaluate(&f_org, &edited);
+        assert_eq!(output, output2);
+        let keeping = evaluator("cached", 10).with_keep_results(true);
+        let (output2, _) = keeping.evaluate(&f_org, &edited);
+        assert_eq!(edited, output2);
+
+        // changed code runs again, even if results are kept
+        let (output2, _) = keeping.evaluate(&f_org, &edited.replace("echo run", "echo rerun"));
+        assert!(output2.contains("#+RESULTS:\n: 2\n"), "{output2}");
+
+        // hash is written with `:cache yes`
+        let (output, _) = plain.evaluate(&f_org, &input.replace("sh\n", "sh :cache yes\n"));
+        assert!(output.contains("#+RESULTS["), "{output}");
+        fs::remove_dir_all(&plain.config.cache_directory).expect("remove");
+        fs::remove_dir_all(&d_run).expect("remove");
+    }
+
+    #[test]
+    fn test_evaluate_indented_table_and_error() {
+        let input = "- item\n  #+begin_src sh :results table :cache yes\n  printf 'a\\tbb\\nccc\\td\\n'\n  #+end_src\n- failed\n  #+begin_src sh\n  exit 3\n  #+end_src\n";
+        let evaluator = evaluator("table", 10);
+        let (output, diagnostics) = evaluator.evaluate("test.org", input);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("exited"));
+        let lines = output.lines().collect::<Vec<_>>();
+        assert!(lines[5].starts_with("  #+RESULTS["));
+        assert_eq!(
+            lines[6..9],
+            ["  | a   | bb |", "  | ccc | d  |", "- failed"]
+        );
+
+        // evaluated again only if hash is changed
+        let (output2, _) = evaluator.evaluate("test.org", &output);
+        assert_eq!(output, output2);
+        fs::remove_dir_all(&evaluator.config.cache_directory).expect("remove");
+    }
+
+    #[test]
+    fn test_dir_out_of_org_directory() {
+        let evaluator = evaluator("dir", 10);
+        for dir in ["..", "/tmp", "a/../../b"] {
+            let input = format!("#+begin_src sh :dir {dir}\npwd\n#+end_src\n");
+            let (output, diagnostics) = evaluator.evaluate("test.org", &input);
+            assert_eq!(output, input);
+            assert!(diagnostics[0].message.contains("out of"), "{diagnostics:?}");
+        }
+    }
+
+    #[test]
+    fn test_timeout() {
+        let d_run =
+            std::env::temp_dir().join(format!("windancer_babel_{}_timeout", std::process::id()));
+        fs::create_dir_all(&d_run).expect("create dir");
+        let input = "#+begin_src sh\n(sleep 2; touch survived) &\nsleep 5\n#+end_src\n";
+        let start = Instant::now();
+        let (output, diagnostics) = evaluator("timeout", 1).evaluate(d_run.join("test.org"), input);
+        assert!(start.elapsed() < Duration::from_secs(4));
+        assert_eq!(output, input);
+        assert!(diagnostics[0].message.contains("killed"));
+
+        // processes spawned by the interpreter are killed as well
+        #[cfg(unix)]
+        {
+            thread::sleep(Duration::from_secs(2));
+            assert!(!d_run.join("survived").exists());
+        }
+        fs::remove_dir_all(&d_run).expect("remove");
+    }
+}
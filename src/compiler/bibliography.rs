@@ -0,0 +1,578 @@
+//! Bibliography of citations, loaded from local `.bib` files given by `#+bibliography:`
+//!
+//! - `#+cite_export:` selects the citation style, e.g., `basic author-year` or `basic numeric`
+//! - only cited entries are kept in [`Bibliography`], in order of first citation, `[cite:@*]`
+//!   cites all entries
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum CitationStyle {
+    /// `(Doe, 2020)`
+    #[default]
+    AuthorYear,
+    /// `[1]`
+    Numeric,
+}
+
+impl CitationStyle {
+    /// Style of `#+cite_export: PROCESSOR BIBLIOGRAPHY-STYLE CITATION-STYLE`
+    pub fn from_cite_export(value: &str) -> Self {
+        let numeric = value.split_whitespace().any(|e| {
+            let e = e.to_ascii_lowercase();
+            [
+                "numeric",
+                "ieee",
+                "vancouver",
+                "nature",
+                "acm",
+                "plain",
+                "unsrt",
+            ]
+            .iter()
+            .any(|style| e.trim_end_matches(".csl").contains(style))
+        });
+        if numeric {
+            CitationStyle::Numeric
+        } else {
+            CitationStyle::AuthorYear
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BibEntry {
+    pub key: String,
+    /// lowercase entry type, e.g., "article"
+    pub entry_type: String,
+    /// lowercase field name -> raw value without delimiters
+    pub fields: BTreeMap<String, String>,
+}
+
+impl BibEntry {
+    /// Field without TeX markup
+    pub fn field(&self, name: &str) -> Option<String> {
+        self.fields.get(name).map(|e| clean_tex(e))
+    }
+
+    /// Names of authors (or editors), as "First Last"
+    pub fn authors(&self) -> Vec<String> {
+        let Some(names) = self.fields.get("author").or(self.fields.get("editor")) else {
+            return vec![];
+        };
+        split_names(names)
+            .into_iter()
+            .map(|name| match split_top_level(name, ',').as_slice() {
+                [last, first, ..] => clean_tex(&format!("{} {}", first.trim(), last.trim())),
+                _ => clean_tex(name.trim()),
+            })
+            .collect()
+    }
+
+    /// Last names of authors (or editors)
+    pub fn last_names(&self) -> Vec<String> {
+        let Some(names) = self.fields.get("author").or(self.fields.get("editor")) else {
+            return vec![];
+        };
+        split_names(names)
+            .into_iter()
+            .map(|name| {
+                let name = name.trim();
+                match split_top_level(name, ',').first() {
+                    Some(last) if name.contains(',') => clean_tex(last.trim()),
+                    // last word, a braced group is one word
+                    _ => clean_tex(split_top_level(name, ' ').last().copied().unwrap_or(name)),
+                }
+            })
+            .collect()
+    }
+
+    /// Year of `year` or `date` field
+    pub fn year(&self) -> Option<String> {
+        self.field("year")
+            .or_else(|| self.field("date").map(|e| e.chars().take(4).collect()))
+    }
+}
+
+/// Cited entries of a document and the style to cite them
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Bibliography {
+    pub style: CitationStyle,
+    /// in order of first citation
+    pub entries: Vec<BibEntry>,
+}
+
+impl Bibliography {
+    /// Entry of `key` and its number (1-based) in numeric style
+    pub fn get(&self, key: &str) -> Option<(usize, &BibEntry)> {
+        self.entries
+            .iter()
+            .enumerate()
+            .find(|(_, e)| e.key == key)
+            .map(|(i, e)| (i + 1, e))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BibError {
+    pub message: String,
+    /// byte offset in the input
+    pub offset: usize,
+}
+
+impl fmt::Display for BibError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for BibError {}
+
+/// Files of `#+bibliography:` in `content` of `f_org`, relative to the directory of `f_org`
+pub fn bibliography_files(f_org: &Path, content: &str) -> Vec<PathBuf> {
+    let d_org = f_org.parent().unwrap_or(Path::new(""));
+    content
+        .lines()
+        .filter_map(|line| {
+            let line = line.trim_start();
+            let (key, value) = line.strip_prefix("#+")?.split_once(':')?;
+            key.eq_ignore_ascii_case("bibliography")
+                .then(|| d_org.join(value.trim()))
+        })
+        .collect()
+}
+
+/// Parse entries of BibTeX, `@string` macros are expanded, `@comment` and `@preamble` are
+/// ignored.
+pub fn parse_bibtex(input: &str) -> Result<Vec<BibEntry>, BibError> {
+    BibParser {
+        input,
+        pos: 0,
+        strings: HashMap::new(),
+    }
+    .parse()
+}
+
+struct BibParser<'a> {
+    input: &'a str,
+    pos: usize,
+    strings: HashMap<String, String>,
+}
+
+impl<'a> BibParser<'a> {
+    fn error<S: Into<String>>(&self, offset: usize, message: S) -> BibError {
+        BibError {
+            message: message.into(),
+            offset,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn read_while(&mut self, f: impl Fn(char) -> bool) -> &'a str {
+        let rest = self.rest();
+        let n = rest.find(|c: char| !f(c)).unwrap_or(rest.len());
+        self.pos += n;
+        &rest[..n]
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), BibError> {
+        self.skip_whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(self.pos, format!("expected `{c}`")))
+        }
+    }
+
+    fn parse(mut self) -> Result<Vec<BibEntry>, BibError> {
+        let mut entries: Vec<BibEntry> = vec![];
+        // text outside entries is comment
+        while let Some(i) = self.rest().find('@') {
+            let start = self.pos + i;
+            self.pos = start + 1;
+            let entry_type = self
+                .read_while(|c| c.is_ascii_alphanumeric())
+                .to_lowercase();
+            self.skip_whitespace();
+            let close = match self.peek() {
+                Some('{') => '}',
+                Some('(') => ')',
+                _ => return Err(self.error(start, "expected `{` after entry type")),
+            };
+            let open = self.pos;
+
+            match entry_type.as_str() {
+                "comment" | "preamble" => {
+                    self.skip_balanced(open)?;
+                }
+                "string" => {
+                    self.pos += 1;
+                    let fields = self.parse_fields(close, open)?;
+                    self.strings.extend(fields);
+                }
+                "" => return Err(self.error(start, "missing entry type")),
+                _ => {
+                    self.pos += 1;
+                    self.skip_whitespace();
+                    let key = self
+                        .read_while(|c| c != ',' && c != close && !c.is_whitespace())
+                        .to_string();
+                    if key.is_empty() {
+                        return Err(self.error(self.pos, "missing citation key"));
+                    }
+                    self.skip_whitespace();
+                    if self.peek() == Some(',') {
+                        self.pos += 1;
+                    }
+                    let fields = self.parse_fields(close, open)?;
+                    if entries.iter().any(|e| e.key == key) {
+                        return Err(self.error(start, format!("duplicate key `{key}`")));
+                    }
+                    entries.push(BibEntry {
+                        key,
+                        entry_type,
+                        fields,
+                    });
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    /// `name = value, ...` until `close`
+    fn parse_fields(
+        &mut self,
+        close: char,
+        open: usize,
+    ) -> Result<BTreeMap<String, String>, BibError> {
+        let mut fields = BTreeMap::new();
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some(c) if c == close => {
+                    self.pos += 1;
+                    return Ok(fields);
+                }
+                None => return Err(self.error(open, format!("missing `{close}` of entry"))),
+                _ => {}
+            }
+            let name_offset = self.pos;
+            let name = self
+                .read_while(|c| c.is_ascii_alphanumeric() || "_-:.+".contains(c))
+                .to_lowercase();
+            if name.is_empty() {
+                return Err(self.error(name_offset, "expected field name"));
+            }
+            self.expect('=')?;
+            let value = self.parse_value()?;
+            fields.insert(name, value);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(c) if c == close => {}
+                None => {}
+                _ => return Err(self.error(self.pos, "expected `,` between fields")),
+            }
+        }
+    }
+
+    /// parts joined by `#`: `{..}`, `".."`, number or `@string` macro
+    fn parse_value(&mut self) -> Result<String, BibError> {
+        let mut value = String::new();
+        loop {
+            self.skip_whitespace();
+            let start = self.pos;
+            match self.peek() {
+                Some('{') => {
+                    self.skip_balanced(start)?;
+                    value.push_str(&self.input[start + 1..self.pos - 1]);
+                }
+                Some('"') => {
+                    self.pos += 1;
+                    let mut depth = 0;
+                    loop {
+                        match self.peek() {
+                            None => return Err(self.error(start, "unterminated `\"`")),
+                            Some('{') => depth += 1,
+                            Some('}') => depth -= 1,
+                            Some('"') if depth == 0 => break,
+                            _ => {}
+                        }
+                        self.pos += self.peek().map_or(1, char::len_utf8);
+                    }
+                    value.push_str(&self.input[start + 1..self.pos]);
+                    self.pos += 1;
+                }
+                Some(c) if c.is_ascii_alphanumeric() => {
+                    let name = self.read_while(|c| c.is_ascii_alphanumeric() || "_-:.".contains(c));
+                    match self.strings.get(&name.to_lowercase()) {
+                        Some(e) => value.push_str(e),
+                        None => value.push_str(month(name).unwrap_or(name)),
+                    }
+                }
+                _ => return Err(self.error(start, "expected field value")),
+            }
+            self.skip_whitespace();
+            if self.peek() == Some('#') {
+                self.pos += 1;
+            } else {
+                return Ok(value);
+            }
+        }
+    }
+
+    /// skip `{..}` or `(..)` starting at current position
+    fn skip_balanced(&mut self, start: usize) -> Result<(), BibError> {
+        let (open, close) = match self.peek() {
+            Some('(') => ('(', ')'),
+            _ => ('{', '}'),
+        };
+        let mut depth = 0;
+        for (i, c) in self.rest().char_indices() {
+            if c == open {
+                depth += 1;
+            } else if c == close {
+                depth -= 1;
+                if depth == 0 {
+                    self.pos += i + 1;
+                    return Ok(());
+                }
+            }
+        }
+        Err(self.error(start, format!("unmatched `{open}`")))
+    }
+}
+
+fn month(name: &str) -> Option<&'static str> {
+    let month = match name.to_ascii_lowercase().as_str() {
+        "jan" => "January",
+        "feb" => "February",
+        "mar" => "March",
+        "apr" => "April",
+        "may" => "May",
+        "jun" => "June",
+        "jul" => "July",
+        "aug" => "August",
+        "sep" => "September",
+        "oct" => "October",
+        "nov" => "November",
+        "dec" => "December",
+        _ => return None,
+    };
+    Some(month)
+}
+
+/// split `s` by `sep` outside of braces
+fn split_top_level(s: &str, sep: char) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in s.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ if c == sep && depth == 0 => {
+                parts.push(&s[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&s[start..]);
+    parts.into_iter().filter(|e| !e.trim().is_empty()).collect()
+}
+
+/// names separated by ` and ` outside of braces
+fn split_names(names: &str) -> Vec<&str> {
+    let mut parts = vec![];
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in names.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => depth -= 1,
+            _ if depth == 0 && c.is_whitespace() => {
+                let rest = &names[i + c.len_utf8()..];
+                if rest.get(..3).is_some_and(|e| e.eq_ignore_ascii_case("and"))
+                    && rest[3..].starts_with(char::is_whitespace)
+                {
+                    parts.push(&names[start..i]);
+                    start = i + c.len_utf8() + 3;
+                }
+            }
+            _ => {}
+        }
+    }
+    parts.push(&names[start..]);
+    parts.into_iter().filter(|e| !e.trim().is_empty()).collect()
+}
+
+/// Remove TeX markup from value, e.g., braces, `\&`, `--` and accents like `\"o`
+pub fn clean_tex(value: &str) -> String {
+    let mut output = String::with_capacity(value.len());
+    let mut chars = value.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' | '}' => {}
+            '~' => output.push('\u{a0}'),
+            '-' if chars.peek() == Some(&'-') => {
+                chars.next();
+                if chars.peek() == Some(&'-') {
+                    chars.next();
+                    output.push('—');
+                } else {
+                    output.push('–');
+                }
+            }
+            '\\' => {
+                let Some(&next) = chars.peek() else {
+                    break;
+                };
+                let accent = match next {
+                    '"' => Some('\u{0308}'),
+                    '\'' => Some('\u{0301}'),
+                    '`' => Some('\u{0300}'),
+                    '^' => Some('\u{0302}'),
+                    '~' => Some('\u{0303}'),
+                    '=' => Some('\u{0304}'),
+                    '.' => Some('\u{0307}'),
+                    _ => None,
+                };
+                if let Some(accent) = accent {
+                    chars.next();
+                    // `\"o` or `\"{o}`
+                    while chars.peek() == Some(&'{') {
+                        chars.next();
+                    }
+                    if let Some(letter) = chars.next() {
+                        output.push(letter);
+                        output.push(accent);
+                    }
+                } else if next.is_ascii_alphabetic() {
+                    let mut name = String::new();
+                    while let Some(c) = chars.next_if(|c| c.is_ascii_alphabetic()) {
+                        name.push(c);
+                    }
+                    // command with argument is dropped, e.g., `\textit{..}` -> `..`, otherwise
+                    // its name is kept, e.g., `\TeX` -> `TeX`
+                    if chars.peek() != Some(&'{') {
+                        output.push_str(&name);
+                    }
+                    if chars.peek() == Some(&' ') {
+                        chars.next();
+                    }
+                } else {
+                    // `\&`, `\%`, `\_`, ..
+                    chars.next();
+                    output.push(next);
+                }
+            }
+            c if c.is_whitespace() => {
+                if !output.ends_with(' ') {
+                    output.push(' ');
+                }
+            }
+            _ => output.push(c),
+        }
+    }
+    output.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{CitationStyle, clean_tex, parse_bibtex};
+
+    #[test]
+    fn test_parse_bibtex() {
+        let input = r#"
+% a comment
+@string{ jcp = "Journal of Computational Physics" }
+@Article{doe2020,
+  author = {Doe, John and Richard Roe and {World Health Organization}},
+  title = {On the {Rust} Language},
+  journal = jcp,
+  year = 2020,
+  month = jan,
+  pages = {1--10},
+}
+@comment{ ignored @book{x, title={y}} }
+@book(roe2019, title = "A " # "Book", editor = {M\"{u}ller, Hans})
+"#;
+        let entries = parse_bibtex(input).unwrap();
+        assert_eq!(entries.len(), 2);
+        let doe = &entries[0];
+        assert_eq!(doe.key, "doe2020");
+        assert_eq!(doe.entry_type, "article");
+        assert_eq!(
+            doe.field("journal").as_deref(),
+            Some("Journal of Computational Physics")
+        );
+        assert_eq!(doe.field("title").as_deref(), Some("On the Rust Language"));
+        assert_eq!(doe.field("pages").as_deref(), Some("1–10"));
+        assert_eq!(doe.field("month").as_deref(), Some("January"));
+        assert_eq!(doe.year().as_deref(), Some("2020"));
+        assert_eq!(
+            doe.last_names(),
+            vec!["Doe", "Roe", "World Health Organization"]
+        );
+        assert_eq!(
+            doe.authors(),
+            vec!["John Doe", "Richard Roe", "World Health Organization"]
+        );
+
+        let roe = &entries[1];
+        assert_eq!(roe.field("title").as_deref(), Some("A Book"));
+        assert_eq!(roe.last_names(), vec!["Mu\u{0308}ller"]);
+
+        let error = parse_bibtex("@article{x, title = {a}\n").unwrap_err();
+        assert_eq!(
+            (error.offset, error.message.as_str()),
+            (8, "missing `}` of entry")
+        );
+        let error = parse_bibtex("@article{x, title {a}}").unwrap_err();
+        assert_eq!((error.offset, error.message.as_str()), (18, "expected `=`"));
+    }
+
+    #[test]
+    fn test_citation_style() {
+        assert_eq!(
+            CitationStyle::from_cite_export("basic numeric"),
+            CitationStyle::Numeric
+        );
+        assert_eq!(
+            CitationStyle::from_cite_export("csl ieee.csl"),
+            CitationStyle::Numeric
+        );
+        assert_eq!(
+            CitationStyle::from_cite_export("basic author-year"),
+            CitationStyle::AuthorYear
+        );
+        assert_eq!(
+            CitationStyle::from_cite_export(""),
+            CitationStyle::AuthorYear
+        );
+        assert_eq!(
+            clean_tex(r"Smith \& {Sons}\textit{ Ltd}"),
+            "Smith & Sons Ltd"
+        );
+        assert_eq!(clean_tex(r"The {\TeX}book"), "The TeXbook");
+    }
+}
@@ -0,0 +1,102 @@
+//! Cache of compiled documents, keyed on blake3 hash of org file content
+//!
+//! Unchanged files are restored from cache without parsing. Only the top level file and its
+//! `#+bibliography:` files are hashed, i.e, changes in files included by `#+INCLUDE` are not
+//! detected.
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::ast_builder::element::OrgFile;
+use crate::compiler::content::DocumentMetadata;
+use crate::compiler::diagnostic::Diagnostic;
+
+#[derive(Serialize, Deserialize)]
+struct CompileCacheEntry {
+    hash: String,
+    ast: OrgFile,
+    metadata: DocumentMetadata,
+    diagnostics: Vec<Diagnostic>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct CompileCache {
+    entries: HashMap<PathBuf, CompileCacheEntry>,
+
+    // state during compiling: files (re)compiled
+    #[serde(skip)]
+    changed: BTreeSet<PathBuf>,
+    // state during compiling: files visited
+    #[serde(skip)]
+    visited: BTreeSet<PathBuf>,
+}
+
+impl CompileCache {
+    pub fn hash(content: &[u8]) -> String {
+        blake3::hash(content).to_hex().to_string()
+    }
+
+    /// Cached (ast, metadata, diagnostics) of `full_path` if its content hash is not changed
+    pub(crate) fn get(
+        &self,
+        full_path: &Path,
+        hash: &str,
+    ) -> Option<(OrgFile, DocumentMetadata, Vec<Diagnostic>)> {
+        self.entries
+            .get(full_path)
+            .filter(|entry| entry.hash == hash)
+            .map(|entry| {
+                (
+                    entry.ast.clone(),
+                    entry.metadata.clone(),
+                    entry.diagnostics.clone(),
+                )
+            })
+    }
+
+    /// Mark `full_path` visited, i.e, restored from cache
+    pub(crate) fn visit(&mut self, full_path: &Path) {
+        self.visited.insert(full_path.to_path_buf());
+    }
+
+    /// Insert compiled `full_path`, which is marked visited and changed
+    pub(crate) fn insert(
+        &mut self,
+        full_path: &Path,
+        hash: &str,
+        ast: &OrgFile,
+        metadata: &DocumentMetadata,
+        diagnostics: &[Diagnostic],
+    ) {
+        self.visited.insert(full_path.to_path_buf());
+        self.changed.insert(full_path.to_path_buf());
+        self.entries.insert(
+            full_path.to_path_buf(),
+            CompileCacheEntry {
+                hash: hash.to_string(),
+                ast: ast.clone(),
+                metadata: metadata.clone(),
+                diagnostics: diagnostics.to_vec(),
+            },
+        );
+    }
+
+    /// Finish a round of compiling, return (changed, removed) files of this round.
+    ///
+    /// - changed: files compiled, i.e., not restored from cache
+    /// - removed: files in cache but not visited, their entries are dropped
+    pub fn finish(&mut self) -> (BTreeSet<PathBuf>, BTreeSet<PathBuf>) {
+        let removed = self
+            .entries
+            .keys()
+            .filter(|path| !self.visited.contains(*path))
+            .cloned()
+            .collect::<BTreeSet<_>>();
+        for path in removed.iter() {
+            self.entries.remove(path);
+        }
+        self.visited.clear();
+        (std::mem::take(&mut self.changed), removed)
+    }
+}
@@ -0,0 +1,285 @@
+//! Time tracking reports from `CLOCK:` lines of compiled [`Section`]
+//!
+//! Each closed clock is collected as a [`ClockEntry`] with its file, heading chain and inherited
+//! tags, which are aggregated per heading, tag or date, optionally restricted to a date range.
+//! Running clocks, i.e., without end time, are ignored.
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{NaiveDate, NaiveDateTime};
+
+use crate::compiler::ast_builder::element::{Element, HeadingSubtree};
+use crate::compiler::content::{Document, Section};
+use crate::compiler::dynamic_block::{child_elements, plain_text};
+
+/// A node of heading chain: file or heading
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockTarget {
+    /// url of file, with `#anchor` for heading
+    pub url: String,
+    pub title: String,
+}
+
+/// A closed clock
+#[derive(Debug, Clone)]
+pub struct ClockEntry {
+    /// file and headings from outermost to innermost
+    pub path: Vec<ClockTarget>,
+    /// filetags and tags of ancestor headings
+    pub tags: Vec<String>,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    pub minutes: u64,
+}
+
+/// Clocked time of a file or heading including its subtree
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClockSum {
+    pub target: ClockTarget,
+    /// depth in heading chain, 0 for file
+    pub level: usize,
+    pub minutes: u64,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ClockReport {
+    /// in document order
+    pub entries: Vec<ClockEntry>,
+}
+
+impl ClockReport {
+    pub fn from_section(section: &Section) -> Self {
+        let mut report = Self::default();
+        report.collect_section(section);
+        report
+    }
+
+    pub fn from_document(document: &Document) -> Self {
+        let mut report = Self::default();
+        report.collect_document(document);
+        report
+    }
+
+    fn collect_section(&mut self, section: &Section) {
+        for document in section.documents.iter() {
+            self.collect_document(document);
+        }
+        for subsection in section.subsections.iter() {
+            self.collect_section(subsection);
+        }
+    }
+
+    fn collect_document(&mut self, document: &Document) {
+        let url = format!("/{}", document.html_path());
+        let file = ClockTarget {
+            url: url.clone(),
+            title: document
+                .metadata
+                .title
+                .clone()
+                .unwrap_or_else(|| document.file_info.name.clone()),
+        };
+        let tags = document.metadata.filetags.clone();
+        if let Some(section) = &document.ast.zeroth_section {
+            self.collect_elements(&section.elements, std::slice::from_ref(&file), &tags);
+        }
+
+        fn collect_heading(
+            report: &mut ClockReport,
+            url: &str,
+            heading: &HeadingSubtree,
+            path: &[ClockTarget],
+            tags: &[String],
+        ) {
+            let mut path = path.to_vec();
+            path.push(ClockTarget {
+                url: format!("{url}#{}", heading.anchor),
+                title: plain_text(&heading.title),
+            });
+            let mut tags = tags.to_vec();
+            for tag in heading.tags.iter() {
+                if !tags.contains(tag) {
+                    tags.push(tag.clone());
+                }
+            }
+            if let Some(section) = &heading.section {
+                report.collect_elements(&section.elements, &path, &tags);
+            }
+            for subtree in heading.sub_heading_subtrees.iter() {
+                collect_heading(report, url, subtree, &path, &tags);
+            }
+        }
+        for heading in document.ast.heading_subtrees.iter() {
+            collect_heading(self, &url, heading, std::slice::from_ref(&file), &tags);
+        }
+    }
+
+    fn collect_elements(&mut self, elements: &[Element], path: &[ClockTarget], tags: &[String]) {
+        for element in elements {
+            match element {
+                Element::Clock(clock) => {
+                    if let (Some(end), Some(minutes)) = (clock.end, clock.minutes) {
+                        self.entries.push(ClockEntry {
+                            path: path.to_vec(),
+                            tags: tags.to_vec(),
+                            start: clock.start,
+                            end,
+                            minutes,
+                        });
+                    }
+                }
+                Element::List(list) => {
+                    for item in list.items.iter() {
+                        self.collect_elements(&item.contents, path, tags);
+                    }
+                }
+                _ => {
+                    if let Some(children) = child_elements(element) {
+                        self.collect_elements(children, path, tags);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Entries started in `[from, to]`, `None` means unbounded
+    pub fn between(&self, from: Option<NaiveDate>, to: Option<NaiveDate>) -> Self {
+        let entries = self
+            .entries
+            .iter()
+            .filter(|e| from.is_none_or(|from| e.start.date() >= from))
+            .filter(|e| to.is_none_or(|to| e.start.date() <= to))
+            .cloned()
+            .collect();
+        Self { entries }
+    }
+
+    /// Entries of headings or files tagged with `tag`
+    pub fn tagged(&self, tag: &str) -> Self {
+        let entries = self
+            .entries
+            .iter()
+            .filter(|e| e.tags.iter().any(|t| t == tag))
+            .cloned()
+            .collect();
+        Self { entries }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.entries.iter().map(|e| e.minutes).sum()
+    }
+
+    /// Clocked time of files and headings including subtrees
+    ///
+    /// Entries are in document order, so that ancestors always precede descendants.
+    pub fn by_heading(&self) -> Vec<ClockSum> {
+        let mut sums: Vec<ClockSum> = vec![];
+        let mut url_to_index: HashMap<&str, usize> = HashMap::new();
+        for entry in self.entries.iter() {
+            for (level, target) in entry.path.iter().enumerate() {
+                match url_to_index.get(target.url.as_str()) {
+                    Some(&index) => sums[index].minutes += entry.minutes,
+                    None => {
+                        url_to_index.insert(&target.url, sums.len());
+                        sums.push(ClockSum {
+                            target: target.clone(),
+                            level,
+                            minutes: entry.minutes,
+                        });
+                    }
+                }
+            }
+        }
+        sums
+    }
+
+    /// Clocked time of each tag, an entry is counted once for each of its tags
+    pub fn by_tag(&self) -> BTreeMap<String, u64> {
+        let mut sums = BTreeMap::new();
+        for entry in self.entries.iter() {
+            for tag in entry.tags.iter() {
+                *sums.entry(tag.clone()).or_default() += entry.minutes;
+            }
+        }
+        sums
+    }
+
+    /// Clocked time of each day, by start date of clock
+    pub fn by_date(&self) -> BTreeMap<NaiveDate, u64> {
+        let mut sums = BTreeMap::new();
+        for entry in self.entries.iter() {
+            *sums.entry(entry.start.date()).or_default() += entry.minutes;
+        }
+        sums
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::testing::compile_section;
+
+    fn report(input: &str) -> ClockReport {
+        let section = compile_section(input);
+        ClockReport::from_section(&section)
+    }
+
+    const INPUT: &str = r##"#+title: Log
+#+filetags: :log:
+
+* a :work:
+:LOGBOOK:
+CLOCK: [2024-01-01 Mon 10:00]--[2024-01-01 Mon 11:30] =>  1:30
+:END:
+** b :x:
+CLOCK: [2024-01-02 Tue 10:00]--[2024-01-02 Tue 10:45] =>  0:45
+CLOCK: [2024-01-03 Wed 09:00]
+* c
+- CLOCK: [2024-01-03 Wed 10:00]--[2024-01-03 Wed 10:20]
+"##;
+
+    #[test]
+    fn test_entries() {
+        let report = report(INPUT);
+        assert_eq!(report.entries.len(), 3);
+        assert_eq!(report.total(), 155);
+        assert_eq!(report.entries[1].tags, vec!["log", "work", "x"]);
+        assert_eq!(
+            report.entries[1]
+                .path
+                .iter()
+                .map(|e| e.title.as_str())
+                .collect::<Vec<_>>(),
+            vec!["Log", "a", "b"]
+        );
+        assert_eq!(report.entries[2].minutes, 20);
+    }
+
+    #[test]
+    fn test_aggregation() {
+        let report = report(INPUT);
+        assert_eq!(
+            report
+                .by_heading()
+                .iter()
+                .map(|e| (e.target.title.as_str(), e.level, e.minutes))
+                .collect::<Vec<_>>(),
+            vec![("Log", 0, 155), ("a", 1, 135), ("b", 2, 45), ("c", 1, 20)]
+        );
+        assert_eq!(
+            report.by_tag().into_iter().collect::<Vec<_>>(),
+            vec![
+                ("log".to_string(), 155),
+                ("work".to_string(), 135),
+                ("x".to_string(), 45)
+            ]
+        );
+        let date = |d| NaiveDate::from_ymd_opt(2024, 1, d).unwrap();
+        assert_eq!(
+            report.by_date().into_iter().collect::<Vec<_>>(),
+            vec![(date(1), 90), (date(2), 45), (date(3), 20)]
+        );
+        assert_eq!(report.between(Some(date(2)), None).total(), 65);
+        assert_eq!(report.between(None, Some(date(2))).total(), 135);
+        assert_eq!(report.tagged("x").total(), 45);
+    }
+}
@@ -11,8 +11,10 @@ use std::path::{Path, PathBuf};
 
 use chrono::{DateTime, Local};
 use petgraph::graph::{DiGraph, NodeIndex};
+use serde::{Deserialize, Serialize};
 
 use crate::compiler::ast_builder::element::OrgFile;
+use crate::compiler::diagnostic::Diagnostic;
 use crate::compiler::org_roam::{EdgeType, RoamGraph, RoamNode};
 use crate::compiler::parser::syntax::SyntaxNode;
 use crate::export::ssg::renderer::Renderer; // remove to exporter?
@@ -26,9 +28,20 @@ pub struct Section {
     pub documents: Vec<Document>,
     pub subsections: Vec<Section>,
     pub metadata: SectionMetadata,
+    /// diagnostics of org files in this directory, including files failed to compile
+    pub diagnostics: Vec<Diagnostic>,
 }
 
 impl Section {
+    /// Diagnostics of this section and all subsections
+    pub fn all_diagnostics(&self) -> Vec<&Diagnostic> {
+        let mut diagnostics = self.diagnostics.iter().collect::<Vec<_>>();
+        for subsection in self.subsections.iter() {
+            diagnostics.extend(subsection.all_diagnostics());
+        }
+        diagnostics
+    }
+
     pub fn build_graph(&self) -> RoamGraph {
         let mut graph = DiGraph::<RoamNode, EdgeType>::new();
         let mut id_to_index: HashMap<String, NodeIndex> = HashMap::new();
@@ -77,6 +90,7 @@ impl Section {
                                                 *target_index,
                                                 EdgeType::ExplicitReference {
                                                     source_path: extracted_link.source_path.clone(),
+                                                    context: extracted_link.context.clone(),
                                                 },
                                             );
                                         }
@@ -234,7 +248,7 @@ impl FileInfo {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct DocumentMetadata {
     pub title: Option<String>,
     pub authors: Vec<String>,
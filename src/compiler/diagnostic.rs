@@ -0,0 +1,176 @@
+//! Diagnostics collected during compiling, rendered in rustc style:
+//!
+//! ```text
+//! error: found ']' expected something else
+//!  --> content/foo.org:3:7
+//!   |
+//! 3 | [[id:]]
+//!   |       ^
+//! ```
+//!
+//! Spans refer to the text being parsed, i.e., after `#+INCLUDE` and macros are expanded.
+use std::fmt;
+use std::ops::Range;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// Location in source, line and column are 1-based
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    /// number of chars underlined, at least 1
+    pub len: usize,
+    /// the line containing the span
+    pub source_line: String,
+}
+
+impl Span {
+    /// Span of byte `range` in `source`
+    pub fn new(source: &str, range: Range<usize>) -> Self {
+        let start = floor_char_boundary(source, range.start);
+        let end = floor_char_boundary(source, range.end.max(start));
+
+        let line_start = source[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = source[start..]
+            .find('\n')
+            .map_or(source.len(), |i| start + i);
+        let line = source[..start].matches('\n').count() + 1;
+        let column = source[line_start..start].chars().count() + 1;
+        let len = source[start..end.min(line_end)].chars().count().max(1);
+
+        Self {
+            line,
+            column,
+            len,
+            source_line: source[line_start..line_end].to_string(),
+        }
+    }
+}
+
+fn floor_char_boundary(s: &str, mut i: usize) -> usize {
+    i = i.min(s.len());
+    while !s.is_char_boundary(i) {
+        i -= 1;
+    }
+    i
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub file: PathBuf,
+    pub span: Option<Span>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn error<P: Into<PathBuf>, S: Into<String>>(file: P, message: S) -> Self {
+        Self {
+            file: file.into(),
+            span: None,
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    pub fn warning<P: Into<PathBuf>, S: Into<String>>(file: P, message: S) -> Self {
+        Self {
+            severity: Severity::Warning,
+            ..Self::error(file, message)
+        }
+    }
+
+    /// Attach byte `range` of `source` to the diagnostic
+    pub fn with_span(mut self, source: &str, range: Range<usize>) -> Self {
+        self.span = Some(Span::new(source, range));
+        self
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}: {}", self.severity, self.message)?;
+        match &self.span {
+            Some(span) => {
+                let width = span.line.to_string().len();
+                let pad = " ".repeat(width);
+                writeln!(
+                    f,
+                    "{pad}--> {}:{}:{}",
+                    self.file.display(),
+                    span.line,
+                    span.column
+                )?;
+                writeln!(f, "{pad} |")?;
+                writeln!(f, "{} | {}", span.line, span.source_line)?;
+                let indent = span
+                    .source_line
+                    .chars()
+                    .take(span.column - 1)
+                    .map(|c| if c == '\t' { '\t' } else { ' ' })
+                    .collect::<String>();
+                write!(f, "{pad} | {indent}{}", "^".repeat(span.len))
+            }
+            None => write!(f, " --> {}", self.file.display()),
+        }
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Span};
+
+    #[test]
+    fn test_span() {
+        let source = "* A\nfoo 中文 bar\n";
+        let span = Span::new(source, 8..14);
+        assert_eq!(span.line, 2);
+        assert_eq!(span.column, 5);
+        assert_eq!(span.len, 2);
+        assert_eq!(span.source_line, "foo 中文 bar");
+
+        // empty range at end of input
+        let span = Span::new(source, source.len()..source.len());
+        assert_eq!((span.line, span.column, span.len), (3, 1, 1));
+    }
+
+    #[test]
+    fn test_render() {
+        let diagnostic =
+            Diagnostic::error("foo.org", "invalid timestamp").with_span("* A\n#+DATE: x\n", 12..13);
+        assert_eq!(
+            diagnostic.to_string(),
+            r##"error: invalid timestamp
+ --> foo.org:2:9
+  |
+2 | #+DATE: x
+  |         ^"##
+        );
+
+        let diagnostic = Diagnostic::warning("foo.org", "oops");
+        assert_eq!(diagnostic.to_string(), "warning: oops\n --> foo.org");
+    }
+}
@@ -0,0 +1,754 @@
+//! Built-in writers of dynamic blocks, contents of `#+BEGIN: NAME PARAMETERS` are re-computed at
+//! build time from compiled [`Section`]
+//!
+//! - `clocktable`: clocked time of headings, `:scope file|subtree`, `:maxlevel N`
+//! - `columnview`: properties of headings as a table, `:id local|global|ID`, `:maxlevel N`,
+//!   `:skip-empty-rows t`, columns are given by `:COLUMNS:` property or `#+COLUMNS:`
+//! - `backlinks`: notes linking to current file through `id:` links, or to `:id ID`
+//! - `tagged`: notes tagged with any of `:tags "TAG1 TAG2"`
+//!
+//! Blocks with other names are kept as is.
+use crate::compiler::ast_builder::SourcePathSegment;
+use crate::compiler::ast_builder::element::{
+    DynamicBlock, Element, HeadingSubtree, Item, List, ListType, OrgFile, Paragraph, Table,
+    TableRow, TableRowType,
+};
+use crate::compiler::ast_builder::object::{Object, TableCell, TableCellType};
+use crate::compiler::content::{Document, Section};
+
+/// Writers depending on other files of site
+const SITE_WRITERS: [&str; 2] = ["backlinks", "tagged"];
+
+const DEFAULT_COLUMNS: &str = "%ITEM %TODO %PRIORITY %TAGS";
+
+#[derive(Debug, Clone)]
+struct Note {
+    url: String,
+    title: String,
+    tags: Vec<String>,
+}
+
+/// Notes and `id:` links of site, used by site-aware writers
+#[derive(Debug, Default)]
+pub struct Notes {
+    /// files and tagged headings
+    notes: Vec<Note>,
+    /// (target roam id, source note)
+    links: Vec<(String, Note)>,
+}
+
+impl Notes {
+    pub fn from_section(section: &Section) -> Self {
+        let mut notes = Self::default();
+        notes.collect(section);
+        notes
+    }
+
+    fn collect(&mut self, section: &Section) {
+        for document in section.documents.iter() {
+            let url = format!("/{}", document.html_path());
+            let title = document
+                .metadata
+                .title
+                .clone()
+                .unwrap_or_else(|| document.file_info.name.clone());
+            let file_note = Note {
+                url: url.clone(),
+                title,
+                tags: document.metadata.filetags.clone(),
+            };
+
+            fn collect_headings(url: &str, heading: &HeadingSubtree, notes: &mut Vec<Note>) {
+                if !heading.tags.is_empty() {
+                    notes.push(Note {
+                        url: format!("{url}#{}", heading.anchor),
+                        title: plain_text(&heading.title),
+                        tags: heading.tags.clone(),
+                    });
+                }
+                for subtree in heading.sub_heading_subtrees.iter() {
+                    collect_headings(url, subtree, notes);
+                }
+            }
+            for heading in document.ast.heading_subtrees.iter() {
+                collect_headings(&url, heading, &mut self.notes);
+            }
+
+            for extracted_link in document.ast.extracted_links.iter() {
+                if extracted_link.link.protocol != "id" {
+                    continue;
+                }
+                let target_id = extracted_link
+                    .link
+                    .path
+                    .strip_prefix("id:")
+                    .unwrap_or(&extracted_link.link.path);
+                let source = match extracted_link.direct_heading() {
+                    Some(SourcePathSegment::Heading {
+                        title,
+                        id: Some(id),
+                        ..
+                    }) => Note {
+                        url: format!("{url}#{id}"),
+                        title: plain_text(title),
+                        tags: vec![],
+                    },
+                    _ => file_note.clone(),
+                };
+                self.links.push((target_id.to_string(), source));
+            }
+
+            self.notes.push(file_note);
+        }
+
+        for subsection in section.subsections.iter() {
+            self.collect(subsection);
+        }
+    }
+}
+
+/// Re-compute contents of dynamic blocks of all documents in `section`
+pub fn update_section(section: &mut Section) {
+    let notes = Notes::from_section(section);
+    fn update(section: &mut Section, notes: &Notes) {
+        for document in section.documents.iter_mut() {
+            update_document(document, notes);
+        }
+        for subsection in section.subsections.iter_mut() {
+            update(subsection, notes);
+        }
+    }
+    update(section, &notes);
+}
+
+/// Re-compute contents of dynamic blocks of `document`, other files of site are given by `notes`
+pub fn update_document(document: &mut Document, notes: &Notes) {
+    let url = format!("/{}", document.html_path());
+
+    // blocks are computed in document order, and then written back in the same order
+    let mut generated = vec![];
+    {
+        let file = &document.ast;
+        let mut visit = |heading: Option<&HeadingSubtree>, block: &DynamicBlock| {
+            generated.push(generate(block, file, heading, &url, notes));
+        };
+        if let Some(section) = &file.zeroth_section {
+            visit_blocks(&section.elements, None, &mut visit);
+        }
+        for heading in file.heading_subtrees.iter() {
+            visit_heading(heading, &mut visit);
+        }
+    }
+
+    if generated.iter().all(Option::is_none) {
+        return;
+    }
+    let mut generated = generated.into_iter();
+    let mut write = |block: &mut DynamicBlock| {
+        if let Some(Some(contents)) = generated.next() {
+            // blocks nested in the replaced contents are dropped with their generated contents
+            let mut n_nested = 0;
+            visit_elements(&block.contents, &mut |e| {
+                n_nested += matches!(e, Element::DynamicBlock(_)) as usize;
+            });
+            generated.by_ref().take(n_nested).for_each(drop);
+            block.contents = contents;
+        }
+    };
+    let file = &mut document.ast;
+    if let Some(section) = &mut file.zeroth_section {
+        visit_blocks_mut(&mut section.elements, &mut write);
+    }
+    for heading in file.heading_subtrees.iter_mut() {
+        visit_heading_mut(heading, &mut write);
+    }
+}
+
+/// Whether `file` has dynamic blocks depending on other files of site
+pub fn has_site_blocks(file: &OrgFile) -> bool {
+    let mut found = false;
+    let mut visit = |_: Option<&HeadingSubtree>, block: &DynamicBlock| {
+        found |= SITE_WRITERS.contains(&block.name.to_ascii_lowercase().as_str());
+    };
+    if let Some(section) = &file.zeroth_section {
+        visit_blocks(&section.elements, None, &mut visit);
+    }
+    for heading in file.heading_subtrees.iter() {
+        visit_heading(heading, &mut visit);
+    }
+    found
+}
+
+fn visit_heading<'a>(
+    heading: &'a HeadingSubtree,
+    visit: &mut impl FnMut(Option<&'a HeadingSubtree>, &'a DynamicBlock),
+) {
+    if let Some(section) = &heading.section {
+        visit_blocks(&section.elements, Some(heading), visit);
+    }
+    for subtree in heading.sub_heading_subtrees.iter() {
+        visit_heading(subtree, visit);
+    }
+}
+
+fn visit_blocks<'a>(
+    elements: &'a [Element],
+    heading: Option<&'a HeadingSubtree>,
+    visit: &mut impl FnMut(Option<&'a HeadingSubtree>, &'a DynamicBlock),
+) {
+    visit_elements(elements, &mut |element| {
+        if let Element::DynamicBlock(block) = element {
+            visit(heading, block);
+        }
+    });
+}
+
+fn visit_heading_mut(heading: &mut HeadingSubtree, visit: &mut impl FnMut(&mut DynamicBlock)) {
+    if let Some(section) = &mut heading.section {
+        visit_blocks_mut(&mut section.elements, visit);
+    }
+    for subtree in heading.sub_heading_subtrees.iter_mut() {
+        visit_heading_mut(subtree, visit);
+    }
+}
+
+fn visit_blocks_mut(elements: &mut [Element], visit: &mut impl FnMut(&mut DynamicBlock)) {
+    visit_elements_mut(elements, &mut |element| {
+        if let Element::DynamicBlock(block) = element {
+            visit(block);
+        }
+    });
+}
+
+// elements in greater element, except list whose items are visited one by one
+pub(crate) fn child_elements(element: &Element) -> Option<&[Element]> {
+    match element {
+        Element::Drawer(e) => Some(&e.contents),
+        Element::CenterBlock(e) => Some(&e.contents),
+        Element::QuoteBlock(e) => Some(&e.contents),
+        Element::SpecialBlock(e) => Some(&e.contents),
+        Element::FootnoteDefinition(e) => Some(&e.contents),
+        Element::Item(e) => Some(&e.contents),
+        Element::InlineTask(e) => e.contents.as_deref(),
+        _ => None,
+    }
+}
+
+fn child_elements_mut(element: &mut Element) -> Option<&mut [Element]> {
+    match element {
+        Element::Drawer(e) => Some(&mut e.contents),
+        Element::CenterBlock(e) => Some(&mut e.contents),
+        Element::QuoteBlock(e) => Some(&mut e.contents),
+        Element::SpecialBlock(e) => Some(&mut e.contents),
+        Element::FootnoteDefinition(e) => Some(&mut e.contents),
+        Element::Item(e) => Some(&mut e.contents),
+        Element::InlineTask(e) => e.contents.as_deref_mut(),
+        _ => None,
+    }
+}
+
+/// Visit `elements` and their descendants in document order, an element is visited before its
+/// children, i.e., [`child_elements`], contents of list items and of dynamic blocks
+pub(crate) fn visit_elements<'a>(elements: &'a [Element], visit: &mut impl FnMut(&'a Element)) {
+    for element in elements {
+        visit(element);
+        match element {
+            Element::List(list) => {
+                for item in list.items.iter() {
+                    visit_elements(&item.contents, visit);
+                }
+            }
+            Element::DynamicBlock(block) => visit_elements(&block.contents, visit),
+            _ => {
+                if let Some(children) = child_elements(element) {
+                    visit_elements(children, visit);
+                }
+            }
+        }
+    }
+}
+
+/// Mutable version of [`visit_elements`], children are visited after `visit` changes the element
+pub(crate) fn visit_elements_mut(elements: &mut [Element], visit: &mut impl FnMut(&mut Element)) {
+    for element in elements {
+        visit(element);
+        match element {
+            Element::List(list) => {
+                for item in list.items.iter_mut() {
+                    visit_elements_mut(&mut item.contents, visit);
+                }
+            }
+            Element::DynamicBlock(block) => visit_elements_mut(&mut block.contents, visit),
+            _ => {
+                if let Some(children) = child_elements_mut(element) {
+                    visit_elements_mut(children, visit);
+                }
+            }
+        }
+    }
+}
+
+fn generate(
+    block: &DynamicBlock,
+    file: &OrgFile,
+    heading: Option<&HeadingSubtree>,
+    url: &str,
+    notes: &Notes,
+) -> Option<Vec<Element>> {
+    match block.name.to_ascii_lowercase().as_str() {
+        "clocktable" => Some(clocktable(block, file, heading)),
+        "columnview" => Some(columnview(block, file, heading)),
+        "backlinks" => Some(backlinks(block, file, url, notes)),
+        "tagged" => Some(tagged(block, url, notes)),
+        _ => None,
+    }
+}
+
+fn clocktable(
+    block: &DynamicBlock,
+    file: &OrgFile,
+    heading: Option<&HeadingSubtree>,
+) -> Vec<Element> {
+    let maxlevel = block
+        .parameter("maxlevel")
+        .and_then(|e| e.parse::<u8>().ok())
+        .unwrap_or(3);
+    let headings = match (block.parameter("scope"), heading) {
+        (Some("subtree" | "tree"), Some(heading)) => vec![heading],
+        _ => file.heading_subtrees.iter().collect(),
+    };
+
+    // (level, title, minutes) in document order
+    fn collect_rows(
+        heading: &HeadingSubtree,
+        maxlevel: u8,
+        rows: &mut Vec<(u8, Vec<Object>, u64)>,
+    ) -> u64 {
+        let idx = rows.len();
+        if heading.level <= maxlevel {
+            rows.push((heading.level, heading.title.clone(), 0));
+        }
+        let mut total = heading
+            .section
+            .as_ref()
+            .map_or(0, |e| clocked_minutes(&e.elements));
+        for subtree in heading.sub_heading_subtrees.iter() {
+            total += collect_rows(subtree, maxlevel, rows);
+        }
+        if heading.level <= maxlevel {
+            if total == 0 {
+                rows.truncate(idx);
+            } else {
+                rows[idx].2 = total;
+            }
+        }
+        total
+    }
+    let mut rows = vec![];
+    let total = headings
+        .iter()
+        .map(|e| collect_rows(e, maxlevel, &mut rows))
+        .sum::<u64>();
+    let min_level = rows.iter().map(|e| e.0).min().unwrap_or(1);
+
+    let mut table_rows = vec![
+        table_row(
+            vec![text_cell("Headline"), text_cell("Time")],
+            TableRowType::Header,
+        ),
+        table_row(
+            vec![
+                vec![Object::Bold(vec![Object::Text("Total time".to_string())])],
+                vec![Object::Bold(vec![Object::Text(format_minutes(total))])],
+            ],
+            TableRowType::Data,
+        ),
+    ];
+    for (level, mut title, minutes) in rows {
+        let indent = "\u{a0}\u{a0}".repeat(usize::from(level - min_level));
+        if !indent.is_empty() {
+            title.insert(0, Object::Text(indent));
+        }
+        table_rows.push(table_row(
+            vec![title, text_cell(&format_minutes(minutes))],
+            TableRowType::Data,
+        ));
+    }
+
+    vec![table(table_rows)]
+}
+
+// clocked minutes of `CLOCK: [..]--[..] =>  H:MM` lines, excluding subtrees
+fn clocked_minutes(elements: &[Element]) -> u64 {
+    let mut minutes = 0;
+    for element in elements {
+        match element {
+            Element::Clock(clock) => minutes += clock.minutes.unwrap_or(0),
+            Element::List(list) => {
+                for item in list.items.iter() {
+                    minutes += clocked_minutes(&item.contents);
+                }
+            }
+            _ => {
+                if let Some(children) = child_elements(element) {
+                    minutes += clocked_minutes(children);
+                }
+            }
+        }
+    }
+    minutes
+}
+
+pub(crate) fn format_minutes(minutes: u64) -> String {
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// `%25ITEM(Task) %TODO %Effort{:}` -> [(property, title)]
+fn parse_columns(spec: &str) -> Vec<(String, String)> {
+    let mut columns = vec![];
+    let mut chars = spec.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        while chars.next_if(|c| c.is_ascii_digit()).is_some() {}
+        let mut property = String::new();
+        while let Some(c) = chars.next_if(|c| !c.is_whitespace() && !matches!(c, '(' | '{' | '%')) {
+            property.push(c);
+        }
+        let mut title = None;
+        if chars.next_if_eq(&'(').is_some() {
+            title = Some(chars.by_ref().take_while(|c| *c != ')').collect::<String>());
+        }
+        if chars.next_if_eq(&'{').is_some() {
+            // summary type is not supported
+            chars.by_ref().take_while(|c| *c != '}').for_each(drop);
+        }
+        if !property.is_empty() {
+            let title = title.unwrap_or_else(|| property.clone());
+            columns.push((property.to_ascii_uppercase(), title));
+        }
+    }
+    columns
+}
+
+fn columnview(
+    block: &DynamicBlock,
+    file: &OrgFile,
+    heading: Option<&HeadingSubtree>,
+) -> Vec<Element> {
+    let maxlevel = block
+        .parameter("maxlevel")
+        .and_then(|e| e.parse::<u8>().ok())
+        .unwrap_or(u8::MAX);
+    let skip_empty_rows = block
+        .parameter("skip-empty-rows")
+        .is_some_and(|e| e != "nil");
+
+    fn find_heading<'a>(headings: &'a [HeadingSubtree], id: &str) -> Option<&'a HeadingSubtree> {
+        headings.iter().find_map(|e| {
+            if e.properties.get("ID").is_some_and(|e| e == id) {
+                Some(e)
+            } else {
+                find_heading(&e.sub_heading_subtrees, id)
+            }
+        })
+    }
+    let headings = match (block.parameter("id"), heading) {
+        (None | Some("local"), Some(heading)) => vec![heading],
+        (None | Some("local" | "global" | "file"), None) | (Some("global" | "file"), _) => {
+            file.heading_subtrees.iter().collect()
+        }
+        (Some(id), _) => find_heading(&file.heading_subtrees, id)
+            .into_iter()
+            .collect(),
+    };
+
+    let spec = headings
+        .first()
+        .filter(|_| headings.len() == 1)
+        .and_then(|e| e.properties.get("COLUMNS").cloned())
+        .or_else(|| file.keywords.get("COLUMNS").map(|e| plain_text(e)))
+        .unwrap_or_else(|| DEFAULT_COLUMNS.to_string());
+    let columns = parse_columns(&spec);
+
+    let mut table_rows = vec![table_row(
+        columns.iter().map(|(_, title)| text_cell(title)).collect(),
+        TableRowType::Header,
+    )];
+    fn collect_rows(
+        heading: &HeadingSubtree,
+        columns: &[(String, String)],
+        maxlevel: u8,
+        skip_empty_rows: bool,
+        table_rows: &mut Vec<TableRow>,
+    ) {
+        if heading.level > maxlevel || heading.is_commented {
+            return;
+        }
+        let cells = columns
+            .iter()
+            .map(|(property, _)| match property.as_str() {
+                "ITEM" => heading.title.clone(),
+                "TODO" => text_cell(heading.keyword.as_deref().unwrap_or_default()),
+                "PRIORITY" => text_cell(heading.priority.as_deref().unwrap_or_default()),
+                "TAGS" | "ALLTAGS" if !heading.tags.is_empty() => {
+                    text_cell(&format!(":{}:", heading.tags.join(":")))
+                }
+                "TAGS" | "ALLTAGS" => vec![],
+                property => text_cell(heading.properties.get(property).map_or("", |e| e.as_str())),
+            })
+            .collect::<Vec<_>>();
+        let is_empty = columns
+            .iter()
+            .zip(cells.iter())
+            .all(|((property, _), cell)| property == "ITEM" || cell.is_empty());
+        if !(skip_empty_rows && is_empty) {
+            table_rows.push(table_row(cells, TableRowType::Data));
+        }
+        for subtree in heading.sub_heading_subtrees.iter() {
+            collect_rows(subtree, columns, maxlevel, skip_empty_rows, table_rows);
+        }
+    }
+    for heading in headings {
+        collect_rows(
+            heading,
+            &columns,
+            maxlevel,
+            skip_empty_rows,
+            &mut table_rows,
+        );
+    }
+
+    vec![table(table_rows)]
+}
+
+fn backlinks(block: &DynamicBlock, file: &OrgFile, url: &str, notes: &Notes) -> Vec<Element> {
+    let target_ids = match block.parameter("id") {
+        Some(id) => vec![id],
+        None => file.roam_nodes.iter().map(|e| e.id.as_str()).collect(),
+    };
+
+    let mut sources: Vec<&Note> = vec![];
+    for (target_id, source) in notes.links.iter() {
+        let page = source.url.split('#').next().unwrap_or_default();
+        if target_ids.contains(&target_id.as_str())
+            && page != url
+            && !sources.iter().any(|e| e.url == source.url)
+        {
+            sources.push(source);
+        }
+    }
+    sources.sort_by(|a, b| (&a.title, &a.url).cmp(&(&b.title, &b.url)));
+
+    link_list(&sources)
+}
+
+fn tagged(block: &DynamicBlock, url: &str, notes: &Notes) -> Vec<Element> {
+    let tags = block
+        .parameter("tags")
+        .unwrap_or_default()
+        .split(|c: char| c.is_whitespace() || c == ':')
+        .filter(|e| !e.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut tagged = notes
+        .notes
+        .iter()
+        .filter(|e| e.url != url && e.tags.iter().any(|t| tags.contains(&t.as_str())))
+        .collect::<Vec<_>>();
+    tagged.sort_by(|a, b| (&a.title, &a.url).cmp(&(&b.title, &b.url)));
+
+    link_list(&tagged)
+}
+
+fn link_list(notes: &[&Note]) -> Vec<Element> {
+    if notes.is_empty() {
+        return vec![];
+    }
+    let items = notes
+        .iter()
+        .map(|note| Item {
+            bullet: "-".to_string(),
+            counter_set: None,
+            checkbox: None,
+            tag: vec![],
+            contents: vec![Element::Paragraph(Paragraph {
+                affiliated_keywords: vec![],
+                objects: vec![Object::Link {
+                    url: note.url.clone(),
+                    text: Some(note.title.clone()),
+                }],
+            })],
+        })
+        .collect();
+    vec![Element::List(List {
+        list_type: ListType::Unordered,
+        items,
+    })]
+}
+
+fn table(rows: Vec<TableRow>) -> Element {
+    let (header, rows): (Vec<_>, Vec<_>) = rows
+        .into_iter()
+        .partition(|e| e.row_type == TableRowType::Header);
+    let rules = match header.is_empty() {
+        true => vec![],
+        false => vec![header.len()],
+    };
+    Element::Table(Table {
+        name: None,
+        caption: vec![],
+        header,
+        separator: None,
+        rows,
+        formulas: vec![],
+        rules,
+    })
+}
+
+fn table_row(cells: Vec<Vec<Object>>, row_type: TableRowType) -> TableRow {
+    let cell_type = match row_type {
+        TableRowType::Header => TableCellType::Header,
+        _ => TableCellType::Data,
+    };
+    TableRow {
+        cells: cells
+            .into_iter()
+            .map(|contents| {
+                Object::TableCell(TableCell {
+                    contents,
+                    cell_type: cell_type.clone(),
+                })
+            })
+            .collect(),
+        row_type,
+    }
+}
+
+fn text_cell(text: &str) -> Vec<Object> {
+    if text.is_empty() {
+        vec![]
+    } else {
+        vec![Object::Text(text.to_string())]
+    }
+}
+
+pub(crate) fn plain_text(objects: &[Object]) -> String {
+    objects
+        .iter()
+        .map(|object| match object {
+            Object::Text(e) | Object::Whitespace(e) => e.clone(),
+            Object::Timestamp(e) => e.raw.clone(),
+            Object::Bold(e)
+            | Object::Italic(e)
+            | Object::Underline(e)
+            | Object::Strikethrough(e)
+            | Object::Code(e)
+            | Object::Verbatim(e)
+            | Object::Superscript(e)
+            | Object::Subscript(e)
+            | Object::RadioLink(e)
+            | Object::RadioTarget(e) => plain_text(e),
+            Object::GeneralLink(link) if link.description.is_empty() => link.path.clone(),
+            Object::GeneralLink(link) => plain_text(&link.description),
+            Object::LineBreak => "\n".to_string(),
+            _ => String::new(),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::testing::compile_document;
+    use crate::export::org::OrgWriter;
+
+    fn update(input: &str) -> String {
+        let mut document = compile_document("content.org", input);
+        update_document(&mut document, &Notes::default());
+        OrgWriter::new().write_org_file(&document.ast)
+    }
+
+    #[test]
+    fn test_clocktable() {
+        let output = update(
+            r##"#+BEGIN: clocktable :scope file :maxlevel 2
+#+END:
+
+* a
+:LOGBOOK:
+CLOCK: [2024-01-01 Mon 10:00]--[2024-01-01 Mon 11:30] =>  1:30
+:END:
+** b
+CLOCK: [2024-01-02 Tue 10:00]--[2024-01-02 Tue 10:45] =>  0:45
+*** c
+CLOCK: [2024-01-02 Tue 11:00]--[2024-01-02 Tue 11:10] =>  0:10
+* d
+"##,
+        );
+        assert!(
+            output.starts_with(
+                "#+begin: clocktable :scope file :maxlevel 2\n|Headline|Time|\n|---+---|\n|*Total time*|*2:25*|\n|a|2:25|\n|\u{a0}\u{a0}b|0:55|\n#+end:\n"
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn test_columnview() {
+        let output = update(
+            r##"* TODO [#A] a :x:
+:PROPERTIES:
+:COLUMNS: %ITEM(Task) %TODO %Effort{:}
+:Effort: 1:00
+:END:
+#+BEGIN: columnview :skip-empty-rows t
+| stale |
+#+END:
+** b
+:PROPERTIES:
+:Effort: 0:30
+:END:
+** c
+"##,
+        );
+        assert!(
+            output.contains(
+                "#+begin: columnview :skip-empty-rows t\n|Task|TODO|Effort|\n|---+---+---|\n|a|TODO|1:00|\n|b||0:30|\n#+end:\n"
+            ),
+            "{output}"
+        );
+    }
+
+    #[test]
+    fn test_parameters_and_unknown_block() {
+        let output = update(
+            r##"#+BEGIN: my-writer :match "a b" :files ("x.org" "y.org") :flag
+keep
+#+END:
+"##,
+        );
+        assert_eq!(
+            output,
+            "#+begin: my-writer :match \"a b\" :files (\"x.org\" \"y.org\") :flag\nkeep\n#+end:\n"
+        );
+
+        let document = compile_document(
+            "plist.org",
+            "#+BEGIN: x :match \"a b\" :files (\"x.org\" \"y.org\") :flag\n#+END:\n",
+        );
+        let Some(Element::DynamicBlock(block)) = document
+            .ast
+            .zeroth_section
+            .as_ref()
+            .and_then(|e| e.elements.first())
+        else {
+            panic!("no dynamic block: {:#?}", document.ast);
+        };
+        assert_eq!(block.parameter("match"), Some("a b"));
+        assert_eq!(block.parameter("files"), Some("(\"x.org\" \"y.org\")"));
+        assert_eq!(block.parameter("flag"), Some(""));
+        assert_eq!(block.parameter("scope"), None);
+    }
+}
@@ -0,0 +1,614 @@
+//! Resolve internal and `file:` links of compiled [`Document`] into hrefs of pages, headings,
+//! targets and named elements, which are written into [`GeneralLink::href`]
+//!
+//! - `[[*Heading title]]`: heading with the title
+//! - `[[#CUSTOM_ID]]`: heading with the `CUSTOM_ID` property
+//! - `[[target]]`: `<<target>>`, element with `#+NAME: target` or heading with the title
+//! - `[[file:other.org]]`: page of another org file of site, relative to directory of current file
+//! - `[[file:other.org::SEARCH]]`: any of above in another org file of site
+//!
+//! Titles and targets are compared with whitespace normalized. Unresolved links, and links to org
+//! files which are missing or excluded by `#+RENDER: nil` are reported.
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::compiler::ast_builder::element::{Element, HeadingSubtree, OrgFile};
+use crate::compiler::ast_builder::object::{GeneralLink, Object};
+use crate::compiler::ast_builder::{ExtractedLink, SourcePathSegment};
+use crate::compiler::content::{Document, Section};
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::dynamic_block::{plain_text, visit_elements, visit_elements_mut};
+use crate::utils::{normalize, target_anchor};
+
+/// Anchors of a document, the first one wins if more than one has the same key
+#[derive(Debug, Default)]
+pub struct Anchors {
+    /// normalized title -> anchor of heading
+    headings: HashMap<String, String>,
+    /// `CUSTOM_ID` -> anchor of heading
+    custom_ids: HashMap<String, String>,
+    /// normalized `<<target>>` or `#+NAME:` -> anchor
+    targets: HashMap<String, String>,
+    /// anchor -> normalized target, to find targets with the same anchor
+    target_names: HashMap<String, String>,
+    /// messages of different targets with the same anchor, e.g., `<<a b>>` and `<<a-b>>`
+    collisions: Vec<String>,
+}
+
+impl Anchors {
+    pub fn from_org_file(file: &OrgFile) -> Self {
+        let mut anchors = Self::default();
+        if let Some(section) = &file.zeroth_section {
+            anchors.collect_elements(&section.elements);
+        }
+        for heading in file.heading_subtrees.iter() {
+            anchors.collect_heading(heading);
+        }
+        for definition in file.footnote_definitions.iter() {
+            anchors.collect_elements(&definition.contents);
+        }
+        anchors
+    }
+
+    /// Anchor of search option, i.e., `*Heading title`, `#CUSTOM_ID` or `target`
+    pub fn resolve(&self, search: &str) -> Option<&String> {
+        if let Some(title) = search.strip_prefix('*') {
+            self.headings.get(&normalize_text(title))
+        } else if let Some(custom_id) = search.strip_prefix('#') {
+            self.custom_ids.get(custom_id)
+        } else {
+            let search = normalize_text(search);
+            self.targets
+                .get(&search)
+                .or_else(|| self.headings.get(&search))
+        }
+    }
+
+    fn collect_heading(&mut self, heading: &HeadingSubtree) {
+        // commented headings are not rendered
+        if heading.is_commented {
+            return;
+        }
+        let anchor = heading.anchor.clone();
+        self.headings
+            .entry(normalize_text(&plain_text(&heading.title)))
+            .or_insert_with(|| anchor.clone());
+        if let Some(custom_id) = heading.properties.get("CUSTOM_ID") {
+            self.custom_ids
+                .entry(custom_id.trim().to_string())
+                .or_insert(anchor);
+        }
+        self.collect_objects(&heading.title);
+        if let Some(section) = &heading.section {
+            self.collect_elements(&section.elements);
+        }
+        for subtree in heading.sub_heading_subtrees.iter() {
+            self.collect_heading(subtree);
+        }
+    }
+
+    fn collect_elements(&mut self, elements: &[Element]) {
+        visit_elements(elements, &mut |element| {
+            if let Some(name) = element.name() {
+                self.collect_target(&name);
+            }
+            match element {
+                Element::Paragraph(e) => self.collect_objects(&e.objects),
+                Element::VerseBlock(e) => self.collect_objects(&e.contents),
+                Element::Table(e) => {
+                    for row in e.header.iter().chain(e.rows.iter()) {
+                        self.collect_objects(&row.cells);
+                    }
+                }
+                Element::List(e) => {
+                    for item in e.items.iter() {
+                        self.collect_objects(&item.tag);
+                    }
+                }
+                Element::Item(e) => self.collect_objects(&e.tag),
+                Element::InlineTask(e) => self.collect_objects(&e.title),
+                _ => {}
+            }
+        });
+    }
+
+    fn collect_objects(&mut self, objects: &[Object]) {
+        for object in objects {
+            match object {
+                Object::Target(target) => self.collect_target(target),
+                Object::Bold(e)
+                | Object::Italic(e)
+                | Object::Underline(e)
+                | Object::Strikethrough(e)
+                | Object::Superscript(e)
+                | Object::Subscript(e) => self.collect_objects(e),
+                Object::TableCell(e) => self.collect_objects(&e.contents),
+                _ => {}
+            }
+        }
+    }
+
+    fn collect_target(&mut self, name: &str) {
+        let name = normalize_text(name);
+        let anchor = target_anchor(&name);
+        match self.target_names.get(&anchor) {
+            Some(other) if *other != name => self.collisions.push(format!(
+                "targets '{other}' and '{name}' have the same anchor {anchor}"
+            )),
+            Some(_) => {}
+            None => {
+                self.target_names.insert(anchor.clone(), name.clone());
+            }
+        }
+        self.targets.entry(name).or_insert(anchor);
+    }
+}
+
+#[derive(Debug)]
+struct Page {
+    url: String,
+    /// false if excluded by `#+RENDER: nil`
+    enable_render: bool,
+    anchors: Anchors,
+}
+
+/// Urls and anchors of all documents of site, keyed by full path of org file
+#[derive(Debug, Default)]
+pub struct LinkIndex {
+    pages: HashMap<PathBuf, Page>,
+}
+
+impl LinkIndex {
+    pub fn from_section(section: &Section) -> Self {
+        let mut index = Self::default();
+        index.collect(section);
+        index
+    }
+
+    fn collect(&mut self, section: &Section) {
+        for document in section.documents.iter() {
+            self.pages.insert(
+                document.file_info.full_path.clone(),
+                Page {
+                    url: format!("/{}", document.html_path()),
+                    enable_render: document.metadata.enable_render,
+                    anchors: Anchors::from_org_file(&document.ast),
+                },
+            );
+        }
+        for subsection in section.subsections.iter() {
+            self.collect(subsection);
+        }
+    }
+}
+
+/// Resolve links of all documents in `section`, unresolved ones are reported as warnings
+pub fn update_section(section: &mut Section) {
+    let index = LinkIndex::from_section(section);
+    fn update(section: &mut Section, index: &LinkIndex) {
+        for document in section.documents.iter_mut() {
+            for e in update_document(document, index) {
+                section
+                    .diagnostics
+                    .push(Diagnostic::warning(&document.file_info.full_path, e));
+            }
+        }
+        for subsection in section.subsections.iter_mut() {
+            update(subsection, index);
+        }
+    }
+    update(section, &index);
+}
+
+/// Resolve internal and `file:` links of `document`, other files of site are given by `index`.
+///
+/// Hrefs are also written into [`OrgFile::extracted_links`], from which dependencies between pages
+/// are collected for incremental build. Messages of unresolved links and targets with the same
+/// anchor are returned.
+pub fn update_document(document: &mut Document, index: &LinkIndex) -> Vec<String> {
+    let f_org = document.file_info.full_path.clone();
+    let anchors = Anchors::from_org_file(&document.ast);
+    let resolver = Resolver {
+        f_org: &f_org,
+        anchors: &anchors,
+        index,
+    };
+
+    let mut errors = anchors.collisions.clone();
+    for e in document.ast.extracted_links.iter_mut() {
+        match resolver.resolve(&e.link) {
+            Some(Ok(href)) => e.link.href = Some(href),
+            Some(Err(message)) => errors.push(format!("{message} in {}", source_path_text(e))),
+            None => {}
+        }
+    }
+
+    visit_links_mut(&mut document.ast, &mut |link: &mut GeneralLink| {
+        if let Some(Ok(href)) = resolver.resolve(link) {
+            link.href = Some(href);
+        }
+    });
+    errors
+}
+
+struct Resolver<'a> {
+    f_org: &'a Path,
+    anchors: &'a Anchors,
+    index: &'a LinkIndex,
+}
+
+impl Resolver<'_> {
+    /// `None` for links which are kept as is, the reason is given if not resolved
+    fn resolve(&self, link: &GeneralLink) -> Option<Result<String, String>> {
+        let search = match link.protocol.as_str() {
+            "fuzzy" | "custom_id" | "internal_section" => link.path.as_str(),
+            "file" => return self.resolve_file(&link.path),
+            _ => return None,
+        };
+        Some(
+            self.anchors
+                .resolve(search)
+                .map(|anchor| format!("#{anchor}"))
+                .ok_or_else(|| format!("unresolved link to '{search}'")),
+        )
+    }
+
+    // `file:other.org` or `file:other.org::SEARCH`, links to other files are kept
+    fn resolve_file(&self, path: &str) -> Option<Result<String, String>> {
+        let path = path.strip_prefix("file:")?;
+        let (file, search) = match path.split_once("::") {
+            Some((file, search)) => (file, Some(search)),
+            None => (path, None),
+        };
+        if !Path::new(file)
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("org"))
+        {
+            return None;
+        }
+
+        let f_target = normalize(&self.f_org.parent()?.join(file));
+        let Some(page) = self.index.pages.get(&f_target) else {
+            let reason = if f_target.exists() {
+                "not in site"
+            } else {
+                "missing"
+            };
+            return Some(Err(format!("link to {reason} file {file}")));
+        };
+        if !page.enable_render {
+            return Some(Err(format!("link to {file} excluded by `#+RENDER: nil`")));
+        }
+
+        // line number is not supported, link to the page instead
+        let search = search.filter(|e| e.parse::<usize>().is_err());
+        Some(match search {
+            Some(search) => page
+                .anchors
+                .resolve(search)
+                .map(|anchor| format!("{}#{anchor}", page.url))
+                .ok_or_else(|| format!("unresolved link to '{search}' of {file}")),
+            None => Ok(page.url.clone()),
+        })
+    }
+}
+
+fn normalize_text(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+// e.g., `a.org > Heading > Subheading`
+fn source_path_text(link: &ExtractedLink) -> String {
+    link.source_path
+        .iter()
+        .filter_map(|segment| match segment {
+            SourcePathSegment::File { path } => {
+                Some(path.file_name()?.to_string_lossy().to_string())
+            }
+            SourcePathSegment::Heading { title, .. } => Some(plain_text(title)),
+            SourcePathSegment::ZerothSection { .. } => None,
+        })
+        .collect::<Vec<_>>()
+        .join(" > ")
+}
+
+fn visit_links_mut(file: &mut OrgFile, visit: &mut impl FnMut(&mut GeneralLink)) {
+    fn visit_heading(heading: &mut HeadingSubtree, visit: &mut impl FnMut(&mut GeneralLink)) {
+        visit_objects(&mut heading.title, visit);
+        if let Some(section) = &mut heading.section {
+            visit_links_of_elements(&mut section.elements, visit);
+        }
+        for subtree in heading.sub_heading_subtrees.iter_mut() {
+            visit_heading(subtree, visit);
+        }
+    }
+
+    fn visit_links_of_elements(elements: &mut [Element], visit: &mut impl FnMut(&mut GeneralLink)) {
+        visit_elements_mut(elements, &mut |element| match element {
+            Element::Paragraph(e) => visit_objects(&mut e.objects, visit),
+            Element::VerseBlock(e) => visit_objects(&mut e.contents, visit),
+            Element::Keyword(e) => visit_objects(&mut e.value, visit),
+            Element::Table(e) => {
+                visit_objects(&mut e.caption, visit);
+                for row in e.header.iter_mut().chain(e.rows.iter_mut()) {
+                    visit_objects(&mut row.cells, visit);
+                }
+            }
+            Element::List(e) => {
+                for item in e.items.iter_mut() {
+                    visit_objects(&mut item.tag, visit);
+                }
+            }
+            Element::Item(e) => visit_objects(&mut e.tag, visit),
+            Element::InlineTask(e) => visit_objects(&mut e.title, visit),
+            _ => {}
+        });
+    }
+
+    fn visit_objects(objects: &mut [Object], visit: &mut impl FnMut(&mut GeneralLink)) {
+        for object in objects {
+            match object {
+                Object::GeneralLink(link) => visit(link),
+                Object::Bold(e)
+                | Object::Italic(e)
+                | Object::Underline(e)
+                | Object::Strikethrough(e)
+                | Object::Superscript(e)
+                | Object::Subscript(e) => visit_objects(e, visit),
+                Object::TableCell(e) => visit_objects(&mut e.contents, visit),
+                Object::Citation {
+                    global_prefix,
+                    global_suffix,
+                    ..
+                } => {
+                    visit_objects(global_prefix, visit);
+                    visit_objects(global_suffix, visit);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    if let Some(section) = &mut file.zeroth_section {
+        visit_links_of_elements(&mut section.elements, visit);
+    }
+    for heading in file.heading_subtrees.iter_mut() {
+        visit_heading(heading, visit);
+    }
+    for definition in file.footnote_definitions.iter_mut() {
+        visit_links_of_elements(&mut definition.contents, visit);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{LinkIndex, update_document, update_section};
+    use crate::compiler::ast_builder::element::{Element, OrgFile};
+    use crate::compiler::ast_builder::object::Object;
+    use crate::compiler::testing::compile_document;
+    use crate::compiler::{Compiler, dynamic_block};
+    use crate::export::ssg::renderer::{Renderer, RendererConfig};
+    use crate::export::ssg::site::{SiteBuilder, SiteConfig};
+
+    fn hrefs(file: &OrgFile) -> Vec<Option<String>> {
+        let mut hrefs = vec![];
+        for heading in file.heading_subtrees.iter() {
+            for element in heading.section.iter().flat_map(|e| e.elements.iter()) {
+                if let Element::Paragraph(paragraph) = element {
+                    for object in paragraph.objects.iter() {
+                        if let Object::GeneralLink(link) = object {
+                            hrefs.push(link.href.clone());
+                        }
+                    }
+                }
+            }
+        }
+        hrefs
+    }
+
+    #[test]
+    fn test_update_document() {
+        let mut doc = compile_document(
+            "a.org",
+            r"* Intro
+:PROPERTIES:
+:CUSTOM_ID: intro
+:END:
+[[*Other   heading]] [[#intro]] [[here]] [[tbl]] [[Intro]] [[nowhere]]
+* Other heading
+A <<here>> target.
+
+#+NAME: tbl
+| a |
+",
+        );
+        let errors = update_document(&mut doc, &LinkIndex::default());
+        let hrefs = hrefs(&doc.ast);
+
+        let anchor = |i: usize| Some(format!("#{}", doc.ast.heading_subtrees[i].anchor));
+        assert_eq!(hrefs.len(), 6);
+        assert_eq!(hrefs[0], anchor(1));
+        assert_eq!(hrefs[0].as_deref(), Some("#other-heading"));
+        assert_eq!(hrefs[1], anchor(0));
+        assert_eq!(hrefs[1].as_deref(), Some("#intro"));
+        assert_eq!(hrefs[4], anchor(0));
+        assert_eq!(hrefs[2].as_deref(), Some("#target-here"));
+        assert_eq!(hrefs[3].as_deref(), Some("#target-tbl"));
+        assert_eq!(hrefs[5], None);
+        assert_eq!(
+            errors,
+            vec!["unresolved link to 'nowhere' in a.org > Intro".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_target_anchor_collision() {
+        let mut doc = compile_document("a.org", "<<a b>> <<a-b>> <<a b>>\n\n#+NAME: a  b\n| a |\n");
+        let errors = update_document(&mut doc, &LinkIndex::default());
+        assert_eq!(
+            errors,
+            vec!["targets 'a b' and 'a-b' have the same anchor target-a-b".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_update_section_file_links() {
+        let d_org = std::env::temp_dir()
+            .join(format!("windancer_file_links_{}", std::process::id()))
+            .join("content");
+        std::fs::create_dir_all(d_org.join("sub")).expect("create dir");
+        std::fs::write(
+            d_org.join("a.org"),
+            r"* Links
+[[file:sub/b.org][B]] [[file:./sub/b.org::*Second]] [[file:sub/b.org::12]] [[file:sub/c.org]] [[file:missing.org]] [[file:x.png]]
+",
+        )
+        .expect("write");
+        std::fs::write(
+            d_org.join("sub/b.org"),
+            "* First\n* Second\n[[file:../a.org::*Links]]\n",
+        )
+        .expect("write");
+        std::fs::write(d_org.join("sub/c.org"), "#+RENDER: nil\n* C\n").expect("write");
+
+        let mut section = Compiler::default()
+            .compile_section(&d_org)
+            .expect("compile section");
+        update_section(&mut section);
+
+        let a = section
+            .documents
+            .iter()
+            .find(|e| e.file_info.file_name == "a.org")
+            .expect("a.org");
+        let a_hrefs = hrefs(&a.ast);
+        assert_eq!(a_hrefs.len(), 6);
+        assert_eq!(a_hrefs[0].as_deref(), Some("/sub/b.html"));
+        assert_eq!(
+            a.ast.extracted_links[0].link.href.as_deref(),
+            Some("/sub/b.html")
+        );
+        assert_eq!(a_hrefs[2].as_deref(), Some("/sub/b.html"));
+        assert_eq!(a_hrefs[3], None);
+        assert_eq!(a_hrefs[4], None);
+        assert_eq!(a_hrefs[5], None);
+
+        let b = section
+            .subsections
+            .iter()
+            .flat_map(|e| e.documents.iter())
+            .find(|e| e.file_info.file_name == "b.org")
+            .expect("b.org");
+        assert_eq!(
+            a_hrefs[1],
+            Some(format!("/sub/b.html#{}", b.ast.heading_subtrees[1].anchor))
+        );
+        assert_eq!(a_hrefs[1].as_deref(), Some("/sub/b.html#second"));
+        assert_eq!(
+            hrefs(&b.ast)[0],
+            Some(format!("/a.html#{}", a.ast.heading_subtrees[0].anchor))
+        );
+        assert_eq!(hrefs(&b.ast)[0].as_deref(), Some("/a.html#links"));
+
+        let mut messages = section
+            .all_diagnostics()
+            .iter()
+            .filter(|e| e.message.contains("link"))
+            .map(|e| e.message.clone())
+            .collect::<Vec<_>>();
+        messages.sort();
+        assert_eq!(
+            messages,
+            vec![
+                "link to missing file missing.org in a.org > Links".to_string(),
+                "link to sub/c.org excluded by `#+RENDER: nil` in a.org > Links".to_string(),
+            ]
+        );
+
+        std::fs::remove_dir_all(d_org.parent().unwrap()).expect("remove dir");
+    }
+
+    #[test]
+    fn test_anchors_of_rendered_page() {
+        let d_root = std::env::temp_dir().join(format!("windancer_anchors_{}", std::process::id()));
+        let d_org = d_root.join("content");
+        std::fs::create_dir_all(&d_org).expect("create dir");
+        std::fs::write(
+            d_org.join("a.org"),
+            r"* Intro
+[[*Clocked]] [[#custom]] [[Intro]] [[Intro 2]] [[file:b.org::*Other]] [[here]] [[My table]]
+* Clocked
+:LOGBOOK:
+CLOCK: [2024-01-01 Mon 10:00]--[2024-01-01 Mon 11:00] =>  1:00
+:END:
+#+BEGIN: clocktable :scope file
+#+END:
+* Custom
+:PROPERTIES:
+:CUSTOM_ID: custom
+:END:
+* Intro 2
+A <<here>> target.
+
+#+NAME: My table
+| a |
+* Intro
+",
+        )
+        .expect("write");
+        std::fs::write(d_org.join("b.org"), "* Other\n").expect("write");
+
+        let mut section = Compiler::default()
+            .compile_section(&d_org)
+            .expect("compile section");
+        update_section(&mut section);
+        // sections of headings are changed after links are resolved
+        dynamic_block::update_section(&mut section);
+
+        let d_output = d_root.join("public");
+        std::fs::create_dir_all(&d_output).expect("create dir");
+        let site = SiteBuilder::new(SiteConfig {
+            output_directory: d_output.clone(),
+            ..SiteConfig::default()
+        })
+        .build(&section)
+        .expect("build site");
+        Renderer::new(RendererConfig {
+            input_directory: d_org,
+            output_directory: d_output.clone(),
+            ..RendererConfig::default()
+        })
+        .render_site(&site);
+        let html = |name: &str| std::fs::read_to_string(d_output.join(name)).expect("read html");
+        let (a, b) = (html("a.html"), html("b.html"));
+
+        let mut anchors = vec![];
+        for part in a.split(r#"href=""#).skip(1) {
+            let href = &part[..part.find('"').expect("end of href")];
+            match href.split_once('#') {
+                Some(("", anchor)) => anchors.push((&a, anchor)),
+                Some(("/b.html", anchor)) => anchors.push((&b, anchor)),
+                _ => {}
+            }
+        }
+        assert!(anchors.len() >= 7);
+        for (html, anchor) in anchors {
+            assert!(
+                html.contains(&format!(r#"id="{anchor}""#)),
+                "no element of id {anchor}"
+            );
+        }
+        assert!(a.contains(r##"<a href="#clocked">"##));
+        assert!(a.contains(r##"<a href="#custom">"##));
+        assert!(a.contains(r##"<a href="#intro">"##));
+        assert!(a.contains(r##"<a href="#intro-2">"##));
+        assert!(a.contains(r#"id="intro-3""#));
+        assert!(a.contains(r#"<a href="/b.html#other">"#));
+        assert!(a.contains(r##"<a href="#target-here">"##));
+        assert!(a.contains(r##"<a href="#target-my-table">"##));
+
+        std::fs::remove_dir_all(&d_root).expect("remove dir");
+    }
+}
@@ -0,0 +1,1412 @@
+//! LaTeX math to MathML at build time, used if `math = "mathml"` in `RendererConfig`, so that
+//! no MathJax is loaded in browser. The compiler uses it to report invalid math and `\ref` to
+//! undefined labels.
+//!
+//! - fragment: `\(..\)`, `$..$`, `\[..\]` and `$$..$$` -> `<math>`
+//! - environment: `equation`, `align`, `gather`, `multline`, `eqnarray` and their starred version
+//!   -> `<math display="block">` of a table, whose rows are numbered per page
+//! - `\label{..}` in environment is referenced by `\ref{..}` or `\eqref{..}`
+//!
+//! Unknown command, unbalanced brace, missing argument, etc. are reported as [`MathError`].
+use std::collections::HashMap;
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MathError {
+    pub message: String,
+    /// byte offset in the input
+    pub offset: usize,
+}
+
+impl fmt::Display for MathError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for MathError {}
+
+/// Split fragment into (offset of content, content, display mode), `None` if it's not delimited
+/// math, e.g., `\ref{x}`
+pub fn split_fragment(text: &str) -> Option<(usize, &str, bool)> {
+    for (open, close, display) in [
+        ("\\(", "\\)", false),
+        ("\\[", "\\]", true),
+        ("$$", "$$", true),
+        ("$", "$", false),
+    ] {
+        if text.len() >= open.len() + close.len()
+            && let Some(content) = text.strip_prefix(open).and_then(|e| e.strip_suffix(close))
+        {
+            return Some((open.len(), content, display));
+        }
+    }
+    None
+}
+
+/// Label of `\ref{label}` or `\eqref{label}`, and whether it's `\eqref`
+pub fn parse_ref(text: &str) -> Option<(&str, bool)> {
+    let (body, is_eqref) = match text.trim().strip_prefix("\\eqref{") {
+        Some(body) => (body, true),
+        None => (text.trim().strip_prefix("\\ref{")?, false),
+    };
+    let label = body.strip_suffix('}')?;
+    (!label.is_empty() && !label.contains(['{', '}'])).then_some((label, is_eqref))
+}
+
+/// Fragment to `<math>`
+pub fn fragment_to_mathml(latex: &str, display: bool) -> Result<String, MathError> {
+    let mut parser = Parser::new(latex);
+    let items = parser.parse_list(false)?;
+    parser.expect_end()?;
+    Ok(format!(
+        r##"<math display="{}">{}</math>"##,
+        if display { "block" } else { "inline" },
+        row(items)
+    ))
+}
+
+const REF_START: char = '\u{E000}';
+const REF_SEP: char = '\u{E001}';
+const REF_END: char = '\u{E002}';
+
+/// Placeholder of `\ref`, replaced by [`Equations::resolve_refs`] after the page is rendered, so
+/// that equations defined later can be referenced.
+pub fn ref_placeholder(label: &str, is_eqref: bool) -> String {
+    let kind = if is_eqref { "eqref" } else { "ref" };
+    format!("{REF_START}{kind}{REF_SEP}{label}{REF_END}")
+}
+
+/// html id of equation with `label`
+pub fn equation_id(label: &str) -> String {
+    format!("eq-{}", label.replace(char::is_whitespace, "-"))
+}
+
+/// Numbers and labels of equations in a page
+#[derive(Debug)]
+pub struct Equations {
+    numbering: bool,
+    counter: usize,
+    /// label -> tag, e.g., "eq:energy" -> "1"
+    labels: HashMap<String, String>,
+}
+
+impl Default for Equations {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+impl Equations {
+    /// Equations are numbered automatically if `numbering`, otherwise only `\tag` is shown
+    pub fn new(numbering: bool) -> Self {
+        Self {
+            numbering,
+            counter: 0,
+            labels: HashMap::new(),
+        }
+    }
+
+    pub fn has_label(&self, label: &str) -> bool {
+        self.labels.contains_key(label)
+    }
+
+    /// Replace placeholders of [`ref_placeholder`] with links to equations
+    pub fn resolve_refs(&self, html: &str) -> String {
+        let mut output = String::with_capacity(html.len());
+        let mut rest = html;
+        while let Some(i) = rest.find(REF_START) {
+            output.push_str(&rest[..i]);
+            let placeholder = &rest[i + REF_START.len_utf8()..];
+            let Some(end) = placeholder.find(REF_END) else {
+                rest = placeholder;
+                continue;
+            };
+            let (kind, label) = placeholder[..end]
+                .split_once(REF_SEP)
+                .unwrap_or(("ref", &placeholder[..end]));
+            match self.labels.get(label) {
+                Some(tag) => {
+                    let text = if kind == "eqref" {
+                        format!("({tag})")
+                    } else {
+                        tag.clone()
+                    };
+                    output.push_str(&format!(
+                        r##"<a href="#{}" class="eqref">{}</a>"##,
+                        html_escape::encode_double_quoted_attribute(&equation_id(label)),
+                        html_escape::encode_text(&text)
+                    ));
+                }
+                None => output.push_str(r##"<span class="math-error">??</span>"##),
+            }
+            rest = &placeholder[end + REF_END.len_utf8()..];
+        }
+        output.push_str(rest);
+        output
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Numbering {
+    None,
+    /// one number for the whole environment, e.g., `equation`
+    Single,
+    /// one number for each row, e.g., `align`
+    PerRow,
+}
+
+/// Environment, e.g., `\begin{align}..\end{align}`, to `<math display="block">`
+pub fn environment_to_mathml(text: &str, equations: &mut Equations) -> Result<String, MathError> {
+    let mut parser = Parser::new(text);
+    parser.skip_whitespace();
+    let begin = parser.pos;
+    if parser.read_command() != Some("begin") {
+        return Err(parser.error_at(begin, "expected `\\begin`"));
+    }
+    let name_offset = parser.pos;
+    let name = parser.read_raw_argument("\\begin")?;
+    let starred = name.ends_with('*');
+    let (numbering, columnalign) = match name.trim_end_matches('*') {
+        "equation" | "displaymath" | "math" => (Numbering::Single, "center"),
+        "multline" => (Numbering::Single, "center"),
+        "gather" => (Numbering::PerRow, "center"),
+        "align" | "flalign" => (Numbering::PerRow, "right left"),
+        "alignat" => {
+            parser.read_raw_argument("\\begin{alignat}")?;
+            (Numbering::PerRow, "right left")
+        }
+        "eqnarray" => (Numbering::PerRow, "right center left"),
+        _ => {
+            return Err(parser.error_at(name_offset, format!("unsupported environment `{name}`")));
+        }
+    };
+    let numbering = match name.as_str() {
+        _ if starred => Numbering::None,
+        "displaymath" | "math" => Numbering::None,
+        _ => numbering,
+    };
+
+    let rows = parser.parse_table(&name, begin, true)?;
+    parser.expect_end()?;
+
+    // label or tag of any row applies to the single number, placed at the last row
+    let mut single_meta = RowMeta::default();
+    if numbering == Numbering::Single {
+        for (_, meta) in rows.iter() {
+            single_meta.label = single_meta.label.take().or(meta.label.clone());
+            single_meta.tag = single_meta.tag.take().or(meta.tag.clone());
+            single_meta.nonumber |= meta.nonumber;
+        }
+    }
+
+    let empty_meta = RowMeta::default();
+    let n_rows = rows.len();
+    let mut tags = vec![];
+    for (i, (_, meta)) in rows.iter().enumerate() {
+        let (meta, numbered) = match numbering {
+            Numbering::None => (meta, false),
+            Numbering::Single if i + 1 == n_rows => (&single_meta, true),
+            Numbering::Single => (&empty_meta, false),
+            Numbering::PerRow => (meta, true),
+        };
+        let tag = match &meta.tag {
+            Some(tag) => Some(tag.clone()),
+            None if numbered && !meta.nonumber && equations.numbering => {
+                equations.counter += 1;
+                Some(equations.counter.to_string())
+            }
+            None => None,
+        };
+        if let (Some(tag), Some(label)) = (&tag, &meta.label) {
+            if equations.labels.contains_key(label) {
+                return Err(parser.error_at(begin, format!("duplicate label `{label}`")));
+            }
+            equations.labels.insert(label.clone(), tag.clone());
+        }
+        tags.push(tag.map(|tag| (tag, meta.label.clone())));
+    }
+
+    let has_tag = tags.iter().any(|e| e.is_some());
+    let mut html = String::new();
+    for ((cells, _), tag) in rows.into_iter().zip(tags) {
+        let cells = cells
+            .into_iter()
+            .map(|cell| format!("<mtd>{cell}</mtd>"))
+            .collect::<String>();
+        match tag {
+            Some((tag, label)) => {
+                let id = label.map_or(String::new(), |label| {
+                    format!(
+                        r##" id="{}""##,
+                        html_escape::encode_double_quoted_attribute(&equation_id(&label))
+                    )
+                });
+                html.push_str(&format!(
+                    r##"<mtr{id}>{cells}<mtd class="eqno"><mtext>({})</mtext></mtd></mtr>"##,
+                    html_escape::encode_text(&tag)
+                ));
+            }
+            None if has_tag => html.push_str(&format!("<mtr>{cells}<mtd></mtd></mtr>")),
+            None => html.push_str(&format!("<mtr>{cells}</mtr>")),
+        }
+    }
+
+    Ok(format!(
+        r##"<math display="block"><mtable displaystyle="true" columnalign="{columnalign}">{html}</mtable></math>"##
+    ))
+}
+
+#[derive(Debug, Default, Clone)]
+struct RowMeta {
+    label: Option<String>,
+    tag: Option<String>,
+    nonumber: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Variant {
+    Italic,
+    Normal,
+    Bold,
+    DoubleStruck,
+    Script,
+    Fraktur,
+    SansSerif,
+    Monospace,
+}
+
+enum Symbol {
+    /// `<mi>`
+    Ident(&'static str),
+    /// `<mi mathvariant="normal">`, e.g., uppercase greek letter
+    Upright(&'static str),
+    /// `<mo>`
+    Op(&'static str),
+    /// large operator whose scripts are limits in display mode, e.g., `\sum`
+    LargeOp(&'static str),
+    /// integral like operator, scripts are never limits
+    Integral(&'static str),
+    /// function name whose scripts are limits in display mode, e.g., `\lim`
+    Limits(&'static str),
+    /// function name, e.g., `\sin`
+    Function,
+    /// `<mspace>` of width
+    Space(&'static str),
+}
+
+fn symbol(name: &str) -> Option<Symbol> {
+    use Symbol::*;
+    let symbol = match name {
+        // greek
+        "alpha" => Ident("α"),
+        "beta" => Ident("β"),
+        "gamma" => Ident("γ"),
+        "delta" => Ident("δ"),
+        "epsilon" => Ident("ϵ"),
+        "varepsilon" => Ident("ε"),
+        "zeta" => Ident("ζ"),
+        "eta" => Ident("η"),
+        "theta" => Ident("θ"),
+        "vartheta" => Ident("ϑ"),
+        "iota" => Ident("ι"),
+        "kappa" => Ident("κ"),
+        "lambda" => Ident("λ"),
+        "mu" => Ident("μ"),
+        "nu" => Ident("ν"),
+        "xi" => Ident("ξ"),
+        "pi" => Ident("π"),
+        "varpi" => Ident("ϖ"),
+        "rho" => Ident("ρ"),
+        "varrho" => Ident("ϱ"),
+        "sigma" => Ident("σ"),
+        "varsigma" => Ident("ς"),
+        "tau" => Ident("τ"),
+        "upsilon" => Ident("υ"),
+        "phi" => Ident("ϕ"),
+        "varphi" => Ident("φ"),
+        "chi" => Ident("χ"),
+        "psi" => Ident("ψ"),
+        "omega" => Ident("ω"),
+        "Gamma" => Upright("Γ"),
+        "Delta" => Upright("Δ"),
+        "Theta" => Upright("Θ"),
+        "Lambda" => Upright("Λ"),
+        "Xi" => Upright("Ξ"),
+        "Pi" => Upright("Π"),
+        "Sigma" => Upright("Σ"),
+        "Upsilon" => Upright("Υ"),
+        "Phi" => Upright("Φ"),
+        "Psi" => Upright("Ψ"),
+        "Omega" => Upright("Ω"),
+
+        // letter like
+        "infty" => Upright("∞"),
+        "partial" => Ident("∂"),
+        "nabla" => Upright("∇"),
+        "emptyset" | "varnothing" => Upright("∅"),
+        "hbar" => Ident("ℏ"),
+        "ell" => Ident("ℓ"),
+        "Re" => Upright("ℜ"),
+        "Im" => Upright("ℑ"),
+        "aleph" => Upright("ℵ"),
+        "wp" => Ident("℘"),
+        "angle" => Upright("∠"),
+        "triangle" => Upright("△"),
+        "prime" => Op("′"),
+        "top" => Upright("⊤"),
+        "bot" => Upright("⊥"),
+
+        // binary operators and relations
+        "pm" => Op("±"),
+        "mp" => Op("∓"),
+        "times" => Op("×"),
+        "div" => Op("÷"),
+        "cdot" => Op("⋅"),
+        "ast" => Op("∗"),
+        "star" => Op("⋆"),
+        "circ" => Op("∘"),
+        "bullet" => Op("∙"),
+        "oplus" => Op("⊕"),
+        "ominus" => Op("⊖"),
+        "otimes" => Op("⊗"),
+        "odot" => Op("⊙"),
+        "cap" => Op("∩"),
+        "cup" => Op("∪"),
+        "wedge" | "land" => Op("∧"),
+        "vee" | "lor" => Op("∨"),
+        "setminus" => Op("∖"),
+        "leq" | "le" => Op("≤"),
+        "geq" | "ge" => Op("≥"),
+        "neq" | "ne" => Op("≠"),
+        "leqslant" => Op("⩽"),
+        "geqslant" => Op("⩾"),
+        "approx" => Op("≈"),
+        "equiv" => Op("≡"),
+        "sim" => Op("∼"),
+        "simeq" => Op("≃"),
+        "cong" => Op("≅"),
+        "propto" => Op("∝"),
+        "ll" => Op("≪"),
+        "gg" => Op("≫"),
+        "prec" => Op("≺"),
+        "succ" => Op("≻"),
+        "in" => Op("∈"),
+        "notin" => Op("∉"),
+        "ni" => Op("∋"),
+        "subset" => Op("⊂"),
+        "supset" => Op("⊃"),
+        "subseteq" => Op("⊆"),
+        "supseteq" => Op("⊇"),
+        "to" | "rightarrow" => Op("→"),
+        "leftarrow" | "gets" => Op("←"),
+        "leftrightarrow" => Op("↔"),
+        "Rightarrow" => Op("⇒"),
+        "Leftarrow" => Op("⇐"),
+        "Leftrightarrow" => Op("⇔"),
+        "longrightarrow" => Op("⟶"),
+        "longleftarrow" => Op("⟵"),
+        "implies" | "Longrightarrow" => Op("⟹"),
+        "impliedby" | "Longleftarrow" => Op("⟸"),
+        "iff" | "Longleftrightarrow" => Op("⟺"),
+        "mapsto" => Op("↦"),
+        "uparrow" => Op("↑"),
+        "downarrow" => Op("↓"),
+        "mid" => Op("∣"),
+        "parallel" => Op("∥"),
+        "perp" => Op("⊥"),
+        "forall" => Op("∀"),
+        "exists" => Op("∃"),
+        "nexists" => Op("∄"),
+        "neg" | "lnot" => Op("¬"),
+        "therefore" => Op("∴"),
+        "because" => Op("∵"),
+        "ldots" | "dots" | "dotsc" | "dotsb" => Op("…"),
+        "cdots" => Op("⋯"),
+        "vdots" => Op("⋮"),
+        "ddots" => Op("⋱"),
+        "colon" => Op(":"),
+        "vert" | "lvert" | "rvert" => Op("|"),
+        "Vert" | "lVert" | "rVert" | "|" => Op("‖"),
+        "langle" => Op("⟨"),
+        "rangle" => Op("⟩"),
+        "lfloor" => Op("⌊"),
+        "rfloor" => Op("⌋"),
+        "lceil" => Op("⌈"),
+        "rceil" => Op("⌉"),
+        "{" | "lbrace" => Op("{"),
+        "}" | "rbrace" => Op("}"),
+        "backslash" => Op("\\"),
+        "%" => Op("%"),
+        "&" => Op("&"),
+        "#" => Op("#"),
+        "$" => Op("$"),
+        "_" => Op("_"),
+        "bmod" | "mod" => Op("mod"),
+
+        // large operators
+        "sum" => LargeOp("∑"),
+        "prod" => LargeOp("∏"),
+        "coprod" => LargeOp("∐"),
+        "bigcup" => LargeOp("⋃"),
+        "bigcap" => LargeOp("⋂"),
+        "bigoplus" => LargeOp("⨁"),
+        "bigotimes" => LargeOp("⨂"),
+        "bigvee" => LargeOp("⋁"),
+        "bigwedge" => LargeOp("⋀"),
+        "int" => Integral("∫"),
+        "iint" => Integral("∬"),
+        "iiint" => Integral("∭"),
+        "oint" => Integral("∮"),
+
+        // functions
+        "lim" => Limits("lim"),
+        "liminf" => Limits("lim inf"),
+        "limsup" => Limits("lim sup"),
+        "max" => Limits("max"),
+        "min" => Limits("min"),
+        "sup" => Limits("sup"),
+        "inf" => Limits("inf"),
+        "det" => Limits("det"),
+        "Pr" => Limits("Pr"),
+        "gcd" => Limits("gcd"),
+        "argmax" => Limits("arg max"),
+        "argmin" => Limits("arg min"),
+        "sin" | "cos" | "tan" | "cot" | "sec" | "csc" | "arcsin" | "arccos" | "arctan" | "sinh"
+        | "cosh" | "tanh" | "coth" | "log" | "ln" | "lg" | "exp" | "deg" | "dim" | "ker"
+        | "hom" | "arg" => Function,
+
+        // spaces
+        "," | "thinspace" => Space("0.1667em"),
+        ":" | ">" | "medspace" => Space("0.2222em"),
+        ";" | "thickspace" => Space("0.2778em"),
+        "!" | "negthinspace" => Space("-0.1667em"),
+        " " | "enspace" => Space("0.5em"),
+        "quad" => Space("1em"),
+        "qquad" => Space("2em"),
+        _ => return None,
+    };
+    Some(symbol)
+}
+
+/// (accent, stretchy, is_over)
+fn accent(name: &str) -> Option<(&'static str, bool, bool)> {
+    let accent = match name {
+        "hat" => ("^", false, true),
+        "widehat" => ("^", true, true),
+        "bar" => ("¯", false, true),
+        "overline" => ("‾", true, true),
+        "vec" => ("→", false, true),
+        "overrightarrow" => ("→", true, true),
+        "overleftarrow" => ("←", true, true),
+        "tilde" => ("~", false, true),
+        "widetilde" => ("˜", true, true),
+        "dot" => ("˙", false, true),
+        "ddot" => ("¨", false, true),
+        "check" => ("ˇ", false, true),
+        "breve" => ("˘", false, true),
+        "acute" => ("´", false, true),
+        "grave" => ("`", false, true),
+        "underline" => ("_", true, false),
+        _ => return None,
+    };
+    Some(accent)
+}
+
+fn variant_of(name: &str) -> Option<Variant> {
+    let variant = match name {
+        "mathrm" | "mathup" | "rm" => Variant::Normal,
+        "mathit" => Variant::Italic,
+        "mathbf" | "boldsymbol" | "bm" | "bf" => Variant::Bold,
+        "mathbb" => Variant::DoubleStruck,
+        "mathcal" | "mathscr" => Variant::Script,
+        "mathfrak" => Variant::Fraktur,
+        "mathsf" => Variant::SansSerif,
+        "mathtt" => Variant::Monospace,
+        _ => return None,
+    };
+    Some(variant)
+}
+
+/// Char of math alphabet `variant` in Unicode Mathematical Alphanumeric Symbols
+fn styled_char(c: char, variant: Variant) -> char {
+    let hole = match (variant, c) {
+        (Variant::DoubleStruck, 'C') => 'ℂ',
+        (Variant::DoubleStruck, 'H') => 'ℍ',
+        (Variant::DoubleStruck, 'N') => 'ℕ',
+        (Variant::DoubleStruck, 'P') => 'ℙ',
+        (Variant::DoubleStruck, 'Q') => 'ℚ',
+        (Variant::DoubleStruck, 'R') => 'ℝ',
+        (Variant::DoubleStruck, 'Z') => 'ℤ',
+        (Variant::Script, 'B') => 'ℬ',
+        (Variant::Script, 'E') => 'ℰ',
+        (Variant::Script, 'F') => 'ℱ',
+        (Variant::Script, 'H') => 'ℋ',
+        (Variant::Script, 'I') => 'ℐ',
+        (Variant::Script, 'L') => 'ℒ',
+        (Variant::Script, 'M') => 'ℳ',
+        (Variant::Script, 'R') => 'ℛ',
+        (Variant::Script, 'e') => 'ℯ',
+        (Variant::Script, 'g') => 'ℊ',
+        (Variant::Script, 'o') => 'ℴ',
+        (Variant::Fraktur, 'C') => 'ℭ',
+        (Variant::Fraktur, 'H') => 'ℌ',
+        (Variant::Fraktur, 'I') => 'ℑ',
+        (Variant::Fraktur, 'R') => 'ℜ',
+        (Variant::Fraktur, 'Z') => 'ℨ',
+        _ => '\0',
+    };
+    if hole != '\0' {
+        return hole;
+    }
+
+    // (A, a, 0)
+    let (upper, lower, digit) = match variant {
+        Variant::Bold => (0x1D400, 0x1D41A, Some(0x1D7CE)),
+        Variant::DoubleStruck => (0x1D538, 0x1D552, Some(0x1D7D8)),
+        Variant::Script => (0x1D49C, 0x1D4B6, None),
+        Variant::Fraktur => (0x1D504, 0x1D51E, None),
+        Variant::SansSerif => (0x1D5A0, 0x1D5BA, Some(0x1D7E2)),
+        Variant::Monospace => (0x1D670, 0x1D68A, Some(0x1D7F6)),
+        Variant::Italic | Variant::Normal => return c,
+    };
+    let code = match c {
+        'A'..='Z' => Some(upper + (c as u32 - 'A' as u32)),
+        'a'..='z' => Some(lower + (c as u32 - 'a' as u32)),
+        '0'..='9' => digit.map(|e| e + (c as u32 - '0' as u32)),
+        _ => None,
+    };
+    code.and_then(char::from_u32).unwrap_or(c)
+}
+
+/// size of `\big` like commands
+fn big_size(name: &str) -> Option<&'static str> {
+    let size = match name.trim_end_matches(['l', 'r', 'm']) {
+        "big" => "1.2em",
+        "Big" => "1.623em",
+        "bigg" => "2.047em",
+        "Bigg" => "2.470em",
+        _ => return None,
+    };
+    Some(size)
+}
+
+fn escape(s: &str) -> String {
+    html_escape::encode_text(s).to_string()
+}
+
+/// wrap items into one element
+fn row(items: Vec<String>) -> String {
+    if items.len() == 1 {
+        items.into_iter().next().expect("one item")
+    } else {
+        format!("<mrow>{}</mrow>", items.concat())
+    }
+}
+
+struct Atom {
+    mathml: String,
+    /// scripts are placed under/over it in display mode
+    limits: bool,
+}
+
+impl Atom {
+    fn new(mathml: String) -> Self {
+        Self {
+            mathml,
+            limits: false,
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+    /// math alphabet of letters, e.g., `\mathbb`
+    variant: Variant,
+    /// label, tag of the current row of environment
+    row_meta: RowMeta,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            input,
+            pos: 0,
+            variant: Variant::Italic,
+            row_meta: RowMeta::default(),
+        }
+    }
+
+    fn error_at<S: Into<String>>(&self, offset: usize, message: S) -> MathError {
+        MathError {
+            message: message.into(),
+            offset,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    /// name of command at current position without consuming it
+    fn peek_command(&self) -> Option<&'a str> {
+        let rest = self.rest().strip_prefix('\\')?;
+        let n = rest
+            .char_indices()
+            .find(|(_, c)| !c.is_ascii_alphabetic())
+            .map_or(rest.len(), |(i, _)| i);
+        if n > 0 {
+            Some(&rest[..n])
+        } else {
+            rest.chars().next().map(|c| &rest[..c.len_utf8()])
+        }
+    }
+
+    fn read_command(&mut self) -> Option<&'a str> {
+        let name = self.peek_command()?;
+        self.pos += 1 + name.len();
+        Some(name)
+    }
+
+    /// everything is consumed
+    fn expect_end(&mut self) -> Result<(), MathError> {
+        self.skip_whitespace();
+        let offset = self.pos;
+        match self.peek() {
+            None => Ok(()),
+            Some('}') => Err(self.error_at(offset, "unexpected `}`")),
+            Some('&') => Err(self.error_at(offset, "`&` outside of alignment environment")),
+            Some(']') => Err(self.error_at(offset, "unexpected `]`")),
+            _ => {
+                let name = self.peek_command().unwrap_or_default();
+                Err(self.error_at(offset, format!("unexpected `\\{name}`")))
+            }
+        }
+    }
+
+    /// Text of `{..}` argument without parsing, e.g., name of environment
+    fn read_raw_argument(&mut self, command: &str) -> Result<String, MathError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        if self.peek() != Some('{') {
+            return Err(self.error_at(start, format!("missing argument of `{command}`")));
+        }
+        let mut depth = 0;
+        for (i, c) in self.rest().char_indices() {
+            match c {
+                '{' => depth += 1,
+                '}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        let text = self.rest()[1..i].to_string();
+                        self.pos += i + 1;
+                        return Ok(text);
+                    }
+                }
+                _ => {}
+            }
+        }
+        Err(self.error_at(start, "unmatched `{`"))
+    }
+
+    /// Parse items until `}`, `&`, `\\`, `\end`, `\right`, `\middle`, `]` (if `in_optional`) or
+    /// end of input. `\\` is ignored if not `in_table`.
+    fn parse_list_until(
+        &mut self,
+        in_table: bool,
+        in_optional: bool,
+    ) -> Result<Vec<String>, MathError> {
+        let mut items = vec![];
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None | Some('}') | Some('&') => break,
+                Some(']') if in_optional => break,
+                Some('\\') => match self.peek_command() {
+                    Some("\\") if in_table => break,
+                    Some("\\") => {
+                        self.pos += 2;
+                        continue;
+                    }
+                    Some("end" | "right" | "middle") => break,
+                    _ => {}
+                },
+                _ => {}
+            }
+            let Some(atom) = self.parse_atom()? else {
+                continue;
+            };
+            items.push(self.parse_scripts(atom)?);
+        }
+        Ok(items)
+    }
+
+    fn parse_list(&mut self, in_table: bool) -> Result<Vec<String>, MathError> {
+        self.parse_list_until(in_table, false)
+    }
+
+    /// `{..}`
+    fn parse_group(&mut self) -> Result<String, MathError> {
+        let start = self.pos;
+        self.pos += 1;
+        let items = self.parse_list(false)?;
+        self.skip_whitespace();
+        if self.peek() != Some('}') {
+            return Err(self.error_at(start, "unmatched `{`"));
+        }
+        self.pos += 1;
+        Ok(row(items))
+    }
+
+    /// argument of command: `{..}` or a single token
+    fn parse_argument(&mut self, command: &str) -> Result<String, MathError> {
+        self.skip_whitespace();
+        let offset = self.pos;
+        match self.peek() {
+            Some('{') => self.parse_group(),
+            None | Some('}') | Some('&') | Some('^') | Some('_') => {
+                Err(self.error_at(offset, format!("missing argument of `{command}`")))
+            }
+            _ => match self.parse_atom()? {
+                Some(atom) => Ok(atom.mathml),
+                None => Err(self.error_at(offset, format!("missing argument of `{command}`"))),
+            },
+        }
+    }
+
+    /// `[..]`
+    fn parse_optional_argument(&mut self) -> Result<Option<String>, MathError> {
+        self.skip_whitespace();
+        if self.peek() != Some('[') {
+            return Ok(None);
+        }
+        let start = self.pos;
+        self.pos += 1;
+        let items = self.parse_list_until(false, true)?;
+        if self.peek() != Some(']') {
+            return Err(self.error_at(start, "unmatched `[`"));
+        }
+        self.pos += 1;
+        Ok(Some(row(items)))
+    }
+
+    /// `^`, `_` and `'` after `atom`
+    fn parse_scripts(&mut self, atom: Atom) -> Result<String, MathError> {
+        let mut sub = None;
+        let mut sup = None;
+        let mut primes = String::new();
+        loop {
+            self.skip_whitespace();
+            let offset = self.pos;
+            match self.peek() {
+                Some('\'') => {
+                    self.pos += 1;
+                    primes.push('′');
+                }
+                Some(c @ ('^' | '_')) => {
+                    self.pos += 1;
+                    let script = self.parse_argument(if c == '^' { "^" } else { "_" })?;
+                    let slot = if c == '^' { &mut sup } else { &mut sub };
+                    if slot.is_some() {
+                        let message = if c == '^' {
+                            "double superscript"
+                        } else {
+                            "double subscript"
+                        };
+                        return Err(self.error_at(offset, message));
+                    }
+                    *slot = Some(script);
+                }
+                _ => break,
+            }
+        }
+
+        let sup = match (primes.is_empty(), sup) {
+            (true, sup) => sup,
+            (false, None) => Some(format!("<mo>{primes}</mo>")),
+            (false, Some(sup)) => Some(format!("<mrow><mo>{primes}</mo>{sup}</mrow>")),
+        };
+        let base = atom.mathml;
+        let (under, over, underover) = if atom.limits {
+            ("munder", "mover", "munderover")
+        } else {
+            ("msub", "msup", "msubsup")
+        };
+        Ok(match (sub, sup) {
+            (None, None) => base,
+            (Some(sub), None) => format!("<{under}>{base}{sub}</{under}>"),
+            (None, Some(sup)) => format!("<{over}>{base}{sup}</{over}>"),
+            (Some(sub), Some(sup)) => format!("<{underover}>{base}{sub}{sup}</{underover}>"),
+        })
+    }
+
+    /// `None` if nothing is rendered, e.g., `\label{..}`
+    fn parse_atom(&mut self) -> Result<Option<Atom>, MathError> {
+        let offset = self.pos;
+        let Some(c) = self.peek() else {
+            return Err(self.error_at(offset, "unexpected end of math"));
+        };
+        let mathml = match c {
+            '{' => self.parse_group()?,
+            '\\' => return self.parse_command(),
+            // script without base
+            '^' | '_' => "<mrow></mrow>".to_string(),
+            '0'..='9' | '.' if self.is_number_start() => {
+                let n = self
+                    .rest()
+                    .char_indices()
+                    .find(|(i, c)| {
+                        !(c.is_ascii_digit()
+                            || (*c == '.'
+                                && self.rest()[i + 1..].starts_with(|c: char| c.is_ascii_digit())))
+                    })
+                    .map_or(self.rest().len(), |(i, _)| i);
+                let number = &self.rest()[..n];
+                self.pos += n;
+                let number = number
+                    .chars()
+                    .map(|c| styled_char(c, self.variant))
+                    .collect::<String>();
+                format!("<mn>{number}</mn>")
+            }
+            '$' => return Err(self.error_at(offset, "unexpected `$` in math")),
+            '#' => return Err(self.error_at(offset, "unexpected `#` in math")),
+            '~' => {
+                self.pos += 1;
+                r##"<mspace width="0.25em"></mspace>"##.to_string()
+            }
+            _ => {
+                self.pos += c.len_utf8();
+                if c.is_alphabetic() {
+                    self.identifier(&styled_char(c, self.variant).to_string())
+                } else {
+                    let op = match c {
+                        '-' => "−".to_string(),
+                        '*' => "∗".to_string(),
+                        '\'' => "′".to_string(),
+                        _ => escape(&c.to_string()),
+                    };
+                    format!("<mo>{op}</mo>")
+                }
+            }
+        };
+        Ok(Some(Atom::new(mathml)))
+    }
+
+    fn is_number_start(&self) -> bool {
+        let rest = self.rest();
+        rest.starts_with(|c: char| c.is_ascii_digit())
+            || (rest.starts_with('.') && rest[1..].starts_with(|c: char| c.is_ascii_digit()))
+    }
+
+    fn identifier(&self, text: &str) -> String {
+        match self.variant {
+            Variant::Normal => format!(r##"<mi mathvariant="normal">{}</mi>"##, escape(text)),
+            _ => format!("<mi>{}</mi>", escape(text)),
+        }
+    }
+
+    fn parse_command(&mut self) -> Result<Option<Atom>, MathError> {
+        let offset = self.pos;
+        let Some(name) = self.read_command() else {
+            return Err(self.error_at(offset, "unexpected end after `\\`"));
+        };
+
+        if let Some(symbol) = symbol(name) {
+            let atom = match symbol {
+                Symbol::Ident(s) => Atom::new(format!("<mi>{}</mi>", escape(s))),
+                Symbol::Upright(s) => {
+                    Atom::new(format!(r##"<mi mathvariant="normal">{}</mi>"##, escape(s)))
+                }
+                Symbol::Op(s) => Atom::new(format!("<mo>{}</mo>", escape(s))),
+                Symbol::LargeOp(s) => Atom {
+                    mathml: format!(r##"<mo largeop="true" movablelimits="true">{s}</mo>"##),
+                    limits: true,
+                },
+                Symbol::Integral(s) => Atom::new(format!(r##"<mo largeop="true">{s}</mo>"##)),
+                Symbol::Limits(s) => Atom {
+                    mathml: format!(r##"<mo movablelimits="true" form="prefix">{s}</mo>"##),
+                    limits: true,
+                },
+                Symbol::Function => Atom::new(format!("<mi>{name}</mi>")),
+                Symbol::Space(width) => {
+                    Atom::new(format!(r##"<mspace width="{width}"></mspace>"##))
+                }
+            };
+            return Ok(Some(atom));
+        }
+
+        if let Some(variant) = variant_of(name) {
+            let saved = self.variant;
+            self.variant = variant;
+            let argument = self.parse_argument(&format!("\\{name}"));
+            self.variant = saved;
+            return Ok(Some(Atom::new(argument?)));
+        }
+
+        if let Some((accent, stretchy, is_over)) = accent(name) {
+            let base = self.parse_argument(&format!("\\{name}"))?;
+            let mathml = if is_over {
+                format!(
+                    r##"<mover accent="true">{base}<mo stretchy="{stretchy}">{accent}</mo></mover>"##
+                )
+            } else {
+                format!(
+                    r##"<munder accentunder="true">{base}<mo stretchy="{stretchy}">{accent}</mo></munder>"##
+                )
+            };
+            return Ok(Some(Atom::new(mathml)));
+        }
+
+        if let Some(size) = big_size(name) {
+            let delimiter = self.parse_delimiter(name)?.unwrap_or_default();
+            return Ok(Some(Atom::new(format!(
+                r##"<mo minsize="{size}" maxsize="{size}">{delimiter}</mo>"##
+            ))));
+        }
+
+        let command = format!("\\{name}");
+        let mathml = match name {
+            "frac" | "dfrac" | "tfrac" | "cfrac" => {
+                let numerator = self.parse_argument(&command)?;
+                let denominator = self.parse_argument(&command)?;
+                let frac = format!("<mfrac>{numerator}{denominator}</mfrac>");
+                match name {
+                    "dfrac" | "cfrac" => {
+                        format!(r##"<mstyle displaystyle="true">{frac}</mstyle>"##)
+                    }
+                    "tfrac" => format!(r##"<mstyle displaystyle="false">{frac}</mstyle>"##),
+                    _ => frac,
+                }
+            }
+            "binom" | "dbinom" | "tbinom" => {
+                let n = self.parse_argument(&command)?;
+                let k = self.parse_argument(&command)?;
+                format!(
+                    r##"<mrow><mo>(</mo><mfrac linethickness="0">{n}{k}</mfrac><mo>)</mo></mrow>"##
+                )
+            }
+            "sqrt" => {
+                let index = self.parse_optional_argument()?;
+                let radicand = self.parse_argument(&command)?;
+                match index {
+                    Some(index) => format!("<mroot>{radicand}{index}</mroot>"),
+                    None => format!("<msqrt>{radicand}</msqrt>"),
+                }
+            }
+            "text" | "textrm" | "textnormal" | "textup" | "mbox" | "hbox" | "textbf" | "textit"
+            | "textsf" | "texttt" => {
+                let text = self.read_raw_argument(&command)?;
+                let style = match name {
+                    "textbf" => r##" style="font-weight: bold""##,
+                    "textit" => r##" style="font-style: italic""##,
+                    "textsf" => r##" style="font-family: sans-serif""##,
+                    "texttt" => r##" style="font-family: monospace""##,
+                    _ => "",
+                };
+                format!("<mtext{style}>{}</mtext>", escape(&text))
+            }
+            "operatorname" => {
+                let limits = self.peek() == Some('*');
+                if limits {
+                    self.pos += 1;
+                }
+                let text = escape(&self.read_raw_argument(&command)?);
+                if limits {
+                    return Ok(Some(Atom {
+                        mathml: format!(r##"<mo movablelimits="true" form="prefix">{text}</mo>"##),
+                        limits: true,
+                    }));
+                }
+                format!(r##"<mi mathvariant="normal">{text}</mi>"##)
+            }
+            "overbrace" | "underbrace" => {
+                let base = self.parse_argument(&command)?;
+                let (tag, brace) = if name == "overbrace" {
+                    ("mover", "⏞")
+                } else {
+                    ("munder", "⏟")
+                };
+                return Ok(Some(Atom {
+                    mathml: format!(r##"<{tag}>{base}<mo stretchy="true">{brace}</mo></{tag}>"##),
+                    limits: true,
+                }));
+            }
+            "overset" | "stackrel" | "underset" => {
+                let script = self.parse_argument(&command)?;
+                let base = self.parse_argument(&command)?;
+                let tag = if name == "underset" {
+                    "munder"
+                } else {
+                    "mover"
+                };
+                format!("<{tag}>{base}{script}</{tag}>")
+            }
+            "pmod" => {
+                let argument = self.parse_argument(&command)?;
+                format!(
+                    r##"<mrow><mspace width="0.5em"></mspace><mo>(</mo><mo>mod</mo>{argument}<mo>)</mo></mrow>"##
+                )
+            }
+            "phantom" => {
+                let argument = self.parse_argument(&command)?;
+                format!("<mphantom>{argument}</mphantom>")
+            }
+            "boxed" => {
+                let argument = self.parse_argument(&command)?;
+                format!(r##"<mrow style="border: 1px solid; padding: 0.2em">{argument}</mrow>"##)
+            }
+            "not" => {
+                self.skip_whitespace();
+                let negated = match self.parse_atom()? {
+                    Some(atom) => atom.mathml,
+                    None => return Err(self.error_at(offset, "missing argument of `\\not`")),
+                };
+                match negated
+                    .strip_prefix("<mo>")
+                    .and_then(|e| e.strip_suffix("</mo>"))
+                {
+                    Some("=") => "<mo>≠</mo>".to_string(),
+                    Some("∈") => "<mo>∉</mo>".to_string(),
+                    Some(op) => format!("<mo>{op}\u{0338}</mo>"),
+                    None => negated,
+                }
+            }
+            "left" => return self.parse_left_right(offset).map(|e| Some(Atom::new(e))),
+            "begin" => {
+                return self
+                    .parse_inner_environment(offset)
+                    .map(|e| Some(Atom::new(e)));
+            }
+            "label" => {
+                let label = self.read_raw_argument(&command)?;
+                self.row_meta.label = Some(label.trim().to_string());
+                return Ok(None);
+            }
+            "tag" => {
+                if self.peek() == Some('*') {
+                    self.pos += 1;
+                }
+                let tag = self.read_raw_argument(&command)?;
+                self.row_meta.tag = Some(tag.trim().to_string());
+                return Ok(None);
+            }
+            "nonumber" | "notag" => {
+                self.row_meta.nonumber = true;
+                return Ok(None);
+            }
+            "displaystyle" | "textstyle" | "scriptstyle" | "limits" | "nolimits"
+            | "displaylimits" | "allowbreak" | "nobreak" | "relax" => return Ok(None),
+            "\\" => return Ok(None),
+            _ => return Err(self.error_at(offset, format!("unknown command `\\{name}`"))),
+        };
+        Ok(Some(Atom::new(mathml)))
+    }
+
+    /// delimiter after `\left`, `\right`, `\big`, etc., `None` for `.`
+    fn parse_delimiter(&mut self, command: &str) -> Result<Option<String>, MathError> {
+        self.skip_whitespace();
+        let offset = self.pos;
+        match self.peek() {
+            Some('.') => {
+                self.pos += 1;
+                Ok(None)
+            }
+            Some('\\') => {
+                let name = self.read_command().unwrap_or_default();
+                match symbol(name) {
+                    Some(Symbol::Op(s)) => Ok(Some(escape(s))),
+                    _ => Err(self.error_at(offset, format!("invalid delimiter `\\{name}`"))),
+                }
+            }
+            Some(c) if "()[]|/<>".contains(c) => {
+                self.pos += 1;
+                let c = match c {
+                    '<' => '⟨',
+                    '>' => '⟩',
+                    _ => c,
+                };
+                Ok(Some(c.to_string()))
+            }
+            _ => Err(self.error_at(offset, format!("missing delimiter of `\\{command}`"))),
+        }
+    }
+
+    fn parse_left_right(&mut self, offset: usize) -> Result<String, MathError> {
+        let fence = |e: Option<String>| {
+            e.map_or(String::new(), |e| {
+                format!(r##"<mo fence="true" stretchy="true">{e}</mo>"##)
+            })
+        };
+        let mut mathml = fence(self.parse_delimiter("left")?);
+        loop {
+            mathml.push_str(&self.parse_list(false)?.concat());
+            match self.peek_command() {
+                Some("middle") => {
+                    self.read_command();
+                    mathml.push_str(&fence(self.parse_delimiter("middle")?));
+                }
+                Some("right") => {
+                    self.read_command();
+                    mathml.push_str(&fence(self.parse_delimiter("right")?));
+                    return Ok(format!("<mrow>{mathml}</mrow>"));
+                }
+                _ => return Err(self.error_at(offset, "missing `\\right` of `\\left`")),
+            }
+        }
+    }
+
+    /// environment inside math, e.g., `pmatrix`, `cases`
+    fn parse_inner_environment(&mut self, begin: usize) -> Result<String, MathError> {
+        let name_offset = self.pos;
+        let name = self.read_raw_argument("\\begin")?;
+        let (open, close, columnalign) = match name.as_str() {
+            "matrix" | "smallmatrix" => ("", "", "center"),
+            "pmatrix" => ("(", ")", "center"),
+            "bmatrix" => ("[", "]", "center"),
+            "Bmatrix" => ("{", "}", "center"),
+            "vmatrix" => ("|", "|", "center"),
+            "Vmatrix" => ("‖", "‖", "center"),
+            "cases" => ("{", "", "left left"),
+            "aligned" | "split" => ("", "", "right left"),
+            "alignedat" => {
+                self.read_raw_argument("\\begin{alignedat}")?;
+                ("", "", "right left")
+            }
+            "gathered" => ("", "", "center"),
+            "array" => {
+                let columns = self.read_raw_argument("\\begin{array}")?;
+                let columnalign = columns
+                    .chars()
+                    .filter_map(|c| match c {
+                        'l' => Some("left"),
+                        'c' => Some("center"),
+                        'r' => Some("right"),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                let rows = self.parse_table(&name, begin, false)?;
+                return Ok(table(rows, &columnalign, "", ""));
+            }
+            _ => {
+                return Err(self.error_at(name_offset, format!("unknown environment `{name}`")));
+            }
+        };
+        let rows = self.parse_table(&name, begin, false)?;
+        Ok(table(rows, columnalign, open, close))
+    }
+
+    /// rows of cells until `\end{name}`, with label/tag of each row if `capture_meta`
+    fn parse_table(
+        &mut self,
+        name: &str,
+        begin: usize,
+        capture_meta: bool,
+    ) -> Result<Vec<(Vec<String>, RowMeta)>, MathError> {
+        let mut rows = vec![];
+        let mut cells = vec![];
+        loop {
+            let cell = self.parse_list(true)?;
+            cells.push(row(cell));
+            self.skip_whitespace();
+            let offset = self.pos;
+            match self.peek() {
+                Some('&') => {
+                    self.pos += 1;
+                    continue;
+                }
+                Some('\\') => {}
+                Some('}') => return Err(self.error_at(offset, "unexpected `}`")),
+                _ => return Err(self.error_at(begin, format!("missing `\\end{{{name}}}`"))),
+            }
+
+            let meta = if capture_meta {
+                std::mem::take(&mut self.row_meta)
+            } else {
+                RowMeta::default()
+            };
+            // skip empty last row, e.g., `a \\ \end{align}`
+            let is_empty = cells.iter().all(|e: &String| e == "<mrow></mrow>");
+            match self.read_command() {
+                Some("\\") => rows.push((std::mem::take(&mut cells), meta)),
+                Some("end") => {
+                    let end_offset = self.pos;
+                    let end_name = self.read_raw_argument("\\end")?;
+                    if end_name != name {
+                        return Err(self.error_at(
+                            end_offset,
+                            format!("`\\begin{{{name}}}` ended by `\\end{{{end_name}}}`"),
+                        ));
+                    }
+                    if !is_empty || rows.is_empty() {
+                        rows.push((cells, meta));
+                    }
+                    return Ok(rows);
+                }
+                Some(other) => {
+                    return Err(self.error_at(offset, format!("unexpected `\\{other}`")));
+                }
+                None => return Err(self.error_at(begin, format!("missing `\\end{{{name}}}`"))),
+            }
+        }
+    }
+}
+
+fn table(rows: Vec<(Vec<String>, RowMeta)>, columnalign: &str, open: &str, close: &str) -> String {
+    let rows = rows
+        .into_iter()
+        .map(|(cells, _)| {
+            let cells = cells
+                .into_iter()
+                .map(|e| format!("<mtd>{e}</mtd>"))
+                .collect::<String>();
+            format!("<mtr>{cells}</mtr>")
+        })
+        .collect::<String>();
+    let columnalign = if columnalign.is_empty() {
+        String::new()
+    } else {
+        format!(r##" columnalign="{columnalign}""##)
+    };
+    let mtable = format!("<mtable{columnalign}>{rows}</mtable>");
+    if open.is_empty() && close.is_empty() {
+        return mtable;
+    }
+    let fence = |e: &str| {
+        if e.is_empty() {
+            String::new()
+        } else {
+            format!(r##"<mo fence="true" stretchy="true">{}</mo>"##, escape(e))
+        }
+    };
+    format!("<mrow>{}{mtable}{}</mrow>", fence(open), fence(close))
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{
+        Equations, environment_to_mathml, fragment_to_mathml, parse_ref, ref_placeholder,
+        split_fragment,
+    };
+
+    #[test]
+    fn test_fragment() {
+        assert_eq!(
+            fragment_to_mathml(r"x_1^2 + \frac{a}{\sqrt{b}} \leq \mathbb{R}", false).unwrap(),
+            r##"<math display="inline"><mrow><msubsup><mi>x</mi><mn>1</mn><mn>2</mn></msubsup><mo>+</mo><mfrac><mi>a</mi><msqrt><mi>b</mi></msqrt></mfrac><mo>≤</mo><mi>ℝ</mi></mrow></math>"##
+        );
+        assert_eq!(
+            fragment_to_mathml(r"\sum_{i=1}^n i", true).unwrap(),
+            r##"<math display="block"><mrow><munderover><mo largeop="true" movablelimits="true">∑</mo><mrow><mi>i</mi><mo>=</mo><mn>1</mn></mrow><mi>n</mi></munderover><mi>i</mi></mrow></math>"##
+        );
+        assert_eq!(
+            fragment_to_mathml(r"\left( \alpha \right] \text{if } f'", false).unwrap(),
+            r##"<math display="inline"><mrow><mrow><mo fence="true" stretchy="true">(</mo><mi>α</mi><mo fence="true" stretchy="true">]</mo></mrow><mtext>if </mtext><msup><mi>f</mi><mo>′</mo></msup></mrow></math>"##
+        );
+    }
+
+    #[test]
+    fn test_fragment_error() {
+        let error = |s: &str| {
+            let e = fragment_to_mathml(s, false).unwrap_err();
+            (e.offset, e.message)
+        };
+        assert_eq!(
+            error(r"a + \foo"),
+            (4, "unknown command `\\foo`".to_string())
+        );
+        assert_eq!(error(r"\frac{a"), (5, "unmatched `{`".to_string()));
+        assert_eq!(error(r"a}"), (1, "unexpected `}`".to_string()));
+        assert_eq!(error(r"x^1^2"), (3, "double superscript".to_string()));
+        assert_eq!(
+            error(r"\left( x"),
+            (0, "missing `\\right` of `\\left`".to_string())
+        );
+        assert_eq!(
+            error(r"\frac{a}"),
+            (8, "missing argument of `\\frac`".to_string())
+        );
+        assert_eq!(
+            error(r"\begin{pmatrix} a \end{bmatrix}"),
+            (
+                22,
+                "`\\begin{pmatrix}` ended by `\\end{bmatrix}`".to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_environment() {
+        let mut equations = Equations::default();
+        let mathml = environment_to_mathml(
+            "\\begin{align}\na &= b \\label{eq:a} \\\\\nc &= d \\nonumber \\\\\n\\end{align}\n\n",
+            &mut equations,
+        )
+        .unwrap();
+        assert_eq!(
+            mathml,
+            r##"<math display="block"><mtable displaystyle="true" columnalign="right left"><mtr id="eq-eq:a"><mtd><mi>a</mi></mtd><mtd><mrow><mo>=</mo><mi>b</mi></mrow></mtd><mtd class="eqno"><mtext>(1)</mtext></mtd></mtr><mtr><mtd><mi>c</mi></mtd><mtd><mrow><mo>=</mo><mi>d</mi></mrow></mtd><mtd></mtd></mtr></mtable></math>"##
+        );
+
+        let mathml = environment_to_mathml(
+            "\\begin{equation}\\label{eq:b}\nE = mc^2\n\\end{equation}",
+            &mut equations,
+        )
+        .unwrap();
+        assert!(mathml.contains(r##"<mtr id="eq-eq:b">"##));
+        assert!(mathml.contains("<mtext>(2)</mtext>"));
+
+        // starred environment is not numbered
+        let mathml =
+            environment_to_mathml("\\begin{equation*}x\\end{equation*}", &mut equations).unwrap();
+        assert!(!mathml.contains("eqno"));
+
+        let html = equations.resolve_refs(&format!(
+            "see {} and {}, {}",
+            ref_placeholder("eq:a", false),
+            ref_placeholder("eq:b", true),
+            ref_placeholder("eq:c", true),
+        ));
+        assert_eq!(
+            html,
+            r##"see <a href="#eq-eq:a" class="eqref">1</a> and <a href="#eq-eq:b" class="eqref">(2)</a>, <span class="math-error">??</span>"##
+        );
+
+        let error = environment_to_mathml(
+            "\\begin{equation}\\label{eq:a}x\\end{equation}",
+            &mut equations,
+        )
+        .unwrap_err();
+        assert_eq!(error.message, "duplicate label `eq:a`");
+        let error = environment_to_mathml("\\begin{tikzpicture}\\end{tikzpicture}", &mut equations)
+            .unwrap_err();
+        assert_eq!(
+            (error.offset, error.message.as_str()),
+            (6, "unsupported environment `tikzpicture`")
+        );
+    }
+
+    #[test]
+    fn test_split_fragment_and_ref() {
+        assert_eq!(split_fragment(r"\(x\)"), Some((2, "x", false)));
+        assert_eq!(split_fragment(r"$$x$$"), Some((2, "x", true)));
+        assert_eq!(split_fragment(r"$x$"), Some((1, "x", false)));
+        assert_eq!(split_fragment(r"\ref{x}"), None);
+        assert_eq!(parse_ref(r"\eqref{eq:1}"), Some(("eq:1", true)));
+        assert_eq!(parse_ref(r"\ref{eq:1}"), Some(("eq:1", false)));
+        assert_eq!(parse_ref(r"\cite{x}"), None);
+    }
+}
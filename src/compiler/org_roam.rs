@@ -26,8 +26,12 @@ pub enum NodeType {
 
 #[derive(Clone)]
 pub enum EdgeType {
-    Parent,                                                    // a --> b: a is parent of b
-    ExplicitReference { source_path: Vec<SourcePathSegment> }, // [[id:...][]]: a refers b
+    Parent, // a --> b: a is parent of b
+    // [[id:...][]]: a refers b, with the enclosing paragraph as context
+    ExplicitReference {
+        source_path: Vec<SourcePathSegment>,
+        context: Option<String>,
+    },
 }
 
 impl fmt::Debug for EdgeType {
@@ -37,7 +41,7 @@ impl fmt::Debug for EdgeType {
                 write!(f, r##""##,)
             }
 
-            EdgeType::ExplicitReference { source_path } => {
+            EdgeType::ExplicitReference { source_path, .. } => {
                 write!(f, r##"{:?}"##, source_path,)
             }
         }
@@ -17,6 +17,9 @@ use rowan::{GreenNode, GreenNodeBuilder, GreenToken, NodeOrToken, WalkEvent};
 use syntax::{OrgSyntaxKind, SyntaxNode};
 use tracing;
 
+use crate::compiler::ast_builder::element::header_argument;
+use crate::compiler::diagnostic::{Diagnostic, Severity};
+
 pub mod config;
 
 type NT = NodeOrToken<GreenNode, GreenToken>;
@@ -279,10 +282,7 @@ impl OrgParser {
                         if args.len() > 0 {
                             let args = args.join("");
                             let ts_parser = object::timestamp::FlexibleDateTimeParser::new();
-                            let ts = ts_parser.parse(
-                                &keyword_value_expanded
-                                    [1..keyword_value_expanded.chars().count() - 1],
-                            );
+                            let ts = ts_parser.parse(keyword_value_expanded);
                             if ts.is_ok() {
                                 let z = ts.unwrap();
                                 let z = z.format(&args).to_string();
@@ -320,6 +320,13 @@ impl OrgParser {
                     builder.finish_node();
                 }
 
+                // results of inline src block or babel call, hidden by `:exports code|none`
+                "RESULTS"
+                    if matches!(
+                        Self::inline_code_exports(node).as_deref(),
+                        Some("code" | "none")
+                    ) => {}
+
                 "RESULTS" => {
                     let args = args.join("");
                     builder.start_node(OSK::Macro.into());
@@ -369,6 +376,28 @@ impl OrgParser {
         builder.finish_node();
     }
 
+    /// `:exports` of the inline src block or inline babel call just before `node`, `results` by default
+    fn inline_code_exports(node: &SyntaxNode) -> Option<String> {
+        let mut prev = node.prev_sibling_or_token();
+        while let Some(NodeOrToken::Token(token)) = &prev
+            && token.text().trim().is_empty()
+        {
+            prev = token.prev_sibling_or_token();
+        }
+        let code = prev?
+            .into_node()
+            .filter(|e| matches!(e.kind(), OSK::InlineSourceBlock | OSK::InlineBabelCall))?;
+        let exports = [
+            OSK::InlineBabelCallHeader2,
+            OSK::InlineBabelCallHeader1,
+            OSK::InlineSourceBlockHeaders,
+        ]
+        .into_iter()
+        .filter_map(|kind| code.children_with_tokens().find(|e| e.kind() == kind))
+        .find_map(|e| header_argument(e.as_token()?.text(), "exports").map(String::from));
+        Some(exports.unwrap_or(String::from("results")))
+    }
+
     fn expand_macro<P: AsRef<Path>>(
         &self,
         syntax_tree: &SyntaxNode,
@@ -442,35 +471,44 @@ impl OrgParser {
         targets
     }
 
-    pub fn parse<P: AsRef<Path>>(&self, input_file: P) -> SyntaxNode {
+    /// Parse `input_file` into syntax tree, errors are pushed into `diagnostics`.
+    ///
+    /// `None` is returned if the file can't be read or parsed.
+    pub fn parse<P: AsRef<Path>>(
+        &self,
+        input_file: P,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<SyntaxNode> {
         let path = input_file.as_ref();
 
-        let input = &fs::read_to_string(path)
-            .unwrap_or_else(|e| panic!("Failed to read {:?}: {}", path, e));
-
-        // radio_target <- "<<<" CONTENTS  ">>>", CONTENTS doest't contain \n, thus we can filter line by line
-        // only use radio target related lines to speed up get the radio targets
-        let radio_target_lines = input
-            .lines()
-            .filter(|s| s.contains("<<<") && s.contains(">>>"))
-            .collect::<String>();
-        let radio_targets = if radio_target_lines.len() > 0 {
-            self.get_radio_targets(radio_target_lines.as_str())
-        } else {
-            HashSet::new()
+        let input = &match fs::read_to_string(path) {
+            Ok(input) => input,
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(path, format!("failed to read: {e}")));
+                return None;
+            }
         };
 
+        let radio_targets = self.collect_radio_targets(input);
+
         // FIXME: process include
         // IncludeProcessor::new().process(line)
         tracing::trace!("  Expand ‘#+include’ keywords in the whole buffer ...");
         let include_preprocessor = IncludePreProcessor {
             input_file: input_file.as_ref().to_path_buf(),
         };
-        let input = &input
-            .lines()
-            .map(|line| include_preprocessor.parse(line))
-            .collect::<Vec<_>>()
-            .join("\n");
+        let input = &{
+            let mut expanded = input
+                .lines()
+                .map(|line| include_preprocessor.parse(line))
+                .collect::<Vec<_>>()
+                .join("\n");
+            // keep the final newline, a heading at the end of file can't be parsed without it
+            if input.ends_with('\n') {
+                expanded.push('\n');
+            }
+            expanded
+        };
 
         tracing::trace!("  Replace macros in the whole buffer ...");
         let n_macro_reference = input
@@ -482,21 +520,23 @@ impl OrgParser {
 
             tracing::debug!(n_macro_reference, "preprocess needed");
             // parse raw input to get syntax(red) tree
-            let parse_result_first_round = org_file::org_file_parser(self.config.clone())
+            let parse_result_first_round = org_file::org_file_parser(self.config_of(input))
                 .parse_with_state(
                     input,
                     &mut extra::SimpleState(ParserState::new(radio_targets.clone())),
                 );
-            if parse_result_first_round.has_errors() {
-                for e in parse_result_first_round.errors() {
-                    tracing::error!("{:?}", e);
-                }
-            }
-            let parse_result_first_round = ParserResult {
-                green: parse_result_first_round
-                    .into_output()
-                    .expect("Parse failed in first round"),
+            let (output, errors) = parse_result_first_round.into_output_errors();
+            let severity = if output.is_some() {
+                Severity::Warning
+            } else {
+                Severity::Error
             };
+            diagnostics.extend(
+                errors
+                    .iter()
+                    .map(|e| Self::diagnostic_of(path, input, e, severity)),
+            );
+            let parse_result_first_round = ParserResult { green: output? };
             let mut syntax_tree_first_round = parse_result_first_round.syntax();
 
             // get keyword and macro_template from sytnax tree
@@ -504,12 +544,7 @@ impl OrgParser {
                 self.get_keyword_and_macro_template(&syntax_tree_first_round);
 
             // get preprocessed_text
-            &self.expand_macro(
-                &mut syntax_tree_first_round,
-                &k2v,
-                &macro_template,
-                input_file,
-            )
+            &self.expand_macro(&mut syntax_tree_first_round, &k2v, &macro_template, path)
         } else {
             tracing::trace!("preprocess ignored");
             input
@@ -517,28 +552,94 @@ impl OrgParser {
 
         tracing::trace!("preprocess done");
 
-        let parse_result = org_file::org_file_parser(self.config.clone()).parse_with_state(
-            input_preprocessed,
+        self.parse_with_radio_targets(path, input_preprocessed, radio_targets, diagnostics)
+    }
+
+    /// Parse `input` of `input_file` as is, i.e., `#+INCLUDE` and macros are not expanded, so
+    /// that the text of syntax tree is exactly `input`.
+    pub(crate) fn parse_source<P: AsRef<Path>>(
+        &self,
+        input_file: P,
+        input: &str,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<SyntaxNode> {
+        let radio_targets = self.collect_radio_targets(input);
+        self.parse_with_radio_targets(input_file.as_ref(), input, radio_targets, diagnostics)
+    }
+
+    // radio_target <- "<<<" CONTENTS  ">>>", CONTENTS doest't contain \n, thus we can filter line by line
+    // only use radio target related lines to speed up get the radio targets
+    fn collect_radio_targets(&self, input: &str) -> HashSet<String> {
+        let radio_target_lines = input
+            .lines()
+            .filter(|s| s.contains("<<<") && s.contains(">>>"))
+            .collect::<String>();
+        if radio_target_lines.len() > 0 {
+            self.get_radio_targets(radio_target_lines.as_str())
+        } else {
+            HashSet::new()
+        }
+    }
+
+    // link abbreviations of `#+LINK: ABBREV TEMPLATE` are link types of this file only
+    fn config_of(&self, input: &str) -> config::OrgParserConfig {
+        let link_abbrevs = input
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim_start();
+                line.get(..7)
+                    .filter(|key| key.eq_ignore_ascii_case("#+LINK:"))
+                    .and_then(|_| line[7..].split_whitespace().next())
+                    .map(String::from)
+            })
+            .collect::<Vec<_>>();
+
+        self.config.clone().with_link_types(link_abbrevs)
+    }
+
+    fn parse_with_radio_targets(
+        &self,
+        path: &Path,
+        input: &str,
+        radio_targets: HashSet<String>,
+        diagnostics: &mut Vec<Diagnostic>,
+    ) -> Option<SyntaxNode> {
+        let parse_result = org_file::org_file_parser(self.config_of(input)).parse_with_state(
+            input,
             &mut extra::SimpleState(ParserState::new(radio_targets)),
         );
         tracing::trace!("parse done");
 
-        if parse_result.has_errors() {
-            for e in parse_result.errors() {
-                tracing::error!("{:?}", e);
-            }
-        }
-        // tracing::trace!("{:#?}", parse_result);
-
-        let green_tree = parse_result
-            .into_output()
-            .expect("Parse failed in into_output()")
-            .into_node()
-            .unwrap();
+        let (output, errors) = parse_result.into_output_errors();
+        let severity = if output.is_some() {
+            Severity::Warning
+        } else {
+            Severity::Error
+        };
+        diagnostics.extend(
+            errors
+                .iter()
+                .map(|e| Self::diagnostic_of(path, input, e, severity)),
+        );
 
+        let green_tree = output?.into_node()?;
         let syntax_tree = SyntaxNode::new_root(green_tree); // i.e, red tree
 
-        syntax_tree
+        Some(syntax_tree)
+    }
+
+    /// chumsky error -> Diagnostic, span of error is byte range of `input`
+    fn diagnostic_of(
+        path: &Path,
+        input: &str,
+        error: &Rich<'_, char>,
+        severity: Severity,
+    ) -> Diagnostic {
+        let diagnostic = Diagnostic {
+            severity,
+            ..Diagnostic::error(path, error.to_string())
+        };
+        diagnostic.with_span(input, error.span().into_range())
     }
 }
 
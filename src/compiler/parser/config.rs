@@ -1,7 +1,7 @@
 /// config for org parser
-use std::collections::HashSet;
+use std::collections::{BTreeMap, HashSet};
 
-use serde::Deserialize;
+use serde::{Deserialize, Deserializer};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -45,6 +45,30 @@ pub struct OrgParserConfig {
     pub org_element_dual_keywords: HashSet<String>,
 
     pub org_element_affiliated_keywords: HashSet<String>,
+
+    /// Rows with at least so many stars are inline tasks instead of headings, at least 2 so that
+    /// `*` always starts a heading
+    #[serde(deserialize_with = "deserialize_inlinetask_min_level")]
+    pub org_inlinetask_min_level: usize,
+
+    /// Link types recognized besides the builtin ones of org.el, e.g., `jira`, `gh`
+    pub org_link_types: HashSet<String>,
+
+    /// Site level link abbreviations, i.e., `org-link-abbrev-alist`: `#+LINK: ABBREV TEMPLATE`
+    /// adds file level ones
+    pub org_link_abbrev_alist: BTreeMap<String, String>,
+}
+
+fn deserialize_inlinetask_min_level<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<usize, D::Error> {
+    let level = usize::deserialize(deserializer)?;
+    if level < 2 {
+        return Err(serde::de::Error::custom(format!(
+            "org_inlinetask_min_level should be at least 2, found {level}"
+        )));
+    }
+    Ok(level)
 }
 
 impl Default for OrgParserConfig {
@@ -71,6 +95,12 @@ impl Default for OrgParserConfig {
             .into_iter()
             .map(String::from)
             .collect(),
+
+            org_inlinetask_min_level: 15,
+
+            org_link_types: HashSet::new(),
+
+            org_link_abbrev_alist: BTreeMap::new(),
         }
     }
 }
@@ -81,9 +111,47 @@ impl OrgParserConfig {
 
         self
     }
+
+    /// Add link types, e.g., abbreviations of `#+LINK:` in a file
+    pub fn with_link_types<I: IntoIterator<Item = String>>(mut self, link_types: I) -> Self {
+        self.org_link_types.extend(link_types);
+
+        self
+    }
+
+    /// Deterministic string of config, i.e, sets are sorted
+    pub fn fingerprint(&self) -> String {
+        fn sorted(set: &HashSet<String>) -> Vec<&String> {
+            let mut v = set.iter().collect::<Vec<_>>();
+            v.sort();
+            v
+        }
+
+        format!(
+            "{:?};{:?};{:?};{:?};{:?};{:?};{};{:?};{:?}",
+            sorted(&self.org_todo_keywords.requiring_action),
+            sorted(&self.org_todo_keywords.no_further_action),
+            self.org_use_sub_superscripts,
+            sorted(&self.org_element_parsed_keywords),
+            sorted(&self.org_element_dual_keywords),
+            sorted(&self.org_element_affiliated_keywords),
+            self.org_inlinetask_min_level,
+            sorted(&self.org_link_types),
+            self.org_link_abbrev_alist,
+        )
+    }
 }
 
 impl OrgParserConfig {
+    // non-builtin link types, including site level link abbreviations
+    pub(crate) fn link_types(&self) -> HashSet<String> {
+        self.org_link_types
+            .iter()
+            .chain(self.org_link_abbrev_alist.keys())
+            .cloned()
+            .collect()
+    }
+
     // dual keywords which contains objects
     pub(crate) fn org_element_dual_keywords_parsed(&self) -> HashSet<String> {
         self.org_element_dual_keywords
@@ -123,3 +191,25 @@ impl OrgParserConfig {
             .collect()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::OrgParserConfig;
+
+    fn from_toml(s: &str) -> Result<OrgParserConfig, ::config::ConfigError> {
+        ::config::Config::builder()
+            .add_source(::config::File::from_str(s, ::config::FileFormat::Toml))
+            .build()?
+            .try_deserialize()
+    }
+
+    #[test]
+    fn test_inlinetask_min_level() {
+        assert!(from_toml("org_inlinetask_min_level = 0").is_err());
+        assert!(from_toml("org_inlinetask_min_level = 1").is_err());
+        let config = from_toml("org_inlinetask_min_level = 2").expect("valid config");
+        assert_eq!(config.org_inlinetask_min_level, 2);
+        let config = from_toml("").expect("default config");
+        assert_eq!(config.org_inlinetask_min_level, 15);
+    }
+}
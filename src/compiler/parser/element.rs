@@ -1,11 +1,16 @@
 //! element parser: greater? excluding heading/section
+pub(crate) mod babel_call;
 pub(crate) mod block;
+pub(crate) mod clock;
 pub(crate) mod comment;
+pub(crate) mod diary_sexp;
 pub(crate) mod drawer;
+pub(crate) mod dynamic_block;
 pub(crate) mod fixed_width;
 pub(crate) mod footnote_definition;
 pub(crate) mod heading;
 pub(crate) mod horizontal_rule;
+pub(crate) mod inlinetask;
 // pub(crate) mod item;
 pub(crate) mod keyword;
 pub(crate) mod latex_environment;
@@ -54,11 +59,14 @@ pub(crate) fn get_element_parser<'a, C: 'a + std::default::Default>(
     let comment_block = block::comment_block_parser(config.clone());
     // let planning = planning::planning_parser();
     let comment = comment::comment_parser();
+    let clock = clock::clock_parser();
+    let diary_sexp = diary_sexp::diary_sexp_parser();
     let table = table::table_parser(config.clone());
     let fixed_width = fixed_width::fixed_width_parser(config.clone());
 
     // to check
     let keyword = keyword::keyword_parser(config.clone());
+    let babel_call = babel_call::babel_call_parser(config.clone());
     let footnote_definition = footnote_definition::footnote_definition_parser(
         config.clone(),
         element_without_tablerow_and_item.clone(),
@@ -70,11 +78,17 @@ pub(crate) fn get_element_parser<'a, C: 'a + std::default::Default>(
     let special_block =
         block::special_block_parser(config.clone(), element_without_tablerow_and_item.clone());
     let drawer = drawer::drawer_parser(config.clone(), element_in_drawer.clone());
+    let dynamic_block = dynamic_block::dynamic_block_parser(
+        config.clone(),
+        element_without_tablerow_and_item.clone(),
+    );
     // let plain_list =
     //     list::plain_list_parser(item::item_parser(element_without_tablerow_and_item.clone()));
 
     let plain_list =
         plain_list::plain_list_parser(config.clone(), element_without_tablerow_and_item.clone());
+    let inlinetask =
+        inlinetask::inlinetask_parser(config.clone(), element_without_tablerow_and_item.clone());
 
     // todo: simple_keyword need? or in paragraph.rs?
     // ONLY used for lookhead
@@ -91,20 +105,25 @@ pub(crate) fn get_element_parser<'a, C: 'a + std::default::Default>(
     element_without_tablerow_and_item.define(choice((
         footnote_definition.clone(),
         drawer.clone(),
+        inlinetask.clone(),
         plain_list.clone(),
         horizontal_rule.clone(),
         latex_environment.clone(),
         center_block.clone(),
         quote_block.clone(),
         special_block.clone(),
+        dynamic_block.clone(),
         src_block.clone(),
         export_block.clone(),
         verse_block.clone(),
         example_block.clone(),
         comment_block.clone(),
         comment.clone(),
+        clock.clone(),
+        diary_sexp.clone(),
         table.clone(),
         fixed_width.clone(),
+        babel_call.clone(),
         keyword.clone(),
         paragraph_parser.clone(),
     )));
@@ -112,40 +131,50 @@ pub(crate) fn get_element_parser<'a, C: 'a + std::default::Default>(
     element_in_section.define(choice((
         footnote_definition.clone(),
         drawer.clone(),
+        inlinetask.clone(),
         plain_list.clone(),
         horizontal_rule.clone(),
         latex_environment.clone(),
         center_block.clone(),
         quote_block.clone(),
         special_block.clone(),
+        dynamic_block.clone(),
         src_block.clone(),
         export_block.clone(),
         verse_block.clone(),
         example_block.clone(),
         comment_block.clone(),
         comment.clone(),
+        clock.clone(),
+        diary_sexp.clone(),
         table.clone(),
         fixed_width.clone(),
+        babel_call.clone(),
         keyword.clone(),
         paragraph_parser.clone(),
     )));
 
     element_in_drawer.define(choice((
         footnote_definition.clone(),
+        inlinetask.clone(),
         plain_list.clone(),
         horizontal_rule.clone(),
         latex_environment.clone(),
         center_block.clone(),
         quote_block.clone(),
         special_block.clone(),
+        dynamic_block.clone(),
         src_block.clone(),
         export_block.clone(),
         verse_block.clone(),
         example_block.clone(),
         comment_block.clone(),
         comment.clone(),
+        clock.clone(),
+        diary_sexp.clone(),
         table.clone(),
         fixed_width.clone(),
+        babel_call.clone(),
         keyword.clone(),
         paragraph_parser.clone(),
     )));
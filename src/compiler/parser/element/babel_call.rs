@@ -0,0 +1,164 @@
+//! Babel call parser
+// babel_call <- AFFILIATED-KEYWORD* "#+CALL:" FUNCTION ("[" INSIDE-HEADER "]")? ("(" ARGUMENTS? ")")? END-HEADER?
+use chumsky::prelude::*;
+
+use crate::compiler::parser::config::OrgParserConfig;
+use crate::compiler::parser::object::inline_babel_call::{arguments_parser, header_parser};
+use crate::compiler::parser::{MyExtra, NT, OSK, element, object};
+
+pub(crate) fn babel_call_parser<'a, C: 'a>(
+    config: OrgParserConfig,
+) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+    let affiliated_keywords = element::keyword::affiliated_keyword_parser(config)
+        .repeated()
+        .collect::<Vec<_>>();
+
+    affiliated_keywords
+        .then(object::whitespaces())
+        .then(just("#+"))
+        .then(object::just_case_insensitive("CALL"))
+        .then(just(":"))
+        .then(object::whitespaces())
+        .then(none_of(" \t[]()\r\n").repeated().at_least(1).to_slice())
+        .then(header_parser().delimited_by(just('['), just(']')).or_not())
+        .then(
+            arguments_parser()
+                .or_not()
+                .delimited_by(just('('), just(')'))
+                .or_not(),
+        )
+        .then(object::whitespaces())
+        .then(
+            none_of(object::CRLF)
+                .repeated()
+                .at_least(1)
+                .to_slice()
+                .or_not(),
+        )
+        .then(object::newline_or_ending())
+        .then(object::blank_line_parser().repeated().collect::<Vec<_>>())
+        .map(
+            |(
+                (
+                    (
+                        (
+                            (
+                                (
+                                    ((((((keywords, ws1), hash_plus), key), colon), ws2), name),
+                                    maybe_inside_header,
+                                ),
+                                maybe_arguments,
+                            ),
+                            ws3,
+                        ),
+                        maybe_end_header,
+                    ),
+                    maybe_newline,
+                ),
+                blanklines,
+            )| {
+                let mut children = Vec::with_capacity(keywords.len() + blanklines.len() + 14);
+                children.extend(keywords);
+                if !ws1.is_empty() {
+                    children.push(crate::token!(OSK::Whitespace, ws1));
+                }
+                children.push(crate::token!(OSK::HashPlus, hash_plus));
+                children.push(crate::node!(
+                    OSK::KeywordKey,
+                    vec![crate::token!(OSK::Text, key)]
+                ));
+                children.push(crate::token!(OSK::Colon, colon));
+                if !ws2.is_empty() {
+                    children.push(crate::token!(OSK::Whitespace, ws2));
+                }
+                children.push(crate::token!(OSK::BabelCallName, name));
+                if let Some(inside_header) = maybe_inside_header {
+                    children.push(crate::token!(OSK::LeftSquareBracket, "["));
+                    children.push(crate::token!(OSK::BabelCallInsideHeader, inside_header));
+                    children.push(crate::token!(OSK::RightSquareBracket, "]"));
+                }
+                if let Some(maybe_arguments) = maybe_arguments {
+                    children.push(crate::token!(OSK::LeftRoundBracket, "("));
+                    if let Some(arguments) = maybe_arguments {
+                        children.push(crate::token!(OSK::BabelCallArguments, arguments));
+                    }
+                    children.push(crate::token!(OSK::RightRoundBracket, ")"));
+                }
+                if !ws3.is_empty() {
+                    children.push(crate::token!(OSK::Whitespace, ws3));
+                }
+                if let Some(end_header) = maybe_end_header {
+                    children.push(crate::token!(OSK::BabelCallEndHeader, end_header));
+                }
+                if let Some(newline) = maybe_newline {
+                    children.push(crate::token!(OSK::Newline, newline));
+                }
+                children.extend(blanklines);
+
+                crate::node!(OSK::BabelCall, children)
+            },
+        )
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::compiler::parser::common::get_parser_output;
+    use crate::compiler::parser::config::OrgParserConfig;
+
+    #[test]
+    fn test_babel_call_01() {
+        let input = "#+call: square[:results silent](x=4) :exports both\n\n";
+        assert_eq!(
+            get_parser_output(babel_call_parser::<()>(OrgParserConfig::default()), input),
+            r##"BabelCall@0..52
+  HashPlus@0..2 "#+"
+  KeywordKey@2..6
+    Text@2..6 "call"
+  Colon@6..7 ":"
+  Whitespace@7..8 " "
+  BabelCallName@8..14 "square"
+  LeftSquareBracket@14..15 "["
+  BabelCallInsideHeader@15..30 ":results silent"
+  RightSquareBracket@30..31 "]"
+  LeftRoundBracket@31..32 "("
+  BabelCallArguments@32..35 "x=4"
+  RightRoundBracket@35..36 ")"
+  Whitespace@36..37 " "
+  BabelCallEndHeader@37..50 ":exports both"
+  Newline@50..51 "\n"
+  BlankLine@51..52 "\n"
+"##
+        );
+    }
+
+    #[test]
+    fn test_babel_call_02() {
+        let input = "#+NAME: four\n#+CALL: square()";
+        assert_eq!(
+            get_parser_output(babel_call_parser::<()>(OrgParserConfig::default()), input),
+            r##"BabelCall@0..29
+  AffiliatedKeyword@0..13
+    HashPlus@0..2 "#+"
+    KeywordKey@2..6
+      Text@2..6 "NAME"
+    Colon@6..7 ":"
+    Whitespace@7..8 " "
+    KeywordValue@8..12
+      Text@8..12 "four"
+    Newline@12..13 "\n"
+  HashPlus@13..15 "#+"
+  KeywordKey@15..19
+    Text@15..19 "CALL"
+  Colon@19..20 ":"
+  Whitespace@20..21 " "
+  BabelCallName@21..27 "square"
+  LeftRoundBracket@27..28 "("
+  RightRoundBracket@28..29 ")"
+"##
+        );
+    }
+}
@@ -230,18 +230,37 @@ pub(crate) fn simple_export_block_parser<'a, C: 'a>(
         .boxed()
 }
 
-pub(crate) fn simple_src_block_parser<'a, C: 'a>(
-    config: OrgParserConfig,
-) -> impl Parser<'a, &'a str, (), MyExtra<'a, C>> + Clone {
-    let language = none_of(" \t\n").repeated().at_least(1).to_slice();
+/// switches of src block, separated by whitespaces: `-l "FORMAT"`, `-n`/`+n` with optional
+/// number, or other flags like `-r`/`-i`/`-k`
+fn switches_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, &'a str, MyExtra<'a, C>> + Clone {
     let switch_p1 = just("-l")
         .then(object::whitespaces_g1())
-        .then(none_of("\n\" \t").repeated())
+        .then(just('"'))
+        .then(none_of("\n\"").repeated().at_least(1))
+        .then(just('"'))
         .to_slice();
     let switch_p2 = one_of("+-")
+        .then(just('n'))
+        .then(
+            object::whitespaces_g1()
+                .then(one_of("0123456789").repeated().at_least(1))
+                .or_not(),
+        )
+        .to_slice();
+    let switch_p3 = one_of("+-")
         .then(any().filter(|c: &char| c.is_alphanumeric()))
         .to_slice();
-    let switches = switch_p1.or(switch_p2).repeated().at_least(1).to_slice();
+    let switch = switch_p1.or(switch_p2).or(switch_p3);
+    switch
+        .then(object::whitespaces_g1().then(switch).repeated())
+        .to_slice()
+}
+
+pub(crate) fn simple_src_block_parser<'a, C: 'a>(
+    config: OrgParserConfig,
+) -> impl Parser<'a, &'a str, (), MyExtra<'a, C>> + Clone {
+    let language = none_of(" \t\n").repeated().at_least(1).to_slice();
+    let switches = switches_parser();
     let arguments = none_of("\n").repeated().at_least(1).to_slice();
     let begin_row = object::whitespaces()
         .then(object::just_case_insensitive("#+BEGIN_"))
@@ -277,14 +296,7 @@ pub(crate) fn src_block_parser<'a, C: 'a>(
     config: OrgParserConfig,
 ) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
     let language = none_of(" \t\n").repeated().at_least(1).to_slice();
-    let switch_p1 = just("-l")
-        .then(object::whitespaces_g1())
-        .then(none_of("\n\" \t").repeated())
-        .to_slice();
-    let switch_p2 = one_of("+-")
-        .then(any().filter(|c: &char| c.is_alphanumeric()))
-        .to_slice();
-    let switches = switch_p1.or(switch_p2).repeated().at_least(1).to_slice();
+    let switches = switches_parser();
     let arguments = none_of("\n").repeated().at_least(1).to_slice();
     let begin_row = object::whitespaces()
         .then(object::just_case_insensitive("#+BEGIN_"))
@@ -1181,6 +1193,32 @@ fn main() {
         );
     }
 
+    #[test]
+    fn test_src_block_04() {
+        let input = r##"#+BEGIN_SRC rust +n 10 -r -l "[%s]" :exports code
+#+END_SRC
+"##;
+        assert_eq!(
+            get_parser_output(src_block_parser::<()>(OrgParserConfig::default()), input),
+            r##"SrcBlock@0..60
+  BlockBegin@0..50
+    Text@0..8 "#+BEGIN_"
+    Text@8..11 "SRC"
+    Whitespace@11..12 " "
+    SrcBlockLanguage@12..16 "rust"
+    Whitespace@16..17 " "
+    SrcBlockSwitches@17..35 "+n 10 -r -l \"[%s]\""
+    Whitespace@35..36 " "
+    SrcBlockHeaderArguments@36..49 ":exports code"
+    Newline@49..50 "\n"
+  BlockEnd@50..60
+    Text@50..56 "#+END_"
+    Text@56..59 "SRC"
+    Newline@59..60 "\n"
+"##
+        );
+    }
+
     #[test]
     fn test_src_block_src_04() {
         let input = r###"#+BEGIN_sRC python
@@ -0,0 +1,142 @@
+//! Clock parser
+// clock <- CLOCK: INACTIVE-TIMESTAMP
+//        / CLOCK: INACTIVE-TIMESTAMP-RANGE => DURATION
+// DURATION <- H+:MM
+use chumsky::prelude::*;
+
+use crate::compiler::parser::{MyExtra, NT, OSK, object};
+
+pub(crate) fn clock_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+    let duration = one_of("0123456789")
+        .repeated()
+        .at_least(1)
+        .then(just(":"))
+        .then(one_of("0123456789").repeated().exactly(2))
+        .to_slice();
+
+    object::whitespaces()
+        .then(just("CLOCK:"))
+        .then(object::whitespaces())
+        .then(
+            just("[")
+                .rewind()
+                .ignore_then(object::timestamp::timestamp_parser()),
+        )
+        .then(
+            object::whitespaces_g1()
+                .then(just("=>"))
+                .then(object::whitespaces_g1())
+                .then(duration)
+                .or_not(),
+        )
+        .then(object::whitespaces())
+        .then(choice((
+            object::newline()
+                .then(object::blank_line_parser().repeated().collect::<Vec<_>>())
+                .map(|(newline, blanklines)| {
+                    let mut children = Vec::with_capacity(1 + blanklines.len());
+                    children.push(crate::token!(OSK::Newline, newline));
+                    children.extend(blanklines);
+
+                    children
+                }),
+            end().to(vec![]),
+        )))
+        .map(
+            |((((((ws1, clock), ws2), timestamp), maybe_duration), ws3), others)| {
+                let mut children = Vec::with_capacity(9 + others.len());
+                if !ws1.is_empty() {
+                    children.push(crate::token!(OSK::Whitespace, ws1));
+                }
+                children.push(crate::token!(OSK::Text, clock));
+                if !ws2.is_empty() {
+                    children.push(crate::token!(OSK::Whitespace, ws2));
+                }
+                children.push(timestamp);
+                if let Some((((ws4, arrow), ws5), duration)) = maybe_duration {
+                    children.push(crate::token!(OSK::Whitespace, ws4));
+                    children.push(crate::token!(OSK::Text, arrow));
+                    children.push(crate::token!(OSK::Whitespace, ws5));
+                    children.push(crate::token!(OSK::ClockDuration, duration));
+                }
+                if !ws3.is_empty() {
+                    children.push(crate::token!(OSK::Whitespace, ws3));
+                }
+                children.extend(others);
+
+                crate::node!(OSK::Clock, children)
+            },
+        )
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::compiler::parser::common::get_parser_output;
+
+    #[test]
+    fn test_clock_01() {
+        assert_eq!(
+            get_parser_output(
+                clock_parser::<()>(),
+                "CLOCK: [2024-01-01 Mon 10:00]--[2024-01-01 Mon 11:30] =>  1:30\n"
+            ),
+            r##"Clock@0..63
+  Text@0..6 "CLOCK:"
+  Whitespace@6..7 " "
+  Timestamp@7..53
+    LeftSquareBracket@7..8 "["
+    TimestampDate@8..18 "2024-01-01"
+    Whitespace@18..19 " "
+    TimestampDayname@19..22 "Mon"
+    Whitespace@22..23 " "
+    TimestampTime@23..28 "10:00"
+    RightSquareBracket@28..29 "]"
+    Text@29..31 "--"
+    LeftSquareBracket@31..32 "["
+    TimestampDate@32..42 "2024-01-01"
+    Whitespace@42..43 " "
+    TimestampDayname@43..46 "Mon"
+    Whitespace@46..47 " "
+    TimestampTime@47..52 "11:30"
+    RightSquareBracket@52..53 "]"
+  Whitespace@53..54 " "
+  Text@54..56 "=>"
+  Whitespace@56..58 "  "
+  ClockDuration@58..62 "1:30"
+  Newline@62..63 "\n"
+"##
+        );
+    }
+
+    #[test]
+    fn test_clock_02_running() {
+        assert_eq!(
+            get_parser_output(clock_parser::<()>(), "  CLOCK: [2024-01-01 Mon 10:00]\n\n"),
+            r##"Clock@0..33
+  Whitespace@0..2 "  "
+  Text@2..8 "CLOCK:"
+  Whitespace@8..9 " "
+  Timestamp@9..31
+    LeftSquareBracket@9..10 "["
+    TimestampDate@10..20 "2024-01-01"
+    Whitespace@20..21 " "
+    TimestampDayname@21..24 "Mon"
+    Whitespace@24..25 " "
+    TimestampTime@25..30 "10:00"
+    RightSquareBracket@30..31 "]"
+  Newline@31..32 "\n"
+  BlankLine@32..33 "\n"
+"##
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_clock_03_active() {
+        get_parser_output(clock_parser::<()>(), "CLOCK: <2024-01-01 Mon 10:00>\n");
+    }
+}
@@ -0,0 +1,65 @@
+//! Diary sexp parser
+// diary sexp <- %%(VALUE, unindented, VALUE is the rest of line
+use chumsky::prelude::*;
+
+use crate::compiler::parser::{MyExtra, NT, OSK, object};
+
+pub(crate) fn diary_sexp_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone
+{
+    just("%%")
+        .then(just("(").then(none_of(object::CRLF).repeated()).to_slice())
+        .then(choice((
+            object::newline()
+                .then(object::blank_line_parser().repeated().collect::<Vec<_>>())
+                .map(|(newline, blanklines)| {
+                    let mut children = Vec::with_capacity(1 + blanklines.len());
+                    children.push(crate::token!(OSK::Newline, newline));
+                    children.extend(blanklines);
+
+                    children
+                }),
+            end().to(vec![]),
+        )))
+        .map(|((percents, value), others)| {
+            let mut children = Vec::with_capacity(2 + others.len());
+            children.push(crate::token!(OSK::Text, percents));
+            children.push(crate::token!(OSK::Text, value));
+            children.extend(others);
+
+            crate::node!(OSK::DiarySexp, children)
+        })
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::compiler::parser::common::get_parser_output;
+
+    #[test]
+    fn test_diary_sexp_01() {
+        assert_eq!(
+            get_parser_output(diary_sexp_parser::<()>(), "%%(diary-float t 4 2)\n\n"),
+            r##"DiarySexp@0..23
+  Text@0..2 "%%"
+  Text@2..21 "(diary-float t 4 2)"
+  Newline@21..22 "\n"
+  BlankLine@22..23 "\n"
+"##
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_diary_sexp_02_indented() {
+        get_parser_output(diary_sexp_parser::<()>(), "  %%(diary-float t 4 2)");
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_diary_sexp_03_no_parenthesis() {
+        get_parser_output(diary_sexp_parser::<()>(), "%% text");
+    }
+}
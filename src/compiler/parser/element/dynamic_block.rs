@@ -0,0 +1,173 @@
+//! Dynamic block parser
+// dynamic_block <- affiliated_keyword* begin_row content end_row blank_line*
+// begin_row <- #+BEGIN: NAME PARAMETERS?
+// end_row <- #+END:
+use chumsky::prelude::*;
+
+use crate::compiler::parser::config::OrgParserConfig;
+use crate::compiler::parser::{MyExtra, NT, OSK, element, object};
+
+fn begin_row_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+    let name = none_of(" \t\r\n").repeated().at_least(1).to_slice();
+    let parameters = none_of("\r\n").repeated().to_slice();
+
+    object::whitespaces()
+        .then(object::just_case_insensitive("#+begin:"))
+        .then(object::whitespaces_g1())
+        .then(name)
+        .then(object::whitespaces_g1().then(parameters).or_not())
+        .then(object::newline())
+        .map(
+            |(((((ws1, begin), ws2), name), maybe_ws3_parameters), nl)| {
+                let mut children = Vec::with_capacity(7);
+                if !ws1.is_empty() {
+                    children.push(crate::token!(OSK::Whitespace, ws1));
+                }
+                children.push(crate::token!(OSK::Text, begin));
+                children.push(crate::token!(OSK::Whitespace, ws2));
+                children.push(crate::token!(OSK::Text, name));
+                if let Some((ws3, parameters)) = maybe_ws3_parameters {
+                    children.push(crate::token!(OSK::Whitespace, ws3));
+                    if !parameters.is_empty() {
+                        children.push(crate::token!(OSK::Text, parameters));
+                    }
+                }
+                children.push(crate::token!(OSK::Newline, nl));
+
+                crate::node!(OSK::BlockBegin, children)
+            },
+        )
+}
+
+fn end_row_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+    object::whitespaces()
+        .then(object::just_case_insensitive("#+end:"))
+        .then(object::whitespaces())
+        .then(object::newline_or_ending())
+        .map(|(((ws1, end), ws2), nl)| {
+            let mut children = Vec::with_capacity(4);
+            if !ws1.is_empty() {
+                children.push(crate::token!(OSK::Whitespace, ws1));
+            }
+            children.push(crate::token!(OSK::Text, end));
+            if !ws2.is_empty() {
+                children.push(crate::token!(OSK::Whitespace, ws2));
+            }
+            if let Some(nl) = nl {
+                children.push(crate::token!(OSK::Newline, nl));
+            }
+
+            crate::node!(OSK::BlockEnd, children)
+        })
+}
+
+pub(crate) fn dynamic_block_parser<'a, C: 'a>(
+    config: OrgParserConfig,
+    element_parser: impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+    let affiliated_keywords = element::keyword::affiliated_keyword_parser(config)
+        .repeated()
+        .collect::<Vec<_>>();
+
+    let content_inner = object::line_parser()
+        .or(object::blank_line_str_parser())
+        .and_is(end_row_parser().ignored().not())
+        .and_is(
+            element::heading::simple_heading_row_parser()
+                .ignored()
+                .not(),
+        )
+        .repeated()
+        .to_slice();
+    let content = element_parser
+        .repeated()
+        .collect::<Vec<_>>()
+        .nested_in(content_inner)
+        .map(|children| crate::node!(OSK::BlockContent, children));
+
+    affiliated_keywords
+        .then(begin_row_parser())
+        .then(content)
+        .then(end_row_parser())
+        .then(object::blank_line_parser().repeated().collect::<Vec<_>>())
+        .map(
+            |((((keywords, begin_row), content), end_row), blank_lines)| {
+                let mut children = Vec::with_capacity(keywords.len() + 3 + blank_lines.len());
+                children.extend(keywords);
+                children.push(begin_row);
+                children.push(content);
+                children.push(end_row);
+                children.extend(blank_lines);
+
+                crate::node!(OSK::DynamicBlock, children)
+            },
+        )
+        .boxed()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::compiler::parser::common::get_parser_output;
+    use crate::compiler::parser::config::OrgParserConfig;
+    use crate::compiler::parser::element;
+
+    #[test]
+    fn test_dynamic_block_01() {
+        assert_eq!(
+            get_parser_output(
+                dynamic_block_parser(
+                    OrgParserConfig::default(),
+                    element::element_parser::<()>(OrgParserConfig::default())
+                ),
+                r##"#+BEGIN: clocktable :scope file :maxlevel 2
+contents
+#+END:
+"##
+            ),
+            r###"DynamicBlock@0..60
+  BlockBegin@0..44
+    Text@0..8 "#+BEGIN:"
+    Whitespace@8..9 " "
+    Text@9..19 "clocktable"
+    Whitespace@19..20 " "
+    Text@20..43 ":scope file :maxlevel 2"
+    Newline@43..44 "\n"
+  BlockContent@44..53
+    Paragraph@44..53
+      Text@44..53 "contents\n"
+  BlockEnd@53..60
+    Text@53..59 "#+END:"
+    Newline@59..60 "\n"
+"###
+        );
+    }
+
+    #[test]
+    fn test_dynamic_block_02() {
+        assert_eq!(
+            get_parser_output(
+                dynamic_block_parser(
+                    OrgParserConfig::default(),
+                    element::element_parser::<()>(OrgParserConfig::default())
+                ),
+                r##"#+begin: backlinks
+#+end:
+"##
+            ),
+            r###"DynamicBlock@0..26
+  BlockBegin@0..19
+    Text@0..8 "#+begin:"
+    Whitespace@8..9 " "
+    Text@9..18 "backlinks"
+    Newline@18..19 "\n"
+  BlockContent@19..19
+  BlockEnd@19..26
+    Text@19..25 "#+end:"
+    Newline@25..26 "\n"
+"###
+        );
+    }
+}
@@ -1,14 +1,14 @@
 //! Heading parser, including HeadingRow, HeadingSubtree
 use chumsky::prelude::*;
 
-use crate::compiler::parser::config::OrgTodoKeywords;
+use crate::compiler::parser::config::{OrgParserConfig, OrgTodoKeywords};
 use crate::compiler::parser::element::{drawer, planning, section};
 use crate::compiler::parser::{MyExtra, MyState, NT, OSK, object};
 
 // todo: why usize in C
 // pub(crate) fn heading_subtree_parser<'a, C:'a + std::default::Default>(
 pub(crate) fn heading_subtree_parser<'a, C: 'a + std::default::Default>(
-    config: OrgTodoKeywords,
+    config: OrgParserConfig,
     object_parser: impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone + 'a,
     element_parser: impl Parser<
         'a,
@@ -23,6 +23,76 @@ pub(crate) fn heading_subtree_parser<'a, C: 'a + std::default::Default>(
 ) -> impl Parser<'a, &'a str, NT, extra::Full<Rich<'a, char>, MyState, ()>> + Clone {
     let mut heading_subtree = Recursive::declare();
 
+    // rows with at least `org_inlinetask_min_level` stars are inline tasks
+    let max_level = config.org_inlinetask_min_level.saturating_sub(1);
+    let stars = just('*')
+        .repeated()
+        .configure(move |cfg, ctx: &&str| cfg.at_least((*ctx).len() + 1).at_most(max_level))
+        .to_slice()
+        // .map(|s: &str| s.len())
+        ;
+
+    let maybe_section_parser =
+        section::section_parser(element_parser.clone(), config.org_inlinetask_min_level).or_not();
+    heading_subtree.define(choice((stars
+        .then_with_ctx(
+            heading_row_tail_parser(config.org_todo_keywords, object_parser)
+                .then(planning::planning_parser().or_not())
+                .then(drawer::property_drawer_parser().or_not())
+                .then(object::blank_line_parser().repeated().collect::<Vec<_>>())
+                .then(maybe_section_parser.clone())
+                .then(heading_subtree.clone().repeated().collect::<Vec<_>>()),
+        )
+        .map(
+            move |(
+                stars,
+                (
+                    (
+                        (((row_tail, maybe_planning), maybe_property_drawer), blanklines),
+                        maybe_section,
+                    ),
+                    subtrees,
+                ),
+            )| {
+                let mut children = vec![];
+
+                children.push(crate::token!(
+                    OSK::HeadingRowStars,
+                    stars // "*".repeat(stars).as_str() // "*".repeat(stars.prev_heading_level).as_str(),
+                          // "*".repeat(e.ctx().prev_heading_level).as_str(),
+                ));
+                children.extend(row_tail);
+
+                if let Some(planning) = maybe_planning {
+                    children.push(planning);
+                }
+
+                if let Some(property_drawer) = maybe_property_drawer {
+                    children.push(property_drawer);
+                }
+
+                children.extend(blanklines);
+                let head_row = crate::node!(OSK::HeadingRow, children);
+
+                let mut children = vec![];
+                children.push(head_row);
+                if let Some(section) = maybe_section {
+                    children.push(section);
+                }
+                for subtree in subtrees {
+                    children.push(subtree);
+                }
+                crate::node!(OSK::HeadingSubtree, children)
+            },
+        ),)));
+    heading_subtree.with_ctx(prev_level).boxed()
+}
+
+/// Heading row after stars, i.e., `KEYWORD PRIORITY COMMENT TITLE TAGS\n`, shared by heading and inline task
+pub(crate) fn heading_row_tail_parser<'a, C: 'a, C2: 'a + std::default::Default>(
+    config: OrgTodoKeywords,
+    object_parser: impl Parser<'a, &'a str, NT, MyExtra<'a, C2>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, Vec<NT>, MyExtra<'a, C>> + Clone {
     let maybe_keyword_ws = choice((
         object::keyword_cs_parser_v2(config.requiring_action),
         object::keyword_cs_parser_v2(config.no_further_action),
@@ -38,12 +108,6 @@ pub(crate) fn heading_subtree_parser<'a, C: 'a + std::default::Default>(
         .then(object::whitespaces_g1())
         .or_not();
     let maybe_comment = just("COMMENT").then(object::whitespaces_g1()).or_not();
-    let stars = just('*')
-        .repeated()
-        .configure(|cfg, ctx: &&str| cfg.at_least((*ctx).len() + 1))
-        .to_slice()
-        // .map(|s: &str| s.len())
-        ;
 
     let tags = just(":")
         .then(
@@ -79,68 +143,30 @@ pub(crate) fn heading_subtree_parser<'a, C: 'a + std::default::Default>(
         .to_slice()
         .then(object::whitespaces())
         .or_not();
-    let maybe_section_parser = section::section_parser(element_parser.clone()).or_not();
-    heading_subtree.define(choice((stars
-        .then_with_ctx(
-            one_of(" \t")
-                .repeated()
-                .at_least(1)
-                .to_slice()
-                .then(maybe_keyword_ws)
-                .then(maybe_priority)
-                .then(maybe_comment)
-                .then(maybe_title)
-                .then(maybe_tag)
-                .then(object::newline())
-                .then(planning::planning_parser().or_not())
-                .then(drawer::property_drawer_parser().or_not())
-                .then(object::blank_line_parser().repeated().collect::<Vec<_>>())
-                .then(maybe_section_parser.clone())
-                .then(heading_subtree.clone().repeated().collect::<Vec<_>>()),
-        )
+
+    one_of(" \t")
+        .repeated()
+        .at_least(1)
+        .to_slice()
+        .then(maybe_keyword_ws)
+        .then(maybe_priority)
+        .then(maybe_comment)
+        .then(maybe_title)
+        .then(maybe_tag)
+        .then(object::newline())
         .map(
             move |(
-                stars,
                 (
                     (
-                        (
-                            (
-                                (
-                                    (
-                                        (
-                                            (
-                                                (
-                                                    (
-                                                        (whitespace1, maybe_keyword_ws),
-                                                        maybe_priority,
-                                                    ),
-                                                    maybe_comment,
-                                                ),
-                                                maybe_title,
-                                            ),
-                                            maybe_tag,
-                                        ),
-                                        newline,
-                                    ),
-                                    maybe_planning,
-                                ),
-                                maybe_property_drawer,
-                            ),
-                            blanklines,
-                        ),
-                        maybe_section,
+                        (((whitespace1, maybe_keyword_ws), maybe_priority), maybe_comment),
+                        maybe_title,
                     ),
-                    subtrees,
+                    maybe_tag,
                 ),
+                newline,
             )| {
                 let mut children = vec![];
 
-                children.push(crate::token!(
-                    OSK::HeadingRowStars,
-                    stars // "*".repeat(stars).as_str() // "*".repeat(stars.prev_heading_level).as_str(),
-                          // "*".repeat(e.ctx().prev_heading_level).as_str(),
-                ));
-
                 children.push(crate::token!(OSK::Whitespace, whitespace1));
 
                 match maybe_keyword_ws {
@@ -226,30 +252,9 @@ pub(crate) fn heading_subtree_parser<'a, C: 'a + std::default::Default>(
                 }
 
                 children.push(crate::token!(OSK::Newline, newline));
-
-                if let Some(planning) = maybe_planning {
-                    children.push(planning);
-                }
-
-                if let Some(property_drawer) = maybe_property_drawer {
-                    children.push(property_drawer);
-                }
-
-                children.extend(blanklines);
-                let head_row = crate::node!(OSK::HeadingRow, children);
-
-                let mut children = vec![];
-                children.push(head_row);
-                if let Some(section) = maybe_section {
-                    children.push(section);
-                }
-                for subtree in subtrees {
-                    children.push(subtree);
-                }
-                crate::node!(OSK::HeadingSubtree, children)
+                children
             },
-        ),)));
-    heading_subtree.with_ctx(prev_level).boxed()
+        )
 }
 
 /// A simple heading row parser WITHOUT state, ONLY used for look ahead
@@ -280,7 +285,7 @@ mod tests {
     fn test_heading_subtree_01() {
         let input = "* 标题1\n 测试\n** 标题1.1\n测试\n测试\ntest \n*** 1.1.1 title\nContent\n";
         let parser = heading_subtree_parser(
-            OrgParserConfig::default().org_todo_keywords,
+            OrgParserConfig::default(),
             object::standard_set_object_parser::<()>(OrgParserConfig::default()),
             element_parser(OrgParserConfig::default()),
             "",
@@ -325,7 +330,7 @@ mod tests {
     fn test_heading_subtree_02() {
         let input = "* 标题1\n 测试\n** 标题1.1\n测试\n测试\ntest\n*** 1.1.1 title\nContent\n* Title\nI have a dream\n"; // overflow
         let parser = heading_subtree_parser(
-            OrgParserConfig::default().org_todo_keywords,
+            OrgParserConfig::default(),
             object::standard_set_object_parser::<()>(OrgParserConfig::default()),
             element_parser(OrgParserConfig::default()),
             "",
@@ -375,6 +380,92 @@ mod tests {
     Section@82..97
       Paragraph@82..97
         Text@82..97 "I have a dream\n"
+"##
+        );
+    }
+
+    #[test]
+    fn test_heading_subtree_03_inlinetask() {
+        let input = "* a\n**** TODO task\ntext\n**** END\n** b\n";
+        let config = OrgParserConfig {
+            org_inlinetask_min_level: 4,
+            ..OrgParserConfig::default()
+        };
+        let parser = heading_subtree_parser(
+            config.clone(),
+            object::standard_set_object_parser::<()>(config.clone()),
+            element_parser(config),
+            "",
+        );
+        assert_eq!(
+            get_parser_output(parser, input),
+            r##"HeadingSubtree@0..38
+  HeadingRow@0..4
+    HeadingRowStars@0..1 "*"
+    Whitespace@1..2 " "
+    HeadingRowTitle@2..3
+      Text@2..3 "a"
+    Newline@3..4 "\n"
+  Section@4..33
+    InlineTask@4..33
+      HeadingRow@4..19
+        HeadingRowStars@4..8 "****"
+        Whitespace@8..9 " "
+        HeadingRowKeywordTodo@9..13 "TODO"
+        Whitespace@13..14 " "
+        HeadingRowTitle@14..18
+          Text@14..18 "task"
+        Newline@18..19 "\n"
+      Section@19..24
+        Paragraph@19..24
+          Text@19..24 "text\n"
+      InlineTaskEnd@24..33
+        HeadingRowStars@24..28 "****"
+        Whitespace@28..29 " "
+        Text@29..32 "END"
+        Newline@32..33 "\n"
+  HeadingSubtree@33..38
+    HeadingRow@33..38
+      HeadingRowStars@33..35 "**"
+      Whitespace@35..36 " "
+      HeadingRowTitle@36..37
+        Text@36..37 "b"
+      Newline@37..38 "\n"
+"##
+        );
+    }
+
+    #[test]
+    fn test_heading_subtree_04_inlinetask_min_level() {
+        // the smallest valid level: only `*` starts a heading
+        let input = "* a\n** task\n";
+        let config = OrgParserConfig {
+            org_inlinetask_min_level: 2,
+            ..OrgParserConfig::default()
+        };
+        let parser = heading_subtree_parser(
+            config.clone(),
+            object::standard_set_object_parser::<()>(config.clone()),
+            element_parser(config),
+            "",
+        );
+        assert_eq!(
+            get_parser_output(parser, input),
+            r##"HeadingSubtree@0..12
+  HeadingRow@0..4
+    HeadingRowStars@0..1 "*"
+    Whitespace@1..2 " "
+    HeadingRowTitle@2..3
+      Text@2..3 "a"
+    Newline@3..4 "\n"
+  Section@4..12
+    InlineTask@4..12
+      HeadingRow@4..12
+        HeadingRowStars@4..6 "**"
+        Whitespace@6..7 " "
+        HeadingRowTitle@7..11
+          Text@7..11 "task"
+        Newline@11..12 "\n"
 "##
         );
     }
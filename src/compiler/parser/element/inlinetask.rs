@@ -0,0 +1,194 @@
+//! Inline task parser
+// inlinetask <- STARS HEADING-ROW-TAIL PLANNING? PROPERTY-DRAWER? (CONTENTS END-ROW)?
+// END-ROW <- STARS END
+// STARS <- at least `org_inlinetask_min_level` stars
+use chumsky::prelude::*;
+
+use crate::compiler::parser::config::OrgParserConfig;
+use crate::compiler::parser::element::{drawer, heading, planning};
+use crate::compiler::parser::{MyExtra, NT, OSK, object};
+
+/// Inline task, a heading which does not start a subtree
+///
+/// - single line: `*************** TODO title :tag:`
+/// - with contents: elements between the task row and the END row `*************** END`
+pub(crate) fn inlinetask_parser<'a, C: 'a + std::default::Default>(
+    config: OrgParserConfig,
+    element_parser: impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone + 'a,
+) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+    let min_level = config.org_inlinetask_min_level;
+    let row = just('*')
+        .repeated()
+        .at_least(min_level)
+        .to_slice()
+        .then(heading::heading_row_tail_parser(
+            config.org_todo_keywords.clone(),
+            object::standard_set_object_parser::<()>(config.clone()),
+        ))
+        .then(planning::planning_parser().or_not())
+        .then(drawer::property_drawer_parser().or_not())
+        .map(|(((stars, tail), maybe_planning), maybe_property_drawer)| {
+            let mut children = Vec::with_capacity(tail.len() + 3);
+            children.push(crate::token!(OSK::HeadingRowStars, stars));
+            children.extend(tail);
+            children.extend(maybe_planning);
+            children.extend(maybe_property_drawer);
+            crate::node!(OSK::HeadingRow, children)
+        });
+
+    // contents end at END row or any other heading row
+    let contents = element_parser
+        .and_is(heading::simple_heading_row_parser().not())
+        .repeated()
+        .collect::<Vec<_>>();
+
+    end_row_parser(min_level)
+        .not()
+        .ignore_then(row)
+        .then(contents.then(end_row_parser(min_level)).or_not())
+        .then(object::blank_line_parser().repeated().collect::<Vec<_>>())
+        .map(|((row, maybe_contents_end), blanklines)| {
+            let mut children = vec![row];
+            if let Some((contents, end)) = maybe_contents_end {
+                if !contents.is_empty() {
+                    children.push(crate::node!(OSK::Section, contents));
+                }
+                children.push(end);
+            }
+            children.extend(blanklines);
+            crate::node!(OSK::InlineTask, children)
+        })
+        .boxed()
+}
+
+fn end_row_parser<'a, C: 'a>(
+    min_level: usize,
+) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+    just('*')
+        .repeated()
+        .at_least(min_level)
+        .to_slice()
+        .then(object::whitespaces_g1())
+        .then(just("END"))
+        .then(object::whitespaces())
+        .then(object::newline_or_ending())
+        .map(|((((stars, ws1), end), ws2), maybe_newline)| {
+            let mut children = vec![
+                crate::token!(OSK::HeadingRowStars, stars),
+                crate::token!(OSK::Whitespace, ws1),
+                crate::token!(OSK::Text, end),
+            ];
+            if !ws2.is_empty() {
+                children.push(crate::token!(OSK::Whitespace, ws2));
+            }
+            if let Some(newline) = maybe_newline {
+                children.push(crate::token!(OSK::Newline, newline));
+            }
+            crate::node!(OSK::InlineTaskEnd, children)
+        })
+}
+
+/// A simple inline task row parser WITHOUT state, ONLY used for look ahead
+// - section parser: inline task row is not heading row
+pub(crate) fn simple_inlinetask_row_parser<'a, C: 'a>(
+    min_level: usize,
+) -> impl Parser<'a, &'a str, &'a str, MyExtra<'a, C>> + Clone {
+    just('*')
+        .repeated()
+        .at_least(min_level)
+        .ignore_then(object::whitespaces_g1())
+        .ignore_then(none_of(object::CRLF).repeated())
+        .ignore_then(object::newline_or_ending())
+        .to_slice()
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+    use crate::compiler::parser::common::get_parser_output;
+    use crate::compiler::parser::config::OrgParserConfig;
+    use crate::compiler::parser::element::element_parser;
+
+    fn parser<'a>() -> impl Parser<'a, &'a str, NT, MyExtra<'a, ()>> + Clone {
+        let config = OrgParserConfig {
+            org_inlinetask_min_level: 4,
+            ..OrgParserConfig::default()
+        };
+        inlinetask_parser(config.clone(), element_parser(config))
+    }
+
+    #[test]
+    fn test_inlinetask_01_single_line() {
+        let input = "**** TODO [#A] call :phone:\n";
+        assert_eq!(
+            get_parser_output(parser(), input),
+            r##"InlineTask@0..28
+  HeadingRow@0..28
+    HeadingRowStars@0..4 "****"
+    Whitespace@4..5 " "
+    HeadingRowKeywordTodo@5..9 "TODO"
+    Whitespace@9..10 " "
+    HeadingRowPriority@10..14
+      LeftSquareBracket@10..11 "["
+      Hash@11..12 "#"
+      Text@12..13 "A"
+      RightSquareBracket@13..14 "]"
+    Whitespace@14..15 " "
+    HeadingRowTitle@15..19
+      Text@15..19 "call"
+    Whitespace@19..20 " "
+    HeadingRowTags@20..27
+      Colon@20..21 ":"
+      HeadingRowTag@21..26 "phone"
+      Colon@26..27 ":"
+    Newline@27..28 "\n"
+"##
+        );
+    }
+
+    #[test]
+    fn test_inlinetask_02_end() {
+        let input = "**** DONE task\nSCHEDULED: <2024-01-01 Mon>\ncontents\n**** END\n\n";
+        assert_eq!(
+            get_parser_output(parser(), input),
+            r##"InlineTask@0..62
+  HeadingRow@0..43
+    HeadingRowStars@0..4 "****"
+    Whitespace@4..5 " "
+    HeadingRowKeywordDone@5..9 "DONE"
+    Whitespace@9..10 " "
+    HeadingRowTitle@10..14
+      Text@10..14 "task"
+    Newline@14..15 "\n"
+    Planning@15..43
+      PlanningKeyword@15..24 "SCHEDULED"
+      Colon@24..25 ":"
+      Whitespace@25..26 " "
+      Timestamp@26..42
+        LeftAngleBracket@26..27 "<"
+        TimestampDate@27..37 "2024-01-01"
+        Whitespace@37..38 " "
+        TimestampDayname@38..41 "Mon"
+        RightAngleBracket@41..42 ">"
+      Newline@42..43 "\n"
+  Section@43..52
+    Paragraph@43..52
+      Text@43..52 "contents\n"
+  InlineTaskEnd@52..61
+    HeadingRowStars@52..56 "****"
+    Whitespace@56..57 " "
+    Text@57..60 "END"
+    Newline@60..61 "\n"
+  BlankLine@61..62 "\n"
+"##
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_inlinetask_03_end_row() {
+        get_parser_output(parser(), "**** END\n");
+    }
+}
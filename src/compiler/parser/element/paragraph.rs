@@ -60,6 +60,8 @@ pub(crate) fn paragraph_parser_with_at_least_n_affiliated_keywords<
                         element::fixed_width::simple_fixed_width_parser(config.clone()),
                         element::horizontal_rule::horizontal_rule_parser().ignored(),
                         element::comment::comment_parser().ignored(),
+                        element::clock::clock_parser().ignored(),
+                        element::diary_sexp::diary_sexp_parser().ignored(),
                         non_paragraph_parser, // other element, this is necessary to find the end of paragraph even thougn paragraph is the last element of choice
                     ))
                         .not(),
@@ -72,7 +72,11 @@ mod tests {
   Colon@12..13 ":"
   Whitespace@13..14 " "
   Timestamp@14..30
-    Text@14..30 "<1999-03-31 Wed>"
+    LeftAngleBracket@14..15 "<"
+    TimestampDate@15..25 "1999-03-31"
+    Whitespace@25..26 " "
+    TimestampDayname@26..29 "Wed"
+    RightAngleBracket@29..30 ">"
 "##
         );
     }
@@ -90,13 +94,21 @@ mod tests {
   Colon@14..15 ":"
   Whitespace@15..16 " "
   Timestamp@16..32
-    Text@16..32 "<2006-03-12 Sun>"
+    LeftAngleBracket@16..17 "<"
+    TimestampDate@17..27 "2006-03-12"
+    Whitespace@27..28 " "
+    TimestampDayname@28..31 "Sun"
+    RightAngleBracket@31..32 ">"
   Whitespace@32..33 " "
   PlanningKeyword@33..41 "DEADLINE"
   Colon@41..42 ":"
   Whitespace@42..43 " "
   Timestamp@43..59
-    Text@43..59 "<2034-03-22 Wed>"
+    LeftAngleBracket@43..44 "<"
+    TimestampDate@44..54 "2034-03-22"
+    Whitespace@54..55 " "
+    TimestampDayname@55..58 "Wed"
+    RightAngleBracket@58..59 ">"
   Whitespace@59..61 "  "
 "##
         );
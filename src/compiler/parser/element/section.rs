@@ -12,17 +12,23 @@ use crate::compiler::parser::{MyExtra, NT, OSK, element};
 ///     - \n + end()
 ///     - end()
 /// - 开头不能以`* Text`开头, 否则部分标题会被识别为Section
+/// - 以`inlinetask_min_level`个以上`*`开头的是inline task, 属于Section
 
 // block_parser
 // blank_line``
 // other_parser
 pub(crate) fn section_parser<'a, C: 'a>(
     element_parser: impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone + 'a,
+    inlinetask_min_level: usize,
 ) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
     Parser::boxed(
         element_parser
             .and_is(
                 element::heading::simple_heading_row_parser()
+                    .and_is(
+                        element::inlinetask::simple_inlinetask_row_parser(inlinetask_min_level)
+                            .not(),
+                    )
                     .ignored()
                     .not(),
             ) // Section不能以<* title>开头，避免HeadingSurbtree被识别为Section
@@ -49,14 +55,20 @@ mod tests {
         let input = "section content
 * heading
 ";
-        let parser = section_parser(element_in_section_parser::<()>(OrgParserConfig::default()));
+        let parser = section_parser(
+            element_in_section_parser::<()>(OrgParserConfig::default()),
+            OrgParserConfig::default().org_inlinetask_min_level,
+        );
         get_parser_output(parser, input);
     }
 
     #[test]
     fn test_section_02_fakedtitle() {
         let input = "0123456789 * faked_title";
-        let parser = section_parser(element_in_section_parser::<()>(OrgParserConfig::default()));
+        let parser = section_parser(
+            element_in_section_parser::<()>(OrgParserConfig::default()),
+            OrgParserConfig::default().org_inlinetask_min_level,
+        );
         assert_eq!(
             get_parser_output(parser, input),
             r##"Section@0..24
@@ -73,14 +85,20 @@ mod tests {
     #[should_panic]
     fn test_section_03_vs_heading_subtree() {
         let input = "* title\n asf\n";
-        let parser = section_parser(element_in_section_parser::<()>(OrgParserConfig::default()));
+        let parser = section_parser(
+            element_in_section_parser::<()>(OrgParserConfig::default()),
+            OrgParserConfig::default().org_inlinetask_min_level,
+        );
         get_parser_output(parser, input);
     }
 
     #[test]
     fn test_section_04_with_end() {
         let input = "0123456789";
-        let parser = section_parser(element_in_section_parser::<()>(OrgParserConfig::default()));
+        let parser = section_parser(
+            element_in_section_parser::<()>(OrgParserConfig::default()),
+            OrgParserConfig::default().org_inlinetask_min_level,
+        );
         assert_eq!(
             get_parser_output(parser, input),
             r##"Section@0..10
@@ -93,7 +111,10 @@ mod tests {
     #[test]
     fn test_section_05_with_newline_end() {
         let input = "0123456789\n";
-        let parser = section_parser(element_in_section_parser::<()>(OrgParserConfig::default()));
+        let parser = section_parser(
+            element_in_section_parser::<()>(OrgParserConfig::default()),
+            OrgParserConfig::default().org_inlinetask_min_level,
+        );
         assert_eq!(
             get_parser_output(parser, input),
             r##"Section@0..11
@@ -106,7 +127,10 @@ mod tests {
     #[test]
     fn test_section_06_with_newline_end() {
         let input = "0123456789\nfoo\nbar\nhello\nnice\nto meet you\n\n";
-        let parser = section_parser(element_in_section_parser::<()>(OrgParserConfig::default()));
+        let parser = section_parser(
+            element_in_section_parser::<()>(OrgParserConfig::default()),
+            OrgParserConfig::default().org_inlinetask_min_level,
+        );
         assert_eq!(
             get_parser_output(parser, input),
             r##"Section@0..43
@@ -121,15 +145,42 @@ mod tests {
     fn test_section_07_with_newline_end() {
         let input = "SCHEDULED: <1999-03-31 Wed>
 "; // planning is not allowed to be in section
-        let parser = section_parser(element_in_section_parser::<()>(OrgParserConfig::default()));
+        let parser = section_parser(
+            element_in_section_parser::<()>(OrgParserConfig::default()),
+            OrgParserConfig::default().org_inlinetask_min_level,
+        );
         assert_eq!(
             get_parser_output(parser, input),
             r##"Section@0..28
   Paragraph@0..28
     Text@0..11 "SCHEDULED: "
     Timestamp@11..27
-      Text@11..27 "<1999-03-31 Wed>"
+      LeftAngleBracket@11..12 "<"
+      TimestampDate@12..22 "1999-03-31"
+      Whitespace@22..23 " "
+      TimestampDayname@23..26 "Wed"
+      RightAngleBracket@26..27 ">"
     Text@27..28 "\n"
+"##
+        );
+    }
+
+    #[test]
+    fn test_section_08_diary_sexp() {
+        let input = "a\n%%(x)\n";
+        let parser = section_parser(
+            element_in_section_parser::<()>(OrgParserConfig::default()),
+            OrgParserConfig::default().org_inlinetask_min_level,
+        );
+        assert_eq!(
+            get_parser_output(parser, input),
+            r##"Section@0..8
+  Paragraph@0..2
+    Text@0..2 "a\n"
+  DiarySexp@2..8
+    Text@2..4 "%%"
+    Text@4..7 "(x)"
+    Newline@7..8 "\n"
 "##
         );
     }
@@ -1,11 +1,12 @@
 //! Object paser
 // extern crate test;
 use std::collections::HashSet;
+use std::rc::Rc;
 mod citation;
 pub mod entity;
 mod export_snippet;
 mod footnote_reference;
-mod inline_babel_call;
+pub(crate) mod inline_babel_call;
 mod inline_source_block;
 mod latex_fragment;
 mod line_break;
@@ -386,6 +387,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
     impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone,
 ) {
     let org_use_sub_superscripts = config.clone().org_use_sub_superscripts;
+    let link_types = Rc::new(config.link_types());
     let mut full_set_object = Recursive::declare();
     let mut minimal_set_object = Recursive::declare();
     let mut standard_set_object = Recursive::declare();
@@ -397,7 +399,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
     let latex_fragment = latex_fragment_parser();
     let entity = entity_parser();
 
-    let angle_link = angle_link_parser();
+    let angle_link = angle_link_parser(link_types.clone());
     let line_break = line_break_parser();
     let r#macro = macro_parser();
     let target = target_parser();
@@ -406,11 +408,11 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
     let inline_babel_call = inline_babel_call_parser();
     let export_snippet = export_snippet_parser();
     let inline_source_block = inline_source_block_parser();
-    let plain_link = plain_link_parser();
+    let plain_link = plain_link_parser(link_types.clone());
 
     let text_markup = text_markup_parser(standard_set_object.clone());
     let radio_link = radio_link_parser(minimal_set_object.clone());
-    let regular_link = regular_link_parser(object_in_regular_link.clone());
+    let regular_link = regular_link_parser(object_in_regular_link.clone(), link_types.clone());
     let radio_target = radio_target_parser(minimal_set_object.clone());
     let footnote_reference = footnote_reference_parser(standard_set_object.clone());
     let citation = citation_parser(minimal_set_object.clone(), standard_set_object.clone());
@@ -531,7 +533,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
                 footnote_reference::simple_footnote_reference_parser(),
                 angle_link.clone(),
                 plain_link.clone(),
-                link::simple_regular_link_parser(),
+                link::simple_regular_link_parser(link_types.clone()),
                 radio_link::simple_radio_link_parser(),
                 r#macro.clone(),
                 radio_target::simple_radio_target_parser(),
@@ -560,7 +562,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
             )));
             let standard_set_without_plain_text_lookahead = Parser::boxed(choice((
                 radio_link::simple_radio_link_parser(),
-                link::simple_regular_link_parser(),
+                link::simple_regular_link_parser(link_types.clone()),
                 independent_object.clone(), // 12个
                 radio_target::simple_radio_target_parser(),
                 text_markup::simple_text_markup_parser(),
@@ -594,7 +596,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
             )));
             let full_set_without_plain_text_lookahead = Parser::boxed(choice((
                 radio_link::simple_radio_link_parser(),
-                link::simple_regular_link_parser(),
+                link::simple_regular_link_parser(link_types.clone()),
                 independent_object.clone(), // 12
                 radio_target::simple_radio_target_parser(),
                 text_markup::simple_text_markup_parser(),
@@ -620,7 +622,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
             )));
             let non_plain_text_parsers_for_keyword_lookahead = Parser::boxed(choice((
                 radio_link::simple_radio_link_parser(),
-                link::simple_regular_link_parser(),
+                link::simple_regular_link_parser(link_types.clone()),
                 independent_object.clone(), // 12个
                 radio_target::simple_radio_target_parser(),
                 text_markup::simple_text_markup_parser(),
@@ -720,7 +722,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
                 footnote_reference::simple_footnote_reference_parser(),
                 angle_link.clone(),
                 plain_link.clone(),
-                link::simple_regular_link_parser(),
+                link::simple_regular_link_parser(link_types.clone()),
                 radio_link::simple_radio_link_parser(),
                 r#macro.clone(),
                 radio_target::simple_radio_target_parser(),
@@ -747,7 +749,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
             )));
             let standard_set_without_plain_text_lookahead = Parser::boxed(choice((
                 radio_link::simple_radio_link_parser(),
-                link::simple_regular_link_parser(),
+                link::simple_regular_link_parser(link_types.clone()),
                 independent_object.clone(), // 12个
                 radio_target::simple_radio_target_parser(),
                 text_markup::simple_text_markup_parser(),
@@ -777,7 +779,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
             )));
             let full_set_without_plain_text_lookahead = Parser::boxed(choice((
                 radio_link::simple_radio_link_parser(),
-                link::simple_regular_link_parser(),
+                link::simple_regular_link_parser(link_types.clone()),
                 independent_object.clone(), // 12
                 radio_target::simple_radio_target_parser(),
                 text_markup::simple_text_markup_parser(),
@@ -799,7 +801,7 @@ pub(crate) fn get_object_parser<'a, C: 'a>(
             )));
             let non_plain_text_parsers_for_keyword_lookahead = Parser::boxed(choice((
                 radio_link::simple_radio_link_parser(),
-                link::simple_regular_link_parser(),
+                link::simple_regular_link_parser(link_types.clone()),
                 independent_object.clone(), // 12个
                 radio_target::simple_radio_target_parser(),
                 text_markup::simple_text_markup_parser(),
@@ -1081,7 +1083,9 @@ other objects (2):
     BackSlash2@305..307 "\\\\"
   Text@307..323 "\n\n09 timestamp: "
   Timestamp@323..335
-    Text@323..335 "<1234-07-31>"
+    LeftAngleBracket@323..324 "<"
+    TimestampDate@324..334 "1234-07-31"
+    RightAngleBracket@334..335 ">"
   Text@335..347 "\n\n10 macro: "
   Macro@347..358
     LeftCurlyBracket3@347..350 "{{{"
@@ -1342,7 +1346,9 @@ other objects (2):
           TableCell@803..828
             Text@803..804 " "
             Timestamp@804..816
-              Text@804..816 "<1234-07-31>"
+              LeftAngleBracket@804..805 "<"
+              TimestampDate@805..815 "1234-07-31"
+              RightAngleBracket@815..816 ">"
             Whitespace@816..827 "           "
             Pipe@827..828 "|"
           TableCell@828..854
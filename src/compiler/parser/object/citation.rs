@@ -56,7 +56,7 @@ fn citation_reference_parser_inner<'a, C: 'a>(
             children.push(crate::token!(OSK::At, at));
             children.push(crate::token!(OSK::CitationReferenceKey, key));
             if let Some(key_suffix) = maybe_key_suffix {
-                children.push(crate::node!(OSK::CitationReferenceKeyPrefix, key_suffix));
+                children.push(crate::node!(OSK::CitationReferenceKeySuffix, key_suffix));
             }
 
             crate::node!(OSK::CitationReference, children)
@@ -305,7 +305,7 @@ mod tests {
     Text@0..4 "see "
   At@4..5 "@"
   CitationReferenceKey@5..8 "key"
-  CitationReferenceKeyPrefix@8..15
+  CitationReferenceKeySuffix@8..15
     Text@8..15 " p. 123"
 "##;
         let minimal_set_object =
@@ -400,13 +400,13 @@ mod tests {
   CitationReference@12..21
     At@12..13 "@"
     CitationReferenceKey@13..16 "foo"
-    CitationReferenceKeyPrefix@16..21
+    CitationReferenceKeySuffix@16..21
       Text@16..21 " p. 7"
   Semicolon@21..22 ";"
   CitationReference@22..32
     At@22..23 "@"
     CitationReferenceKey@23..26 "bar"
-    CitationReferenceKeyPrefix@26..32
+    CitationReferenceKeySuffix@26..32
       Text@26..32 " pp. 4"
   Semicolon@32..33 ";"
   CitationGlobalSuffix@33..39
@@ -445,7 +445,7 @@ mod tests {
       Text@15..34 "the very important "
     At@34..35 "@"
     CitationReferenceKey@35..41 "@atkey"
-    CitationReferenceKeyPrefix@41..48
+    CitationReferenceKeySuffix@41..48
       Text@41..48 " @ once"
   Semicolon@48..49 ";"
   CitationReference@49..72
@@ -453,7 +453,7 @@ mod tests {
       Text@49..61 "the crucial "
     At@61..62 "@"
     CitationReferenceKey@62..65 "baz"
-    CitationReferenceKeyPrefix@65..72
+    CitationReferenceKeySuffix@65..72
       Text@65..72 " vol. 3"
   LeftSquareBracket@72..73 "]"
 "##;
@@ -488,7 +488,7 @@ mod tests {
       Text@27..34 "prefix "
     At@34..35 "@"
     CitationReferenceKey@35..38 "key"
-    CitationReferenceKeyPrefix@38..45
+    CitationReferenceKeySuffix@38..45
       Text@38..45 " suffix"
   Semicolon@45..46 ";"
   CitationReference@46..66
@@ -496,7 +496,7 @@ mod tests {
       Text@46..54 " prefix "
     At@54..55 "@"
     CitationReferenceKey@55..58 "foo"
-    CitationReferenceKeyPrefix@58..66
+    CitationReferenceKeySuffix@58..66
       Text@58..66 " suffix "
   Semicolon@66..67 ";"
   CitationGlobalSuffix@67..81
@@ -3,9 +3,9 @@ use chumsky::prelude::*;
 
 use crate::compiler::parser::{MyExtra, NT, OSK, object};
 
-// PEG: inline_babel_call <- "call_" NAME ("[" HEADER1 "]")? "(" ARGUMENTS ")" ("[" HEADER2 "]")?
-pub(crate) fn inline_babel_call_parser<'a, C: 'a>()
--> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+/// `foo` / `[foo]` / `[[[foo]]]`, content of `[HEADER]`, balanced brackets allowed
+pub(crate) fn header_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, &'a str, MyExtra<'a, C>> + Clone
+{
     let mut header_single_expression = Recursive::declare(); // foo / [foo] / [[[foo]]]
     header_single_expression.define(choice((
         none_of("[]\r\n").repeated().at_least(1).to_slice(),
@@ -15,8 +15,12 @@ pub(crate) fn inline_babel_call_parser<'a, C: 'a>()
             .delimited_by(just('['), just(']'))
             .to_slice(),
     )));
-    let header = header_single_expression.repeated().at_least(1).to_slice();
+    header_single_expression.repeated().at_least(1).to_slice()
+}
 
+/// `foo` / `(foo)` / `(((foo)))`, content of `(ARGUMENTS)`, balanced parentheses allowed
+pub(crate) fn arguments_parser<'a, C: 'a>()
+-> impl Parser<'a, &'a str, &'a str, MyExtra<'a, C>> + Clone {
     let mut arguments_single_expression = Recursive::declare(); // foo / {foo} / {{{foo}}}
     arguments_single_expression.define(choice((
         none_of("()\r\n").repeated().at_least(1).to_slice(),
@@ -26,10 +30,17 @@ pub(crate) fn inline_babel_call_parser<'a, C: 'a>()
             .delimited_by(just('('), just(')'))
             .to_slice(),
     )));
-    let arguments = arguments_single_expression
+    arguments_single_expression
         .repeated()
         .at_least(1)
-        .to_slice();
+        .to_slice()
+}
+
+// PEG: inline_babel_call <- "call_" NAME ("[" HEADER1 "]")? "(" ARGUMENTS ")" ("[" HEADER2 "]")?
+pub(crate) fn inline_babel_call_parser<'a, C: 'a>()
+-> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+    let header = header_parser();
+    let arguments = arguments_parser();
 
     object::prev_valid_parser(|c| c.map_or(true, |e| e.is_whitespace()))
         .ignore_then(group((
@@ -1,5 +1,7 @@
 //! link parser, including angle/plain/regular link
+use std::collections::HashSet;
 use std::ops::Range;
+use std::rc::Rc;
 
 use chumsky::input::InputRef;
 use chumsky::prelude::*;
@@ -16,14 +18,17 @@ pub(crate) static LINK_PROTOCOLS: phf::Set<&'static str> = phf_set! {
 
 /// PROTOCOL: A string which is one of the link type strings in org-link-parameters
 // - Consume only verified to reduce # of backtracks: rollbackstate.on_save 1761022 -> 1661398 (94.3%)
+// - `link_types`: user defined link types and link abbreviations besides `LINK_PROTOCOLS`
 // #[allow(unused)]
 
-pub(crate) fn protocol<'a, C: 'a>() -> impl Parser<'a, &'a str, &'a str, MyExtra<'a, C>> + Clone {
-    custom(|inp: &mut InputRef<'a, '_, &'a str, MyExtra<'a, C>>| {
+pub(crate) fn protocol<'a, C: 'a>(
+    link_types: Rc<HashSet<String>>,
+) -> impl Parser<'a, &'a str, &'a str, MyExtra<'a, C>> + Clone {
+    custom(move |inp: &mut InputRef<'a, '_, &'a str, MyExtra<'a, C>>| {
         let before = inp.cursor();
         loop {
             match inp.peek() {
-                Some(c) if c.is_ascii_lowercase() || matches!(c, '+') => {
+                Some(c) if c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '_') => {
                     inp.next();
                 }
                 _ => {
@@ -33,7 +38,9 @@ pub(crate) fn protocol<'a, C: 'a>() -> impl Parser<'a, &'a str, &'a str, MyExtra
         }
         let protocol: &str = inp.slice_since(&before..);
 
-        if protocol.is_empty() || !LINK_PROTOCOLS.contains(protocol) {
+        if protocol.is_empty()
+            || !(LINK_PROTOCOLS.contains(protocol) || link_types.contains(protocol))
+        {
             return Err(Rich::custom(
                 inp.span_since(&before),
                 format!("invalid protocol: '{}'", protocol),
@@ -167,9 +174,10 @@ fn path_plain_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, String, MyExtra<'a
 }
 
 /// plain link parser
-pub(crate) fn plain_link_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone
-{
-    let protocol = protocol();
+pub(crate) fn plain_link_parser<'a, C: 'a>(
+    link_types: Rc<HashSet<String>>,
+) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+    let protocol = protocol(link_types);
     let post = any()
         .filter(|c: &char| !c.is_alphanumeric())
         .or(end().to('x'));
@@ -217,15 +225,16 @@ pub(crate) fn plain_link_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyE
 }
 
 /// angle link parser
-pub(crate) fn angle_link_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone
-{
+pub(crate) fn angle_link_parser<'a, C: 'a>(
+    link_types: Rc<HashSet<String>>,
+) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
     let path_angle = none_of(">") // . is permitted: orgmode.org, xx@xx.com
         .repeated()
         .at_least(1)
         .to_slice();
 
     just("<")
-        .then(protocol())
+        .then(protocol(link_types))
         .then(just(":"))
         .then(path_angle)
         .then(just(">"))
@@ -249,6 +258,7 @@ pub(crate) fn angle_link_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyE
 /// regular link parser
 pub(crate) fn regular_link_parser_inner<'a, C: 'a>(
     description_parser: impl Parser<'a, &'a str, Vec<NT>, MyExtra<'a, C>> + Clone + 'a,
+    link_types: Rc<HashSet<String>>,
 ) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
     let description = just("[")
         .then(description_parser)
@@ -285,7 +295,7 @@ pub(crate) fn regular_link_parser_inner<'a, C: 'a>(
                 .at_least(1),
         )
         .to_slice();
-    let protocol_pathinner = protocol()
+    let protocol_pathinner = protocol(link_types)
         .then(just(":"))
         .then(just("//").or_not())
         .then(string_without_brackets)
@@ -347,6 +357,7 @@ pub(crate) fn regular_link_parser_inner<'a, C: 'a>(
 
 pub(crate) fn regular_link_parser<'a, C: 'a>(
     object_parser: impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone + 'a,
+    link_types: Rc<HashSet<String>>,
 ) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
     let minimal_and_other_objects_parser = object_parser
         .clone()
@@ -362,11 +373,12 @@ pub(crate) fn regular_link_parser<'a, C: 'a>(
 
     let description_parser = minimal_and_other_objects_parser.nested_in(description_inner_parser);
 
-    regular_link_parser_inner(description_parser)
+    regular_link_parser_inner(description_parser, link_types)
 }
 
-pub(crate) fn simple_regular_link_parser<'a, C: 'a>()
--> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
+pub(crate) fn simple_regular_link_parser<'a, C: 'a>(
+    link_types: Rc<HashSet<String>>,
+) -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone {
     let description_inner_parser = none_of("]")
         .to_slice()
         .or(just("]").then(none_of("]")).to_slice()) // any().and_is(just("]]").not())
@@ -376,7 +388,7 @@ pub(crate) fn simple_regular_link_parser<'a, C: 'a>()
     let description_parser =
         description_inner_parser.map(|s: &str| vec![crate::token!(OSK::Text, s)]);
 
-    regular_link_parser_inner(description_parser)
+    regular_link_parser_inner(description_parser, link_types)
 }
 
 #[cfg(test)]
@@ -953,6 +965,32 @@ mod tests {
       Text@103..132 " <<not-supported-targ ..."
       RightSquareBracket@132..133 "]"
     RightSquareBracket@133..134 "]"
+"##
+        );
+    }
+
+    #[test]
+    fn test_plain_link_04_user_defined_type() {
+        assert_eq!(
+            get_parsers_output(
+                object::objects_parser::<()>(OrgParserConfig::default()),
+                r"jira:ABC-12"
+            ),
+            r##"Root@0..11
+  Text@0..11 "jira:ABC-12"
+"##
+        );
+
+        let config = OrgParserConfig::default().with_link_types(["jira".to_string()]);
+        assert_eq!(
+            get_parsers_output(object::objects_parser::<()>(config), r"<jira:ABC-12>"),
+            r##"Root@0..13
+  AngleLink@0..13
+    LeftAngleBracket@0..1 "<"
+    Text@1..5 "jira"
+    Colon@5..6 ":"
+    Text@6..12 "ABC-12"
+    RightAngleBracket@12..13 ">"
 "##
         );
     }
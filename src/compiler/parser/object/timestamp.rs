@@ -1,11 +1,15 @@
 //! timestamp parser
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
 use chumsky::prelude::*;
 
-use crate::compiler::parser::{MyExtra, NT, OSK};
+use crate::compiler::ast_builder::timestamp::Timestamp;
+use crate::compiler::parser::syntax::SyntaxNode;
+use crate::compiler::parser::{MyExtra, NT, OSK, ParserState};
 
+/// Parse datetime of metadata, such as `#+date:` or `:CREATED:`, in local timezone
 pub struct FlexibleDateTimeParser {
     formats: Vec<&'static str>,
+    date_formats: Vec<&'static str>,
 }
 
 impl FlexibleDateTimeParser {
@@ -14,16 +18,36 @@ impl FlexibleDateTimeParser {
             formats: vec![
                 "%Y-%m-%d %a %H:%M",
                 "%Y-%m-%d %H:%M",
-                "%Y-%m-%d",
-                "%Y-%m-%dT%H:%M:%S%z",
+                "%Y-%m-%d %H:%M:%S",
+                "%Y-%m-%dT%H:%M",
+                "%Y-%m-%dT%H:%M:%S",
             ],
+            date_formats: vec!["%Y-%m-%d %a", "%Y-%m-%d"],
         }
     }
 
     pub fn parse(&self, s: &str) -> Result<DateTime<Local>, &str> {
+        let s = s.trim();
+        let to_local = |dt: NaiveDateTime| Local.from_local_datetime(&dt).earliest().ok_or("error");
+
+        // org timestamp: `<2024-01-01 Mon 10:00>`, `[2024-01-01 Mon]`
+        if let Some(timestamp) = Timestamp::parse(s) {
+            return to_local(timestamp.start);
+        }
+        // with offset: `2024-01-01T10:00:00+0800`, `2024-01-01T10:00:00+08:00`
+        if let Ok(dt) = DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%z")
+            .or_else(|_| DateTime::parse_from_rfc3339(s))
+        {
+            return Ok(dt.with_timezone(&Local));
+        }
         for &format in &self.formats {
             if let Ok(dt) = NaiveDateTime::parse_from_str(s, format) {
-                return Ok(Local.from_local_datetime(&dt).unwrap());
+                return to_local(dt);
+            }
+        }
+        for &format in &self.date_formats {
+            if let Ok(date) = NaiveDate::parse_from_str(s, format) {
+                return to_local(date.and_time(NaiveTime::MIN));
             }
         }
 
@@ -33,104 +57,144 @@ impl FlexibleDateTimeParser {
 
 use super::whitespaces_g1;
 
-/// timestamp parser: <<TIMESTAMP>>
-pub(crate) fn timestamp_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone
-{
+/// Parse `text` as a whole timestamp, e.g., value of `#+DATE:`
+pub(crate) fn parse_timestamp(text: &str) -> Option<SyntaxNode> {
+    let mut state = extra::SimpleState(ParserState::default());
+    let output = timestamp_parser::<()>()
+        .then_ignore(end())
+        .parse_with_state(text, &mut state)
+        .into_output()?;
+    Some(SyntaxNode::new_root(output.into_node()?))
+}
+
+/// One bracketed part of timestamp: `<DATE DAYNAME TIME REPEATER-OR-DELAY>`, or `[...]` if not
+/// `active`; time is required to be a span `TIME-TIME` if `time_span`
+fn timestamp_part<'a, C: 'a>(
+    active: bool,
+    time_span: bool,
+) -> impl Parser<'a, &'a str, Vec<NT>, MyExtra<'a, C>> + Clone {
     let yyyymmdd = one_of("0123456789")
         .repeated()
-        .at_least(4)
-        .at_most(4)
+        .exactly(4)
         .then(just("-"))
-        .then(one_of("0123456789").repeated().at_least(2).at_most(2))
+        .then(one_of("0123456789").repeated().exactly(2))
         .then(just("-"))
-        .then(one_of("0123456789").repeated().at_least(2).at_most(2));
-    let daytime = none_of(" \t+-]>0123456789\n").repeated().at_least(1);
-
-    let date = yyyymmdd.then(whitespaces_g1().then(daytime).or_not());
+        .then(one_of("0123456789").repeated().exactly(2))
+        .to_slice();
+    let dayname = none_of(" \t+-]>0123456789\n")
+        .repeated()
+        .at_least(1)
+        .to_slice();
     let time = one_of("0123456789")
         .repeated()
         .at_least(1)
         .at_most(2)
         .then(just(":"))
-        .then(one_of("0123456789").repeated().at_least(2).at_most(2));
-
-    let repeater_or_delay = just("++")
-        .or(just(".+"))
-        .or(just("+"))
-        .or(just("--"))
-        .or(just("-"))
-        .then(one_of("0123456789").repeated().at_least(1))
-        .then(one_of("hdwmy"));
-
-    let p1a = just("<")
-        .then(date.clone())
-        .then(whitespaces_g1().then(time).or_not())
-        .then(
-            whitespaces_g1()
-                .then(repeater_or_delay)
-                .repeated()
-                .at_most(2),
-        )
-        .then(just(">"))
-        .to_slice()
-        .map(|s| crate::node!(OSK::Timestamp, vec![crate::token!(OSK::Text, s)]));
+        .then(one_of("0123456789").repeated().exactly(2))
+        .to_slice();
 
-    let p1b = just("[")
-        .then(date.clone())
-        .then(whitespaces_g1().then(time).or_not())
-        .then(
-            whitespaces_g1()
-                .then(repeater_or_delay)
-                .repeated()
-                .at_most(2),
-        )
-        .then(just("]"))
+    let interval = one_of("0123456789")
+        .repeated()
+        .at_least(1)
+        .then(one_of("hdwmy"));
+    // with deadline of habit, e.g., `.+2d/3d`
+    let repeater = choice((just("++"), just(".+"), just("+")))
+        .then(interval.clone())
+        .then(just("/").then(interval.clone()).or_not())
         .to_slice()
-        .map(|s| crate::node!(OSK::Timestamp, vec![crate::token!(OSK::Text, s)]));
-
-    let p2a = p1a
-        .clone()
-        .then(just("--"))
-        .then(p1a.clone())
+        .map(|s| crate::token!(OSK::TimestampRepeater, s));
+    let delay = choice((just("--"), just("-")))
+        .then(interval)
         .to_slice()
-        .map(|s| crate::node!(OSK::Timestamp, vec![crate::token!(OSK::Text, s)]));
+        .map(|s| crate::token!(OSK::TimestampDelay, s));
 
-    let p2b = p1b
-        .clone()
-        .then(just("--"))
-        .then(p1b.clone())
-        .to_slice()
-        .map(|s| crate::node!(OSK::Timestamp, vec![crate::token!(OSK::Text, s)]));
+    let (open, close, open_kind, close_kind) = if active {
+        ("<", ">", OSK::LeftAngleBracket, OSK::RightAngleBracket)
+    } else {
+        ("[", "]", OSK::LeftSquareBracket, OSK::RightSquareBracket)
+    };
+    let n_span = usize::from(time_span);
 
-    let p3a = just("<")
-        .then(date.clone())
-        .then(whitespaces_g1().then(time).then(just("-").then(time)))
+    just(open)
+        .then(yyyymmdd)
+        .then(whitespaces_g1().then(dayname).or_not())
         .then(
             whitespaces_g1()
-                .then(repeater_or_delay)
+                .then(time.clone())
+                .then(
+                    just("-")
+                        .then(time.clone())
+                        .repeated()
+                        .exactly(n_span)
+                        .collect::<Vec<_>>(),
+                )
                 .repeated()
-                .at_most(2),
+                .at_least(n_span)
+                .at_most(1)
+                .collect::<Vec<_>>(),
         )
-        .then(just(">"))
-        .to_slice()
-        .map(|s| crate::node!(OSK::Timestamp, vec![crate::token!(OSK::Text, s)]));
-
-    let p3b = just("[")
-        .then(date.clone())
-        .then(whitespaces_g1().then(time).then(just("-").then(time)))
         .then(
             whitespaces_g1()
-                .then(repeater_or_delay)
+                .then(choice((repeater, delay)))
                 .repeated()
-                .at_most(2),
+                .at_most(2)
+                .collect::<Vec<_>>(),
         )
-        .then(just("]"))
-        .to_slice()
-        .map(|s| crate::node!(OSK::Timestamp, vec![crate::token!(OSK::Text, s)]));
+        .then(just(close))
+        .map(
+            move |(((((open, date), maybe_dayname), times), repeaters_or_delays), close)| {
+                let mut children = vec![
+                    crate::token!(open_kind, open),
+                    crate::token!(OSK::TimestampDate, date),
+                ];
+                if let Some((ws, dayname)) = maybe_dayname {
+                    children.push(crate::token!(OSK::Whitespace, ws));
+                    children.push(crate::token!(OSK::TimestampDayname, dayname));
+                }
+                for ((ws, time), span) in times {
+                    children.push(crate::token!(OSK::Whitespace, ws));
+                    children.push(crate::token!(OSK::TimestampTime, time));
+                    for (dash, time) in span {
+                        children.push(crate::token!(OSK::Dash, dash));
+                        children.push(crate::token!(OSK::TimestampTime, time));
+                    }
+                }
+                for (ws, repeater_or_delay) in repeaters_or_delays {
+                    children.push(crate::token!(OSK::Whitespace, ws));
+                    children.push(repeater_or_delay);
+                }
+                children.push(crate::token!(close_kind, close));
+                children
+            },
+        )
+}
 
-    choice((p2a, p2b, p3a, p3b, p1a, p1b)).boxed()
-    // Parser::boxed(choice((p2a, p2b, p3a, p3b, p1a, p1b)))
-    // p2a.or(p2b).or(p3a).or(p3b).or(p1a).or(p1b)
+/// timestamp parser: <<TIMESTAMP>>
+///
+/// Range `<DATE>--<DATE>` is kept as one node, whose parts are separated by a `Text` of `--`.
+pub(crate) fn timestamp_parser<'a, C: 'a>() -> impl Parser<'a, &'a str, NT, MyExtra<'a, C>> + Clone
+{
+    let range = |active| {
+        timestamp_part::<C>(active, false)
+            .then(just("--"))
+            .then(timestamp_part::<C>(active, false))
+            .map(|((mut children, dashes), end)| {
+                children.push(crate::token!(OSK::Text, dashes));
+                children.extend(end);
+                children
+            })
+    };
+
+    choice((
+        range(true),
+        range(false),
+        timestamp_part(true, true),
+        timestamp_part(false, true),
+        timestamp_part(true, false),
+        timestamp_part(false, false),
+    ))
+    .map(|children| crate::node!(OSK::Timestamp, children))
+    .boxed()
 }
 
 #[cfg(test)]
@@ -150,7 +214,17 @@ mod tests {
             ),
             r##"Root@0..34
   Timestamp@0..34
-    Text@0..34 "[2004-08-24 Tue]--[20 ..."
+    LeftSquareBracket@0..1 "["
+    TimestampDate@1..11 "2004-08-24"
+    Whitespace@11..12 " "
+    TimestampDayname@12..15 "Tue"
+    RightSquareBracket@15..16 "]"
+    Text@16..18 "--"
+    LeftSquareBracket@18..19 "["
+    TimestampDate@19..29 "2004-08-26"
+    Whitespace@29..30 " "
+    TimestampDayname@30..33 "Thu"
+    RightSquareBracket@33..34 "]"
 "##
         );
     }
@@ -164,8 +238,83 @@ mod tests {
             ),
             r##"Root@0..24
   Timestamp@0..24
-    Text@0..24 "<2030-10-05 Sat +1m -3d>"
+    LeftAngleBracket@0..1 "<"
+    TimestampDate@1..11 "2030-10-05"
+    Whitespace@11..12 " "
+    TimestampDayname@12..15 "Sat"
+    Whitespace@15..16 " "
+    TimestampRepeater@16..19 "+1m"
+    Whitespace@19..20 " "
+    TimestampDelay@20..23 "-3d"
+    RightAngleBracket@23..24 ">"
 "##
         );
     }
+
+    #[test]
+    fn test_timestamp_03_time_span() {
+        assert_eq!(
+            get_parsers_output(
+                object::objects_parser::<()>(OrgParserConfig::default()),
+                r"<2024-01-01 Mon 10:00-11:30>"
+            ),
+            r##"Root@0..28
+  Timestamp@0..28
+    LeftAngleBracket@0..1 "<"
+    TimestampDate@1..11 "2024-01-01"
+    Whitespace@11..12 " "
+    TimestampDayname@12..15 "Mon"
+    Whitespace@15..16 " "
+    TimestampTime@16..21 "10:00"
+    Dash@21..22 "-"
+    TimestampTime@22..27 "11:30"
+    RightAngleBracket@27..28 ">"
+"##
+        );
+    }
+
+    #[test]
+    fn test_timestamp_04_habit() {
+        assert_eq!(
+            get_parsers_output(
+                object::objects_parser::<()>(OrgParserConfig::default()),
+                r"<2024-01-01 Mon .+2d/3d>"
+            ),
+            r##"Root@0..24
+  Timestamp@0..24
+    LeftAngleBracket@0..1 "<"
+    TimestampDate@1..11 "2024-01-01"
+    Whitespace@11..12 " "
+    TimestampDayname@12..15 "Mon"
+    Whitespace@15..16 " "
+    TimestampRepeater@16..23 ".+2d/3d"
+    RightAngleBracket@23..24 ">"
+"##
+        );
+    }
+
+    #[test]
+    fn test_flexible_datetime_parser() {
+        let parser = super::FlexibleDateTimeParser::new();
+        let format = |s| {
+            parser
+                .parse(s)
+                .map(|e| e.format("%Y-%m-%d %H:%M").to_string())
+        };
+        assert_eq!(
+            format("<2024-01-02 Tue 10:30 +1w>"),
+            Ok("2024-01-02 10:30".to_string())
+        );
+        assert_eq!(
+            format("[2024-01-02 Tue]"),
+            Ok("2024-01-02 00:00".to_string())
+        );
+        assert_eq!(format("2024-01-02 Tue"), Ok("2024-01-02 00:00".to_string()));
+        assert_eq!(format("2024-01-02"), Ok("2024-01-02 00:00".to_string()));
+        assert_eq!(
+            format("2024-01-02T10:30:00"),
+            Ok("2024-01-02 10:30".to_string())
+        );
+        assert!(format("2024-01-02 foo").is_err());
+    }
 }
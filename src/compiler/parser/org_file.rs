@@ -17,12 +17,15 @@ pub(crate) fn org_file_parser<'a>(
         .then(element::comment::comment_parser().or_not())
         .then(element::drawer::property_drawer_parser().or_not())
         .then(
-            element::section::section_parser(element::element_in_section_parser(config.clone()))
-                .or_not(),
+            element::section::section_parser(
+                element::element_in_section_parser(config.clone()),
+                config.org_inlinetask_min_level,
+            )
+            .or_not(),
         )
         .then(
             element::heading::heading_subtree_parser(
-                config.clone().org_todo_keywords,
+                config.clone(),
                 object::standard_set_object_parser::<()>(config.clone()),
                 element::element_parser(config.clone()),
                 "",
@@ -23,6 +23,8 @@ pub enum OrgSyntaxKind {
     Item,
     PlainList,
     FootnoteDefinition,
+    InlineTask,
+    InlineTaskEnd,
 
     // Element :: Lesser Element
     Paragraph,
@@ -65,6 +67,11 @@ pub enum OrgSyntaxKind {
     RadioTarget,
     RadioLink,
     Timestamp,
+    TimestampDate,     // 2024-01-01
+    TimestampDayname,  // Mon
+    TimestampTime,     // 10:00
+    TimestampRepeater, // +1w, ++1m, .+2d/3d
+    TimestampDelay,    // -2d, --2d
 
     FootnoteReference,
     FootnoteReferenceLabel, // simplify ast
@@ -101,6 +108,11 @@ pub enum OrgSyntaxKind {
     InlineBabelCallArguments,
     InlineBabelCallHeader2,
 
+    BabelCallName,
+    BabelCallInsideHeader,
+    BabelCallArguments,
+    BabelCallEndHeader,
+
     Citation,
 
     CitationCitestyle,
@@ -146,6 +158,7 @@ pub enum OrgSyntaxKind {
     Planning,
     PlanningKeyword,
     Clock,
+    ClockDuration,
 
     Unknown,
 
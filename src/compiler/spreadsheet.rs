@@ -0,0 +1,1224 @@
+//! Evaluate formulas of `#+TBLFM:` with the semantics of org spreadsheet
+//!
+//! - column formula `$3=$1*$2` is applied to every row below the first hline, or to every row
+//!   if the table has no header; field formula `@2$4=...` (or a range of fields `@2$1..@3$2`) is
+//!   applied after column formulas
+//! - rows: `@N`, `@<`, `@>`, `@-1` (relative), `@I`/`@II` (hlines, `@I+1` is the first row
+//!   after the first hline); columns: `$N`, `$<`, `$>`, `$+1`; `@0`/`$0` are the current row and
+//!   column
+//! - ranges: `@2$1..@>$1`, `@I..@II` (rows between hlines) and `$1..$3` (fields of current row),
+//!   empty fields in ranges are skipped
+//! - operators `+ - * / ^`, functions `vsum`, `vmean`, `vmedian`, `vmax`, `vmin`, `vcount`,
+//!   `vprod`, `abs`, `sqrt`, `exp`, `ln`, `log10`, `floor`, `ceil`, `round`, and
+//!   `remote(NAME, REF)` refers to the table named NAME in the same file
+//! - format after `;`: printf-like `%.2f`, `%d`, `%e` and mode `N` (non-numeric fields are 0)
+//!
+//! Tables are recalculated once in document order. Emacs lisp formulas `'(...)` are not
+//! supported.
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use walkdir::WalkDir;
+
+use crate::compiler::ast_builder::element::{
+    Element, HeadingSubtree, OrgFile, Table, TableRow, TableRowType,
+};
+use crate::compiler::ast_builder::object::{Object, TableCell, TableCellType};
+use crate::compiler::content::Section;
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::dynamic_block::{plain_text, visit_elements_mut};
+use crate::compiler::parser::OrgParser;
+use crate::compiler::parser::config::OrgParserConfig;
+use crate::compiler::parser::syntax::{OrgSyntaxKind, SyntaxNode};
+use crate::export::org::formatter::{self, align_table, table_rows};
+
+/// Fields of table, hlines are kept as positions between rows
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sheet {
+    /// fields of standard rows, all rows have the same number of fields
+    pub rows: Vec<Vec<String>>,
+    /// number of rows before each hline
+    pub hlines: Vec<usize>,
+}
+
+impl Sheet {
+    pub fn new(mut rows: Vec<Vec<String>>, hlines: Vec<usize>) -> Self {
+        let n_columns = rows.iter().map(|e| e.len()).max().unwrap_or(0);
+        for row in rows.iter_mut() {
+            row.resize(n_columns, String::new());
+        }
+        Self { rows, hlines }
+    }
+
+    fn n_columns(&self) -> usize {
+        self.rows.first().map_or(0, |e| e.len())
+    }
+
+    /// Evaluate `formulas`, i.e., values of `#+TBLFM:` lines, tables referred by `remote()` are
+    /// given by `remotes`.
+    ///
+    /// Errors are returned, one for each failed formula, whose fields are kept as is.
+    pub fn recalc(&mut self, formulas: &[String], remotes: &HashMap<String, Sheet>) -> Vec<String> {
+        let mut errors = vec![];
+        let formulas = formulas
+            .iter()
+            .flat_map(|e| e.split("::"))
+            .map(str::trim)
+            .filter(|e| !e.is_empty())
+            .filter_map(|e| match Formula::parse(e) {
+                Ok(formula) => Some(formula),
+                Err(err) => {
+                    errors.push(format!("{e}: {err}"));
+                    None
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut failed = vec![false; formulas.len()];
+        let mut report = |i: usize, result: Result<(), String>| {
+            if let Err(e) = result
+                && !failed[i]
+            {
+                failed[i] = true;
+                errors.push(format!("{}: {e}", formulas[i].text));
+            }
+        };
+
+        let first_row = match self.hlines.first() {
+            Some(&n) if n > 0 => n,
+            _ => 0,
+        };
+        for row in first_row..self.rows.len() {
+            for (i, formula) in formulas.iter().enumerate() {
+                if let Target::Column(column) = &formula.target {
+                    let result = column
+                        .resolve(self.n_columns(), 0)
+                        .and_then(|column| self.apply(formula, row, column, remotes));
+                    report(i, result);
+                }
+            }
+        }
+        for (i, formula) in formulas.iter().enumerate() {
+            if let Target::Fields(start, end) = &formula.target {
+                let result = self.fields(start, end.as_ref()).and_then(|fields| {
+                    fields
+                        .into_iter()
+                        .try_for_each(|(row, column)| self.apply(formula, row, column, remotes))
+                });
+                report(i, result);
+            }
+        }
+        errors
+    }
+
+    /// Evaluate `formula` at field (`row`, `column`) and write the result into it
+    fn apply(
+        &mut self,
+        formula: &Formula,
+        row: usize,
+        column: usize,
+        remotes: &HashMap<String, Sheet>,
+    ) -> Result<(), String> {
+        let mut expression = Expression {
+            chars: formula.expression.chars().collect(),
+            pos: 0,
+            sheet: self,
+            remotes,
+            row,
+            column,
+            numeric: formula.numeric,
+        };
+        let value = expression.parse()?;
+        self.rows[row][column] = value.format(formula.format.as_deref())?;
+        Ok(())
+    }
+
+    /// Target fields of field formula, row by row
+    fn fields(
+        &self,
+        start: &FieldRef,
+        end: Option<&FieldRef>,
+    ) -> Result<Vec<(usize, usize)>, String> {
+        let (row, column) = start.resolve(self, 0, 0, false)?;
+        let Some(end) = end else {
+            return Ok(vec![(row, column)]);
+        };
+        let (end_row, end_column) = end.resolve(self, 0, 0, true)?;
+        Ok((row.min(end_row)..=row.max(end_row))
+            .flat_map(|r| (column.min(end_column)..=column.max(end_column)).map(move |c| (r, c)))
+            .collect())
+    }
+
+    /// Numbers of fields in range, empty fields are skipped
+    fn range(
+        &self,
+        start: (usize, usize),
+        end: (usize, usize),
+        numeric: bool,
+    ) -> Result<Vec<f64>, String> {
+        let mut numbers = vec![];
+        for row in start.0.min(end.0)..=start.0.max(end.0) {
+            for column in start.1.min(end.1)..=start.1.max(end.1) {
+                let field = &self.rows[row][column];
+                if field.is_empty() {
+                    continue;
+                }
+                match (parse_number(field), numeric) {
+                    (Some(x), _) => numbers.push(x),
+                    (None, true) => numbers.push(0.0),
+                    (None, false) => return Err(format!("`{field}` is not a number")),
+                }
+            }
+        }
+        Ok(numbers)
+    }
+}
+
+/// `TARGET=EXPRESSION;FORMAT`
+#[derive(Debug)]
+struct Formula {
+    text: String,
+    target: Target,
+    expression: String,
+    /// printf-like format, e.g., `%.2f`
+    format: Option<String>,
+    /// mode `N`: non-numeric fields are 0
+    numeric: bool,
+}
+
+#[derive(Debug)]
+enum Target {
+    Column(ColumnRef),
+    Fields(FieldRef, Option<FieldRef>),
+}
+
+impl Formula {
+    fn parse(s: &str) -> Result<Self, String> {
+        let (target, rest) = s.split_once('=').ok_or("`=` is missing")?;
+        if rest.trim_start().starts_with('\'') {
+            return Err("emacs lisp formulas are not supported".to_string());
+        }
+        let (expression, modes) = rest.rsplit_once(';').unwrap_or((rest, ""));
+        let (format, numeric) = parse_modes(modes)?;
+
+        let target = target.trim();
+        let target = match target.split_once("..") {
+            Some((start, end)) => {
+                Target::Fields(parse_target_field(start)?, Some(parse_target_field(end)?))
+            }
+            None if target.starts_with('$') => {
+                let chars = target.chars().collect::<Vec<_>>();
+                let mut pos = 1;
+                let column = ColumnRef::parse(&chars, &mut pos)?;
+                if pos < chars.len() {
+                    return Err(format!("invalid target `{target}`"));
+                }
+                Target::Column(column)
+            }
+            None => Target::Fields(parse_target_field(target)?, None),
+        };
+        Ok(Self {
+            text: s.to_string(),
+            target,
+            expression: expression.trim().to_string(),
+            format,
+            numeric,
+        })
+    }
+}
+
+fn parse_target_field(s: &str) -> Result<FieldRef, String> {
+    let chars = s.trim().chars().collect::<Vec<_>>();
+    let mut pos = 0;
+    let field = FieldRef::parse(&chars, &mut pos)?;
+    if pos < chars.len() || field.row.is_none() || field.column.is_none() {
+        return Err(format!("invalid target `{s}`"));
+    }
+    Ok(field)
+}
+
+/// printf-like format and mode `N`, other modes are ignored
+fn parse_modes(modes: &str) -> Result<(Option<String>, bool), String> {
+    let mut format = None;
+    let mut numeric = false;
+    let mut chars = modes.trim().chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '%' => {
+                let mut spec = String::from('%');
+                for c in chars.by_ref() {
+                    spec.push(c);
+                    if c.is_ascii_alphabetic() {
+                        break;
+                    }
+                }
+                if !spec.ends_with(['d', 'i', 'f', 'e', 'g', 's']) {
+                    return Err(format!("unsupported format `{spec}`"));
+                }
+                format = Some(spec);
+            }
+            'N' => numeric = true,
+            _ => {}
+        }
+    }
+    Ok((format, numeric))
+}
+
+#[derive(Debug, Clone, Copy)]
+enum RowRef {
+    /// 1-based
+    Absolute(usize),
+    /// `@<`, `@<<`, ...
+    First(usize),
+    /// `@>`, `@>>`, ...
+    Last(usize),
+    /// `@-1`, `@+1`, and `@0` for the current row
+    Relative(isize),
+    /// `@I`, `@II+1`, ...
+    Hline(usize, isize),
+}
+
+impl RowRef {
+    /// Index of row, an hline without offset is the row after it, or the row before it if it's
+    /// the end of range
+    fn resolve(&self, sheet: &Sheet, current: usize, is_end: bool) -> Result<usize, String> {
+        let n_rows = sheet.rows.len() as isize;
+        let row = match *self {
+            RowRef::Absolute(n) => n as isize - 1,
+            RowRef::First(n) => n as isize - 1,
+            RowRef::Last(n) => n_rows - n as isize,
+            RowRef::Relative(n) => current as isize + n,
+            RowRef::Hline(k, offset) => {
+                let hline = *sheet
+                    .hlines
+                    .get(k - 1)
+                    .ok_or_else(|| format!("no hline {}", "I".repeat(k)))?
+                    as isize;
+                match offset {
+                    0 if is_end => hline - 1,
+                    0 => hline,
+                    _ if offset > 0 => hline + offset - 1,
+                    _ => hline + offset,
+                }
+            }
+        };
+        if row < 0 || row >= n_rows {
+            return Err(format!("row {} is out of range", row + 1));
+        }
+        Ok(row as usize)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ColumnRef {
+    /// 1-based
+    Absolute(usize),
+    /// `$<`, `$<<`, ...
+    First(usize),
+    /// `$>`, `$>>`, ...
+    Last(usize),
+    /// `$-1`, `$+1`, and `$0` for the current column
+    Relative(isize),
+}
+
+impl ColumnRef {
+    /// Parse column after `$`
+    fn parse(chars: &[char], pos: &mut usize) -> Result<Self, String> {
+        let start = *pos;
+        while let Some(&c) = chars.get(*pos) {
+            let is_part = match chars[start..*pos].first() {
+                None => c.is_ascii_digit() || matches!(c, '<' | '>' | '+' | '-'),
+                Some('<') => c == '<',
+                Some('>') => c == '>',
+                Some(_) => c.is_ascii_digit(),
+            };
+            if !is_part {
+                break;
+            }
+            *pos += 1;
+        }
+        let s = chars[start..*pos].iter().collect::<String>();
+        match s.chars().next() {
+            Some('<') => Ok(ColumnRef::First(s.len())),
+            Some('>') => Ok(ColumnRef::Last(s.len())),
+            Some('+' | '-') => s
+                .parse()
+                .map(ColumnRef::Relative)
+                .map_err(|_| format!("invalid column `${s}`")),
+            Some(_) => match s.parse().map_err(|_| format!("invalid column `${s}`"))? {
+                0 => Ok(ColumnRef::Relative(0)),
+                n => Ok(ColumnRef::Absolute(n)),
+            },
+            None => Err("column is missing after `$`".to_string()),
+        }
+    }
+
+    fn resolve(&self, n_columns: usize, current: usize) -> Result<usize, String> {
+        let column = match *self {
+            ColumnRef::Absolute(n) => n as isize - 1,
+            ColumnRef::First(n) => n as isize - 1,
+            ColumnRef::Last(n) => n_columns as isize - n as isize,
+            ColumnRef::Relative(n) => current as isize + n,
+        };
+        if column < 0 || column >= n_columns as isize {
+            return Err(format!("column ${} is out of range", column + 1));
+        }
+        Ok(column as usize)
+    }
+}
+
+/// `@ROW$COLUMN`, `@ROW` or `$COLUMN`, the missing part is the current row or column
+#[derive(Debug, Clone, Copy)]
+struct FieldRef {
+    row: Option<RowRef>,
+    column: Option<ColumnRef>,
+}
+
+impl FieldRef {
+    fn parse(chars: &[char], pos: &mut usize) -> Result<Self, String> {
+        let mut row = None;
+        if chars.get(*pos) == Some(&'@') {
+            *pos += 1;
+            row = Some(parse_row(chars, pos)?);
+        }
+        let mut column = None;
+        if chars.get(*pos) == Some(&'$') {
+            *pos += 1;
+            column = Some(ColumnRef::parse(chars, pos)?);
+        }
+        if row.is_none() && column.is_none() {
+            return Err("reference is missing".to_string());
+        }
+        Ok(Self { row, column })
+    }
+
+    fn resolve(
+        &self,
+        sheet: &Sheet,
+        row: usize,
+        column: usize,
+        is_end: bool,
+    ) -> Result<(usize, usize), String> {
+        let row = match &self.row {
+            Some(e) => e.resolve(sheet, row, is_end)?,
+            None => row,
+        };
+        let column = match &self.column {
+            Some(e) => e.resolve(sheet.n_columns(), column)?,
+            None => column,
+        };
+        Ok((row, column))
+    }
+}
+
+/// Parse row after `@`
+fn parse_row(chars: &[char], pos: &mut usize) -> Result<RowRef, String> {
+    let take = |pos: &mut usize, f: &dyn Fn(char) -> bool| {
+        let start = *pos;
+        while chars.get(*pos).is_some_and(|&c| f(c)) {
+            *pos += 1;
+        }
+        chars[start..*pos].iter().collect::<String>()
+    };
+    let signed = |pos: &mut usize| -> Option<isize> {
+        let sign = match chars.get(*pos) {
+            Some('+') => 1,
+            Some('-') => -1,
+            _ => return None,
+        };
+        if !chars.get(*pos + 1).is_some_and(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        *pos += 1;
+        take(pos, &|c| c.is_ascii_digit())
+            .parse::<isize>()
+            .ok()
+            .map(|n| sign * n)
+    };
+
+    match chars.get(*pos) {
+        Some('<') => Ok(RowRef::First(take(pos, &|c| c == '<').len())),
+        Some('>') => Ok(RowRef::Last(take(pos, &|c| c == '>').len())),
+        Some('I') => {
+            let k = take(pos, &|c| c == 'I').len();
+            Ok(RowRef::Hline(k, signed(pos).unwrap_or(0)))
+        }
+        Some('+' | '-') => signed(pos)
+            .map(RowRef::Relative)
+            .ok_or_else(|| "invalid row".to_string()),
+        Some(c) if c.is_ascii_digit() => {
+            match take(pos, &|c| c.is_ascii_digit()).parse().unwrap_or(0) {
+                0 => Ok(RowRef::Relative(0)),
+                n => Ok(RowRef::Absolute(n)),
+            }
+        }
+        _ => Err("row is missing after `@`".to_string()),
+    }
+}
+
+fn parse_number(s: &str) -> Option<f64> {
+    let s = s.trim();
+    s.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'))
+        .then(|| s.parse::<f64>().ok())
+        .flatten()
+}
+
+#[derive(Debug, Clone)]
+enum Value {
+    Number(f64),
+    Text(String),
+    Vector(Vec<f64>),
+}
+
+impl Value {
+    fn number(&self) -> Result<f64, String> {
+        match self {
+            Value::Number(x) => Ok(*x),
+            Value::Text(s) => Err(format!("`{s}` is not a number")),
+            Value::Vector(_) => Err("a range is used as a number".to_string()),
+        }
+    }
+
+    fn numbers(self) -> Result<Vec<f64>, String> {
+        match self {
+            Value::Vector(v) => Ok(v),
+            _ => Ok(vec![self.number()?]),
+        }
+    }
+
+    /// Text of field, formatted by printf-like `format`
+    fn format(&self, format: Option<&str>) -> Result<String, String> {
+        let x = match self {
+            Value::Text(s) => return Ok(s.clone()),
+            _ => self.number()?,
+        };
+        if !x.is_finite() {
+            return Err("result is not a finite number".to_string());
+        }
+        Ok(match format {
+            Some(spec) => printf(spec, x),
+            None => format_number(x),
+        })
+    }
+}
+
+/// Number with at most 12 significant digits, integers are written without decimal point
+fn format_number(x: f64) -> String {
+    if x.fract() == 0.0 && x.abs() < 1e15 {
+        return format!("{}", x as i64);
+    }
+    let magnitude = x.abs().log10().floor() as i32;
+    let decimals = (11 - magnitude).clamp(0, 15) as usize;
+    let s = format!("{x:.decimals$}");
+    match s.contains('.') {
+        true => s.trim_end_matches('0').trim_end_matches('.').to_string(),
+        false => s,
+    }
+}
+
+/// `%[-0][WIDTH][.PRECISION](d|i|f|e|g|s)`
+fn printf(spec: &str, x: f64) -> String {
+    let body = &spec[1..spec.len() - 1];
+    let conversion = spec.chars().last().unwrap_or('s');
+    let left = body.contains('-');
+    let zero = body.trim_start_matches('-').starts_with('0');
+    let body = body.trim_start_matches(['-', '0']);
+    let (width, precision) = match body.split_once('.') {
+        Some((width, precision)) => (width, precision.parse::<usize>().ok()),
+        None => (body, None),
+    };
+    let width = width.parse::<usize>().unwrap_or(0);
+
+    let s = match conversion {
+        'd' | 'i' => format!("{}", x.round() as i64),
+        'f' => format!("{x:.*}", precision.unwrap_or(6)),
+        'e' => {
+            let s = format!("{x:.*e}", precision.unwrap_or(6));
+            let (mantissa, exponent) = s.split_once('e').unwrap_or((&s, "0"));
+            let exponent = exponent.parse::<i32>().unwrap_or(0);
+            let sign = if exponent < 0 { '-' } else { '+' };
+            format!("{mantissa}e{sign}{:02}", exponent.abs())
+        }
+        _ => format_number(x),
+    };
+    let padding = width.saturating_sub(s.chars().count());
+    match (left, zero) {
+        (true, _) => format!("{s}{}", " ".repeat(padding)),
+        (false, true) => match s.strip_prefix('-') {
+            Some(digits) => format!("-{}{digits}", "0".repeat(padding)),
+            None => format!("{}{s}", "0".repeat(padding)),
+        },
+        (false, false) => format!("{}{s}", " ".repeat(padding)),
+    }
+}
+
+/// Recursive descent parser of formula expression, evaluated at field (`row`, `column`)
+struct Expression<'a> {
+    chars: Vec<char>,
+    pos: usize,
+    sheet: &'a Sheet,
+    remotes: &'a HashMap<String, Sheet>,
+    row: usize,
+    column: usize,
+    numeric: bool,
+}
+
+impl Expression<'_> {
+    fn parse(&mut self) -> Result<Value, String> {
+        let value = self.sum()?;
+        self.skip_whitespaces();
+        match self.chars.get(self.pos) {
+            None => Ok(value),
+            Some(c) => Err(format!("unexpected `{c}`")),
+        }
+    }
+
+    fn skip_whitespaces(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// Skip whitespaces and consume `c` if it's the next char
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_whitespaces();
+        let found = self.chars.get(self.pos) == Some(&c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn sum(&mut self) -> Result<Value, String> {
+        let mut value = self.product()?;
+        loop {
+            let op = if self.eat('+') {
+                '+'
+            } else if self.eat('-') {
+                '-'
+            } else {
+                return Ok(value);
+            };
+            let rhs = self.product()?;
+            value = binary(value, rhs, op)?;
+        }
+    }
+
+    fn product(&mut self) -> Result<Value, String> {
+        let mut value = self.unary()?;
+        loop {
+            let op = if self.eat('*') {
+                '*'
+            } else if self.eat('/') {
+                '/'
+            } else {
+                return Ok(value);
+            };
+            let rhs = self.unary()?;
+            value = binary(value, rhs, op)?;
+        }
+    }
+
+    fn unary(&mut self) -> Result<Value, String> {
+        if self.eat('-') {
+            let value = self.unary()?;
+            return binary(Value::Number(0.0), value, '-');
+        }
+        if self.eat('+') {
+            return self.unary();
+        }
+        let value = self.primary()?;
+        if self.eat('^') {
+            let exponent = self.unary()?;
+            return binary(value, exponent, '^');
+        }
+        Ok(value)
+    }
+
+    fn primary(&mut self) -> Result<Value, String> {
+        self.skip_whitespaces();
+        match self.chars.get(self.pos).copied() {
+            Some('(') => {
+                self.pos += 1;
+                let value = self.sum()?;
+                match self.eat(')') {
+                    true => Ok(value),
+                    false => Err("`)` is missing".to_string()),
+                }
+            }
+            Some('@' | '$') => self.reference(self.sheet),
+            Some(c) if c.is_ascii_digit() || c == '.' => {
+                let start = self.pos;
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_digit() || *c == '.' || *c == 'e' || *c == 'E')
+                {
+                    self.pos += 1;
+                }
+                let s = self.chars[start..self.pos].iter().collect::<String>();
+                s.parse()
+                    .map(Value::Number)
+                    .map_err(|_| format!("invalid number `{s}`"))
+            }
+            Some(c) if c.is_ascii_alphabetic() => {
+                let start = self.pos;
+                while self
+                    .chars
+                    .get(self.pos)
+                    .is_some_and(|c| c.is_ascii_alphanumeric())
+                {
+                    self.pos += 1;
+                }
+                let name = self.chars[start..self.pos].iter().collect::<String>();
+                match (name.as_str(), self.eat('(')) {
+                    ("pi", false) => Ok(Value::Number(std::f64::consts::PI)),
+                    ("remote", true) => self.remote(),
+                    (_, true) => self.call(&name),
+                    (_, false) => Err(format!("unknown name `{name}`")),
+                }
+            }
+            Some(c) => Err(format!("unexpected `{c}`")),
+            None => Err("expression is incomplete".to_string()),
+        }
+    }
+
+    /// Field or range in `sheet`
+    fn reference(&mut self, sheet: &Sheet) -> Result<Value, String> {
+        let start = FieldRef::parse(&self.chars, &mut self.pos)?;
+        let is_range =
+            self.chars.get(self.pos) == Some(&'.') && self.chars.get(self.pos + 1) == Some(&'.');
+        if !is_range {
+            let (row, column) = start.resolve(sheet, self.row, self.column, false)?;
+            let field = &sheet.rows[row][column];
+            return Ok(
+                match (field.is_empty(), parse_number(field), self.numeric) {
+                    (true, _, _) => Value::Number(0.0),
+                    (false, Some(x), _) => Value::Number(x),
+                    (false, None, true) => Value::Number(0.0),
+                    (false, None, false) => Value::Text(field.clone()),
+                },
+            );
+        }
+
+        self.pos += 2;
+        let end = FieldRef::parse(&self.chars, &mut self.pos)?;
+        let start = start.resolve(sheet, self.row, self.column, false)?;
+        let end = end.resolve(sheet, self.row, self.column, true)?;
+        sheet.range(start, end, self.numeric).map(Value::Vector)
+    }
+
+    /// `remote(NAME, REF)`, after `remote(`
+    fn remote(&mut self) -> Result<Value, String> {
+        let start = self.pos;
+        while self.chars.get(self.pos).is_some_and(|c| *c != ',') {
+            self.pos += 1;
+        }
+        let name = self.chars[start..self.pos]
+            .iter()
+            .collect::<String>()
+            .trim()
+            .to_string();
+        if !self.eat(',') {
+            return Err("`,` is missing in remote()".to_string());
+        }
+        let remotes = self.remotes;
+        let sheet = remotes
+            .get(&name)
+            .ok_or_else(|| format!("no table named {name}"))?;
+        self.skip_whitespaces();
+        let value = self.reference(sheet)?;
+        match self.eat(')') {
+            true => Ok(value),
+            false => Err("`)` is missing".to_string()),
+        }
+    }
+
+    /// Function call, after `NAME(`
+    fn call(&mut self, name: &str) -> Result<Value, String> {
+        let mut args = vec![];
+        if !self.eat(')') {
+            loop {
+                args.push(self.sum()?);
+                if self.eat(')') {
+                    break;
+                }
+                if !self.eat(',') {
+                    return Err(format!("`)` is missing in {name}()"));
+                }
+            }
+        }
+
+        if name.starts_with('v') {
+            let mut v = vec![];
+            for arg in args {
+                v.extend(arg.numbers()?);
+            }
+            if v.is_empty() && name != "vcount" && name != "vsum" {
+                return Err(format!("{name}() of empty range"));
+            }
+            let x = match name {
+                "vsum" => v.iter().sum::<f64>(),
+                "vprod" => v.iter().product::<f64>(),
+                "vcount" => v.len() as f64,
+                "vmean" => v.iter().sum::<f64>() / v.len() as f64,
+                "vmax" => v.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+                "vmin" => v.iter().copied().fold(f64::INFINITY, f64::min),
+                "vmedian" => {
+                    v.sort_by(|a, b| a.total_cmp(b));
+                    let n = v.len();
+                    match n % 2 {
+                        0 => (v[n / 2 - 1] + v[n / 2]) / 2.0,
+                        _ => v[n / 2],
+                    }
+                }
+                _ => return Err(format!("unknown function {name}()")),
+            };
+            return Ok(Value::Number(x));
+        }
+
+        let numbers = args
+            .iter()
+            .map(|e| e.number())
+            .collect::<Result<Vec<_>, _>>()?;
+        let x = match (name, numbers.as_slice()) {
+            ("abs", [x]) => x.abs(),
+            ("sqrt", [x]) => x.sqrt(),
+            ("exp", [x]) => x.exp(),
+            ("ln", [x]) => x.ln(),
+            ("log10", [x]) => x.log10(),
+            ("floor", [x]) => x.floor(),
+            ("ceil", [x]) => x.ceil(),
+            ("round", [x]) => x.round(),
+            ("round", [x, n]) => {
+                let scale = 10f64.powi(*n as i32);
+                (x * scale).round() / scale
+            }
+            ("abs" | "sqrt" | "exp" | "ln" | "log10" | "floor" | "ceil" | "round", _) => {
+                return Err(format!("wrong number of arguments of {name}()"));
+            }
+            _ => return Err(format!("unknown function {name}()")),
+        };
+        Ok(Value::Number(x))
+    }
+}
+
+/// Arithmetic of numbers, a range with a number is computed element-wise
+fn binary(lhs: Value, rhs: Value, op: char) -> Result<Value, String> {
+    let f = |a: f64, b: f64| match op {
+        '+' => a + b,
+        '-' => a - b,
+        '*' => a * b,
+        '/' => a / b,
+        _ => a.powf(b),
+    };
+    match (lhs, rhs) {
+        (Value::Vector(a), Value::Vector(b)) if a.len() == b.len() => Ok(Value::Vector(
+            a.iter().zip(b.iter()).map(|(a, b)| f(*a, *b)).collect(),
+        )),
+        (Value::Vector(_), Value::Vector(_)) => Err("ranges of different sizes".to_string()),
+        (Value::Vector(a), b) => {
+            let b = b.number()?;
+            Ok(Value::Vector(a.iter().map(|a| f(*a, b)).collect()))
+        }
+        (a, Value::Vector(b)) => {
+            let a = a.number()?;
+            Ok(Value::Vector(b.iter().map(|b| f(a, *b)).collect()))
+        }
+        (a, b) => Ok(Value::Number(f(a.number()?, b.number()?))),
+    }
+}
+
+/// Recalculate tables with `#+TBLFM:` of documents in `section`, failed formulas are reported as
+/// diagnostics of section
+pub fn update_section(section: &mut Section) {
+    for document in section.documents.iter_mut() {
+        let f_org = &document.file_info.full_path;
+        for e in update_org_file(&mut document.ast) {
+            section.diagnostics.push(Diagnostic::warning(
+                f_org,
+                format!("failed to evaluate table formula {e}"),
+            ));
+        }
+    }
+    for subsection in section.subsections.iter_mut() {
+        update_section(subsection);
+    }
+}
+
+/// Recalculate tables with `#+TBLFM:` of `file` in document order, errors of formulas are returned
+pub fn update_org_file(file: &mut OrgFile) -> Vec<String> {
+    // tables referred by remote() may appear later
+    let mut remotes = HashMap::new();
+    visit_tables_mut(file, &mut |table| {
+        if let Some(name) = &table.name {
+            remotes
+                .entry(name.trim().to_string())
+                .or_insert_with(|| sheet_of_table(table));
+        }
+    });
+
+    let mut errors = vec![];
+    visit_tables_mut(file, &mut |table| {
+        if table.formulas.is_empty() {
+            return;
+        }
+        let mut sheet = sheet_of_table(table);
+        let formulas = table
+            .formulas
+            .iter()
+            .map(|e| e.data.clone())
+            .collect::<Vec<_>>();
+        errors.extend(sheet.recalc(&formulas, &remotes));
+        write_table(table, &sheet);
+        if let Some(name) = &table.name {
+            remotes.insert(name.trim().to_string(), sheet);
+        }
+    });
+    errors
+}
+
+fn visit_tables_mut(file: &mut OrgFile, visit: &mut impl FnMut(&mut Table)) {
+    fn visit_heading(heading: &mut HeadingSubtree, visit: &mut impl FnMut(&mut Table)) {
+        if let Some(section) = &mut heading.section {
+            visit_elements(&mut section.elements, visit);
+        }
+        for subtree in heading.sub_heading_subtrees.iter_mut() {
+            visit_heading(subtree, visit);
+        }
+    }
+
+    fn visit_elements(elements: &mut [Element], visit: &mut impl FnMut(&mut Table)) {
+        visit_elements_mut(elements, &mut |element| {
+            if let Element::Table(table) = element {
+                visit(table);
+            }
+        });
+    }
+
+    if let Some(section) = &mut file.zeroth_section {
+        visit_elements(&mut section.elements, visit);
+    }
+    for heading in file.heading_subtrees.iter_mut() {
+        visit_heading(heading, visit);
+    }
+}
+
+fn cell_text(cell: &Object) -> String {
+    match cell {
+        Object::TableCell(cell) => plain_text(&cell.contents).trim().to_string(),
+        _ => plain_text(std::slice::from_ref(cell)).trim().to_string(),
+    }
+}
+
+fn sheet_of_table(table: &Table) -> Sheet {
+    let rows = table
+        .header
+        .iter()
+        .chain(table.rows.iter())
+        .map(|row| row.cells.iter().map(cell_text).collect())
+        .collect();
+    Sheet::new(rows, table.rules.clone())
+}
+
+/// Write changed fields of `sheet` back into cells of `table`
+fn write_table(table: &mut Table, sheet: &Sheet) {
+    let rows: Vec<&mut TableRow> = table
+        .header
+        .iter_mut()
+        .chain(table.rows.iter_mut())
+        .collect();
+    for (row, fields) in rows.into_iter().zip(sheet.rows.iter()) {
+        // fields beyond existing cells are added only if not empty
+        let n_cells = fields
+            .iter()
+            .rposition(|e| !e.is_empty())
+            .map_or(0, |i| i + 1)
+            .max(row.cells.len());
+        for (i, field) in fields.iter().enumerate().take(n_cells) {
+            match row.cells.get_mut(i) {
+                Some(cell) if cell_text(cell) == *field => {}
+                Some(Object::TableCell(cell)) => cell.contents = vec![Object::Text(field.clone())],
+                Some(cell) => *cell = Object::Text(field.clone()),
+                None => row.cells.push(Object::TableCell(TableCell {
+                    contents: vec![Object::Text(field.clone())],
+                    cell_type: match row.row_type {
+                        TableRowType::Header => TableCellType::Header,
+                        _ => TableCellType::Data,
+                    },
+                })),
+            }
+        }
+    }
+}
+
+/// Recalculate tables in org files and write updated fields back, the same as `C-u C-c *` in
+/// emacs
+pub struct TableCalculator {
+    parser: OrgParser,
+}
+
+impl Default for TableCalculator {
+    fn default() -> Self {
+        Self::new(OrgParserConfig::default())
+    }
+}
+
+impl TableCalculator {
+    pub fn new(config: OrgParserConfig) -> Self {
+        Self {
+            parser: OrgParser::new(config),
+        }
+    }
+
+    /// Recalculate org files under `path`, which is a file or directory, and write back changed
+    /// files.
+    pub fn recalc_path<P: AsRef<Path>>(&self, path: P) -> std::io::Result<Vec<Diagnostic>> {
+        let mut diagnostics = vec![];
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = entry?;
+            let f_org = entry.path();
+            if !entry.file_type().is_file() || f_org.extension().is_none_or(|e| e != "org") {
+                continue;
+            }
+
+            let input = fs::read_to_string(f_org)?;
+            let (output, file_diagnostics) = self.recalc(f_org, &input);
+            diagnostics.extend(file_diagnostics);
+            if output != input {
+                fs::write(f_org, output)?;
+                tracing::info!("recalculated {}", f_org.display());
+            }
+        }
+        Ok(diagnostics)
+    }
+
+    /// Recalculate tables of `input` read from `f_org`, return `input` with updated tables
+    /// realigned, the rest is kept as is.
+    pub fn recalc<P: AsRef<Path>>(&self, f_org: P, input: &str) -> (String, Vec<Diagnostic>) {
+        let f_org = f_org.as_ref();
+        let mut diagnostics = vec![];
+        if !input.to_ascii_lowercase().contains("#+tblfm:") {
+            return (input.to_string(), diagnostics);
+        }
+        let syntax_tree = self.parser.parse_source(f_org, input, &mut diagnostics);
+        let root = match syntax_tree {
+            Some(root) if diagnostics.is_empty() && root.text() == input => root,
+            _ => {
+                diagnostics.push(Diagnostic::warning(
+                    f_org,
+                    "tables are not recalculated since file can't be parsed completely",
+                ));
+                return (input.to_string(), diagnostics);
+            }
+        };
+
+        let tables = root
+            .descendants()
+            .filter(|e| e.kind() == OrgSyntaxKind::Table)
+            .collect::<Vec<_>>();
+        let mut remotes = HashMap::new();
+        for table in tables.iter() {
+            if let Some(name) = table_name(table) {
+                remotes
+                    .entry(name)
+                    .or_insert_with(|| sheet_of_rows(&table_rows(table)));
+            }
+        }
+
+        let mut edits = vec![];
+        for table in tables.iter() {
+            let formulas = table
+                .children()
+                .filter(|e| e.kind() == OrgSyntaxKind::TableFormula)
+                .collect::<Vec<_>>();
+            let Some(first_formula) = formulas.first() else {
+                continue;
+            };
+            let mut rows = table_rows(table);
+            let mut sheet = sheet_of_rows(&rows);
+            let old_sheet = sheet.clone();
+            let values = formulas
+                .iter()
+                .filter_map(|e| e.first_child_by_kind(&|c| c == OrgSyntaxKind::TableFormulaValue))
+                .map(|e| e.text().to_string().trim().to_string())
+                .collect::<Vec<_>>();
+            let range = first_formula.text_range();
+            for e in sheet.recalc(&values, &remotes) {
+                diagnostics.push(
+                    Diagnostic::warning(f_org, format!("failed to evaluate table formula {e}"))
+                        .with_span(input, usize::from(range.start())..usize::from(range.end())),
+                );
+            }
+            if let Some(name) = table_name(table) {
+                remotes.insert(name, sheet.clone());
+            }
+            if sheet == old_sheet {
+                continue;
+            }
+
+            let mut fields = sheet.rows.into_iter();
+            for row in rows.iter_mut() {
+                if let formatter::TableRow::Standard { cells, .. } = row
+                    && let Some(fields) = fields.next()
+                {
+                    *cells = fields;
+                }
+            }
+            let lines = align_table(&rows).unwrap_or_default();
+            let row_nodes = table.children().filter(|e| {
+                matches!(
+                    e.kind(),
+                    OrgSyntaxKind::TableStandardRow | OrgSyntaxKind::TableRuleRow
+                )
+            });
+            for (node, line) in row_nodes.zip(lines) {
+                let start = usize::from(node.text_range().start());
+                let text = node.text().to_string();
+                let end = start + text.strip_suffix('\n').unwrap_or(&text).len();
+                edits.push((start..end, line));
+            }
+        }
+
+        let mut output = input.to_string();
+        for (range, text) in edits.into_iter().rev() {
+            output.replace_range(range, &text);
+        }
+        (output, diagnostics)
+    }
+}
+
+fn table_name(table: &SyntaxNode) -> Option<String> {
+    table
+        .children()
+        .filter(|e| e.kind() == OrgSyntaxKind::AffiliatedKeyword)
+        .find(|e| {
+            e.first_child_by_kind(&|c| c == OrgSyntaxKind::KeywordKey)
+                .is_some_and(|k| k.text().to_string().eq_ignore_ascii_case("NAME"))
+        })
+        .and_then(|e| e.first_child_by_kind(&|c| c == OrgSyntaxKind::KeywordValue))
+        .map(|e| e.text().to_string().trim().to_string())
+}
+
+fn sheet_of_rows(rows: &[formatter::TableRow]) -> Sheet {
+    let mut fields = vec![];
+    let mut hlines = vec![];
+    for row in rows {
+        match row {
+            formatter::TableRow::Standard { cells, .. } => fields.push(cells.clone()),
+            formatter::TableRow::Rule { .. } => hlines.push(fields.len()),
+        }
+    }
+    Sheet::new(fields, hlines)
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    fn sheet(rows: &[&[&str]], hlines: &[usize]) -> Sheet {
+        Sheet::new(
+            rows.iter()
+                .map(|row| row.iter().map(|e| e.to_string()).collect())
+                .collect(),
+            hlines.to_vec(),
+        )
+    }
+
+    #[test]
+    fn test_recalc() {
+        let mut s = sheet(
+            &[
+                &["item", "price", "n", "total"],
+                &["a", "1.5", "2", ""],
+                &["b", "2", "3", ""],
+                &["sum", "", "", ""],
+            ],
+            &[1, 3],
+        );
+        let errors = s.recalc(
+            &[
+                "$4=$2*$3::@>$4=vsum(@I..@II);%.2f".to_string(),
+                "@>$3=vmean(@2..@-1)::@>$2=vmax(@I$2..@II$2)".to_string(),
+            ],
+            &HashMap::new(),
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(
+            s.rows,
+            vec![
+                vec!["item", "price", "n", "total"],
+                vec!["a", "1.5", "2", "3"],
+                vec!["b", "2", "3", "6"],
+                vec!["sum", "2", "2.5", "9.00"],
+            ]
+        );
+
+        let mut s = sheet(&[&["1", "x"], &["2", ""]], &[]);
+        let errors = s.recalc(&["$2=$1/3::$>=$-1+1;%05.1f".to_string()], &HashMap::new());
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(s.rows, vec![vec!["1", "002.0"], vec!["2", "003.0"]]);
+
+        let errors = s.recalc(&["$2=$1+foo::@3$1=1".to_string()], &HashMap::new());
+        assert_eq!(
+            errors,
+            vec![
+                "$2=$1+foo: unknown name `foo`",
+                "@3$1=1: row 3 is out of range"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_remote_and_text() {
+        let remotes = HashMap::from([(
+            "prices".to_string(),
+            sheet(&[&["a", "10"], &["b", "20"]], &[]),
+        )]);
+        let mut s = sheet(&[&["", "", ""]], &[]);
+        let errors = s.recalc(
+            &[
+                "$1=remote(prices, @2$1)::$2=vsum(remote(prices,@1$2..@>$2))::$3=round(pi, 2)"
+                    .to_string(),
+            ],
+            &remotes,
+        );
+        assert!(errors.is_empty(), "{errors:?}");
+        assert_eq!(s.rows, vec![vec!["b", "30", "3.14"]]);
+    }
+
+    #[test]
+    fn test_recalc_source() {
+        let input = r#"* table
+#+NAME: t
+| a | b |   c |
+|---+---+-----|
+| 1 | 2 |     |
+| 3 | 4 | old |
+#+TBLFM: $3=$1+$2
+
+text
+"#;
+        let (output, diagnostics) = TableCalculator::default().recalc("test.org", input);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        assert_eq!(
+            output,
+            r#"* table
+#+NAME: t
+| a | b | c |
+|---+---+---|
+| 1 | 2 | 3 |
+| 3 | 4 | 7 |
+#+TBLFM: $3=$1+$2
+
+text
+"#
+        );
+        assert_eq!(
+            TableCalculator::default().recalc("test.org", &output).0,
+            output
+        );
+    }
+}
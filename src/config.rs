@@ -1,4 +1,4 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use serde::Deserialize;
 
@@ -6,14 +6,49 @@ use crate::compiler::CompilerConfig;
 use crate::export::ssg::SsgConfig;
 
 #[allow(dead_code)]
-#[derive(Debug, Deserialize)]
-pub(crate) struct WindancerConfig {
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct WindancerConfig {
     pub general: General,
     pub compiler: CompilerConfig,
     pub ssg: SsgConfig,
 }
 
 impl WindancerConfig {
+    /// load config from toml file `f_config`, default config is used if it doesn't exist
+    pub fn from_file<P: AsRef<Path>>(f_config: P) -> Result<Self, ::config::ConfigError> {
+        let builder = ::config::Config::builder()
+            .add_source(::config::File::from(f_config.as_ref()).required(false));
+        let config = builder.build()?;
+
+        // project root is the directory of config file, not the current directory
+        let d_root = std::path::absolute(f_config.as_ref())
+            .ok()
+            .and_then(|e| e.parent().map(Path::to_path_buf));
+        config.try_deserialize().map(|mut e: WindancerConfig| {
+            e.update(true);
+            if let Some(d_root) = &d_root {
+                e.compiler.resolve_paths(d_root);
+            }
+            e
+        })
+    }
+
+    pub fn tracing_max_level(&self) -> tracing::Level {
+        match self.general.tracing_max_level.as_str() {
+            "error" => tracing::Level::ERROR,
+            "warn" => tracing::Level::WARN,
+            "info" => tracing::Level::INFO,
+            "debug" => tracing::Level::DEBUG,
+            _ => tracing::Level::TRACE,
+        }
+    }
+
+    pub fn update_output_directory<P: Into<PathBuf>>(&mut self, output_directory: P) {
+        self.ssg.output_directory = output_directory.into();
+        self.update(true);
+    }
+
     pub(crate) fn update(&mut self, force: bool) {
         if !force {
             if self.ssg.renderer.input_directory.as_os_str().is_empty() {
@@ -42,7 +77,33 @@ impl WindancerConfig {
 
 #[allow(dead_code)]
 #[derive(Debug, Deserialize)]
-pub(crate) struct General {
+#[serde(default)]
+pub struct General {
     pub input_directory: PathBuf,
     pub tracing_max_level: String,
 }
+
+impl Default for General {
+    fn default() -> Self {
+        Self {
+            input_directory: "content".into(),
+            tracing_max_level: "info".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_missing_config_file() {
+        let config = WindancerConfig::from_file("no/such/config.toml").expect("default config");
+        assert_eq!(config.general.input_directory, PathBuf::from("content"));
+        assert_eq!(
+            config.ssg.site.output_directory,
+            config.ssg.output_directory
+        );
+    }
+}
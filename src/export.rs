@@ -1 +1,4 @@
+pub mod ical;
+pub mod org;
 pub mod ssg;
+pub mod tangle;
@@ -0,0 +1,516 @@
+//! Export planning and active timestamps of headings to iCalendar (RFC 5545)
+//!
+//! For each heading, with UID suffixed by `ID` property, or path of org file and anchor of heading,
+//! which is unique in file and kept when other headings or sections are edited:
+//! - TODO keyword: `VTODO` with `DTSTART` from `SCHEDULED` and `DUE` from `DEADLINE`, `UID:TODO-*`
+//! - `SCHEDULED`: `VEVENT` with `UID:SC-*`
+//! - `DEADLINE`: `VEVENT` with summary prefixed by `DL: `, `UID:DL-*`
+//! - active timestamps in title and section: `VEVENT` with `UID:TS<N>-*`
+//!
+//! Org repeaters are exported as `RRULE`, inherited tags as `CATEGORIES`.
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use chrono::{TimeDelta, Utc};
+
+use crate::compiler::ast_builder::element::{Element, HeadingSubtree, OrgFile};
+use crate::compiler::ast_builder::object::Object;
+use crate::compiler::ast_builder::timestamp::{TimeUnit, Timestamp};
+use crate::compiler::content::Section;
+use crate::compiler::dynamic_block::{child_elements, plain_text};
+use crate::compiler::parser::config::OrgTodoKeywords;
+
+/// A `VEVENT` or `VTODO`
+#[derive(Debug, Clone)]
+pub struct CalendarComponent {
+    pub uid: String,
+    /// filetags and tags of heading and its ancestors
+    pub tags: Vec<String>,
+    /// content lines, from `BEGIN:` to `END:`, unfolded
+    pub lines: Vec<String>,
+}
+
+pub struct ICalendarExporter {
+    /// TODO states exported as `STATUS:NEEDS-ACTION`, others as `STATUS:COMPLETED`
+    open_states: HashSet<String>,
+    /// `DTSTAMP` of all components
+    dtstamp: String,
+}
+
+impl Default for ICalendarExporter {
+    fn default() -> Self {
+        Self {
+            open_states: OrgTodoKeywords::default().requiring_action,
+            dtstamp: Utc::now().format("%Y%m%dT%H%M%SZ").to_string(),
+        }
+    }
+}
+
+impl ICalendarExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_open_states(mut self, open_states: HashSet<String>) -> Self {
+        self.open_states = open_states;
+        self
+    }
+
+    /// Components of all documents in `section`, in document order
+    pub fn components_of_section(&self, section: &Section) -> Vec<CalendarComponent> {
+        let mut components = vec![];
+        for document in section.documents.iter() {
+            let path = document
+                .file_info
+                .relative_path
+                .as_ref()
+                .unwrap_or(&document.file_info.file_name);
+            components.extend(self.components_of_org_file(
+                &document.ast,
+                path,
+                &document.metadata.filetags,
+            ));
+        }
+        for subsection in section.subsections.iter() {
+            components.extend(self.components_of_section(subsection));
+        }
+        components
+    }
+
+    /// Components of `org_file`, whose `path` relative to content directory identifies headings
+    /// without `ID`
+    pub fn components_of_org_file(
+        &self,
+        org_file: &OrgFile,
+        path: &str,
+        filetags: &[String],
+    ) -> Vec<CalendarComponent> {
+        let mut components = vec![];
+        for heading in org_file.heading_subtrees.iter() {
+            self.collect_heading(heading, path, filetags, &mut components);
+        }
+        components
+    }
+
+    fn collect_heading(
+        &self,
+        heading: &HeadingSubtree,
+        path: &str,
+        tags: &[String],
+        components: &mut Vec<CalendarComponent>,
+    ) {
+        if heading.is_commented {
+            return;
+        }
+        let mut tags = tags.to_vec();
+        for tag in heading.tags.iter() {
+            if !tags.contains(tag) {
+                tags.push(tag.clone());
+            }
+        }
+
+        let id = match heading.properties.get("ID") {
+            Some(id) => id.clone(),
+            None => format!("{path}#{}", heading.anchor),
+        };
+        let summary = plain_text(&heading.title).trim().to_string();
+        let planning = |keyword| heading.planning.as_ref().and_then(|e| e.get(keyword));
+        let (scheduled, deadline) = (planning("SCHEDULED"), planning("DEADLINE"));
+        let mut push = |uid: String, mut lines: Vec<String>| {
+            if !tags.is_empty() {
+                lines.insert(
+                    lines.len() - 1,
+                    format!(
+                        "CATEGORIES:{}",
+                        tags.iter().map(|e| escape(e)).collect::<Vec<_>>().join(",")
+                    ),
+                );
+            }
+            components.push(CalendarComponent {
+                uid,
+                tags: tags.clone(),
+                lines,
+            });
+        };
+
+        if let Some(keyword) = &heading.keyword {
+            let uid = format!("TODO-{id}");
+            let mut lines = self.begin("VTODO", &uid, &summary);
+            if let Some(ts) = scheduled {
+                lines.push(datetime_line("DTSTART", ts, false));
+            }
+            if let Some(ts) = deadline {
+                lines.push(datetime_line("DUE", ts, false));
+            }
+            if let Some(priority) = &heading.priority {
+                lines.push(format!("PRIORITY:{}", ical_priority(priority)));
+            }
+            let status = if self.open_states.contains(keyword) {
+                "NEEDS-ACTION"
+            } else {
+                "COMPLETED"
+            };
+            lines.push(format!("STATUS:{status}"));
+            lines.push("END:VTODO".to_string());
+            push(uid, lines);
+        }
+
+        for (prefix, summary_prefix, ts) in [("SC", "", scheduled), ("DL", "DL: ", deadline)] {
+            if let Some(ts) = ts {
+                let uid = format!("{prefix}-{id}");
+                push(
+                    uid.clone(),
+                    self.event(&uid, &format!("{summary_prefix}{summary}"), ts),
+                );
+            }
+        }
+
+        let mut timestamps = vec![];
+        collect_timestamps(&heading.title, &mut timestamps);
+        if let Some(section) = &heading.section {
+            collect_timestamps_of_elements(&section.elements, &mut timestamps);
+        }
+        for (i, ts) in timestamps.into_iter().filter(|e| e.active).enumerate() {
+            let uid = format!("TS{}-{id}", i + 1);
+            push(uid.clone(), self.event(&uid, &summary, &ts));
+        }
+
+        for subtree in heading.sub_heading_subtrees.iter() {
+            self.collect_heading(subtree, path, &tags, components);
+        }
+    }
+
+    fn begin(&self, component: &str, uid: &str, summary: &str) -> Vec<String> {
+        vec![
+            format!("BEGIN:{component}"),
+            format!("UID:{}", escape(uid)),
+            format!("DTSTAMP:{}", self.dtstamp),
+            format!("SUMMARY:{}", escape(summary)),
+        ]
+    }
+
+    fn event(&self, uid: &str, summary: &str, ts: &Timestamp) -> Vec<String> {
+        let mut lines = self.begin("VEVENT", uid, summary);
+        lines.push(datetime_line("DTSTART", ts, false));
+        if ts.end.is_some() || !ts.has_time {
+            lines.push(datetime_line("DTEND", ts, true));
+        }
+        if let Some(repeater) = ts.repeater.filter(|e| e.value > 0) {
+            let freq = match repeater.unit {
+                TimeUnit::Hour => "HOURLY",
+                TimeUnit::Day => "DAILY",
+                TimeUnit::Week => "WEEKLY",
+                TimeUnit::Month => "MONTHLY",
+                TimeUnit::Year => "YEARLY",
+            };
+            lines.push(format!("RRULE:FREQ={freq};INTERVAL={}", repeater.value));
+        }
+        lines.push("END:VEVENT".to_string());
+        lines
+    }
+
+    /// Write `calendar.ics` of all components and `tags/TAG.ics` of each tag to `output_directory`
+    ///
+    /// Stale feeds of tags are removed, and nothing is written if there is no component.
+    pub fn export<P: AsRef<Path>>(
+        &self,
+        section: &Section,
+        output_directory: P,
+    ) -> std::io::Result<()> {
+        let output_directory = output_directory.as_ref();
+        let components = self.components_of_section(section);
+
+        let mut by_tag: BTreeMap<&str, Vec<&CalendarComponent>> = BTreeMap::new();
+        for component in components.iter() {
+            for tag in component.tags.iter() {
+                by_tag.entry(tag).or_default().push(component);
+            }
+        }
+
+        let d_tags = output_directory.join("tags");
+        if d_tags.is_dir() {
+            for entry in fs::read_dir(&d_tags)?.flatten() {
+                let path = entry.path();
+                if path.extension().is_some_and(|e| e == "ics")
+                    && path
+                        .file_stem()
+                        .and_then(|e| e.to_str())
+                        .is_none_or(|tag| !by_tag.contains_key(tag))
+                {
+                    fs::remove_file(path)?;
+                }
+            }
+        }
+
+        let f_calendar = output_directory.join("calendar.ics");
+        if components.is_empty() {
+            if f_calendar.exists() {
+                fs::remove_file(f_calendar)?;
+            }
+            return Ok(());
+        }
+        fs::write(f_calendar, write_calendar("calendar", components.iter()))?;
+        if !by_tag.is_empty() {
+            fs::create_dir_all(&d_tags)?;
+        }
+        for (tag, components) in by_tag {
+            fs::write(
+                d_tags.join(format!("{tag}.ics")),
+                write_calendar(tag, components.into_iter()),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// `VCALENDAR` of `components`, with folded lines ending with CRLF
+pub fn write_calendar<'a>(
+    name: &str,
+    components: impl Iterator<Item = &'a CalendarComponent>,
+) -> String {
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!(
+            "PRODID:-//windancer//kite {}//EN",
+            env!("CARGO_PKG_VERSION")
+        ),
+        "CALSCALE:GREGORIAN".to_string(),
+        format!("X-WR-CALNAME:{}", escape(name)),
+    ];
+    for component in components {
+        lines.extend(component.lines.iter().cloned());
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines.iter().map(|e| fold(e)).collect()
+}
+
+fn collect_timestamps(objects: &[Object], timestamps: &mut Vec<Timestamp>) {
+    for object in objects {
+        match object {
+            Object::Timestamp(ts) => timestamps.push(ts.clone()),
+            Object::Bold(e)
+            | Object::Italic(e)
+            | Object::Underline(e)
+            | Object::Strikethrough(e) => collect_timestamps(e, timestamps),
+            _ => {}
+        }
+    }
+}
+
+fn collect_timestamps_of_elements(elements: &[Element], timestamps: &mut Vec<Timestamp>) {
+    for element in elements {
+        match element {
+            Element::Paragraph(paragraph) => collect_timestamps(&paragraph.objects, timestamps),
+            Element::List(list) => {
+                for item in list.items.iter() {
+                    collect_timestamps_of_elements(&item.contents, timestamps);
+                }
+            }
+            _ => {
+                if let Some(children) = child_elements(element) {
+                    collect_timestamps_of_elements(children, timestamps);
+                }
+            }
+        }
+    }
+}
+
+/// `DTSTART:20240101T100000` or `DTSTART;VALUE=DATE:20240101`, in floating local time
+///
+/// The end of date is exclusive, i.e., the next day of the last day.
+fn datetime_line(name: &str, ts: &Timestamp, is_end: bool) -> String {
+    let datetime = match (is_end, ts.end) {
+        (true, Some(end)) => end,
+        _ => ts.start,
+    };
+    if ts.has_time {
+        format!("{name}:{}", datetime.format("%Y%m%dT%H%M%S"))
+    } else {
+        let date = if is_end {
+            datetime.date() + TimeDelta::days(1)
+        } else {
+            datetime.date()
+        };
+        format!("{name};VALUE=DATE:{}", date.format("%Y%m%d"))
+    }
+}
+
+/// `[#A]` -> 1, `[#B]` -> 5, `[#C]` -> 9
+fn ical_priority(priority: &str) -> u8 {
+    match priority {
+        "A" => 1,
+        "B" => 5,
+        "C" => 9,
+        _ => 0,
+    }
+}
+
+/// Escape TEXT value
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace('\n', "\\n")
+}
+
+/// Fold content line into lines of at most 75 octets, each ends with CRLF
+fn fold(line: &str) -> String {
+    let mut s = String::with_capacity(line.len() + 8);
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > 75 {
+            s.push_str("\r\n ");
+            width = 1;
+        }
+        s.push(c);
+        width += c.len_utf8();
+    }
+    s.push_str("\r\n");
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::testing::compile_section;
+
+    fn export(input: &str) -> Vec<CalendarComponent> {
+        let section = compile_section(input);
+        ICalendarExporter::new().components_of_section(&section)
+    }
+
+    fn uids(components: &[CalendarComponent]) -> Vec<&str> {
+        components.iter().map(|e| e.uid.as_str()).collect()
+    }
+
+    #[test]
+    fn test_components() {
+        let components = export(
+            r##"#+filetags: :project:
+
+* TODO [#A] Write report :work:
+SCHEDULED: <2024-01-03 Wed 09:00-10:30> DEADLINE: <2024-01-12 Fri>
+:PROPERTIES:
+:ID: report-id
+:END:
+* Standup
+SCHEDULED: <2024-01-01 Mon 09:30 +1w>
+* Trip, with comma
+Leave on <2024-02-01 Thu>--<2024-02-03 Sat>, inactive [2024-01-01 Mon] ignored.
+"##,
+        );
+        assert_eq!(
+            uids(&components),
+            vec![
+                "TODO-report-id",
+                "SC-report-id",
+                "DL-report-id",
+                "SC-content.org#standup",
+                "TS1-content.org#trip-with-comma",
+            ]
+        );
+
+        let lines = |i: usize| {
+            components[i]
+                .lines
+                .iter()
+                .filter(|e| !e.starts_with("DTSTAMP:") && !e.starts_with("UID:"))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            lines(0),
+            vec![
+                "BEGIN:VTODO",
+                "SUMMARY:Write report",
+                "DTSTART:20240103T090000",
+                "DUE;VALUE=DATE:20240112",
+                "PRIORITY:1",
+                "STATUS:NEEDS-ACTION",
+                "CATEGORIES:project,work",
+                "END:VTODO"
+            ]
+        );
+        assert_eq!(
+            lines(2),
+            vec![
+                "BEGIN:VEVENT",
+                "SUMMARY:DL: Write report",
+                "DTSTART;VALUE=DATE:20240112",
+                "DTEND;VALUE=DATE:20240113",
+                "CATEGORIES:project,work",
+                "END:VEVENT"
+            ]
+        );
+        assert_eq!(
+            lines(3)[1..4],
+            [
+                "SUMMARY:Standup",
+                "DTSTART:20240101T093000",
+                "RRULE:FREQ=WEEKLY;INTERVAL=1"
+            ]
+        );
+        assert_eq!(
+            lines(4)[1..4],
+            [
+                "SUMMARY:Trip\\, with comma",
+                "DTSTART;VALUE=DATE:20240201",
+                "DTEND;VALUE=DATE:20240204"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_uid() {
+        let input = "* Standup\nSCHEDULED: <2024-01-01 Mon 09:30>\n";
+        let d_org = std::env::temp_dir().join(format!("windancer_ical_uid_{}", std::process::id()));
+        std::fs::create_dir_all(d_org.join("sub")).expect("create dir");
+        std::fs::write(d_org.join("a.org"), input).expect("write");
+        std::fs::write(d_org.join("sub/b.org"), input).expect("write");
+        let section = Compiler::default()
+            .compile_section(&d_org)
+            .expect("no Section compiled");
+        std::fs::remove_dir_all(&d_org).expect("remove");
+
+        let mut components = ICalendarExporter::new().components_of_section(&section);
+        components.sort_by(|a, b| a.uid.cmp(&b.uid));
+        assert_eq!(
+            uids(&components),
+            vec!["SC-a.org#standup", "SC-sub/b.org#standup"]
+        );
+
+        // headings with the same title in a file
+        let components = export(
+            "* Standup\nSCHEDULED: <2024-01-01 Mon 09:30>\nNotes <2024-01-02 Tue>.\n* Standup\n<2024-01-03 Wed>\n",
+        );
+        assert_eq!(
+            uids(&components),
+            vec![
+                "SC-content.org#standup",
+                "TS1-content.org#standup",
+                "TS1-content.org#standup-2"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_write_calendar() {
+        let component = CalendarComponent {
+            uid: "x".to_string(),
+            tags: vec![],
+            lines: vec![
+                "BEGIN:VEVENT".to_string(),
+                format!("SUMMARY:{}", "长".repeat(30)),
+                "END:VEVENT".to_string(),
+            ],
+        };
+        let calendar = write_calendar("all", [&component].into_iter());
+        assert!(calendar.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(calendar.ends_with("END:VEVENT\r\nEND:VCALENDAR\r\n"));
+        assert!(calendar.lines().all(|e| e.len() <= 75));
+        assert!(calendar.contains("\r\n 长"));
+    }
+}
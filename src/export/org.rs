@@ -0,0 +1,892 @@
+//! Write AST back to Org
+//!
+//! OrgWriter serializes AST into canonical Org text, including three levels:
+//! - OrgFile: `write_org_file()` writes the `OrgFile` node of AST, which calls
+//!   - `write_section()`
+//!     - `write_element()`
+//!   - `write_heading_subtree()`
+//!     - `write_section()`
+//!     - `write_heading_subtree()` call self **recursively**
+//! - Element: `write_element()` writes `Element` with an indentation, which calls
+//!   - `write_paragraph()`
+//!   - `write_table()` -> `write_table_row()`
+//!   - `write_drawer()`, `write_property_drawer()`
+//!   - `write_greater_block()`, `write_lesser_block()`, `write_src_block()`
+//!   - `write_list()`, `write_item()`
+//!   - `write_footnote_definition()`
+//! - Object: `write_object()` writes `Object`
+//!
+//! The AST is not lossless (e.g, spaces around objects in paragraph are trimmed), so the output is
+//! canonical rather than the original text: parse → write → parse gives the same AST.
+pub mod formatter;
+
+use std::collections::{HashMap, HashSet};
+
+use crate::compiler::ast_builder::element::{
+    AffiliatedKeyword, BabelCall, CachedResults, Drawer, Element, FootnoteDefinition,
+    HeadingSubtree, InlineTask, Item, List, OrgFile, Paragraph, Planning, PropertyDrawer, Section,
+    SrcBlock, Table, TableRow,
+};
+use crate::compiler::ast_builder::object::{CitationReference, Object};
+
+/// chars after which no space is needed before an object in paragraph
+const PRE_CHARS: &str = "-({'\"";
+/// chars before which no space is needed after an object in paragraph
+const POST_CHARS: &str = "-.,;:!?')}[\"\\";
+
+#[derive(Default)]
+pub struct OrgWriter {
+    // inline or anonymous footnote definitions, written back into their first reference
+    inline_footnotes: HashMap<String, FootnoteDefinition>,
+}
+
+impl OrgWriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn write_org_file(&mut self, org_file: &OrgFile) -> String {
+        let mut labels = HashSet::new();
+        if let Some(section) = &org_file.zeroth_section {
+            collect_footnote_labels(section, &mut labels);
+        }
+        for subtree in org_file.heading_subtrees.iter() {
+            collect_footnote_labels_of_subtree(subtree, &mut labels);
+        }
+        self.inline_footnotes = org_file
+            .footnote_definitions
+            .iter()
+            .filter(|e| !labels.contains(&e.label))
+            .map(|e| (e.label.clone(), e.clone()))
+            .collect();
+
+        let mut parts = vec![];
+        if let Some(section) = &org_file.zeroth_section {
+            parts.push(self.write_section(section));
+        }
+        for subtree in org_file.heading_subtrees.iter() {
+            parts.push(self.write_heading_subtree(subtree));
+        }
+        parts.join("\n")
+    }
+
+    pub fn write_heading_subtree(&self, heading: &HeadingSubtree) -> String {
+        let mut s = self.write_heading_row(
+            heading.level,
+            &heading.keyword,
+            &heading.priority,
+            heading.is_commented,
+            &heading.title,
+            &heading.tags,
+        );
+        if let Some(planning) = &heading.planning {
+            s.push_str(&self.write_planning(planning, ""));
+        }
+        if let Some(property_drawer) = &heading.property_drawer {
+            s.push_str(&self.write_property_drawer(property_drawer, ""));
+        }
+        if let Some(section) = &heading.section {
+            s.push('\n');
+            s.push_str(&self.write_section(section));
+        }
+        for subtree in heading.sub_heading_subtrees.iter() {
+            s.push('\n');
+            s.push_str(&self.write_heading_subtree(subtree));
+        }
+        s
+    }
+
+    fn write_heading_row(
+        &self,
+        level: u8,
+        keyword: &Option<String>,
+        priority: &Option<String>,
+        is_commented: bool,
+        title: &[Object],
+        tags: &[String],
+    ) -> String {
+        let mut row = vec!["*".repeat(level as usize)];
+        if let Some(keyword) = keyword {
+            row.push(keyword.clone());
+        }
+        if let Some(priority) = priority {
+            row.push(format!("[#{priority}]"));
+        }
+        if is_commented {
+            row.push(String::from("COMMENT"));
+        }
+        let title = self.write_objects(title, false);
+        if !title.trim().is_empty() {
+            row.push(title.trim().to_string());
+        }
+        if !tags.is_empty() {
+            row.push(format!(":{}:", tags.join(":")));
+        }
+        format!("{}\n", row.join(" "))
+    }
+
+    /// Inline task is written without indentation, otherwise it is not a task any more
+    fn write_inline_task(&self, task: &InlineTask) -> String {
+        let mut s = self.write_heading_row(
+            task.level,
+            &task.keyword,
+            &task.priority,
+            false,
+            &task.title,
+            &task.tags,
+        );
+        if let Some(planning) = &task.planning {
+            s.push_str(&self.write_planning(planning, ""));
+        }
+        if let Some(property_drawer) = &task.property_drawer {
+            s.push_str(&self.write_property_drawer(property_drawer, ""));
+        }
+        if let Some(contents) = &task.contents {
+            s.push_str(&self.write_elements(contents, ""));
+            s.push_str(&format!("{} END\n", "*".repeat(task.level as usize)));
+        }
+        s
+    }
+
+    pub fn write_section(&self, section: &Section) -> String {
+        self.write_elements(&section.elements, "")
+    }
+
+    /// Write elements separated by blank line
+    fn write_elements(&self, elements: &[Element], indent: &str) -> String {
+        let mut s = String::new();
+        for (i, element) in elements.iter().enumerate() {
+            if i > 0 {
+                match (&elements[i - 1], element) {
+                    // keywords are kept together
+                    (
+                        Element::Keyword(_) | Element::ZerothSectionPreamble(_),
+                        Element::Keyword(_),
+                    ) => {}
+                    // one blank line would join two adjacent lists
+                    (Element::List(_), Element::List(_)) => s.push_str("\n\n"),
+                    _ => s.push('\n'),
+                }
+            }
+            s.push_str(&self.write_element(element, indent));
+        }
+        s
+    }
+
+    /// Write `element` with each line prefixed by `indent`, the result ends with newline.
+    ///
+    /// Contents of lesser blocks are written as is, since they keep the original indentation.
+    pub fn write_element(&self, element: &Element, indent: &str) -> String {
+        match element {
+            Element::Paragraph(paragraph) => self.write_paragraph(paragraph, indent),
+            Element::Table(table) => self.write_table(table, indent),
+            Element::TableRow(row) => self.write_table_row(row, indent),
+            Element::Drawer(drawer) => self.write_drawer(drawer, indent),
+            Element::PropertyDrawer(drawer) => self.write_property_drawer(drawer, indent),
+            Element::NodeProperty(property) => match &property.value {
+                Some(value) => format!("{indent}:{}: {value}\n", property.name),
+                None => format!("{indent}:{}:\n", property.name),
+            },
+            Element::CenterBlock(block) => {
+                self.write_greater_block("center", &block.parameters, &block.contents, indent)
+            }
+            Element::QuoteBlock(block) => {
+                self.write_greater_block("quote", &block.parameters, &block.contents, indent)
+            }
+            Element::SpecialBlock(block) => {
+                self.write_greater_block(&block.name, &block.parameters, &block.contents, indent)
+            }
+            Element::DynamicBlock(block) => {
+                let parameters = block
+                    .parameters
+                    .as_ref()
+                    .map_or(String::new(), |e| format!(" {e}"));
+                format!(
+                    "{indent}#+begin: {}{parameters}\n{}{indent}#+end:\n",
+                    block.name,
+                    self.write_elements(&block.contents, indent)
+                )
+            }
+            Element::ExampleBlock(block) => {
+                self.write_lesser_block("example", &block.data, &block.contents, indent)
+            }
+            Element::CommentBlock(block) => {
+                self.write_lesser_block("comment", &block.data, &block.contents, indent)
+            }
+            Element::VerseBlock(block) => {
+                self.write_lesser_block("verse", &block.data, &block.contents, indent)
+            }
+            Element::ExportBlock(block) => {
+                self.write_lesser_block("export", &block.data, &block.contents, indent)
+            }
+            Element::SrcBlock(block) => self.write_src_block(block, indent),
+            Element::BabelCall(call) => self.write_babel_call(call, indent),
+            Element::List(list) => self.write_list(list, indent),
+            Element::Item(item) => self.write_item(item, indent),
+            Element::FootnoteDefinition(definition) => {
+                self.write_footnote_definition(definition, indent)
+            }
+            Element::InlineTask(task) => self.write_inline_task(task),
+            Element::ZerothSectionPreamble(preamble) => {
+                let mut s = String::new();
+                if let Some(comment) = &preamble.comment {
+                    s.push_str(&write_lines(&comment.text, indent, "#"));
+                }
+                if let Some(drawer) = &preamble.property_drawer {
+                    s.push_str(&self.write_property_drawer(drawer, indent));
+                }
+                s
+            }
+            Element::HorizontalRule(_) => format!("{indent}-----\n"),
+            Element::LatexEnvironment(latex) => ends_with_newline(latex.text.trim_end()),
+            Element::Keyword(keyword) => format!(
+                "{indent}#+{}: {}\n",
+                keyword.key,
+                self.write_objects(&keyword.value, false)
+            ),
+            Element::AffiliatedKeyword(keyword) => self.write_affiliated_keyword(keyword, indent),
+            Element::FixedWidth(fixed_width) => write_lines(&fixed_width.text, indent, ":"),
+            Element::Planning(planning) => self.write_planning(planning, indent),
+            Element::Clock(clock) => {
+                let duration = match (clock.end, clock.minutes) {
+                    (Some(_), Some(minutes)) => {
+                        format!(" =>  {}:{:02}", minutes / 60, minutes % 60)
+                    }
+                    _ => String::new(),
+                };
+                format!(
+                    "{indent}CLOCK: {}{duration}\n",
+                    self.write_object(&clock.timestamp, false)
+                )
+            }
+            Element::DiarySexp(diary_sexp) => format!("%%{}\n", diary_sexp.value),
+            Element::Comment(comment) => write_lines(&comment.text, indent, "#"),
+        }
+    }
+
+    fn write_planning(&self, planning: &Planning, indent: &str) -> String {
+        let items = planning
+            .items
+            .iter()
+            .map(|(keyword, timestamp)| {
+                format!("{keyword}: {}", self.write_object(timestamp, false))
+            })
+            .collect::<Vec<_>>();
+        format!("{indent}{}\n", items.join(" "))
+    }
+
+    fn write_affiliated_keyword(&self, keyword: &AffiliatedKeyword, indent: &str) -> String {
+        let optvalue = keyword
+            .optvalue
+            .as_ref()
+            .map_or(String::new(), |e| format!("[{e}]"));
+        let value = self.write_objects(&keyword.value, false);
+        let line = format!("{indent}#+{}{optvalue}: {value}", keyword.key);
+        format!("{}\n", line.trim_end())
+    }
+
+    fn write_paragraph(&self, paragraph: &Paragraph, indent: &str) -> String {
+        let mut s = paragraph
+            .affiliated_keywords
+            .iter()
+            .map(|e| self.write_affiliated_keyword(e, indent))
+            .collect::<String>();
+        let text = self.write_objects(&paragraph.objects, true);
+        for line in text.trim_end().lines() {
+            // leading spaces are trimmed by parser, keep the line from being a heading
+            let is_heading = line.starts_with('*') && line.trim_start_matches('*').starts_with(' ');
+            let indent = if is_heading && indent.is_empty() {
+                " "
+            } else {
+                indent
+            };
+            s.push_str(&format!("{indent}{line}\n"));
+        }
+        s
+    }
+
+    fn write_table(&self, table: &Table, indent: &str) -> String {
+        let mut s = String::new();
+        if let Some(name) = &table.name {
+            s.push_str(&format!("{indent}#+NAME: {name}\n"));
+        }
+        if !table.caption.is_empty() {
+            s.push_str(&format!(
+                "{indent}#+CAPTION: {}\n",
+                self.write_objects(&table.caption, false)
+            ));
+        }
+        for row in table.header.iter() {
+            s.push_str(&self.write_table_row(row, indent));
+        }
+        if !table.header.is_empty() {
+            let n_cells = table
+                .header
+                .iter()
+                .map(|e| e.cells.len())
+                .max()
+                .unwrap_or(1);
+            s.push_str(&format!("{indent}|{}|\n", vec!["---"; n_cells].join("+")));
+        }
+        for row in table.rows.iter() {
+            s.push_str(&self.write_table_row(row, indent));
+        }
+        for formula in table.formulas.iter() {
+            s.push_str(&format!("{indent}#+TBLFM: {}\n", formula.data));
+        }
+        s
+    }
+
+    fn write_table_row(&self, row: &TableRow, indent: &str) -> String {
+        let cells = row
+            .cells
+            .iter()
+            .map(|e| self.write_object(e, false))
+            .collect::<Vec<_>>();
+        format!("{indent}|{}|\n", cells.join("|"))
+    }
+
+    fn write_drawer(&self, drawer: &Drawer, indent: &str) -> String {
+        let mut s = drawer
+            .affiliated_keywords
+            .iter()
+            .map(|e| self.write_affiliated_keyword(e, indent))
+            .collect::<String>();
+        s.push_str(&format!("{indent}:{}:\n", drawer.name));
+        s.push_str(&self.write_elements(&drawer.contents, indent));
+        s.push_str(&format!("{indent}:END:\n"));
+        s
+    }
+
+    fn write_property_drawer(&self, drawer: &PropertyDrawer, indent: &str) -> String {
+        let mut s = format!("{indent}:PROPERTIES:\n");
+        for property in drawer.contents.iter() {
+            s.push_str(&self.write_element(&Element::NodeProperty(property.clone()), indent));
+        }
+        s.push_str(&format!("{indent}:END:\n"));
+        s
+    }
+
+    /// center/quote/special block, whose contents are elements
+    fn write_greater_block(
+        &self,
+        name: &str,
+        parameters: &Option<String>,
+        contents: &[Element],
+        indent: &str,
+    ) -> String {
+        let parameters = parameters
+            .as_ref()
+            .map_or(String::new(), |e| format!(" {e}"));
+        format!(
+            "{indent}#+begin_{name}{parameters}\n{}{indent}#+end_{name}\n",
+            self.write_elements(contents, indent)
+        )
+    }
+
+    /// example/comment/verse/export block, whose contents are objects
+    fn write_lesser_block(
+        &self,
+        name: &str,
+        data: &Option<String>,
+        contents: &[Object],
+        indent: &str,
+    ) -> String {
+        let data = data.as_ref().map_or(String::new(), |e| format!(" {e}"));
+        format!(
+            "{indent}#+begin_{name}{data}\n{}{indent}#+end_{name}\n",
+            ends_with_newline(&self.write_objects(contents, false))
+        )
+    }
+
+    fn write_src_block(&self, block: &SrcBlock, indent: &str) -> String {
+        let name = block
+            .name
+            .as_ref()
+            .map_or(String::new(), |e| format!("{indent}#+NAME: {e}\n"));
+        let mut begin = vec![format!("{name}{indent}#+begin_src")];
+        if !block.language.is_empty() {
+            begin.push(block.language.clone());
+        }
+        begin.extend(block.switches.iter().cloned());
+        if let Some(results) = &block.results {
+            begin.push(format!(":results {results}"));
+        }
+        if let Some(exports) = &block.exports {
+            begin.push(format!(":exports {exports}"));
+        }
+        for (name, value) in block.vars.iter() {
+            begin.push(format!(":var {name}={value}"));
+        }
+        for (key, value) in block.other_args.iter() {
+            match value.is_empty() {
+                true => begin.push(format!(":{key}")),
+                false => begin.push(format!(":{key} {value}")),
+            }
+        }
+
+        format!(
+            "{}\n{}{indent}#+end_src\n{}",
+            begin.join(" "),
+            ends_with_newline(&self.write_objects(&block.contents, false)),
+            self.write_cached_results(&block.cached_results, indent)
+        )
+    }
+
+    fn write_babel_call(&self, call: &BabelCall, indent: &str) -> String {
+        let mut s = call
+            .affiliated_keywords
+            .iter()
+            .map(|e| self.write_affiliated_keyword(e, indent))
+            .collect::<String>();
+        s.push_str(&format!("{indent}#+CALL: {}", call.name));
+        if let Some(inside_header) = &call.inside_header {
+            s.push_str(&format!("[{inside_header}]"));
+        }
+        if let Some(arguments) = &call.arguments {
+            s.push_str(&format!("({arguments})"));
+        }
+        if let Some(end_header) = &call.end_header {
+            s.push_str(&format!(" {end_header}"));
+        }
+        s.push('\n');
+        s.push_str(&self.write_cached_results(&call.cached_results, indent));
+        s
+    }
+
+    /// `#+RESULTS:` line and its element, separated from the code by a blank line
+    fn write_cached_results(&self, cached_results: &Option<CachedResults>, indent: &str) -> String {
+        let Some(results) = cached_results else {
+            return String::new();
+        };
+        let mut s = format!(
+            "\n{}",
+            self.write_affiliated_keyword(&results.keyword, indent)
+        );
+        if let Some(element) = &results.element {
+            s.push_str(&self.write_element(element, indent));
+        }
+        s
+    }
+
+    fn write_list(&self, list: &List, indent: &str) -> String {
+        list.items
+            .iter()
+            .map(|e| self.write_item(e, indent))
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn write_item(&self, item: &Item, indent: &str) -> String {
+        let mut row = vec![item.bullet.clone()];
+        if let Some(counter_set) = &item.counter_set {
+            row.push(format!("[@{counter_set}]"));
+        }
+        if let Some(checkbox) = &item.checkbox {
+            row.push(checkbox.clone());
+        }
+        if !item.tag.is_empty() {
+            row.push(format!(
+                "{} ::",
+                self.write_objects(&item.tag, false).trim()
+            ));
+        }
+        let row = format!("{indent}{}", row.join(" "));
+
+        // contents are aligned after the bullet
+        let content_indent = format!("{indent}{}", " ".repeat(item.bullet.chars().count() + 1));
+        match item.contents.split_first() {
+            Some((Element::Paragraph(first), rest)) if first.affiliated_keywords.is_empty() => {
+                let first = self.write_paragraph(first, &content_indent);
+                let mut s = format!("{row} {}", first.trim_start());
+                if !rest.is_empty() {
+                    s.push('\n');
+                    s.push_str(&self.write_elements(rest, &content_indent));
+                }
+                s
+            }
+            Some(_) => format!(
+                "{row}\n{}",
+                self.write_elements(&item.contents, &content_indent)
+            ),
+            None => format!("{row}\n"),
+        }
+    }
+
+    fn write_footnote_definition(&self, definition: &FootnoteDefinition, indent: &str) -> String {
+        let contents = self.write_elements(&definition.contents, indent);
+        // two blank lines end the definition
+        format!(
+            "{indent}[fn:{}] {}\n\n",
+            definition.label,
+            contents.trim_start()
+        )
+    }
+
+    /// Write objects, spaces trimmed by parser are restored in paragraph if `trimmed`
+    fn write_objects(&self, objects: &[Object], trimmed: bool) -> String {
+        let mut s = String::new();
+        for (i, object) in objects.iter().enumerate() {
+            let text = match object {
+                Object::Text(text) if trimmed => split_joined_text(text),
+                // regular link swallows the newline at the end of paragraph, while angle link not
+                Object::GeneralLink(link)
+                    if trimmed
+                        && link.description.is_empty()
+                        && link.path.contains(':')
+                        && matches!(&objects[i + 1..], [Object::Text(t)] if t.is_empty()) =>
+                {
+                    format!("<{}>", link.path)
+                }
+                _ => self.write_object(object, trimmed),
+            };
+            if trimmed && i > 0 && need_space(&objects[i - 1], object, &s, &text) {
+                s.push(' ');
+            }
+            s.push_str(&text);
+        }
+        s
+    }
+
+    pub fn write_object(&self, object: &Object, trimmed: bool) -> String {
+        match object {
+            Object::Text(text) | Object::Whitespace(text) => text.clone(),
+
+            Object::Bold(objects) => format!("*{}*", self.write_objects(objects, trimmed)),
+            Object::Italic(objects) => format!("/{}/", self.write_objects(objects, trimmed)),
+            Object::Underline(objects) => format!("_{}_", self.write_objects(objects, trimmed)),
+            Object::Strikethrough(objects) => {
+                format!("+{}+", self.write_objects(objects, trimmed))
+            }
+            Object::Code(objects) => format!("~{}~", self.write_objects(objects, false)),
+            Object::Verbatim(objects) => format!("={}=", self.write_objects(objects, false)),
+
+            Object::TableCell(cell) => self.write_objects(&cell.contents, false),
+
+            Object::Link { url, text } => match text {
+                Some(text) => format!("[[{url}][{text}]]"),
+                None => format!("[[{url}]]"),
+            },
+            Object::GeneralLink(link) => {
+                if link.description.is_empty() {
+                    format!("[[{}]]", link.path)
+                } else {
+                    format!(
+                        "[[{}][{}]]",
+                        link.path,
+                        self.write_objects(&link.description, trimmed)
+                    )
+                }
+            }
+            Object::RadioLink(objects) => self.write_objects(objects, trimmed),
+            Object::RadioTarget(objects) => {
+                format!("<<<{}>>>", self.write_objects(objects, trimmed))
+            }
+            Object::Target(target) => format!("<<{target}>>"),
+
+            Object::Superscript(objects) => {
+                format!("^{{{}}}", self.write_objects(objects, trimmed))
+            }
+            Object::Subscript(objects) => format!("_{{{}}}", self.write_objects(objects, trimmed)),
+
+            Object::Timestamp(timestamp) => timestamp.raw.clone(),
+
+            Object::FootnoteReference {
+                label, label_rid, ..
+            } => match self.inline_footnotes.get(label) {
+                Some(definition) if *label_rid == 1 => {
+                    let contents = definition
+                        .contents
+                        .iter()
+                        .map(|e| match e {
+                            Element::Paragraph(paragraph) => {
+                                self.write_objects(&paragraph.objects, true)
+                            }
+                            _ => self.write_element(e, ""),
+                        })
+                        .collect::<String>();
+                    if label.starts_with("anonymous_") {
+                        format!("[fn::{}]", contents.trim())
+                    } else {
+                        format!("[fn:{label}:{}]", contents.trim())
+                    }
+                }
+                _ => format!("[fn:{label}]"),
+            },
+
+            Object::Entity { name } => {
+                if name.starts_with('_') {
+                    format!("\\{name}")
+                } else {
+                    format!("\\{name}{{}}")
+                }
+            }
+
+            Object::LatexFragment {
+                display_mode,
+                content,
+            } => match display_mode {
+                Some(true) => format!("\\[{content}\\]"),
+                Some(false) => format!("\\({content}\\)"),
+                None => content.clone(),
+            },
+
+            Object::LineBreak => String::from("\\\\\n"),
+
+            Object::Macro { name, arguments } => {
+                if arguments.is_empty() {
+                    format!("{{{{{{{name}}}}}}}")
+                } else {
+                    let arguments = arguments
+                        .iter()
+                        .map(|e| e.replace(',', "\\,"))
+                        .collect::<Vec<_>>()
+                        .join(",");
+                    format!("{{{{{{{name}({arguments})}}}}}}")
+                }
+            }
+
+            Object::InlineSourceBlock {
+                lang,
+                headers,
+                body,
+            } => {
+                let headers = headers.as_ref().map_or(String::new(), |e| format!("[{e}]"));
+                format!("src_{lang}{headers}{{{body}}}")
+            }
+
+            Object::InlineBabelCall {
+                name,
+                header1,
+                arguments,
+                header2,
+            } => {
+                let header1 = header1.as_ref().map_or(String::new(), |e| format!("[{e}]"));
+                let header2 = header2.as_ref().map_or(String::new(), |e| format!("[{e}]"));
+                format!("call_{name}{header1}({arguments}){header2}")
+            }
+
+            Object::StatisticsCookie(value) => format!("[{value}]"),
+
+            Object::CitationReference(reference) => self.write_citation_reference(reference),
+
+            Object::Citation {
+                global_prefix,
+                citestyle,
+                references,
+                global_suffix,
+            } => {
+                let mut parts = vec![];
+                if !global_prefix.is_empty() {
+                    parts.push(self.write_objects(global_prefix, false));
+                }
+                parts.extend(references.iter().map(|e| self.write_citation_reference(e)));
+                if !global_suffix.is_empty() {
+                    parts.push(self.write_objects(global_suffix, false));
+                }
+                format!(
+                    "[cite{}:{}]",
+                    citestyle.as_deref().unwrap_or(""),
+                    parts.join(";")
+                )
+            }
+
+            Object::ExportSnippet { backend, value } => format!("@@{backend}:{value}@@"),
+        }
+    }
+
+    fn write_citation_reference(&self, reference: &CitationReference) -> String {
+        let suffix = self.write_objects(&reference.key_suffix, false);
+        // suffix starting with a key char would extend the key
+        let separator = if suffix
+            .starts_with(|c: char| c.is_alphanumeric() || "-.:?!`'/*@+|(){}<>&_^$#%~".contains(c))
+        {
+            " "
+        } else {
+            ""
+        };
+        format!(
+            "{}@{}{separator}{suffix}",
+            self.write_objects(&reference.key_prefix, false),
+            reference.key,
+        )
+    }
+}
+
+/// Whether a space is needed between `prev` and `next` object in paragraph, whose texts are
+/// `written` and `text`
+fn need_space(prev: &Object, next: &Object, written: &str, text: &str) -> bool {
+    match (prev, next) {
+        (Object::Text(_), Object::Text(_)) => false,
+        (_, Object::Subscript(_) | Object::Superscript(_)) => false,
+        // spaces are part of the entity
+        (Object::Entity { name }, _) if name.starts_with('_') => false,
+        (Object::Text(_), _) => !written.ends_with(|c: char| PRE_CHARS.contains(c)),
+        (_, Object::Text(_)) => !text.starts_with(|c: char| POST_CHARS.contains(c)),
+        _ => false,
+    }
+}
+
+/// Lines of paragraph are trimmed and joined by parser, so some text stays plain only because it
+/// spans lines, e.g, `<<not\na target>>`. Break such text into lines again.
+fn split_joined_text(text: &str) -> String {
+    let mut s = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        if rest.starts_with("\\\\\\") {
+            // `\\\` followed by a letter is neither a line break nor a latex fragment
+            let n = rest.len() - rest.trim_start_matches('\\').len();
+            s.push_str(&rest[..n]);
+            rest = &rest[n..];
+            if rest.starts_with(char::is_alphabetic) {
+                s.push('\n');
+            }
+        } else if let Some(after) = rest.strip_prefix("<<")
+            && let Some(end) = after.find(">>")
+        {
+            // target or radio target
+            let chars = after[..end].char_indices().collect::<Vec<_>>();
+            let split = chars
+                .windows(2)
+                .find(|w| !w[0].1.is_whitespace() && !w[1].1.is_whitespace())
+                .map(|w| w[1].0);
+            match split {
+                Some(split) => {
+                    s.push_str(&rest[..2 + split]);
+                    s.push('\n');
+                    rest = &rest[2 + split..];
+                }
+                None => {
+                    s.push_str("<<");
+                    rest = after;
+                }
+            }
+        } else {
+            s.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+    }
+    s
+}
+
+/// Prefix each line of `text` with `marker`, e.g, comment and fixed width
+fn write_lines(text: &str, indent: &str, marker: &str) -> String {
+    if text.is_empty() {
+        return format!("{indent}{marker}\n");
+    }
+    text.lines()
+        .map(|line| {
+            if line.is_empty() {
+                format!("{indent}{marker}\n")
+            } else {
+                format!("{indent}{marker} {line}\n")
+            }
+        })
+        .collect()
+}
+
+fn ends_with_newline(text: &str) -> String {
+    if text.is_empty() || text.ends_with('\n') {
+        text.to_string()
+    } else {
+        format!("{text}\n")
+    }
+}
+
+fn collect_footnote_labels(section: &Section, labels: &mut HashSet<String>) {
+    for element in section.elements.iter() {
+        if let Element::FootnoteDefinition(definition) = element {
+            labels.insert(definition.label.clone());
+        }
+    }
+}
+
+fn collect_footnote_labels_of_subtree(subtree: &HeadingSubtree, labels: &mut HashSet<String>) {
+    if let Some(section) = &subtree.section {
+        collect_footnote_labels(section, labels);
+    }
+    for sub in subtree.sub_heading_subtrees.iter() {
+        collect_footnote_labels_of_subtree(sub, labels);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::OrgWriter;
+    use crate::compiler::ast_builder::element::Element;
+    use crate::compiler::testing::compile_document;
+
+    /// parse → write → parse of `input` gives the same `OrgFile`, and writing is idempotent
+    fn assert_round_trip(name: &str, input: &str) {
+        let doc = compile_document(name, input);
+        let text = OrgWriter::new().write_org_file(&doc.ast);
+        let doc2 = compile_document(name, &text);
+
+        assert_eq!(
+            format!("{:#?}", doc.ast),
+            format!("{:#?}", doc2.ast),
+            "{name}"
+        );
+        assert_eq!(text, OrgWriter::new().write_org_file(&doc2.ast), "{name}");
+    }
+
+    #[test]
+    fn test_round_trip() {
+        for entry in std::fs::read_dir("tests").expect("read tests") {
+            let path = entry.expect("entry").path();
+            if path.extension().is_some_and(|e| e == "org") {
+                let input = std::fs::read_to_string(&path).expect("read");
+                let name = path.file_name().expect("file name").to_string_lossy();
+                assert_round_trip(&name, &input);
+            }
+        }
+    }
+
+    #[test]
+    fn test_round_trip_metadata() {
+        // keywords, properties and footnote definitions are collected into `OrgFile` as well
+        let input = r"#+TITLE: Round trip
+#+FILETAGS: :a:b:
+:PROPERTIES:
+:ID: round-trip-id
+:CREATED: [2024-01-01 Mon]
+:END:
+
+A footnote[fn:1], an inline one[fn:inline: defined here] and an anonymous one[fn:: anonymous].
+
+* Heading :tag:
+:PROPERTIES:
+:CUSTOM_ID: heading
+:END:
+See [[#heading]] and the footnote again[fn:1].
+
+[fn:1] The definition.
+";
+        assert_round_trip("metadata.org", input);
+    }
+
+    #[test]
+    fn test_cached_results() {
+        let input = "#+CALL: square[:results silent](x=4) :exports both\n\n#+RESULTS:\n: 16\n\n#+begin_src python\nreturn 1\n#+end_src\n#+RESULTS:\n\nparagraph\n";
+        let doc = compile_document("call.org", input);
+
+        let elements = &doc.ast.zeroth_section.as_ref().expect("section").elements;
+        assert_eq!(elements.len(), 3);
+        let Element::BabelCall(call) = &elements[0] else {
+            panic!("not a babel call: {:?}", elements[0]);
+        };
+        assert_eq!(call.exports(), Some("both"));
+        assert!(matches!(
+            call.cached_results
+                .as_ref()
+                .and_then(|e| e.element.as_deref()),
+            Some(Element::FixedWidth(_))
+        ));
+        assert_eq!(
+            OrgWriter::new().write_section(doc.ast.zeroth_section.as_ref().unwrap()),
+            "#+CALL: square[:results silent](x=4) :exports both\n\n#+RESULTS:\n: 16\n\n#+begin_src python\nreturn 1\n#+end_src\n\n#+RESULTS:\n\nparagraph\n"
+        );
+    }
+}
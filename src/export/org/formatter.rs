@@ -0,0 +1,590 @@
+//! Format org file on the lossless syntax tree, only the following are changed:
+//! - tags of heading are right aligned to `tags_column`
+//! - tables are realigned
+//! - bullets of ordered list are renumbered
+//! - blank lines between elements are normalized: at most one blank line between elements and
+//!   before heading (two after list and footnote definition, which end them), none at the
+//!   beginning and end of file
+//! - `#+BEGIN_SRC`/`#+END_SRC` are lowercased
+//!
+//! Everything else, e.g., comments and unknown constructs, is kept as is.
+use std::path::Path;
+
+use rowan::NodeOrToken;
+
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::parser::OrgParser;
+use crate::compiler::parser::config::OrgParserConfig;
+use crate::compiler::parser::syntax::{OrgSyntaxKind, SyntaxElement, SyntaxNode};
+
+pub struct OrgFormatter {
+    parser: OrgParser,
+    tags_column: usize,
+}
+
+impl Default for OrgFormatter {
+    fn default() -> Self {
+        Self::new(OrgParserConfig::default())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Alignment {
+    Left,
+    Right,
+    Center,
+}
+
+/// Row of table, cells are trimmed
+pub(crate) enum TableRow {
+    Standard { indent: String, cells: Vec<String> },
+    Rule { indent: String },
+}
+
+impl OrgFormatter {
+    pub fn new(config: OrgParserConfig) -> Self {
+        Self {
+            parser: OrgParser::new(config),
+            tags_column: 77,
+        }
+    }
+
+    /// Tags end at `tags_column`, the same as `org-tags-column` of -77 in emacs
+    pub fn with_tags_column(mut self, tags_column: usize) -> Self {
+        self.tags_column = tags_column;
+        self
+    }
+
+    /// Format `input` read from `f_org`.
+    ///
+    /// Diagnostics are returned as error if `input` can't be parsed completely, in which case
+    /// formatting is not safe.
+    pub fn format<P: AsRef<Path>>(&self, f_org: P, input: &str) -> Result<String, Vec<Diagnostic>> {
+        let f_org = f_org.as_ref();
+        let mut diagnostics = vec![];
+        let syntax_tree = self.parser.parse_source(f_org, input, &mut diagnostics);
+        match syntax_tree {
+            Some(root) if diagnostics.is_empty() => {
+                if root.text() != input {
+                    return Err(vec![Diagnostic::error(
+                        f_org,
+                        "syntax tree is not lossless, file is left as is",
+                    )]);
+                }
+                let mut s = self.format_element(&NodeOrToken::Node(root));
+                set_blank_lines(&mut s, 0);
+                Ok(s)
+            }
+            _ => Err(diagnostics),
+        }
+    }
+
+    fn format_element(&self, element: &SyntaxElement) -> String {
+        match element {
+            NodeOrToken::Token(token) => token.text().to_string(),
+            NodeOrToken::Node(node) => match node.kind() {
+                OrgSyntaxKind::OrgFile | OrgSyntaxKind::HeadingSubtree => self.format_outline(node),
+                OrgSyntaxKind::HeadingRow => self.format_heading_row(node),
+                OrgSyntaxKind::Section => self.format_section(node),
+                OrgSyntaxKind::Table => self.format_table(node),
+                OrgSyntaxKind::List => self.format_list(node),
+                OrgSyntaxKind::SrcBlock => self.format_src_block(node),
+                _ => self.format_children(node),
+            },
+        }
+    }
+
+    fn format_children(&self, node: &SyntaxNode) -> String {
+        node.children_with_tokens()
+            .map(|e| self.format_element(&e))
+            .collect()
+    }
+
+    // OrgFile or HeadingSubtree
+    fn format_outline(&self, node: &SyntaxNode) -> String {
+        let mut s = String::new();
+        for child in node.children_with_tokens() {
+            match child.kind() {
+                // blank lines at the beginning of file
+                OrgSyntaxKind::Section | OrgSyntaxKind::HeadingSubtree if s.trim().is_empty() => {
+                    s.clear()
+                }
+                OrgSyntaxKind::Section | OrgSyntaxKind::HeadingSubtree
+                    if count_blank_lines(&s) > 1 =>
+                {
+                    set_blank_lines(&mut s, 1)
+                }
+                _ => {}
+            }
+            s.push_str(&self.format_element(&child));
+        }
+        s
+    }
+
+    fn format_heading_row(&self, node: &SyntaxNode) -> String {
+        let mut row = String::new();
+        let mut whitespaces = String::new();
+        let mut tags = None;
+        let mut rest = String::new();
+        for child in node.children_with_tokens() {
+            match child.kind() {
+                // planning, property drawer and blank lines after the row
+                _ if !rest.is_empty() => rest.push_str(&self.format_element(&child)),
+                OrgSyntaxKind::Newline => rest.push_str(&self.format_element(&child)),
+                OrgSyntaxKind::Whitespace => whitespaces.push_str(&self.format_element(&child)),
+                OrgSyntaxKind::HeadingRowTags => tags = Some(self.format_element(&child)),
+                _ => {
+                    row.push_str(&whitespaces);
+                    whitespaces.clear();
+                    row.push_str(&self.format_element(&child));
+                }
+            }
+        }
+
+        match tags {
+            Some(tags) => {
+                let width = display_width(&row) + display_width(&tags);
+                let padding = self.tags_column.saturating_sub(width).max(1);
+                format!("{row}{}{tags}{rest}", " ".repeat(padding))
+            }
+            None => format!("{row}{whitespaces}{rest}"),
+        }
+    }
+
+    fn format_section(&self, node: &SyntaxNode) -> String {
+        let mut s = String::new();
+        let mut prev_kind = None;
+        for child in node.children_with_tokens() {
+            if let NodeOrToken::Node(element) = &child {
+                // two blank lines end list and footnote definition
+                let max_blank_lines = match prev_kind {
+                    None => usize::MAX,
+                    Some(OrgSyntaxKind::List | OrgSyntaxKind::FootnoteDefinition) => 2,
+                    Some(_) => 1,
+                };
+                if count_blank_lines(&s) > max_blank_lines {
+                    set_blank_lines(&mut s, max_blank_lines);
+                }
+                prev_kind = Some(element.kind());
+            }
+            s.push_str(&self.format_element(&child));
+        }
+        s
+    }
+
+    fn format_table(&self, node: &SyntaxNode) -> String {
+        let Some(lines) = align_table(&table_rows(node)) else {
+            return self.format_children(node);
+        };
+
+        let mut lines = lines.into_iter();
+        let mut s = String::new();
+        for child in node.children_with_tokens() {
+            let newline = if child.to_string().ends_with('\n') {
+                "\n"
+            } else {
+                ""
+            };
+            match (child.kind(), &child) {
+                (
+                    OrgSyntaxKind::TableStandardRow | OrgSyntaxKind::TableRuleRow,
+                    NodeOrToken::Node(_),
+                ) => match lines.next() {
+                    Some(line) => s.push_str(&format!("{line}{newline}")),
+                    None => s.push_str(&self.format_element(&child)),
+                },
+                _ => s.push_str(&self.format_element(&child)),
+            }
+        }
+        s
+    }
+
+    fn format_list(&self, node: &SyntaxNode) -> String {
+        let first_bullet = node
+            .children()
+            .find(|e| e.kind() == OrgSyntaxKind::ListItem)
+            .and_then(|e| item_bullet(&e));
+        let delimiter = match first_bullet {
+            Some(bullet) if bullet.starts_with(|c: char| c.is_ascii_digit()) => bullet
+                .chars()
+                .last()
+                .expect("ordered bullet ends with . or )"),
+            // unordered or alphabetical list
+            _ => return self.format_children(node),
+        };
+
+        let mut counter = 1;
+        let mut s = String::new();
+        for child in node.children_with_tokens() {
+            match &child {
+                NodeOrToken::Node(item) if item.kind() == OrgSyntaxKind::ListItem => {
+                    if let Some(counter_set) = item_counter_set(item) {
+                        counter = counter_set;
+                    }
+                    for e in item.children_with_tokens() {
+                        match &e {
+                            NodeOrToken::Node(bullet)
+                                if bullet.kind() == OrgSyntaxKind::ListItemBullet =>
+                            {
+                                for t in bullet.children_with_tokens() {
+                                    if t.kind() == OrgSyntaxKind::Text {
+                                        s.push_str(&format!("{counter}{delimiter}"));
+                                    } else {
+                                        s.push_str(&self.format_element(&t));
+                                    }
+                                }
+                            }
+                            _ => s.push_str(&self.format_element(&e)),
+                        }
+                    }
+                    counter += 1;
+                }
+                _ => s.push_str(&self.format_element(&child)),
+            }
+        }
+        s
+    }
+
+    fn format_src_block(&self, node: &SyntaxNode) -> String {
+        let mut s = String::new();
+        for child in node.children_with_tokens() {
+            match &child {
+                // `#+BEGIN_` `SRC` -> `#+begin_` `src`
+                NodeOrToken::Node(e)
+                    if matches!(
+                        e.kind(),
+                        OrgSyntaxKind::BlockBegin | OrgSyntaxKind::BlockEnd
+                    ) =>
+                {
+                    let mut n_text = 0;
+                    for t in e.children_with_tokens() {
+                        if t.kind() == OrgSyntaxKind::Text && n_text < 2 {
+                            n_text += 1;
+                            s.push_str(&t.to_string().to_lowercase());
+                        } else {
+                            s.push_str(&self.format_element(&t));
+                        }
+                    }
+                }
+                _ => s.push_str(&self.format_element(&child)),
+            }
+        }
+        s
+    }
+}
+
+/// Standard and rule rows of `Table` node
+pub(crate) fn table_rows(node: &SyntaxNode) -> Vec<TableRow> {
+    node.children()
+        .filter_map(|row| {
+            let text = row.text().to_string();
+            let indent = text[..text.len() - text.trim_start().len()].to_string();
+            match row.kind() {
+                OrgSyntaxKind::TableStandardRow => Some(TableRow::Standard {
+                    indent,
+                    cells: row
+                        .children()
+                        .filter(|e| e.kind() == OrgSyntaxKind::TableCell)
+                        .map(|e| {
+                            let cell = e.text().to_string();
+                            cell.strip_suffix('|').unwrap_or(&cell).trim().to_string()
+                        })
+                        .collect(),
+                }),
+                OrgSyntaxKind::TableRuleRow => Some(TableRow::Rule { indent }),
+                _ => None,
+            }
+        })
+        .collect()
+}
+
+/// Aligned lines of `rows` without newline, `None` if there is no cell
+pub(crate) fn align_table(rows: &[TableRow]) -> Option<Vec<String>> {
+    let cells = rows
+        .iter()
+        .filter_map(|row| match row {
+            TableRow::Standard { cells, .. } => Some(cells),
+            TableRow::Rule { .. } => None,
+        })
+        .collect::<Vec<_>>();
+    let n_columns = cells.iter().map(|e| e.len()).max().unwrap_or(0);
+    if n_columns == 0 {
+        return None;
+    }
+    let widths = (0..n_columns)
+        .map(|i| {
+            cells
+                .iter()
+                .filter_map(|row| row.get(i))
+                .map(|e| display_width(e))
+                .max()
+                .unwrap_or(0)
+                .max(1)
+        })
+        .collect::<Vec<_>>();
+    let alignments = (0..n_columns)
+        .map(|i| column_alignment(cells.iter().filter_map(|row| row.get(i))))
+        .collect::<Vec<_>>();
+
+    let lines = rows
+        .iter()
+        .map(|row| match row {
+            TableRow::Standard { indent, cells } => {
+                let cells = (0..n_columns)
+                    .map(|i| {
+                        let cell = cells.get(i).map_or("", |e| e.as_str());
+                        format!(" {} ", align(cell, widths[i], alignments[i]))
+                    })
+                    .collect::<Vec<_>>();
+                format!("{indent}|{}|", cells.join("|"))
+            }
+            TableRow::Rule { indent } => {
+                let dashes = widths.iter().map(|w| "-".repeat(w + 2)).collect::<Vec<_>>();
+                format!("{indent}|{}|", dashes.join("+"))
+            }
+        })
+        .collect();
+    Some(lines)
+}
+
+fn item_bullet(item: &SyntaxNode) -> Option<String> {
+    item.children()
+        .find(|e| e.kind() == OrgSyntaxKind::ListItemBullet)?
+        .children_with_tokens()
+        .find(|e| e.kind() == OrgSyntaxKind::Text)
+        .map(|e| e.to_string())
+}
+
+// counter of `[@N]`
+fn item_counter_set(item: &SyntaxNode) -> Option<usize> {
+    item.children()
+        .find(|e| e.kind() == OrgSyntaxKind::ListItemCounter)?
+        .children_with_tokens()
+        .find(|e| e.kind() == OrgSyntaxKind::Text)?
+        .to_string()
+        .parse()
+        .ok()
+}
+
+/// Alignment of table column: by cookie like `<r>` or `<l10>`, otherwise numbers are right
+/// aligned if most cells are numbers
+fn column_alignment<'a>(cells: impl Iterator<Item = &'a String>) -> Alignment {
+    let mut n_cells = 0;
+    let mut n_numbers = 0;
+    for cell in cells {
+        if let Some(cookie) = cell.strip_prefix('<').and_then(|e| e.strip_suffix('>'))
+            && cookie
+                .trim_start_matches(['l', 'r', 'c'])
+                .chars()
+                .all(|c| c.is_ascii_digit())
+        {
+            match cookie.chars().next() {
+                Some('r') => return Alignment::Right,
+                Some('c') => return Alignment::Center,
+                Some('l') => return Alignment::Left,
+                _ => continue,
+            }
+        }
+        if cell.is_empty() {
+            continue;
+        }
+        n_cells += 1;
+        if is_number(cell) {
+            n_numbers += 1;
+        }
+    }
+    if n_cells > 0 && n_numbers * 2 >= n_cells {
+        Alignment::Right
+    } else {
+        Alignment::Left
+    }
+}
+
+fn is_number(s: &str) -> bool {
+    let s = s.strip_suffix('%').unwrap_or(s);
+    s.starts_with(|c: char| c.is_ascii_digit() || matches!(c, '-' | '+' | '.'))
+        && s.parse::<f64>().is_ok()
+}
+
+fn align(cell: &str, width: usize, alignment: Alignment) -> String {
+    let padding = width.saturating_sub(display_width(cell));
+    match alignment {
+        Alignment::Left => format!("{cell}{}", " ".repeat(padding)),
+        Alignment::Right => format!("{}{cell}", " ".repeat(padding)),
+        Alignment::Center => format!(
+            "{}{cell}{}",
+            " ".repeat(padding / 2),
+            " ".repeat(padding - padding / 2)
+        ),
+    }
+}
+
+/// Width in terminal, CJK and emoji chars take two columns
+fn display_width(s: &str) -> usize {
+    s.chars()
+        .map(|c| match c as u32 {
+            0x1100..=0x115F
+            | 0x2E80..=0x303E
+            | 0x3041..=0x33FF
+            | 0x3400..=0x4DBF
+            | 0x4E00..=0x9FFF
+            | 0xA000..=0xA4CF
+            | 0xAC00..=0xD7A3
+            | 0xF900..=0xFAFF
+            | 0xFE30..=0xFE4F
+            | 0xFF00..=0xFF60
+            | 0xFFE0..=0xFFE6
+            | 0x1F300..=0x1F64F
+            | 0x1F900..=0x1F9FF
+            | 0x20000..=0x3FFFD => 2,
+            _ => 1,
+        })
+        .sum()
+}
+
+// end of the last non blank line in `s`
+fn content_end(s: &str) -> usize {
+    match s.rfind(|c: char| !c.is_whitespace()) {
+        Some(i) => s[i..].find('\n').map_or(s.len(), |j| i + j + 1),
+        None => 0,
+    }
+}
+
+fn count_blank_lines(s: &str) -> usize {
+    s[content_end(s)..].matches('\n').count()
+}
+
+/// Replace blank lines at the end of `s` with `n` blank lines
+fn set_blank_lines(s: &mut String, n: usize) {
+    s.truncate(content_end(s));
+    if !s.is_empty() && !s.ends_with('\n') {
+        s.push('\n');
+    }
+    s.push_str(&"\n".repeat(n));
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::OrgFormatter;
+
+    fn format(input: &str) -> String {
+        OrgFormatter::default()
+            .with_tags_column(30)
+            .format("test.org", input)
+            .expect("format failed")
+    }
+
+    #[test]
+    fn test_heading_tags() {
+        assert_eq!(
+            format("* TODO [#A] heading   :a:b:\n** 中文 :c:\n"),
+            r##"* TODO [#A] heading      :a:b:
+** 中文                    :c:
+"##
+        );
+    }
+
+    #[test]
+    fn test_table() {
+        assert_eq!(
+            format("  | name | n |\n  |-+-|\n  | foo bar | 10 |\n  | x | 2 |\n  #+TBLFM: $2=1\n"),
+            r##"  | name    |  n |
+  |---------+----|
+  | foo bar | 10 |
+  | x       |  2 |
+  #+TBLFM: $2=1
+"##
+        );
+        assert_eq!(
+            format("| <r> | <c> |\n| a | b |\n| long | long |\n"),
+            r##"|  <r> | <c>  |
+|    a |  b   |
+| long | long |
+"##
+        );
+    }
+
+    #[test]
+    fn test_list() {
+        assert_eq!(
+            format("3. a\n5. b\n   7) nested\n   7) nested\n9. [@10] c\n1. d\n"),
+            r##"1. a
+2. b
+   1) nested
+   2) nested
+10. [@10] c
+11. d
+"##
+        );
+        // unordered list is kept as is
+        assert_eq!(format("- a\n+ b\n"), "- a\n+ b\n");
+    }
+
+    #[test]
+    fn test_blank_lines() {
+        assert_eq!(
+            format(
+                "\n\npara\n\n\n\npara\n- item\n\n\n\npara\n* h1\n\n\n\ntext\n\n\n** h2\n* h3\n\n\n"
+            ),
+            r##"para
+
+para
+- item
+
+
+para
+* h1
+
+text
+
+** h2
+* h3
+"##
+        );
+    }
+
+    #[test]
+    fn test_src_block() {
+        assert_eq!(
+            format("#+BEGIN_SRC python :results output\nprint(1)\n#+END_SRC\n"),
+            "#+begin_src python :results output\nprint(1)\n#+end_src\n"
+        );
+    }
+
+    #[test]
+    fn test_indented_src_block() {
+        assert_eq!(
+            format("  #+BEGIN_SRC python :results output\n  print(1)\n  #+END_SRC\n"),
+            "  #+begin_src python :results output\n  print(1)\n  #+end_src\n"
+        );
+    }
+
+    #[test]
+    fn test_untouched() {
+        let input = r##"#+title: x
+# a comment   with   spaces
+#+begin_quote
+  quoted   text
+#+end_quote
+:drawer:
+  anything    here
+:end:
+"##;
+        assert_eq!(format(input), input);
+    }
+
+    #[test]
+    fn test_idempotent() {
+        let input = std::fs::read_to_string("tests/test.org").expect("read");
+        let formatter = OrgFormatter::default();
+        let formatted = formatter.format("tests/test.org", &input).expect("format");
+        assert_eq!(
+            formatter
+                .format("tests/test.org", &formatted)
+                .expect("format"),
+            formatted
+        );
+    }
+}
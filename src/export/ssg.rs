@@ -1,4 +1,9 @@
+pub mod backlinks;
+pub mod cache;
+pub mod citation;
 mod engine;
+pub mod graph;
+pub mod highlight;
 pub mod renderer;
 pub mod site;
 pub mod toc;
@@ -11,38 +16,79 @@ use fs_extra::dir::create_all;
 use petgraph::dot::Dot;
 use serde::Deserialize;
 
+use crate::compiler::babel::Evaluator;
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::dynamic_block::{self, Notes};
+use crate::compiler::link_resolver::{self, LinkIndex};
+use crate::compiler::spreadsheet;
 use crate::compiler::{Compiler, CompilerConfig};
-use crate::export::ssg::renderer::{Renderer, RendererConfig};
+use crate::export::ical::ICalendarExporter;
+use crate::export::ssg::cache::BuildCache;
+use crate::export::ssg::renderer::{MathRenderer, Renderer, RendererConfig};
 use crate::export::ssg::site::{SiteBuilder, SiteConfig};
 
 pub struct StaticSiteGenerator {
     pub compiler: Compiler,
+    /// evaluate src blocks before compiling, if enabled
+    evaluator: Option<Evaluator>,
+    /// recalculate tables with `#+TBLFM:` after compiling
+    recalc_tables: bool,
     pub site_builder: SiteBuilder,
     pub renderer: Renderer,
+    incremental: bool,
+    cache_file: PathBuf,
 }
 
 impl Default for StaticSiteGenerator {
     fn default() -> Self {
+        let config = SsgConfig::default();
         Self {
             compiler: Compiler::default(),
+            evaluator: None,
+            recalc_tables: false,
             site_builder: SiteBuilder::default(),
             renderer: Renderer::default(),
+            incremental: config.incremental,
+            cache_file: config.cache_file,
         }
     }
 }
 
 #[derive(Debug, Deserialize)]
+#[serde(default)]
 pub struct SsgConfig {
     pub output_directory: PathBuf,
     pub site: SiteConfig,
     pub renderer: RendererConfig,
+
+    /// incremental build: only re-parse changed files and re-render affected pages
+    ///
+    /// Disabled by default, since the output directory is not backed up in incremental build.
+    pub incremental: bool,
+    /// build cache used by incremental build
+    pub cache_file: PathBuf,
+}
+
+impl Default for SsgConfig {
+    fn default() -> Self {
+        Self {
+            output_directory: "public".into(),
+            site: SiteConfig::default(),
+            renderer: RendererConfig::default(),
+            incremental: false,
+            cache_file: ".cache/windancer.bin".into(),
+        }
+    }
 }
 
 impl StaticSiteGenerator {
-    pub fn generate<P: AsRef<Path>>(&mut self, d_org: P) -> std::io::Result<String> {
+    /// Generate site from `d_org`, output directory is backed up first if not incremental
+    ///
+    /// Diagnostics of src block evaluation and org files are returned, see [`Self::rebuild`].
+    pub fn generate<P: AsRef<Path>>(&mut self, d_org: P) -> std::io::Result<Vec<Diagnostic>> {
         tracing::info!("prepare output directory ...");
         let output_directory = &self.site_config().output_directory;
-        if output_directory.exists() {
+        if output_directory.exists() && !self.incremental {
             let now_utc = chrono::Utc::now();
             let created_ts = now_utc.format("%Y%m%dT%H%M%SZ").to_string();
             let backup_directory = format!("{}.backup_{}", output_directory.display(), created_ts);
@@ -53,14 +99,51 @@ impl StaticSiteGenerator {
             );
             fs::rename(output_directory, backup_directory)?;
         }
-        let _ = create_all(output_directory, true);
 
-        tracing::info!("compile ...");
+        self.rebuild(d_org)
+    }
+
+    /// Compile, build and render site into output directory, without backup of output directory.
+    ///
+    /// Only changed files are compiled and only affected pages are rendered if incremental.
+    /// Files with errors are skipped, and the diagnostics are returned for the caller to report.
+    pub fn rebuild<P: AsRef<Path>>(&mut self, d_org: P) -> std::io::Result<Vec<Diagnostic>> {
+        let output_directory = &self.site_config().output_directory;
+        if !output_directory.is_dir() {
+            let _ = create_all(output_directory, false);
+        }
+
         let d_org = d_org.as_ref();
-        let section = self
-            .compiler
-            .compile_section(d_org)
-            .expect("NO document compiled");
+        let mut diagnostics = vec![];
+        if let Some(evaluator) = &self.evaluator {
+            tracing::info!("evaluate src blocks ...");
+            diagnostics.extend(evaluator.evaluate_path(d_org)?);
+        }
+
+        tracing::info!("compile ...");
+        let mut cache = self
+            .incremental
+            .then(|| BuildCache::load(&self.cache_file, &self.fingerprint()));
+        let mut section = match cache.as_mut() {
+            Some(cache) => self
+                .compiler
+                .compile_section_cached(d_org, &mut cache.compile),
+            None => self.compiler.compile_section(d_org),
+        }
+        .map_err(|e| std::io::Error::other(e.to_string()))?;
+        if self.recalc_tables {
+            spreadsheet::update_section(&mut section);
+        }
+        link_resolver::update_section(&mut section);
+
+        let section_diagnostics = section.all_diagnostics();
+        let n_error = section_diagnostics.iter().filter(|d| d.is_error()).count();
+        if n_error > 0 {
+            tracing::warn!("  {} error(s), failed files are skipped", n_error);
+        }
+        diagnostics.extend(section_diagnostics.into_iter().cloned());
+
+        dynamic_block::update_section(&mut section);
 
         let g = section.build_graph();
         let g_dot = Dot::new(&g.graph);
@@ -68,24 +151,79 @@ impl StaticSiteGenerator {
         tracing::debug!("{:#?}", g.graph);
 
         tracing::info!("build site ...");
-        let site = self
-            .site_builder
-            .build(&section)
-            .expect("site_builder.build() failed");
+        let site = self.site_builder.build(&section)?;
 
         tracing::info!("render site ...");
-        self.renderer.render_site(&site);
+        if let Some(mut cache) = cache {
+            let (changed, removed) = cache.compile.finish();
+            tracing::info!(
+                "  {} file(s) changed, {} file(s) removed",
+                changed.len(),
+                removed.len()
+            );
+            let output_directory = self.site_config().output_directory.clone();
+            let plan = cache.plan(&section, &site, &changed, &removed, &output_directory);
+            for f in plan.stale_files.iter() {
+                tracing::debug!("  remove {}", f);
+                match fs::remove_file(output_directory.join(f)) {
+                    Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                        tracing::warn!("failed to remove stale file {}: {}", f, e);
+                    }
+                    _ => {}
+                }
+            }
+            if let Some(page_ids) = &plan.page_ids {
+                tracing::info!("  render {} page(s)", page_ids.len());
+            }
+            diagnostics.extend(self.renderer.render_site_partial(
+                &site,
+                plan.page_ids.as_ref(),
+                plan.tags.as_ref(),
+            ));
+            cache.save(&self.cache_file)?;
+        } else {
+            diagnostics.extend(self.renderer.render_site(&site));
+        }
+
+        tracing::info!("export calendar ...");
+        let mut exporter = ICalendarExporter::new();
+        if let Some(open_states) = &self.site_config().agenda.open_states {
+            exporter = exporter.with_open_states(open_states.clone());
+        }
+        exporter.export(&section, &self.site_config().output_directory)?;
 
         tracing::info!("done");
-        Ok(String::from("todo"))
+        Ok(diagnostics)
+    }
+
+    /// Fingerprint of version, config and templates
+    fn fingerprint(&self) -> String {
+        let s = format!(
+            "{};{};recalc_tables={};{:?};{}",
+            env!("CARGO_PKG_VERSION"),
+            self.compiler.fingerprint(),
+            self.recalc_tables,
+            self.site_config(),
+            self.renderer.fingerprint()
+        );
+        blake3::hash(s.as_bytes()).to_hex().to_string()
     }
 
-    pub fn generate_html<P: AsRef<Path>>(&mut self, f_org: P) -> String {
+    pub fn generate_html<P: AsRef<Path>>(
+        &mut self,
+        f_org: P,
+    ) -> Result<String, Box<dyn std::error::Error>> {
         let start = Instant::now();
-        let doc = self
-            .compiler
-            .compile_file(f_org)
-            .expect("compile org to Document(AST)");
+        let mut doc = self.compiler.compile_file(f_org)?;
+        if self.recalc_tables {
+            for e in spreadsheet::update_org_file(&mut doc.ast) {
+                tracing::warn!("failed to evaluate table formula {}", e);
+            }
+        }
+        for e in link_resolver::update_document(&mut doc, &LinkIndex::default()) {
+            tracing::warn!("{}", e);
+        }
+        dynamic_block::update_document(&mut doc, &Notes::default());
         let duration = start.elapsed();
         tracing::info!("windancer@parser           : {:?}", duration);
 
@@ -98,22 +236,50 @@ impl StaticSiteGenerator {
         let html = self.renderer.render_page_inner(&page);
         let duration = start.elapsed();
         tracing::info!("windancer@renderer         : {:?}", duration);
-        html
+        Ok(html)
     }
 
     pub fn new(compiler_config: CompilerConfig, ssg_config: SsgConfig) -> Self {
-        let site_config = ssg_config.site;
+        let mut site_config = ssg_config.site;
+        site_config.agenda.open_states.get_or_insert_with(|| {
+            compiler_config
+                .parser()
+                .org_todo_keywords
+                .requiring_action
+                .clone()
+        });
         let renderer_config = ssg_config.renderer;
-        let compiler = Compiler::new(compiler_config);
+        let evaluator = compiler_config.babel().enabled.then(|| {
+            Evaluator::new(
+                compiler_config.babel().clone(),
+                compiler_config.parser().clone(),
+            )
+        });
+        let recalc_tables = compiler_config.recalc_tables();
+        let compiler = Compiler::new(
+            compiler_config.with_equation_numbering(renderer_config.automatic_equaiton_numbering),
+        )
+        .with_check_math(renderer_config.math == MathRenderer::Mathml);
         let site_builder = SiteBuilder::new(site_config);
         let renderer = Renderer::new(renderer_config);
         Self {
             compiler,
+            evaluator,
+            recalc_tables,
             site_builder,
             renderer,
+            incremental: ssg_config.incremental,
+            cache_file: ssg_config.cache_file,
         }
     }
 
+    /// Keep existing results of src blocks whose code is not changed, see
+    /// [`Evaluator::with_keep_results`]
+    pub fn with_babel_keep_results(mut self, keep_results: bool) -> Self {
+        self.evaluator = self.evaluator.map(|e| e.with_keep_results(keep_results));
+        self
+    }
+
     pub fn site_config(&self) -> &SiteConfig {
         &self.site_builder.config
     }
@@ -0,0 +1,341 @@
+//! Backlinks of roam nodes, rendered at the end of pages and headings with `ID`
+//!
+//! - linked: incoming `id:` links of knowledge graph, with the enclosing paragraph as context
+//! - unlinked: plain-text mentions of title or `ROAM_ALIASES` in other notes, which are not linked
+//!   yet, only collected if `unlinked_references` of site is enabled
+use std::collections::HashMap;
+
+use petgraph::Direction;
+use petgraph::visit::EdgeRef;
+use serde::Serialize;
+
+use crate::compiler::ast_builder::SourcePathSegment;
+use crate::compiler::ast_builder::element::{Element, HeadingSubtree, OrgFile};
+use crate::compiler::dynamic_block::{child_elements, plain_text};
+use crate::compiler::org_roam::EdgeType;
+use crate::export::ssg::site::{KnowledgeGraph, Page, PageId};
+
+/// A note referring to a roam node
+#[derive(Debug, Clone, Serialize)]
+pub struct Backlink {
+    pub title: String,
+    pub url: String,
+    /// page title and headings from page to the referring paragraph
+    pub breadcrumbs: Vec<String>,
+    /// plain text of the referring paragraph
+    pub context: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Backlinks {
+    /// roam id -> notes linking to it
+    pub linked: HashMap<String, Vec<Backlink>>,
+    /// roam id -> notes mentioning its title or aliases without a link
+    pub unlinked: HashMap<String, Vec<Backlink>>,
+}
+
+impl Backlinks {
+    pub fn build(
+        knowledge_graph: &KnowledgeGraph,
+        pages: &HashMap<PageId, Page>,
+        unlinked_references: bool,
+    ) -> Self {
+        let page_titles = pages
+            .values()
+            .map(|page| (page.url.as_str(), page.title.as_str()))
+            .collect::<HashMap<_, _>>();
+
+        let graph = &knowledge_graph.graph;
+        let mut linked: HashMap<String, Vec<Backlink>> = HashMap::new();
+        for index in graph.node_indices() {
+            let mut backlinks = graph
+                .edges_directed(index, Direction::Incoming)
+                .filter_map(|edge| match edge.weight() {
+                    EdgeType::ExplicitReference {
+                        source_path,
+                        context,
+                    } => {
+                        let source = &graph[edge.source()];
+                        let page_url = page_url(&source.url);
+                        let mut breadcrumbs = vec![
+                            page_titles
+                                .get(page_url)
+                                .map_or(page_url.to_string(), |e| e.to_string()),
+                        ];
+                        breadcrumbs.extend(source_path.iter().filter_map(|seg| match seg {
+                            SourcePathSegment::Heading { title, .. } => Some(plain_text(title)),
+                            _ => None,
+                        }));
+                        Some(Backlink {
+                            title: plain_text(&source.title),
+                            url: source.url.clone(),
+                            breadcrumbs,
+                            context: context.clone(),
+                        })
+                    }
+                    EdgeType::Parent => None,
+                })
+                .collect::<Vec<_>>();
+            if !backlinks.is_empty() {
+                backlinks.sort_by(|a, b| a.url.cmp(&b.url));
+                linked.insert(graph[index].id.clone(), backlinks);
+            }
+        }
+
+        let mut unlinked = HashMap::new();
+        if unlinked_references {
+            let mut paragraphs = vec![];
+            for page in pages.values() {
+                collect_paragraphs(page, &mut paragraphs);
+            }
+            paragraphs.sort_by(|a, b| a.url.cmp(&b.url));
+            // lowercased once, rather than for each node
+            let texts = paragraphs
+                .iter()
+                .map(|e| e.context.as_deref().unwrap_or_default().to_lowercase())
+                .collect::<Vec<_>>();
+
+            for node in graph.node_weights() {
+                let names = std::iter::once(plain_text(&node.title))
+                    .chain(node.aliases.iter().map(|e| e.trim_matches('"').to_string()))
+                    .map(|e| e.trim().to_lowercase())
+                    .filter(|e| !e.is_empty())
+                    .collect::<Vec<_>>();
+                let node_page_url = page_url(&node.url);
+                let linked_urls = linked
+                    .get(&node.id)
+                    .map(|e| e.iter().map(|e| e.url.as_str()).collect::<Vec<_>>())
+                    .unwrap_or_default();
+                let backlinks = paragraphs
+                    .iter()
+                    .zip(texts.iter())
+                    .filter(|(e, _)| page_url(&e.url) != node_page_url)
+                    .filter(|(e, _)| !linked_urls.contains(&e.url.as_str()))
+                    .filter(|(_, text)| names.iter().any(|name| mentions(text, name)))
+                    .map(|(e, _)| e.clone())
+                    .collect::<Vec<_>>();
+                if !backlinks.is_empty() {
+                    unlinked.insert(node.id.clone(), backlinks);
+                }
+            }
+        }
+
+        Self { linked, unlinked }
+    }
+}
+
+/// url of page without anchor
+fn page_url(url: &str) -> &str {
+    url.split_once('#').map_or(url, |(e, _)| e)
+}
+
+/// Paragraphs of `page` as backlinks from the nearest roam node, or the page itself
+fn collect_paragraphs(page: &Page, paragraphs: &mut Vec<Backlink>) {
+    fn collect(
+        elements: &[Element],
+        source: &(String, String),
+        breadcrumbs: &[String],
+        paragraphs: &mut Vec<Backlink>,
+    ) {
+        for element in elements {
+            match element {
+                Element::Paragraph(paragraph) => {
+                    let text = plain_text(&paragraph.objects)
+                        .split_whitespace()
+                        .collect::<Vec<_>>()
+                        .join(" ");
+                    if !text.is_empty() {
+                        paragraphs.push(Backlink {
+                            title: source.0.clone(),
+                            url: source.1.clone(),
+                            breadcrumbs: breadcrumbs.to_vec(),
+                            context: Some(text),
+                        });
+                    }
+                }
+                Element::List(list) => {
+                    for item in list.items.iter() {
+                        collect(&item.contents, source, breadcrumbs, paragraphs);
+                    }
+                }
+                _ => {
+                    if let Some(children) = child_elements(element) {
+                        collect(children, source, breadcrumbs, paragraphs);
+                    }
+                }
+            }
+        }
+    }
+
+    fn collect_heading(
+        heading: &HeadingSubtree,
+        source: &(String, String),
+        breadcrumbs: &mut Vec<String>,
+        page_url: &str,
+        paragraphs: &mut Vec<Backlink>,
+    ) {
+        if heading.is_commented {
+            return;
+        }
+        let title = plain_text(&heading.title);
+        let source = match heading.properties.get("ID") {
+            Some(id) => (title.clone(), format!("{page_url}#{id}")),
+            None => source.clone(),
+        };
+        breadcrumbs.push(title);
+        if let Some(section) = &heading.section {
+            collect(&section.elements, &source, breadcrumbs, paragraphs);
+        }
+        for subtree in heading.sub_heading_subtrees.iter() {
+            collect_heading(subtree, &source, breadcrumbs, page_url, paragraphs);
+        }
+        breadcrumbs.pop();
+    }
+
+    let file: &OrgFile = &page.ast;
+    let source = match file.properties.get("ID") {
+        Some(id) => (page.title.clone(), format!("{}#{id}", page.url)),
+        None => (page.title.clone(), page.url.clone()),
+    };
+    let mut breadcrumbs = vec![page.title.clone()];
+    if let Some(section) = &file.zeroth_section {
+        collect(&section.elements, &source, &breadcrumbs, paragraphs);
+    }
+    for heading in file.heading_subtrees.iter() {
+        collect_heading(heading, &source, &mut breadcrumbs, &page.url, paragraphs);
+    }
+}
+
+/// Whether `text` mentions `name` as a whole word, both are lowercase
+fn mentions(text: &str, name: &str) -> bool {
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let (first, last) = match (name.chars().next(), name.chars().last()) {
+        (Some(first), Some(last)) => (first, last),
+        _ => return false,
+    };
+    text.match_indices(name).any(|(start, _)| {
+        let before = text[..start].chars().last();
+        let after = text[start + name.len()..].chars().next();
+        !(is_word(first) && before.is_some_and(is_word))
+            && !(is_word(last) && after.is_some_and(is_word))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::Compiler;
+    use crate::export::ssg::renderer::{Renderer, RendererConfig};
+    use crate::export::ssg::site::{SiteBuilder, SiteConfig};
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_mentions() {
+        assert!(mentions("see rust notes here", "rust"));
+        assert!(mentions("rust", "rust"));
+        assert!(!mentions("trusty tools", "rust"));
+        assert!(!mentions("rust_lang", "rust"));
+        assert!(mentions("学习笔记很有用", "笔记"));
+    }
+
+    #[test]
+    fn test_build_backlinks() {
+        let d_root =
+            std::env::temp_dir().join(format!("windancer_backlinks_{}", std::process::id()));
+        let d_org = d_root.join("content");
+        std::fs::create_dir_all(&d_org).expect("create dir");
+        std::fs::write(
+            d_org.join("rust.org"),
+            r":PROPERTIES:
+:ID: rust-id
+:ROAM_ALIASES: Ferris
+:END:
+#+TITLE: Rust
+
+* Ownership
+:PROPERTIES:
+:ID: ownership-id
+:END:
+Move semantics.
+",
+        )
+        .expect("write");
+        std::fs::write(
+            d_org.join("notes.org"),
+            r":PROPERTIES:
+:ID: notes-id
+:END:
+#+TITLE: Notes
+
+* Languages
+I like [[id:rust-id][it]] a lot.
+
+* Mentions
+:PROPERTIES:
+:ID: mentions-id
+:END:
+Ferris is a crab, and ownership matters.
+
+Trusty tools.
+",
+        )
+        .expect("write");
+
+        let section = Compiler::default()
+            .compile_section(&d_org)
+            .expect("compile section");
+        let d_output = d_root.join("public");
+        std::fs::create_dir_all(&d_output).expect("create dir");
+        let config = SiteConfig {
+            output_directory: d_output.clone(),
+            unlinked_references: true,
+            ..SiteConfig::default()
+        };
+        let site = SiteBuilder::new(config)
+            .build(&section)
+            .expect("build site");
+
+        let linked = &site.backlinks.linked["rust-id"];
+        assert_eq!(linked.len(), 1);
+        assert_eq!(linked[0].url, "/notes.html#notes-id");
+        assert_eq!(linked[0].breadcrumbs, vec!["Notes", "Languages"]);
+        assert_eq!(linked[0].context.as_deref(), Some("I like it a lot."));
+
+        let unlinked = &site.backlinks.unlinked["rust-id"];
+        assert_eq!(unlinked.len(), 1);
+        assert_eq!(unlinked[0].url, "/notes.html#mentions-id");
+        assert_eq!(unlinked[0].breadcrumbs, vec!["Notes", "Mentions"]);
+        assert_eq!(
+            unlinked[0].context.as_deref(),
+            Some("Ferris is a crab, and ownership matters.")
+        );
+        assert_eq!(site.backlinks.unlinked["ownership-id"].len(), 1);
+
+        let mut renderer = Renderer::new(RendererConfig {
+            input_directory: d_org,
+            output_directory: d_output.clone(),
+            ..RendererConfig::default()
+        });
+        renderer.render_site(&site);
+        let html = std::fs::read_to_string(d_output.join("rust.html")).expect("read rust.html");
+        let (_, aside) = html.split_once("<aside>").expect("backlinks of page");
+        let (aside, _) = aside.split_once("</aside>").expect("end of backlinks");
+        assert!(aside.contains(r#"<div class="backlinks">"#));
+        assert!(aside.contains(r#"<a href="/notes.html#notes-id">Notes</a>"#));
+        assert!(aside.contains("<blockquote>I like it a lot.</blockquote>"));
+        assert!(aside.contains(r#"<a href="/notes.html#mentions-id">Mentions</a>"#));
+
+        // panel of heading with ID, between the heading and the end of its section
+        let (_, heading) = html
+            .split_once(r#"id="ownership-id""#)
+            .expect("heading with ID");
+        let (heading, _) = heading.split_once("</section>").expect("end of heading");
+        assert!(heading.contains(r#"<div class="unlinked-references">"#));
+        assert!(
+            heading.contains("<blockquote>Ferris is a crab, and ownership matters.</blockquote>")
+        );
+        assert!(!heading.contains(r#"<div class="backlinks">"#));
+
+        std::fs::remove_dir_all(&d_root).expect("remove dir");
+    }
+}
@@ -0,0 +1,360 @@
+//! Persistent build cache for incremental build
+//!
+//! - compiled documents are keyed on blake3 hash of org file, see `CompileCache`
+//! - each page records the pages linking to it and the pages it links to, through `id:` links and
+//!   links resolved by `link_resolver`, e.g., `file:` links, and its tags
+//! - pages linked by a changed file before or after the change are rendered, for their backlinks
+//!   and local graphs
+//! - pages with site-aware dynamic blocks, e.g., `backlinks`, are rendered if any file changed
+//! - the site toc is embedded in every page, all pages are rendered if toc changed
+//!
+//! The whole cache is dropped if fingerprint (version, config and templates) changed.
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::ast_builder::element::Id;
+use crate::compiler::cache::CompileCache;
+use crate::compiler::content::{Document, Section};
+use crate::compiler::dynamic_block;
+use crate::export::ssg::site::{PageId, Site};
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct PageCacheEntry {
+    /// html path relative to output directory
+    pub html_path: String,
+    pub tags: BTreeSet<String>,
+    /// org files linking to this page
+    pub dependents: BTreeSet<PathBuf>,
+    /// org files linked by this page
+    pub targets: BTreeSet<PathBuf>,
+    /// has dynamic blocks depending on other files
+    pub site_aware: bool,
+}
+
+/// Pages and tag pages to render, `None` means all
+pub struct RenderPlan {
+    pub page_ids: Option<HashSet<PageId>>,
+    pub tags: Option<HashSet<String>>,
+    /// files relative to output directory, which are generated by last build but outdated now
+    pub stale_files: BTreeSet<String>,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct BuildCache {
+    fingerprint: String,
+    pub compile: CompileCache,
+    /// hash of site toc
+    toc: String,
+    /// full path of org file -> page
+    pages: HashMap<PathBuf, PageCacheEntry>,
+}
+
+impl BuildCache {
+    pub fn new(fingerprint: String) -> Self {
+        Self {
+            fingerprint,
+            ..Self::default()
+        }
+    }
+
+    /// Load cache from `f_cache`, an empty cache is returned if it is missing, broken or outdated
+    pub fn load<P: AsRef<Path>>(f_cache: P, fingerprint: &str) -> Self {
+        let f_cache = f_cache.as_ref();
+        let maybe_cache = fs::read(f_cache)
+            .ok()
+            .and_then(|bytes| bincode::deserialize::<BuildCache>(&bytes).ok());
+        match maybe_cache {
+            Some(cache) if cache.fingerprint == fingerprint => {
+                tracing::info!("  load cache from {}", f_cache.display());
+                cache
+            }
+            Some(_) => {
+                tracing::info!("  config/templates changed, cache dropped");
+                Self::new(fingerprint.to_string())
+            }
+            None => Self::new(fingerprint.to_string()),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, f_cache: P) -> std::io::Result<()> {
+        let f_cache = f_cache.as_ref();
+        if let Some(d_cache) = f_cache.parent()
+            && !d_cache.as_os_str().is_empty()
+            && !d_cache.is_dir()
+        {
+            fs::create_dir_all(d_cache)?;
+        }
+        let bytes = bincode::serialize(self).map_err(std::io::Error::other)?;
+        fs::write(f_cache, bytes)
+    }
+
+    /// Collect pages of `site`, keyed on full path of org file
+    fn collect_pages(section: &Section, site: &Site) -> HashMap<PathBuf, (PageId, PageCacheEntry)> {
+        fn collect_documents<'a>(section: &'a Section, documents: &mut Vec<&'a Document>) {
+            documents.extend(section.documents.iter());
+            for subsection in section.subsections.iter() {
+                collect_documents(subsection, documents);
+            }
+        }
+        let mut documents = vec![];
+        collect_documents(section, &mut documents);
+
+        let mut pages = HashMap::new();
+        let mut roam_id_to_path = HashMap::new();
+        for doc in documents.iter() {
+            let path = &doc.file_info.full_path;
+            for node in doc.ast.roam_nodes.iter() {
+                roam_id_to_path.insert(node.id.as_str(), path);
+            }
+
+            let page_id = doc.ast.id();
+            if let Some(page) = site.pages.get(&page_id) {
+                let entry = PageCacheEntry {
+                    html_path: page.html_path.clone(),
+                    tags: page.tags.iter().cloned().collect(),
+                    dependents: BTreeSet::new(),
+                    targets: BTreeSet::new(),
+                    // unlinked references may come from any page, and so does local graph
+                    // beyond linked pages
+                    site_aware: dynamic_block::has_site_blocks(&doc.ast)
+                        || (!doc.ast.roam_nodes.is_empty()
+                            && (site.config.unlinked_references
+                                || site.config.graph.local_depth > 1)),
+                };
+                pages.insert(path.clone(), (page_id, entry));
+            }
+        }
+
+        let url_to_path = pages
+            .iter()
+            .map(|(path, (_, entry))| (format!("/{}", entry.html_path), path.clone()))
+            .collect::<HashMap<_, _>>();
+        let mut links = vec![];
+        for doc in documents.iter() {
+            let source = &doc.file_info.full_path;
+            if !pages.contains_key(source) {
+                continue;
+            }
+            for extracted_link in doc.ast.extracted_links.iter() {
+                let link = &extracted_link.link;
+                // `id:` links are rendered by roam id, others are resolved by `link_resolver`
+                let target = if link.protocol == "id" {
+                    let target_id = link.path.strip_prefix("id:").unwrap_or(&link.path);
+                    roam_id_to_path.get(target_id).copied()
+                } else {
+                    link.href
+                        .as_deref()
+                        .and_then(|href| url_to_path.get(href.split('#').next().unwrap_or(href)))
+                };
+                if let Some(target) = target
+                    && target != source
+                {
+                    links.push((source.clone(), target.clone()));
+                }
+            }
+        }
+        for (source, target) in links {
+            if let Some((_, entry)) = pages.get_mut(&target) {
+                entry.dependents.insert(source.clone());
+            }
+            if let Some((_, entry)) = pages.get_mut(&source) {
+                entry.targets.insert(target);
+            }
+        }
+
+        pages
+    }
+
+    /// Compare `site` with last build, update the cache and return pages to render.
+    ///
+    /// `changed` and `removed` are org files returned by `CompileCache::finish()`.
+    pub fn plan(
+        &mut self,
+        section: &Section,
+        site: &Site,
+        changed: &BTreeSet<PathBuf>,
+        removed: &BTreeSet<PathBuf>,
+        d_output: &Path,
+    ) -> RenderPlan {
+        let toc = {
+            let bytes = bincode::serialize(&site.toc()).expect("serialize failed");
+            blake3::hash(&bytes).to_hex().to_string()
+        };
+        let pages = Self::collect_pages(section, site);
+
+        let new_html_paths = pages
+            .values()
+            .map(|(_, e)| e.html_path.as_str())
+            .collect::<HashSet<_>>();
+        let new_tags = pages
+            .values()
+            .flat_map(|(_, e)| e.tags.iter().map(|t| t.as_str()))
+            .collect::<HashSet<_>>();
+        let mut stale_files = BTreeSet::new();
+        for (path, old) in self.pages.iter() {
+            if !pages.contains_key(path) && !new_html_paths.contains(old.html_path.as_str()) {
+                stale_files.insert(old.html_path.clone());
+            }
+            for tag in old.tags.iter() {
+                if !new_tags.contains(tag.as_str()) {
+                    stale_files.insert(format!("tags/{tag}.html"));
+                }
+            }
+        }
+
+        let plan = if self.pages.is_empty() || self.toc != toc {
+            tracing::debug!("  toc changed, render all pages");
+            RenderPlan {
+                page_ids: None,
+                tags: None,
+                stale_files,
+            }
+        } else {
+            let mut dirty = BTreeSet::new();
+            let mut tags = HashSet::new();
+            for path in changed.iter().chain(removed.iter()) {
+                dirty.insert(path.clone());
+                for entry in [self.pages.get(path), pages.get(path).map(|(_, e)| e)]
+                    .into_iter()
+                    .flatten()
+                {
+                    dirty.extend(entry.dependents.iter().cloned());
+                    dirty.extend(entry.targets.iter().cloned());
+                    tags.extend(entry.tags.iter().cloned());
+                }
+            }
+
+            if !changed.is_empty() || !removed.is_empty() {
+                for (path, (_, entry)) in pages.iter() {
+                    if entry.site_aware {
+                        dirty.insert(path.clone());
+                    }
+                }
+            }
+
+            // removed from output directory manually
+            for (path, (_, entry)) in pages.iter() {
+                if !d_output.join(&entry.html_path).is_file() {
+                    dirty.insert(path.clone());
+                }
+            }
+            for tag in new_tags.iter() {
+                if !d_output.join(format!("tags/{tag}.html")).is_file() {
+                    tags.insert(tag.to_string());
+                }
+            }
+
+            let page_ids = dirty
+                .iter()
+                .filter_map(|path| pages.get(path).map(|(id, _)| id.clone()))
+                .collect::<HashSet<_>>();
+            tracing::debug!("  dirty pages: {:?}", dirty);
+            RenderPlan {
+                page_ids: Some(page_ids),
+                tags: Some(tags),
+                stale_files,
+            }
+        };
+
+        self.toc = toc;
+        self.pages = pages.into_iter().map(|(path, (_, e))| (path, e)).collect();
+
+        plan
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compiler::{Compiler, link_resolver};
+    use crate::export::ssg::site::{SiteBuilder, SiteConfig};
+    use pretty_assertions::assert_eq;
+
+    /// Build site of `content/` in `d_root` with `cache`, pages to render are written as empty
+    /// files, whose html paths are returned
+    fn build(d_root: &Path, cache: &mut BuildCache) -> BTreeSet<String> {
+        let mut section = Compiler::default()
+            .compile_section_cached(d_root.join("content"), &mut cache.compile)
+            .expect("compile section");
+        link_resolver::update_section(&mut section);
+        let d_output = d_root.join("public");
+        fs::create_dir_all(&d_output).expect("create dir");
+        let site = SiteBuilder::new(SiteConfig {
+            output_directory: d_output.clone(),
+            ..SiteConfig::default()
+        })
+        .build(&section)
+        .expect("build site");
+
+        let (changed, removed) = cache.compile.finish();
+        let plan = cache.plan(&section, &site, &changed, &removed, &d_output);
+        let rendered = site
+            .pages
+            .values()
+            .filter(|page| {
+                plan.page_ids
+                    .as_ref()
+                    .is_none_or(|ids| ids.contains(&page.id))
+            })
+            .map(|page| page.html_path.clone())
+            .collect::<BTreeSet<_>>();
+        for html_path in rendered.iter() {
+            fs::write(d_output.join(html_path), "").expect("write");
+        }
+        rendered
+    }
+
+    fn html_paths(paths: &[&str]) -> BTreeSet<String> {
+        paths.iter().map(|e| e.to_string()).collect()
+    }
+
+    #[test]
+    fn test_plan_link_targets() {
+        let d_root = std::env::temp_dir().join(format!("windancer_plan_{}", std::process::id()));
+        let d_org = d_root.join("content");
+        fs::create_dir_all(&d_org).expect("create dir");
+        let a = ":PROPERTIES:\n:ID: a-id\n:END:\n#+TITLE: A\n";
+        fs::write(d_org.join("a.org"), a).expect("write");
+        fs::write(
+            d_org.join("b.org"),
+            ":PROPERTIES:\n:ID: b-id\n:END:\n#+TITLE: B\n",
+        )
+        .expect("write");
+        fs::write(d_org.join("c.org"), "#+TITLE: C\n").expect("write");
+        let mut cache = BuildCache::new("test".to_string());
+        assert!(
+            build(&d_root, &mut cache).is_superset(&html_paths(&["a.html", "b.html", "c.html"]))
+        );
+        assert_eq!(build(&d_root, &mut cache), html_paths(&[]));
+
+        // backlinks of b and c are added
+        fs::write(
+            d_org.join("a.org"),
+            format!("{a}[[id:b-id][B]] [[file:c.org][C]]\n"),
+        )
+        .expect("write");
+        assert_eq!(
+            build(&d_root, &mut cache),
+            html_paths(&["a.html", "b.html", "c.html"])
+        );
+
+        // a links to c
+        fs::write(d_org.join("c.org"), "#+TITLE: C\nEdited.\n").expect("write");
+        assert_eq!(
+            build(&d_root, &mut cache),
+            html_paths(&["a.html", "c.html"])
+        );
+
+        // backlinks of b and c are removed
+        fs::write(d_org.join("a.org"), a).expect("write");
+        assert_eq!(
+            build(&d_root, &mut cache),
+            html_paths(&["a.html", "b.html", "c.html"])
+        );
+
+        fs::remove_dir_all(&d_root).expect("remove dir");
+    }
+}
@@ -0,0 +1,355 @@
+//! Citations and bibliography of a page, whose cited entries are resolved by compiler, see
+//! [`Bibliography`].
+//!
+//! Objects are rendered without page, so citation is rendered as placeholder first, and replaced
+//! by [`resolve_citations`] after the whole page is rendered.
+//!
+//! - style: `[cite:@key]` (default), `/t` (text), `/a` (author), `/na` (noauthor), `/n` (nocite)
+//! - variant: `/b` (bare), `/c` (caps), `/f` (full), or combined, e.g., `/t/bc`
+use crate::compiler::bibliography::{BibEntry, Bibliography, CitationStyle};
+
+const CITE_START: char = '\u{E003}';
+const CITE_SEP: char = '\u{E004}';
+const CITE_REF_SEP: char = '\u{E005}';
+const CITE_END: char = '\u{E006}';
+
+/// Placeholder of `#+print_bibliography:`
+pub const BIBLIOGRAPHY_PLACEHOLDER: &str = "\u{E007}";
+
+/// Placeholder of citation, prefixes and suffixes are rendered html
+pub fn citation_placeholder(
+    citestyle: Option<&str>,
+    global_prefix: &str,
+    references: &[(String, &str, String)],
+    global_suffix: &str,
+) -> String {
+    let mut placeholder = format!(
+        "{CITE_START}{}{CITE_SEP}{global_prefix}{CITE_SEP}{global_suffix}",
+        citestyle.unwrap_or_default()
+    );
+    for (prefix, key, suffix) in references {
+        placeholder.push_str(&format!(
+            "{CITE_SEP}{prefix}{CITE_REF_SEP}{key}{CITE_REF_SEP}{suffix}"
+        ));
+    }
+    placeholder.push(CITE_END);
+    placeholder
+}
+
+/// Replace placeholders of citations and bibliography in `html`
+pub fn resolve_citations(html: &str, bibliography: Option<&Bibliography>) -> String {
+    let mut output = String::with_capacity(html.len());
+    let mut rest = html;
+    while let Some(i) = rest.find(CITE_START) {
+        output.push_str(&rest[..i]);
+        let placeholder = &rest[i + CITE_START.len_utf8()..];
+        let Some(end) = placeholder.find(CITE_END) else {
+            rest = placeholder;
+            continue;
+        };
+        let mut fields = placeholder[..end].split(CITE_SEP);
+        let citestyle = fields.next().unwrap_or_default();
+        let global_prefix = fields.next().unwrap_or_default();
+        let global_suffix = fields.next().unwrap_or_default();
+        let references = fields
+            .filter_map(|e| {
+                let mut parts = e.split(CITE_REF_SEP);
+                Some((parts.next()?, parts.next()?, parts.next()?))
+            })
+            .collect::<Vec<_>>();
+        output.push_str(&render_citation(
+            bibliography,
+            citestyle,
+            global_prefix,
+            &references,
+            global_suffix,
+        ));
+        rest = &placeholder[end + CITE_END.len_utf8()..];
+    }
+    output.push_str(rest);
+
+    if output.contains(BIBLIOGRAPHY_PLACEHOLDER) {
+        let bibliography = bibliography.map_or(String::new(), render_bibliography);
+        output = output.replace(BIBLIOGRAPHY_PLACEHOLDER, &bibliography);
+    }
+    output
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Paren,
+    Text,
+    Author,
+    NoAuthor,
+}
+
+fn render_citation(
+    bibliography: Option<&Bibliography>,
+    citestyle: &str,
+    global_prefix: &str,
+    references: &[(&str, &str, &str)],
+    global_suffix: &str,
+) -> String {
+    let mut parts = citestyle.trim_start_matches('/').split('/');
+    let kind = match parts.next().unwrap_or_default() {
+        "t" | "text" => Kind::Text,
+        "a" | "author" => Kind::Author,
+        "na" | "noauthor" => Kind::NoAuthor,
+        "n" | "nocite" => return String::new(),
+        _ => Kind::Paren,
+    };
+    let variant = parts.next().unwrap_or_default();
+    let has = |short: char, long: &str| {
+        variant.contains(long) || (variant.len() <= 3 && variant.contains(short))
+    };
+    let (bare, caps, full) = (has('b', "bare"), has('c', "caps"), has('f', "full"));
+    let numeric = bibliography.is_some_and(|e| e.style == CitationStyle::Numeric);
+
+    let items = references
+        .iter()
+        .map(|(prefix, key, suffix)| {
+            let Some((number, entry)) = bibliography.and_then(|e| e.get(key)) else {
+                return format!(
+                    r##"<span class="citation-error">@{}</span>"##,
+                    html_escape::encode_text(key)
+                );
+            };
+            let mut author = short_authors(entry, full);
+            if caps && let Some(first) = author.chars().next() {
+                author = format!("{}{}", first.to_uppercase(), &author[first.len_utf8()..]);
+            }
+            let year = entry.year().unwrap_or("n.d.".to_string());
+            let text = match (numeric, kind) {
+                (_, Kind::Author) => author,
+                (false, Kind::Paren) => format!("{author}, {year}"),
+                (false, Kind::Text) if bare => format!("{author} {year}"),
+                (false, Kind::Text) => format!("{author} ({year})"),
+                (false, Kind::NoAuthor) => year,
+                (true, Kind::Text) if bare => format!("{author} {number}"),
+                (true, Kind::Text) => format!("{author} [{number}]"),
+                (true, _) => number.to_string(),
+            };
+            format!(
+                r##"{}<a href="#{}" class="citation">{}</a>{}"##,
+                with_prefix(prefix),
+                html_escape::encode_double_quoted_attribute(&cite_id(key)),
+                html_escape::encode_text(&text),
+                with_suffix(suffix)
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let (separator, open, close) = match (numeric, kind) {
+        (_, Kind::Text | Kind::Author) => (", ", "", ""),
+        (false, _) => ("; ", "(", ")"),
+        (true, _) => (", ", "[", "]"),
+    };
+    let (open, close) = if bare { ("", "") } else { (open, close) };
+    format!(
+        r##"<span class="citation">{open}{}{}{}{close}</span>"##,
+        with_prefix(global_prefix),
+        items.join(separator),
+        with_suffix(global_suffix)
+    )
+}
+
+/// html id of bibliography entry
+fn cite_id(key: &str) -> String {
+    format!("cite-{key}")
+}
+
+fn with_prefix(prefix: &str) -> String {
+    match prefix.trim() {
+        "" => String::new(),
+        prefix => format!("{prefix} "),
+    }
+}
+
+fn with_suffix(suffix: &str) -> String {
+    match suffix.trim() {
+        "" => String::new(),
+        suffix if suffix.starts_with([',', ';', '.']) => suffix.to_string(),
+        suffix => format!(", {suffix}"),
+    }
+}
+
+/// "Doe", "Doe and Roe" or "Doe et al.", all authors if `full`
+fn short_authors(entry: &BibEntry, full: bool) -> String {
+    let names = entry.last_names();
+    match names.as_slice() {
+        [] => entry.key.clone(),
+        [_, _, _, ..] if !full => format!("{} et al.", names[0]),
+        _ => join_names(&names),
+    }
+}
+
+/// "A", "A and B" or "A, B, and C"
+fn join_names(names: &[String]) -> String {
+    match names {
+        [] => String::new(),
+        [name] => name.clone(),
+        [a, b] => format!("{a} and {b}"),
+        [init @ .., last] => format!("{}, and {last}", init.join(", ")),
+    }
+}
+
+/// `#+print_bibliography:`, entries are sorted by author and year in author-year style
+fn render_bibliography(bibliography: &Bibliography) -> String {
+    let mut entries = bibliography.entries.iter().collect::<Vec<_>>();
+    let tag = match bibliography.style {
+        CitationStyle::Numeric => "ol",
+        CitationStyle::AuthorYear => {
+            entries.sort_by_key(|e| (e.last_names(), e.year()));
+            "ul"
+        }
+    };
+    let items = entries
+        .into_iter()
+        .map(|e| {
+            format!(
+                r##"<li id="{}">{}</li>"##,
+                html_escape::encode_double_quoted_attribute(&cite_id(&e.key)),
+                render_entry(e, bibliography.style)
+            )
+        })
+        .collect::<String>();
+    format!(r##"<{tag} class="bibliography">{items}</{tag}>"##)
+}
+
+fn render_entry(entry: &BibEntry, style: CitationStyle) -> String {
+    let escape = |e: &str| html_escape::encode_text(e).to_string();
+    let authors = escape(&join_names(&entry.authors()));
+    let year = escape(&entry.year().unwrap_or("n.d.".to_string()));
+
+    let mut parts = vec![];
+    match style {
+        CitationStyle::AuthorYear => parts.push(format!("{authors} ({year})").trim().to_string()),
+        CitationStyle::Numeric if !authors.is_empty() => parts.push(authors),
+        CitationStyle::Numeric => {}
+    }
+    if let Some(title) = entry.field("title") {
+        parts.push(match entry.entry_type.as_str() {
+            "book" | "phdthesis" | "mastersthesis" | "manual" => {
+                format!("<i>{}</i>", escape(&title))
+            }
+            _ => escape(&title),
+        });
+    }
+    if let Some(container) = entry.field("journal").or(entry.field("booktitle")) {
+        let mut container = format!("<i>{}</i>", escape(&container));
+        if let Some(volume) = entry.field("volume") {
+            container.push_str(&format!(", {}", escape(&volume)));
+            if let Some(number) = entry.field("number") {
+                container.push_str(&format!("({})", escape(&number)));
+            }
+        }
+        if let Some(pages) = entry.field("pages") {
+            container.push_str(&format!(", {}", escape(&pages)));
+        }
+        parts.push(container);
+    }
+    if let Some(publisher) = entry
+        .field("publisher")
+        .or(entry.field("institution"))
+        .or(entry.field("school"))
+    {
+        parts.push(escape(&publisher));
+    }
+    if style == CitationStyle::Numeric {
+        parts.push(year);
+    }
+    if let Some(doi) = entry.field("doi") {
+        parts.push(format!(
+            r##"<a href="https://doi.org/{}">doi:{}</a>"##,
+            html_escape::encode_double_quoted_attribute(&doi),
+            escape(&doi)
+        ));
+    } else if let Some(url) = entry.field("url") {
+        parts.push(format!(
+            r##"<a href="{}">{}</a>"##,
+            html_escape::encode_double_quoted_attribute(&url),
+            escape(&url)
+        ));
+    }
+    // no period is added after part ending with one, e.g., "J. Sys."
+    let mut html = String::new();
+    for part in parts {
+        if !html.is_empty() {
+            html.push(' ');
+        }
+        html.push_str(&part);
+        if !part.trim_end_matches("</i>").ends_with(['.', '?', '!']) {
+            html.push('.');
+        }
+    }
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{BIBLIOGRAPHY_PLACEHOLDER, citation_placeholder, resolve_citations};
+    use crate::compiler::bibliography::{Bibliography, CitationStyle, parse_bibtex};
+
+    fn bibliography(style: CitationStyle) -> Bibliography {
+        let entries = parse_bibtex(
+            r#"
+@article{doe, author = {Doe, John}, title = {Rust?}, journal = {J. Sys.}, year = 2020,
+          volume = 1, number = 2, pages = {3--4}}
+@book{roe, author = {Roe, R. and Poe, P. and Moe, M.}, title = {Org}, year = 2019,
+       publisher = {Pub}}
+"#,
+        )
+        .unwrap();
+        Bibliography { style, entries }
+    }
+
+    fn cite(citestyle: Option<&str>, references: &[(&str, &str, &str)]) -> String {
+        let references = references
+            .iter()
+            .map(|(prefix, key, suffix)| (prefix.to_string(), *key, suffix.to_string()))
+            .collect::<Vec<_>>();
+        citation_placeholder(citestyle, "", &references, "")
+    }
+
+    #[test]
+    fn test_author_year() {
+        let bibliography = bibliography(CitationStyle::AuthorYear);
+        let html = format!(
+            "{} {} {} {}",
+            cite(None, &[("see", "doe", "p. 3"), ("", "roe", "")]),
+            cite(Some("/t"), &[("", "doe", "")]),
+            cite(Some("/na/b"), &[("", "doe", "")]),
+            cite(Some("/n"), &[("", "doe", "")]),
+        );
+        assert_eq!(
+            resolve_citations(&html, Some(&bibliography)),
+            r##"<span class="citation">(see <a href="#cite-doe" class="citation">Doe, 2020</a>, p. 3; <a href="#cite-roe" class="citation">Roe et al., 2019</a>)</span> <span class="citation"><a href="#cite-doe" class="citation">Doe (2020)</a></span> <span class="citation"><a href="#cite-doe" class="citation">2020</a></span> "##
+        );
+
+        assert_eq!(
+            resolve_citations(BIBLIOGRAPHY_PLACEHOLDER, Some(&bibliography)),
+            r##"<ul class="bibliography"><li id="cite-doe">John Doe (2020). Rust? <i>J. Sys.</i>, 1(2), 3–4.</li><li id="cite-roe">R. Roe, P. Poe, and M. Moe (2019). <i>Org</i>. Pub.</li></ul>"##
+        );
+    }
+
+    #[test]
+    fn test_numeric() {
+        let bibliography = bibliography(CitationStyle::Numeric);
+        let html = format!(
+            "{} {} {}",
+            cite(None, &[("", "doe", ""), ("", "roe", "")]),
+            cite(Some("/t/f"), &[("", "roe", "")]),
+            cite(None, &[("", "unknown", "")]),
+        );
+        assert_eq!(
+            resolve_citations(&html, Some(&bibliography)),
+            r##"<span class="citation">[<a href="#cite-doe" class="citation">1</a>, <a href="#cite-roe" class="citation">2</a>]</span> <span class="citation"><a href="#cite-roe" class="citation">Roe, Poe, and Moe [2]</a></span> <span class="citation">[<span class="citation-error">@unknown</span>]</span>"##
+        );
+        assert_eq!(
+            resolve_citations(BIBLIOGRAPHY_PLACEHOLDER, Some(&bibliography)),
+            r##"<ol class="bibliography"><li id="cite-doe">John Doe. Rust? <i>J. Sys.</i>, 1(2), 3–4. 2020.</li><li id="cite-roe">R. Roe, P. Poe, and M. Moe. <i>Org</i>. Pub. 2019.</li></ol>"##
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Knowledge graph of site for visualization
+//!
+//! - `graph.json` and `graph.html`: all roam nodes with parent and `id:` reference edges
+//! - local graph: neighbourhood of roam nodes of a page within `local_depth` edges, rendered at
+//!   the end of page
+//!
+//! Nodes are filtered by tag and node type in browser by `graph.js`, which is copied into output
+//! directory, so that graphs work offline.
+use std::collections::{HashMap, HashSet};
+
+use petgraph::visit::EdgeRef;
+use serde::{Deserialize, Serialize};
+
+use crate::compiler::dynamic_block::plain_text;
+use crate::compiler::org_roam::{EdgeType, NodeType};
+use crate::export::ssg::site::KnowledgeGraph;
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GraphConfig {
+    /// render `graph.html` and `graph.json`
+    pub enabled: bool,
+    /// max distance from roam nodes of page in local graph, `0` disables local graph
+    pub local_depth: usize,
+}
+
+impl Default for GraphConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            local_depth: 1,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphFeedNode {
+    pub id: String,
+    pub title: String,
+    pub url: String,
+    pub tags: Vec<String>,
+    pub level: u8,
+    pub node_type: NodeType,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct GraphFeedEdge {
+    pub source: String,
+    pub target: String,
+    /// `parent` or `reference`
+    #[serde(rename = "type")]
+    pub edge_type: String,
+}
+
+/// Machine-readable knowledge graph, written to `graph.json`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GraphFeed {
+    pub nodes: Vec<GraphFeedNode>,
+    pub edges: Vec<GraphFeedEdge>,
+}
+
+impl GraphFeed {
+    pub fn from_knowledge_graph(knowledge_graph: &KnowledgeGraph) -> Self {
+        let graph = &knowledge_graph.graph;
+        let mut nodes = graph
+            .node_weights()
+            .map(|node| {
+                let title = plain_text(&node.title);
+                GraphFeedNode {
+                    id: node.id.clone(),
+                    title: if title.is_empty() {
+                        node.id.clone()
+                    } else {
+                        title
+                    },
+                    url: node.url.clone(),
+                    tags: node.tags.clone(),
+                    level: node.level,
+                    node_type: node.node_type.clone(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut edges = graph
+            .edge_references()
+            .map(|edge| GraphFeedEdge {
+                source: graph[edge.source()].id.clone(),
+                target: graph[edge.target()].id.clone(),
+                edge_type: match edge.weight() {
+                    EdgeType::Parent => "parent",
+                    EdgeType::ExplicitReference { .. } => "reference",
+                }
+                .to_string(),
+            })
+            .collect::<Vec<_>>();
+
+        // nodes are collected from HashMap of sections, sort for deterministic output
+        nodes.sort_by(|a, b| a.url.cmp(&b.url));
+        edges.sort_by(|a, b| (&a.source, &a.target).cmp(&(&b.source, &b.target)));
+        Self { nodes, edges }
+    }
+
+    /// Nodes within `depth` edges from `ids` regardless of direction, and edges between them
+    pub fn neighbourhood(&self, ids: &[&str], depth: usize) -> Self {
+        let mut adjacency: HashMap<&str, Vec<&str>> = HashMap::new();
+        for edge in self.edges.iter() {
+            adjacency
+                .entry(edge.source.as_str())
+                .or_default()
+                .push(edge.target.as_str());
+            adjacency
+                .entry(edge.target.as_str())
+                .or_default()
+                .push(edge.source.as_str());
+        }
+
+        let mut visited = ids.iter().copied().collect::<HashSet<_>>();
+        let mut frontier = ids.to_vec();
+        for _ in 0..depth {
+            frontier = frontier
+                .iter()
+                .flat_map(|id| adjacency.get(id).into_iter().flatten().copied())
+                .filter(|id| visited.insert(*id))
+                .collect();
+        }
+
+        Self {
+            nodes: self
+                .nodes
+                .iter()
+                .filter(|e| visited.contains(e.id.as_str()))
+                .cloned()
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .filter(|e| {
+                    visited.contains(e.source.as_str()) && visited.contains(e.target.as_str())
+                })
+                .cloned()
+                .collect(),
+        }
+    }
+
+    /// JSON to be embedded in `<script>` of html
+    pub fn to_script_json(&self) -> String {
+        serde_json::to_string(self)
+            .expect("serialize graph")
+            .replace("</", "<\\/")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    fn node(id: &str) -> GraphFeedNode {
+        GraphFeedNode {
+            id: id.to_string(),
+            title: id.to_string(),
+            url: format!("/{id}.html#{id}"),
+            tags: vec![],
+            level: 0,
+            node_type: NodeType::File,
+        }
+    }
+
+    fn edge(source: &str, target: &str) -> GraphFeedEdge {
+        GraphFeedEdge {
+            source: source.to_string(),
+            target: target.to_string(),
+            edge_type: "reference".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_neighbourhood() {
+        // a -> b -> c <- d, e
+        let feed = GraphFeed {
+            nodes: ["a", "b", "c", "d", "e"].into_iter().map(node).collect(),
+            edges: vec![edge("a", "b"), edge("b", "c"), edge("d", "c")],
+        };
+        let ids = |feed: &GraphFeed| feed.nodes.iter().map(|e| e.id.clone()).collect::<Vec<_>>();
+
+        assert_eq!(ids(&feed.neighbourhood(&["b"], 0)), vec!["b"]);
+        assert_eq!(ids(&feed.neighbourhood(&["b"], 1)), vec!["a", "b", "c"]);
+        assert_eq!(feed.neighbourhood(&["b"], 1).edges.len(), 2);
+        assert_eq!(
+            ids(&feed.neighbourhood(&["b"], 2)),
+            vec!["a", "b", "c", "d"]
+        );
+        assert_eq!(ids(&feed.neighbourhood(&["e"], 2)), vec!["e"]);
+    }
+
+    #[test]
+    fn test_to_script_json() {
+        let mut feed = GraphFeed {
+            nodes: vec![node("a")],
+            edges: vec![],
+        };
+        feed.nodes[0].title = "</script>".to_string();
+        assert!(feed.to_script_json().contains(r#""title":"<\/script>""#));
+    }
+}
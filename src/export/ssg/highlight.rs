@@ -0,0 +1,1164 @@
+//! Syntax highlighting of src block at build time, no javascript or network is needed.
+//!
+//! A small lexer of each supported language splits code into tokens (keyword, string, comment,
+//! ...), which are rendered as `<span class="hl-*">`. Colors are defined by [`HighlightTheme`],
+//! whose stylesheet is written as `highlight.css` next to `default.css`.
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum HighlightTheme {
+    #[default]
+    GithubLight,
+    GithubDark,
+    Monokai,
+    SolarizedLight,
+    SolarizedDark,
+}
+
+struct Palette {
+    background: &'static str,
+    foreground: &'static str,
+    keyword: &'static str,
+    r#type: &'static str,
+    constant: &'static str,
+    string: &'static str,
+    number: &'static str,
+    comment: &'static str,
+    function: &'static str,
+    meta: &'static str,
+    variable: &'static str,
+    line_number: &'static str,
+    highlight: &'static str,
+}
+
+impl HighlightTheme {
+    fn palette(self) -> Palette {
+        match self {
+            HighlightTheme::GithubLight => Palette {
+                background: "#f6f8fa",
+                foreground: "#24292f",
+                keyword: "#cf222e",
+                r#type: "#953800",
+                constant: "#0550ae",
+                string: "#0a3069",
+                number: "#0550ae",
+                comment: "#6e7781",
+                function: "#8250df",
+                meta: "#116329",
+                variable: "#953800",
+                line_number: "#8c959f",
+                highlight: "#fff8c5",
+            },
+            HighlightTheme::GithubDark => Palette {
+                background: "#161b22",
+                foreground: "#c9d1d9",
+                keyword: "#ff7b72",
+                r#type: "#ffa657",
+                constant: "#79c0ff",
+                string: "#a5d6ff",
+                number: "#79c0ff",
+                comment: "#8b949e",
+                function: "#d2a8ff",
+                meta: "#7ee787",
+                variable: "#ffa657",
+                line_number: "#6e7681",
+                highlight: "#3b2e00",
+            },
+            HighlightTheme::Monokai => Palette {
+                background: "#272822",
+                foreground: "#f8f8f2",
+                keyword: "#f92672",
+                r#type: "#66d9ef",
+                constant: "#ae81ff",
+                string: "#e6db74",
+                number: "#ae81ff",
+                comment: "#75715e",
+                function: "#a6e22e",
+                meta: "#f92672",
+                variable: "#fd971f",
+                line_number: "#90908a",
+                highlight: "#49483e",
+            },
+            HighlightTheme::SolarizedLight => Palette {
+                background: "#fdf6e3",
+                foreground: "#657b83",
+                keyword: "#859900",
+                r#type: "#b58900",
+                constant: "#cb4b16",
+                string: "#2aa198",
+                number: "#d33682",
+                comment: "#93a1a1",
+                function: "#268bd2",
+                meta: "#6c71c4",
+                variable: "#b58900",
+                line_number: "#93a1a1",
+                highlight: "#eee8d5",
+            },
+            HighlightTheme::SolarizedDark => Palette {
+                background: "#002b36",
+                foreground: "#839496",
+                keyword: "#859900",
+                r#type: "#b58900",
+                constant: "#cb4b16",
+                string: "#2aa198",
+                number: "#d33682",
+                comment: "#586e75",
+                function: "#268bd2",
+                meta: "#6c71c4",
+                variable: "#b58900",
+                line_number: "#586e75",
+                highlight: "#073642",
+            },
+        }
+    }
+
+    /// Stylesheet of the theme, i.e., content of `highlight.css`
+    pub fn stylesheet(self) -> String {
+        let p = self.palette();
+        format!(
+            r##"/* generated by windancer, theme: {self:?} */
+pre.src {{ background-color: {background}; color: {foreground}; }}
+pre.src:before {{ background-color: {background}99; color: {line_number}; }}
+.hl-keyword {{ color: {keyword}; font-weight: bold; }}
+.hl-type {{ color: {type}; }}
+.hl-constant {{ color: {constant}; }}
+.hl-string {{ color: {string}; }}
+.hl-number {{ color: {number}; }}
+.hl-comment {{ color: {comment}; font-style: italic; }}
+.hl-function {{ color: {function}; }}
+.hl-meta {{ color: {meta}; }}
+.hl-variable {{ color: {variable}; }}
+.linenr {{ color: {line_number}; user-select: none; padding-right: 1em; }}
+.coderef {{ color: {comment}; }}
+.coderef-target:target {{ background-color: {highlight}; }}
+"##,
+            background = p.background,
+            foreground = p.foreground,
+            keyword = p.keyword,
+            type = p.r#type,
+            constant = p.constant,
+            string = p.string,
+            number = p.number,
+            comment = p.comment,
+            function = p.function,
+            meta = p.meta,
+            variable = p.variable,
+            line_number = p.line_number,
+            highlight = p.highlight,
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Plain,
+    Keyword,
+    Type,
+    Constant,
+    String,
+    Number,
+    Comment,
+    Function,
+    Meta,
+    Variable,
+}
+
+impl TokenKind {
+    fn class(self) -> Option<&'static str> {
+        match self {
+            TokenKind::Plain => None,
+            TokenKind::Keyword => Some("hl-keyword"),
+            TokenKind::Type => Some("hl-type"),
+            TokenKind::Constant => Some("hl-constant"),
+            TokenKind::String => Some("hl-string"),
+            TokenKind::Number => Some("hl-number"),
+            TokenKind::Comment => Some("hl-comment"),
+            TokenKind::Function => Some("hl-function"),
+            TokenKind::Meta => Some("hl-meta"),
+            TokenKind::Variable => Some("hl-variable"),
+        }
+    }
+}
+
+struct Syntax {
+    line_comments: &'static [&'static str],
+    block_comments: &'static [(&'static str, &'static str)],
+    /// delimiters of string, longer one first
+    strings: &'static [&'static str],
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    constants: &'static [&'static str],
+    /// chars allowed in identifier besides alphanumeric and `_`
+    ident_chars: &'static str,
+    /// capitalized identifier is type, all uppercase one is constant
+    capitalized_types: bool,
+    /// `(name` is function call, i.e., lisp
+    prefix_call: bool,
+    /// `name!` is macro, i.e., rust
+    bang_macro: bool,
+    /// `'x'` is char while `'a` is lifetime, i.e., rust
+    char_literal: bool,
+    /// line starting with it is meta, e.g., `#` of C preprocessor
+    meta_line: Option<&'static str>,
+    /// identifier starting with it is meta, e.g., `@` of python decorator
+    meta_prefix: Option<char>,
+    /// identifier starting with it is variable, e.g., `$` of shell
+    variable_prefix: Option<char>,
+}
+
+const PLAIN: Syntax = Syntax {
+    line_comments: &[],
+    block_comments: &[],
+    strings: &["\""],
+    keywords: &[],
+    types: &[],
+    constants: &[],
+    ident_chars: "",
+    capitalized_types: false,
+    prefix_call: false,
+    bang_macro: false,
+    char_literal: false,
+    meta_line: None,
+    meta_prefix: None,
+    variable_prefix: None,
+};
+
+static RUST: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &["\""],
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum",
+        "extern", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut",
+        "pub", "ref", "return", "self", "Self", "static", "struct", "super", "trait", "type",
+        "unsafe", "use", "where", "while", "yield",
+    ],
+    types: &[
+        "bool", "char", "str", "u8", "u16", "u32", "u64", "u128", "usize", "i8", "i16", "i32",
+        "i64", "i128", "isize", "f32", "f64",
+    ],
+    constants: &["true", "false", "None", "Some", "Ok", "Err"],
+    capitalized_types: true,
+    bang_macro: true,
+    char_literal: true,
+    meta_line: Some("#"),
+    ..PLAIN
+};
+
+static PYTHON: Syntax = Syntax {
+    line_comments: &["#"],
+    strings: &["\"\"\"", "'''", "\"", "'"],
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del",
+        "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in", "is",
+        "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with",
+        "yield", "match", "case",
+    ],
+    types: &[
+        "int", "float", "str", "bytes", "bool", "list", "dict", "set", "tuple", "object", "type",
+    ],
+    constants: &["True", "False", "None", "self", "cls"],
+    capitalized_types: true,
+    meta_prefix: Some('@'),
+    ..PLAIN
+};
+
+static C: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &["\"", "'"],
+    keywords: &[
+        "auto",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "constexpr",
+        "continue",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "enum",
+        "explicit",
+        "extern",
+        "for",
+        "friend",
+        "goto",
+        "if",
+        "inline",
+        "namespace",
+        "new",
+        "operator",
+        "private",
+        "protected",
+        "public",
+        "register",
+        "return",
+        "sizeof",
+        "static",
+        "struct",
+        "switch",
+        "template",
+        "this",
+        "throw",
+        "try",
+        "typedef",
+        "typename",
+        "union",
+        "using",
+        "virtual",
+        "volatile",
+        "while",
+    ],
+    types: &[
+        "bool", "char", "double", "float", "int", "long", "short", "signed", "unsigned", "void",
+        "size_t", "int8_t", "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t",
+        "uint64_t", "std", "string", "vector",
+    ],
+    constants: &["true", "false", "NULL", "nullptr"],
+    meta_line: Some("#"),
+    ..PLAIN
+};
+
+static JAVA: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &["\"", "'"],
+    keywords: &[
+        "abstract",
+        "assert",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "continue",
+        "default",
+        "do",
+        "else",
+        "enum",
+        "extends",
+        "final",
+        "finally",
+        "for",
+        "if",
+        "implements",
+        "import",
+        "instanceof",
+        "interface",
+        "native",
+        "new",
+        "package",
+        "private",
+        "protected",
+        "public",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "synchronized",
+        "this",
+        "throw",
+        "throws",
+        "try",
+        "var",
+        "volatile",
+        "while",
+    ],
+    types: &[
+        "boolean", "byte", "char", "double", "float", "int", "long", "short", "void",
+    ],
+    constants: &["true", "false", "null"],
+    capitalized_types: true,
+    meta_prefix: Some('@'),
+    ..PLAIN
+};
+
+static JAVASCRIPT: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &["\"", "'", "`"],
+    keywords: &[
+        "async",
+        "await",
+        "break",
+        "case",
+        "catch",
+        "class",
+        "const",
+        "continue",
+        "debugger",
+        "default",
+        "delete",
+        "do",
+        "else",
+        "export",
+        "extends",
+        "finally",
+        "for",
+        "from",
+        "function",
+        "if",
+        "import",
+        "in",
+        "instanceof",
+        "interface",
+        "let",
+        "new",
+        "of",
+        "return",
+        "static",
+        "super",
+        "switch",
+        "this",
+        "throw",
+        "try",
+        "type",
+        "typeof",
+        "var",
+        "void",
+        "while",
+        "with",
+        "yield",
+    ],
+    types: &[
+        "any", "boolean", "number", "string", "unknown", "never", "object", "symbol",
+    ],
+    constants: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+    ident_chars: "$",
+    capitalized_types: true,
+    meta_prefix: Some('@'),
+    ..PLAIN
+};
+
+static GO: Syntax = Syntax {
+    line_comments: &["//"],
+    block_comments: &[("/*", "*/")],
+    strings: &["\"", "`", "'"],
+    keywords: &[
+        "break",
+        "case",
+        "chan",
+        "const",
+        "continue",
+        "default",
+        "defer",
+        "else",
+        "fallthrough",
+        "for",
+        "func",
+        "go",
+        "goto",
+        "if",
+        "import",
+        "interface",
+        "map",
+        "package",
+        "range",
+        "return",
+        "select",
+        "struct",
+        "switch",
+        "type",
+        "var",
+    ],
+    types: &[
+        "bool",
+        "byte",
+        "complex64",
+        "complex128",
+        "error",
+        "float32",
+        "float64",
+        "int",
+        "int8",
+        "int16",
+        "int32",
+        "int64",
+        "rune",
+        "string",
+        "uint",
+        "uint8",
+        "uint16",
+        "uint32",
+        "uint64",
+        "uintptr",
+        "any",
+    ],
+    constants: &["true", "false", "nil", "iota"],
+    ..PLAIN
+};
+
+static SHELL: Syntax = Syntax {
+    line_comments: &["#"],
+    strings: &["\"", "'"],
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac",
+        "in", "function", "return", "local", "export", "readonly", "set", "unset", "source",
+        "alias", "exit", "echo", "cd", "shift", "eval", "exec", "trap",
+    ],
+    constants: &["true", "false"],
+    variable_prefix: Some('$'),
+    ..PLAIN
+};
+
+static LISP: Syntax = Syntax {
+    line_comments: &[";"],
+    block_comments: &[("#|", "|#")],
+    strings: &["\""],
+    keywords: &[
+        "defun",
+        "defmacro",
+        "defvar",
+        "defcustom",
+        "defconst",
+        "defgroup",
+        "defface",
+        "defsubst",
+        "define",
+        "define-key",
+        "lambda",
+        "let",
+        "let*",
+        "if",
+        "when",
+        "unless",
+        "cond",
+        "progn",
+        "prog1",
+        "setq",
+        "setf",
+        "and",
+        "or",
+        "not",
+        "while",
+        "dolist",
+        "dotimes",
+        "catch",
+        "throw",
+        "condition-case",
+        "unwind-protect",
+        "save-excursion",
+        "require",
+        "provide",
+        "interactive",
+        "use-package",
+        "quote",
+        "function",
+        "loop",
+        "case",
+        "ns",
+        "defn",
+        "def",
+        "fn",
+        "do",
+    ],
+    constants: &["nil", "t", "#t", "#f"],
+    ident_chars: "-*+/<>=?!%&.",
+    prefix_call: true,
+    meta_prefix: Some(':'),
+    ..PLAIN
+};
+
+static SQL: Syntax = Syntax {
+    line_comments: &["--"],
+    block_comments: &[("/*", "*/")],
+    strings: &["'", "\""],
+    keywords: &[
+        "select",
+        "from",
+        "where",
+        "and",
+        "or",
+        "not",
+        "insert",
+        "into",
+        "values",
+        "update",
+        "set",
+        "delete",
+        "create",
+        "table",
+        "drop",
+        "alter",
+        "index",
+        "view",
+        "join",
+        "left",
+        "right",
+        "inner",
+        "outer",
+        "full",
+        "on",
+        "as",
+        "group",
+        "by",
+        "order",
+        "having",
+        "limit",
+        "offset",
+        "union",
+        "all",
+        "distinct",
+        "case",
+        "when",
+        "then",
+        "else",
+        "end",
+        "in",
+        "is",
+        "like",
+        "between",
+        "exists",
+        "primary",
+        "key",
+        "foreign",
+        "references",
+        "with",
+        "asc",
+        "desc",
+        "SELECT",
+        "FROM",
+        "WHERE",
+        "AND",
+        "OR",
+        "NOT",
+        "INSERT",
+        "INTO",
+        "VALUES",
+        "UPDATE",
+        "SET",
+        "DELETE",
+        "CREATE",
+        "TABLE",
+        "DROP",
+        "ALTER",
+        "INDEX",
+        "VIEW",
+        "JOIN",
+        "LEFT",
+        "RIGHT",
+        "INNER",
+        "OUTER",
+        "FULL",
+        "ON",
+        "AS",
+        "GROUP",
+        "BY",
+        "ORDER",
+        "HAVING",
+        "LIMIT",
+        "OFFSET",
+        "UNION",
+        "ALL",
+        "DISTINCT",
+        "CASE",
+        "WHEN",
+        "THEN",
+        "ELSE",
+        "END",
+        "IN",
+        "IS",
+        "LIKE",
+        "BETWEEN",
+        "EXISTS",
+        "PRIMARY",
+        "KEY",
+        "FOREIGN",
+        "REFERENCES",
+        "WITH",
+        "ASC",
+        "DESC",
+    ],
+    types: &[
+        "int",
+        "integer",
+        "bigint",
+        "text",
+        "varchar",
+        "char",
+        "boolean",
+        "date",
+        "timestamp",
+        "float",
+        "real",
+        "numeric",
+        "INT",
+        "INTEGER",
+        "BIGINT",
+        "TEXT",
+        "VARCHAR",
+        "CHAR",
+        "BOOLEAN",
+        "DATE",
+        "TIMESTAMP",
+        "FLOAT",
+        "REAL",
+        "NUMERIC",
+    ],
+    constants: &["null", "true", "false", "NULL", "TRUE", "FALSE"],
+    ..PLAIN
+};
+
+static JSON: Syntax = Syntax {
+    constants: &["true", "false", "null"],
+    ..PLAIN
+};
+
+static CONF: Syntax = Syntax {
+    line_comments: &["#"],
+    strings: &["\"\"\"", "'''", "\"", "'"],
+    constants: &["true", "false", "null", "yes", "no", "on", "off"],
+    ident_chars: "-",
+    meta_line: Some("["),
+    ..PLAIN
+};
+
+fn syntax_of(language: &str) -> Option<&'static Syntax> {
+    match language.to_lowercase().as_str() {
+        "rust" | "rs" => Some(&RUST),
+        "python" | "py" | "python3" | "ipython" => Some(&PYTHON),
+        "c" | "cpp" | "c++" | "cc" | "h" | "hpp" | "objc" => Some(&C),
+        "java" | "kotlin" | "scala" | "csharp" | "c#" => Some(&JAVA),
+        "js" | "javascript" | "ts" | "typescript" | "jsx" | "tsx" => Some(&JAVASCRIPT),
+        "go" | "golang" => Some(&GO),
+        "sh" | "bash" | "shell" | "zsh" | "fish" => Some(&SHELL),
+        "emacs-lisp" | "elisp" | "lisp" | "scheme" | "clojure" | "racket" => Some(&LISP),
+        "sql" | "sqlite" | "postgresql" | "mysql" => Some(&SQL),
+        "json" => Some(&JSON),
+        "toml" | "yaml" | "yml" | "conf" | "ini" => Some(&CONF),
+        _ => None,
+    }
+}
+
+/// Tokens of `code`, `None` if `language` is not supported
+pub fn highlight<'a>(language: &str, code: &'a str) -> Option<Vec<(TokenKind, &'a str)>> {
+    syntax_of(language).map(|syntax| tokenize(syntax, code))
+}
+
+/// Highlighted html of each line of `code`, i.e., one item for each of `code.split('\n')`.
+///
+/// Code of unsupported language is escaped only.
+pub fn highlight_lines(language: &str, code: &str) -> Vec<String> {
+    let tokens = highlight(language, code).unwrap_or_else(|| vec![(TokenKind::Plain, code)]);
+
+    // tokens, e.g., block comment, may cross lines
+    let mut lines = vec![String::new()];
+    for (kind, text) in tokens {
+        for (i, part) in text.split('\n').enumerate() {
+            if i > 0 {
+                lines.push(String::new());
+            }
+            if part.is_empty() {
+                continue;
+            }
+            let line = lines.last_mut().expect("at least one line");
+            let escaped = html_escape::encode_text(part);
+            match kind.class() {
+                Some(class) => {
+                    line.push_str(&format!(r##"<span class="{class}">{escaped}</span>"##))
+                }
+                None => line.push_str(&escaped),
+            }
+        }
+    }
+    lines
+}
+
+fn tokenize<'a>(syntax: &Syntax, code: &'a str) -> Vec<(TokenKind, &'a str)> {
+    // (kind, start, end)
+    let mut tokens: Vec<(TokenKind, usize, usize)> = vec![];
+    let mut pos = 0;
+    // only whitespaces before `pos` in current line
+    let mut at_line_start = true;
+    while let Some(c) = code[pos..].chars().next() {
+        let rest = &code[pos..];
+        let after_open_paren = code[..pos].trim_end().ends_with('(');
+
+        let (kind, len) = if at_line_start
+            && let Some(prefix) = syntax.meta_line
+            && rest.starts_with(prefix)
+        {
+            (TokenKind::Meta, line_len(rest))
+        } else if syntax.line_comments.iter().any(|e| rest.starts_with(e)) {
+            (TokenKind::Comment, line_len(rest))
+        } else if let Some((open, close)) = syntax
+            .block_comments
+            .iter()
+            .find(|(open, _)| rest.starts_with(open))
+        {
+            let len = rest[open.len()..]
+                .find(close)
+                .map_or(rest.len(), |i| open.len() + i + close.len());
+            (TokenKind::Comment, len)
+        } else if let Some(quote) = syntax.strings.iter().find(|e| rest.starts_with(**e)) {
+            (TokenKind::String, string_len(rest, quote))
+        } else if syntax.char_literal && c == '\'' {
+            match char_literal_len(rest) {
+                Some(len) => (TokenKind::String, len),
+                // lifetime
+                None => (TokenKind::Variable, 1 + ident_len(syntax, &rest[1..])),
+            }
+        } else if c.is_ascii_digit() {
+            (TokenKind::Number, number_len(rest))
+        } else if Some(c) == syntax.variable_prefix {
+            // `${name}`, `$name` or special one like `$?`
+            let name = &rest[1..];
+            let len = match name.strip_prefix('{') {
+                Some(s) => s.find('}').map_or(rest.len(), |i| i + 3),
+                None if ident_len(syntax, name) > 0 => 1 + ident_len(syntax, name),
+                None if name.starts_with(|c: char| c.is_ascii_punctuation()) => 2,
+                None => 1,
+            };
+            (TokenKind::Variable, len)
+        } else if Some(c) == syntax.meta_prefix && ident_len(syntax, &rest[1..]) > 0 {
+            (TokenKind::Meta, 1 + ident_len(syntax, &rest[1..]))
+        } else if is_ident_start(syntax, c) {
+            let len = ident_len(syntax, rest);
+            let word = &rest[..len];
+            let next = &rest[len..];
+            if syntax.bang_macro && next.starts_with('!') && !next.starts_with("!=") {
+                (TokenKind::Meta, len + 1)
+            } else {
+                (classify(syntax, word, next, after_open_paren), len)
+            }
+        } else {
+            (TokenKind::Plain, c.len_utf8())
+        };
+
+        let text = &rest[..len];
+        at_line_start = match text.rfind('\n') {
+            Some(i) => text[i + 1..].trim().is_empty(),
+            None => at_line_start && text.trim().is_empty(),
+        };
+        match tokens.last_mut() {
+            Some((last_kind, _, end)) if *last_kind == kind && kind == TokenKind::Plain => {
+                *end = pos + len
+            }
+            _ => tokens.push((kind, pos, pos + len)),
+        }
+        pos += len;
+    }
+
+    tokens
+        .into_iter()
+        .map(|(kind, start, end)| (kind, &code[start..end]))
+        .collect()
+}
+
+fn classify(syntax: &Syntax, word: &str, next: &str, after_open_paren: bool) -> TokenKind {
+    if syntax.keywords.contains(&word) {
+        TokenKind::Keyword
+    } else if syntax.constants.contains(&word) {
+        TokenKind::Constant
+    } else if syntax.types.contains(&word) {
+        TokenKind::Type
+    } else if next.starts_with('(') || (syntax.prefix_call && after_open_paren) {
+        TokenKind::Function
+    } else if syntax.capitalized_types && word.starts_with(|c: char| c.is_ascii_uppercase()) {
+        if word.chars().any(|c| c.is_ascii_lowercase()) {
+            TokenKind::Type
+        } else {
+            TokenKind::Constant
+        }
+    } else {
+        TokenKind::Plain
+    }
+}
+
+fn is_ident_start(syntax: &Syntax, c: char) -> bool {
+    c.is_alphabetic() || c == '_' || syntax.ident_chars.contains(c)
+}
+
+fn ident_len(syntax: &Syntax, s: &str) -> usize {
+    s.char_indices()
+        .find(|(_, c)| !(c.is_alphanumeric() || *c == '_' || syntax.ident_chars.contains(*c)))
+        .map_or(s.len(), |(i, _)| i)
+}
+
+fn line_len(s: &str) -> usize {
+    s.find('\n').unwrap_or(s.len())
+}
+
+// backslash escapes the next char, unterminated string lasts to the end
+fn string_len(s: &str, quote: &str) -> usize {
+    let mut chars = s.char_indices().skip(quote.chars().count());
+    while let Some((i, c)) = chars.next() {
+        if c == '\\' {
+            chars.next();
+        } else if s[i..].starts_with(quote) {
+            return i + quote.len();
+        }
+    }
+    s.len()
+}
+
+// `'x'` or `'\n'`, `None` for lifetime `'a`
+fn char_literal_len(s: &str) -> Option<usize> {
+    let body = &s[1..];
+    let mut chars = body.char_indices();
+    let (_, c) = chars.next()?;
+    if c == '\\' {
+        // the escaped char may be `'` or multibyte, search the closing quote after it
+        let (i, escaped) = chars.next()?;
+        let start = i + escaped.len_utf8();
+        body[start..].find('\'').map(|j| start + j + 2)
+    } else {
+        body[c.len_utf8()..]
+            .starts_with('\'')
+            .then(|| 2 + c.len_utf8())
+    }
+}
+
+// `1_000`, `0x1f`, `1.5e3`, `2u8`, but `1..2` is a range
+fn number_len(s: &str) -> usize {
+    let bytes = s.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        let b = bytes[i];
+        let is_decimal_point = b == b'.' && bytes.get(i + 1).is_some_and(|e| e.is_ascii_digit());
+        if !(b.is_ascii_alphanumeric() || b == b'_' || is_decimal_point) {
+            break;
+        }
+        i += 1;
+    }
+    i
+}
+
+/// How lines of src block are numbered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineNumbering {
+    /// `-n N`: start from N, default 1
+    Restart(usize),
+    /// `+n N`: continue from previous numbered block, plus N, default 0
+    Continue(usize),
+}
+
+/// Switches of src block, e.g., `-n 10 -r -l "[%s]"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SrcSwitches {
+    pub line_numbering: Option<LineNumbering>,
+    /// `-r`: remove coderef labels from code
+    pub remove_labels: bool,
+    /// `-l "FORMAT"`: format of coderef label, `%s` is the label
+    pub label_format: String,
+}
+
+impl Default for SrcSwitches {
+    fn default() -> Self {
+        Self {
+            line_numbering: None,
+            remove_labels: false,
+            label_format: "(ref:%s)".to_string(),
+        }
+    }
+}
+
+impl SrcSwitches {
+    /// Parse switches split by whitespace
+    pub fn parse(switches: &[String]) -> Self {
+        let mut ans = Self::default();
+        let mut iter = switches.iter().peekable();
+        while let Some(switch) = iter.next() {
+            match switch.as_str() {
+                "-n" | "+n" => {
+                    let n = iter.next_if(|e| e.parse::<usize>().is_ok());
+                    let n = n.map(|e| e.parse::<usize>().expect("checked"));
+                    ans.line_numbering = Some(if switch == "-n" {
+                        LineNumbering::Restart(n.unwrap_or(1))
+                    } else {
+                        LineNumbering::Continue(n.unwrap_or(0))
+                    });
+                }
+                "-r" => ans.remove_labels = true,
+                "-l" => {
+                    // quoted format may be split by whitespace
+                    let mut format = iter.next().cloned().unwrap_or_default();
+                    while format.starts_with('"') && (format.len() == 1 || !format.ends_with('"')) {
+                        let Some(part) = iter.next() else {
+                            break;
+                        };
+                        format.push(' ');
+                        format.push_str(part);
+                    }
+                    let format = format.trim_matches('"');
+                    if format.contains("%s") {
+                        ans.label_format = format.to_string();
+                    }
+                }
+                _ => {}
+            }
+        }
+        ans
+    }
+
+    /// Split coderef label from the end of `line`: (code, label)
+    pub fn split_label<'a>(&self, line: &'a str) -> Option<(&'a str, &'a str)> {
+        let (prefix, suffix) = self.label_format.split_once("%s")?;
+        let body = line.trim_end().strip_suffix(suffix)?;
+        let i = body.rfind(prefix)?;
+        let label = &body[i + prefix.len()..];
+        let code = &line[..i];
+        let is_label = !label.is_empty()
+            && !label.starts_with(' ')
+            && label
+                .chars()
+                .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | ' '));
+        (is_label && (code.is_empty() || code.ends_with(char::is_whitespace)))
+            .then_some((code, label))
+    }
+}
+
+/// html id of line with coderef `label`
+pub fn coderef_id(label: &str) -> String {
+    format!("coderef-{}", label.replace(' ', "-"))
+}
+
+/// Highlighted html of `code` in src block, lines are numbered from `first_line_number` if
+/// numbered, and lines with coderef label have id of [`coderef_id`].
+pub fn render_code(
+    language: &str,
+    code: &str,
+    switches: &SrcSwitches,
+    first_line_number: usize,
+) -> String {
+    let lines = code.split('\n').collect::<Vec<_>>();
+    let labels = lines
+        .iter()
+        .map(|line| switches.split_label(line))
+        .collect::<Vec<_>>();
+    let code_without_labels = lines
+        .iter()
+        .zip(labels.iter())
+        .map(|(line, label)| match label {
+            Some((code, _)) if switches.remove_labels => code.trim_end(),
+            Some((code, _)) => code,
+            None => line,
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let last_line_number = first_line_number + lines.len().saturating_sub(1);
+    let width = last_line_number.to_string().len();
+    highlight_lines(language, &code_without_labels)
+        .into_iter()
+        .zip(labels)
+        .enumerate()
+        .map(|(i, (html, label))| {
+            let mut line = String::new();
+            if switches.line_numbering.is_some() {
+                let n = first_line_number + i;
+                line.push_str(&format!(r##"<span class="linenr">{n:>width$}</span>"##));
+            }
+            line.push_str(&html);
+            match label {
+                Some((_, label)) => {
+                    if !switches.remove_labels {
+                        let text = switches.label_format.replace("%s", label);
+                        line.push_str(&format!(
+                            r##"<span class="coderef">{}</span>"##,
+                            html_escape::encode_text(&text)
+                        ));
+                    }
+                    format!(
+                        r##"<span id="{}" class="coderef-target">{line}</span>"##,
+                        html_escape::encode_double_quoted_attribute(&coderef_id(label))
+                    )
+                }
+                None => line,
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::{LineNumbering, SrcSwitches, TokenKind, highlight, render_code};
+
+    #[test]
+    fn test_highlight_rust() {
+        let tokens = highlight(
+            "rust",
+            "fn main() {\n    let s = \"a\\\"b\"; // c\n    println!(\"{}\", 'x');\n}",
+        )
+        .expect("supported");
+        let kinds = tokens
+            .iter()
+            .filter(|(kind, _)| *kind != TokenKind::Plain)
+            .map(|(kind, text)| (*kind, *text))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            vec![
+                (TokenKind::Keyword, "fn"),
+                (TokenKind::Function, "main"),
+                (TokenKind::Keyword, "let"),
+                (TokenKind::String, "\"a\\\"b\""),
+                (TokenKind::Comment, "// c"),
+                (TokenKind::Meta, "println!"),
+                (TokenKind::String, "\"{}\""),
+                (TokenKind::String, "'x'"),
+            ]
+        );
+        assert!(highlight("unknown", "x").is_none());
+    }
+
+    #[test]
+    fn test_char_literal_escape() {
+        let tokens = highlight("rust", "let c = '\\é';").expect("supported");
+        assert!(tokens.contains(&(TokenKind::String, "'\\é'")));
+
+        // unterminated escape at the end of input
+        for code in ["'\\", "let c = '\\"] {
+            let tokens = highlight("rust", code).expect("supported");
+            assert_eq!(
+                tokens.iter().map(|(_, text)| *text).collect::<String>(),
+                code
+            );
+        }
+    }
+
+    #[test]
+    fn test_block_comment_across_lines() {
+        assert_eq!(
+            render_code("c", "/* a\n b */ int x;", &SrcSwitches::default(), 1),
+            r##"<span class="hl-comment">/* a</span>
+<span class="hl-comment"> b */</span> <span class="hl-type">int</span> x;"##
+        );
+    }
+
+    #[test]
+    fn test_switches() {
+        let switches =
+            SrcSwitches::parse(&["+n", "10", "-r", "-l", "\"[[%s]]\""].map(String::from));
+        assert_eq!(switches.line_numbering, Some(LineNumbering::Continue(10)));
+        assert!(switches.remove_labels);
+        assert_eq!(switches.label_format, "[[%s]]");
+
+        let switches = SrcSwitches::parse(&["-n", "-l", "\"<ref %s>\""].map(String::from));
+        assert_eq!(switches.line_numbering, Some(LineNumbering::Restart(1)));
+        assert_eq!(switches.label_format, "<ref %s>");
+    }
+
+    #[test]
+    fn test_line_numbers_and_coderef() {
+        let code = "x = 1  (ref:init)\ny = x";
+        let switches = SrcSwitches::parse(&["-n", "9"].map(String::from));
+        assert_eq!(
+            render_code("python", code, &switches, 9),
+            r##"<span id="coderef-init" class="coderef-target"><span class="linenr"> 9</span>x = <span class="hl-number">1</span>  <span class="coderef">(ref:init)</span></span>
+<span class="linenr">10</span>y = x"##
+        );
+
+        let switches = SrcSwitches::parse(&["-r"].map(String::from));
+        assert_eq!(
+            render_code("unknown", code, &switches, 1),
+            "<span id=\"coderef-init\" class=\"coderef-target\">x = 1</span>\ny = x"
+        );
+    }
+}
@@ -32,25 +32,39 @@
 //! - css: better apperance
 //! - title: property
 //! - footnote
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
 use std::hash::{DefaultHasher, Hash, Hasher};
+use std::panic::{self, AssertUnwindSafe};
 use std::path::{Path, PathBuf};
 
 use chrono::{Datelike, Local};
 use html_escape;
+use rayon::prelude::*;
 use serde::Deserialize;
 
 use crate::compiler::ast_builder::element::{
-    self, CenterBlock, Drawer, Element, ExampleBlock, ExportBlock, FixedWidth, FootnoteDefinition,
-    HeadingSubtree, Id, Item, Keyword, LatexEnvironment, List, ListType, OrgFile, Paragraph,
-    QuoteBlock, Section, SpecialBlock, SrcBlock, Table, TableRow, TableRowType, VerseBlock,
+    self, BabelCall, CachedResults, CenterBlock, Drawer, DynamicBlock, Element, ExampleBlock,
+    ExportBlock, FixedWidth, FootnoteDefinition, HeadingSubtree, InlineTask, Item, Keyword,
+    LatexEnvironment, List, ListType, OrgFile, Paragraph, QuoteBlock, Section, SpecialBlock,
+    SrcBlock, Table, TableRow, TableRowType, VerseBlock,
 };
-use crate::compiler::ast_builder::object::{GeneralLink, Object, TableCellType};
+use crate::compiler::ast_builder::object::{
+    GeneralLink, Object, TableCellType, expand_link_template,
+};
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::dynamic_block::format_minutes;
+use crate::compiler::math::{self, Equations};
 use crate::constants::entity::ENTITYNAME_TO_HTML;
+use crate::export::org::OrgWriter;
+use crate::export::ssg::backlinks::{Backlink, Backlinks};
+use crate::export::ssg::citation;
+use crate::export::ssg::graph::GraphFeed;
+use crate::export::ssg::highlight::{self, HighlightTheme, LineNumbering, SrcSwitches};
 use crate::export::ssg::site::{Page, PageId, Site};
 use crate::export::ssg::toc::{TableOfContents, TocNode};
 use crate::export::ssg::view_model::{PageNavContext, TableViewModel};
+use crate::utils::target_anchor;
 
 fn hash_string_to_usize(input: &str) -> usize {
     let mut hasher = DefaultHasher::new();
@@ -58,37 +72,59 @@ fn hash_string_to_usize(input: &str) -> usize {
     hasher.finish() as usize
 }
 
+/// Context shared by all pages of site
 pub struct RendererContext {
     tera: tera::Tera,
-    // table counter in sit or page?
-    pub table_counter: usize,
-    pub figure_counter: usize,
     pub toc: TableOfContents,
     pub pageid_to_url: HashMap<PageId, String>,
     pub roamid_to_url: HashMap<String, String>,
-    pub prev_head_level: Vec<u8>,
+    pub backlinks: Backlinks,
+    pub graph: GraphFeed,
+    /// max distance of local graph of page, `0` disables local graph
+    pub local_graph_depth: usize,
 }
 
 impl Default for RendererContext {
     fn default() -> Self {
-        let tera_dir = "src/export/ssg/templates/**/*.html";
-        let mut tera = match tera::Tera::new(tera_dir) {
-            Ok(t) => t,
-            Err(e) => {
-                tracing::error!("template error: {}", e);
-                ::std::process::exit(1);
-            }
-        };
+        let mut tera = build_merged_tera("").unwrap_or_else(|e| {
+            tracing::error!("template error: {}", e);
+            ::std::process::exit(1);
+        });
         tera.autoescape_on(vec![]);
 
         Self {
             tera,
-            table_counter: 0,
-            figure_counter: 0,
             toc: TableOfContents::default(),
             pageid_to_url: HashMap::default(),
             roamid_to_url: HashMap::default(),
+            backlinks: Backlinks::default(),
+            graph: GraphFeed::default(),
+            local_graph_depth: 0,
+        }
+    }
+}
+
+/// Mutable state during rendering a single page, so that pages can be rendered in parallel
+pub struct PageContext {
+    pub table_counter: usize,
+    pub figure_counter: usize,
+    pub prev_head_level: Vec<u8>,
+    pub footnote_definitions: Vec<FootnoteDefinition>,
+    /// last line number of numbered src blocks, continued by `+n`
+    pub src_line_number: usize,
+    /// numbers and labels of equations rendered as MathML
+    pub equations: Equations,
+}
+
+impl Default for PageContext {
+    fn default() -> Self {
+        Self {
+            table_counter: 0,
+            figure_counter: 0,
             prev_head_level: vec![0],
+            footnote_definitions: vec![],
+            src_line_number: 0,
+            equations: Equations::default(),
         }
     }
 }
@@ -96,8 +132,20 @@ impl Default for RendererContext {
 // context: prev / next
 pub struct Renderer {
     config: RendererConfig,
-    footnote_defintions: Vec<FootnoteDefinition>,
     context: RendererContext,
+    // hash of templates used, for build cache
+    templates_fingerprint: String,
+}
+
+/// How LaTeX math is rendered
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MathRenderer {
+    /// kept as LaTeX, typeset by MathJax in browser
+    #[default]
+    Mathjax,
+    /// converted to MathML at build time, see [`math`]
+    Mathml,
 }
 
 #[derive(Debug, Deserialize, Clone)]
@@ -110,6 +158,18 @@ pub struct RendererConfig {
     /// debug mode: true will generate syntax_tree/ast data in json format
     pub debug: bool,
     pub bgcolor_for_white: Vec<String>,
+
+    /// url of live reload endpoint (server-sent events), injected into page by `kite serve`
+    pub live_reload: Option<String>,
+
+    /// theme of src block highlighting, whose stylesheet is written as `highlight.css`
+    pub highlight_theme: HighlightTheme,
+
+    /// `mathml`: convert LaTeX math to MathML at build time instead of loading MathJax
+    pub math: MathRenderer,
+
+    /// href template of link types, e.g., `jira = "https://jira.example.com/browse/%s"`
+    pub link_templates: BTreeMap<String, String>,
 }
 
 impl Default for RendererConfig {
@@ -130,6 +190,10 @@ impl Default for RendererConfig {
             .into_iter()
             .map(String::from)
             .collect(),
+            live_reload: None,
+            highlight_theme: HighlightTheme::default(),
+            math: MathRenderer::default(),
+            link_templates: BTreeMap::new(),
         }
     }
 }
@@ -157,43 +221,78 @@ fn load_templates_from_dir(dir: &str) -> std::io::Result<HashMap<String, String>
     Ok(templates)
 }
 
-fn build_merged_tera(default_dir: &str, user_dir: &str) -> std::io::Result<tera::Tera> {
-    let mut tera = tera::Tera::default();
+/// builtin templates, embedded into binary so that it works outside of the source tree
+const DEFAULT_TEMPLATES: [(&str, &str); 7] = [
+    (
+        "agenda.tera.html",
+        include_str!("templates/agenda.tera.html"),
+    ),
+    ("base.tera.html", include_str!("templates/base.tera.html")),
+    ("clock.tera.html", include_str!("templates/clock.tera.html")),
+    ("graph.tera.html", include_str!("templates/graph.tera.html")),
+    ("page.tera.html", include_str!("templates/page.tera.html")),
+    ("table.tera.html", include_str!("templates/table.tera.html")),
+    ("tag.tera.html", include_str!("templates/tag.tera.html")),
+];
+
+/// builtin templates, (name, content)
+pub fn default_templates() -> &'static [(&'static str, &'static str)] {
+    &DEFAULT_TEMPLATES
+}
+
+/// builtin templates overridden by templates in `user_dir`, (name -> content)
+fn load_merged_templates(user_dir: &str) -> std::io::Result<HashMap<String, String>> {
     let mut all_templates = HashMap::new();
 
-    let default_tmpls = load_templates_from_dir(default_dir)?;
-    all_templates.extend(default_tmpls);
+    all_templates.extend(
+        DEFAULT_TEMPLATES
+            .iter()
+            .map(|(name, content)| (name.to_string(), content.to_string())),
+    );
 
     let user_tmpls = load_templates_from_dir(user_dir)?;
     all_templates.extend(user_tmpls);
 
-    let name = "macros.tera.html";
-    if let Some(content) = all_templates.remove(name) {
-        tera.add_raw_template(name, &content)
-            .expect("add raw template");
-    }
+    Ok(all_templates)
+}
 
-    for (name, content) in all_templates {
-        tera.add_raw_template(&name, &content)
-            .expect("add raw template");
-    }
+fn build_merged_tera(user_dir: &str) -> std::io::Result<tera::Tera> {
+    let mut tera = tera::Tera::default();
+    // added at once, so that templates can extend `base.tera.html` or import macros in any order
+    tera.add_raw_templates(load_merged_templates(user_dir)?)
+        .map_err(std::io::Error::other)?;
 
     Ok(tera)
 }
 
+/// blake3 hash of merged templates
+fn templates_fingerprint(user_dir: &str) -> std::io::Result<String> {
+    let mut templates = load_merged_templates(user_dir)?
+        .into_iter()
+        .collect::<Vec<_>>();
+    templates.sort();
+
+    let mut hasher = blake3::Hasher::new();
+    for (name, content) in templates {
+        hasher.update(name.as_bytes());
+        hasher.update(content.as_bytes());
+    }
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
 impl Default for Renderer {
     fn default() -> Self {
         let config = RendererConfig::default();
         let input_directory = std::path::Path::new(&config.input_directory);
 
-        let default_dir = "src/export/ssg/templates";
         let binding = input_directory
             .parent()
             .expect("parent should exists")
             .join("templates");
         let user_dir = binding.to_str().expect("todo");
-        let mut tera = build_merged_tera(default_dir, user_dir).expect("");
+        let mut tera = build_merged_tera(user_dir).expect("");
         tera.autoescape_on(vec![]);
+        let templates_fingerprint = templates_fingerprint(user_dir).expect("");
 
         Self {
             config,
@@ -201,38 +300,41 @@ impl Default for Renderer {
                 tera,
                 ..RendererContext::default()
             },
-            footnote_defintions: vec![],
+            templates_fingerprint,
         }
     }
 }
 
 impl Renderer {
-    fn get_toc_of_page(&mut self, page: &Page) -> TableOfContents {
-        self.context.prev_head_level = vec![0];
+    fn get_toc_of_page(&self, page_ctx: &mut PageContext, page: &Page) -> TableOfContents {
+        page_ctx.prev_head_level = vec![0];
         let mut children = vec![];
         for heading_subtree in page.ast.heading_subtrees.iter() {
             if !heading_subtree.is_commented {
-                children.push(self.get_toc_of_heading_subtree(heading_subtree));
+                children.push(self.get_toc_of_heading_subtree(page_ctx, heading_subtree));
             }
         }
         TableOfContents::new(children)
     }
 
-    fn get_toc_of_heading_subtree(&mut self, heading: &HeadingSubtree) -> TocNode {
-        if heading.level > self.context.prev_head_level.len() as u8 {
-            self.context.prev_head_level.push(1);
-        } else if heading.level == self.context.prev_head_level.len() as u8 {
-            let tmp = self.context.prev_head_level.pop().unwrap();
-            self.context.prev_head_level.push(tmp + 1);
+    fn get_toc_of_heading_subtree(
+        &self,
+        page_ctx: &mut PageContext,
+        heading: &HeadingSubtree,
+    ) -> TocNode {
+        if heading.level > page_ctx.prev_head_level.len() as u8 {
+            page_ctx.prev_head_level.push(1);
+        } else if heading.level == page_ctx.prev_head_level.len() as u8 {
+            let tmp = page_ctx.prev_head_level.pop().unwrap();
+            page_ctx.prev_head_level.push(tmp + 1);
         } else {
-            while heading.level < self.context.prev_head_level.len() as u8 {
-                let _ = self.context.prev_head_level.pop().unwrap();
+            while heading.level < page_ctx.prev_head_level.len() as u8 {
+                let _ = page_ctx.prev_head_level.pop().unwrap();
             }
-            let tmp = self.context.prev_head_level.pop().unwrap();
-            self.context.prev_head_level.push(tmp + 1);
+            let tmp = page_ctx.prev_head_level.pop().unwrap();
+            page_ctx.prev_head_level.push(tmp + 1);
         }
-        let index = self
-            .context
+        let index = page_ctx
             .prev_head_level
             .iter()
             .map(|n| n.to_string())
@@ -246,13 +348,13 @@ impl Renderer {
             .collect::<String>();
         let title = format!("{} {}", index, title);
 
-        let path = format!("#{}", heading.id());
+        let path = format!("#{}", heading.anchor);
         let level = heading.level;
 
         let mut children = vec![];
         for sub_heading in heading.sub_heading_subtrees.iter() {
             if !sub_heading.is_commented {
-                children.push(self.get_toc_of_heading_subtree(sub_heading));
+                children.push(self.get_toc_of_heading_subtree(page_ctx, sub_heading));
             }
         }
 
@@ -273,14 +375,14 @@ impl Renderer {
 
     pub fn new(config: RendererConfig) -> Self {
         let input_directory = std::path::Path::new(&config.input_directory);
-        let default_dir = "src/export/ssg/templates";
         let binding = input_directory
             .parent()
             .expect("parent should exists")
             .join("templates");
         let user_dir = binding.to_str().expect("todo");
-        let mut tera = build_merged_tera(default_dir, user_dir).expect("");
+        let mut tera = build_merged_tera(user_dir).expect("");
         tera.autoescape_on(vec![]);
+        let templates_fingerprint = templates_fingerprint(user_dir).expect("");
 
         Self {
             config,
@@ -288,62 +390,244 @@ impl Renderer {
                 tera,
                 ..RendererContext::default()
             },
-            footnote_defintions: vec![],
+            templates_fingerprint,
         }
     }
 
-    pub fn render_site(&mut self, site: &Site) {
+    /// Fingerprint of config and templates, rendered pages in cache are invalid if it changed
+    pub fn fingerprint(&self) -> String {
+        format!(
+            "{:?};templates={};year={}",
+            self.config,
+            self.templates_fingerprint,
+            Local::now().year()
+        )
+    }
+
+    pub fn render_site(&mut self, site: &Site) -> Vec<Diagnostic> {
+        self.render_site_partial(site, None, None)
+    }
+
+    /// Render pages in `page_ids` and tag pages in `tags` of site, `None` means all.
+    ///
+    /// Pages which panicked are skipped, and returned as diagnostics.
+    pub fn render_site_partial(
+        &mut self,
+        site: &Site,
+        page_ids: Option<&HashSet<PageId>>,
+        tags: Option<&HashSet<String>>,
+    ) -> Vec<Diagnostic> {
         tracing::debug!("  render site todo");
+        // pages are still rendered, which fall back to unstyled src blocks
+        let f_css = self.config.output_directory.join("highlight.css");
+        if let Err(e) = fs::write(&f_css, self.config.highlight_theme.stylesheet()) {
+            tracing::error!("failed to write {}: {}", f_css.display(), e);
+        }
         self.context.toc = site.toc();
         self.context.pageid_to_url = site.pageid_to_url.clone();
         self.context.roamid_to_url = site.knowledge_graph.id_to_url.clone();
+        self.context.backlinks = site.backlinks.clone();
+        self.context.graph = site.graph.clone();
+        self.context.local_graph_depth = site.config.graph.local_depth;
+
+        // pages are independent, render them in parallel
+        let this = &*self;
+        let diagnostics = site
+            .pages
+            .par_iter()
+            .filter(|(id, _)| page_ids.is_none_or(|ids| ids.contains(*id)))
+            .filter_map(|(_, page)| {
+                // a panic in one page should not abort the whole site
+                match panic::catch_unwind(AssertUnwindSafe(|| this.render_page(page))) {
+                    Ok(Ok(_)) => None,
+                    Ok(Err(e)) => {
+                        tracing::error!("failed to render page {}: {}", page.html_path, e);
+                        None
+                    }
+                    Err(_) => Some(Diagnostic::error(
+                        this.config.output_directory.join(&page.html_path),
+                        "internal error: renderer panicked",
+                    )),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // render tags
+        site.tag_index
+            .par_iter()
+            .filter(|(tag, _)| tags.is_none_or(|tags| tags.contains(*tag)))
+            .for_each(|(tag, page_ids)| {
+                if let Err(e) = this.render_tag(site, tag, page_ids) {
+                    tracing::error!("failed to render tag {}: {}", tag, e);
+                }
+            });
+
+        // clocked time and agenda may come from any page, and agenda changes with date
+        if let Err(e) = self.render_clock_report(site) {
+            tracing::error!("failed to render clock report: {}", e);
+        }
+        if let Err(e) = self.render_agenda(site) {
+            tracing::error!("failed to render agenda: {}", e);
+        }
+        if let Err(e) = self.render_graph(site) {
+            tracing::error!("failed to render graph: {}", e);
+        }
+        diagnostics
+    }
 
-        for (_id, page) in site.pages.iter() {
-            self.render_page(page).expect("render_page should success");
+    /// Render knowledge graph of site to `graph.html` and `graph.json`, which are removed if
+    /// disabled or no roam node is found
+    fn render_graph(&self, site: &Site) -> std::io::Result<()> {
+        let f_html = self.config.output_directory.join("graph.html");
+        let f_json = self.config.output_directory.join("graph.json");
+        let graph = &site.graph;
+        if !site.config.graph.enabled || graph.nodes.is_empty() {
+            let _ = fs::remove_file(&f_html);
+            let _ = fs::remove_file(&f_json);
+            return Ok(());
         }
 
-        // render tags
-        for (tag, page_ids) in site.tag_index.iter() {
-            let pages = page_ids
-                .iter()
-                .map(|e| site.pages.get(e).expect("get page"))
-                .collect::<Vec<_>>();
-            let mut ctx = tera::Context::new();
-            ctx.insert("tag", &tag);
+        let json = serde_json::to_string_pretty(graph)?;
+        fs::write(&f_json, json)?;
 
-            let url_titles = pages
-                .iter()
-                .map(|p| (p.url.clone(), p.title.clone()))
-                .collect::<Vec<(String, String)>>();
-            ctx.insert("url_titles", &url_titles);
-            ctx.insert("is_home", &false);
-            let now = Local::now();
-            let current_year = now.year();
-            ctx.insert("current_year", &current_year);
-
-            ctx.insert("toc", &self.context.toc.to_html_nav(None));
-
-            let html = self
-                .context
-                .tera
-                .render("tag.tera.html", &ctx)
-                .unwrap_or_else(|err| format!("Template rendering page failed: {}", err));
-
-            let f_html = self
-                .config
-                .output_directory
-                .join(format!("tags/{tag}.html").as_str());
-            let d_html = f_html.parent().expect("should have parent directory");
-            if !d_html.is_dir() {
-                fs::create_dir_all(d_html).expect("create dir");
-            }
-            fs::write(&f_html, &html).expect("write file");
+        let mut ctx = tera::Context::new();
+        ctx.insert("graph", &graph.to_script_json());
+        ctx.insert("is_home", &false);
+        ctx.insert("current_year", &Local::now().year());
+        ctx.insert("toc", &self.context.toc.to_html_nav(None));
+        ctx.insert("live_reload", &self.config.live_reload);
+
+        let html = self
+            .context
+            .tera
+            .render("graph.tera.html", &ctx)
+            .unwrap_or_else(|err| format!("Template rendering page failed: {}", err));
+        fs::write(&f_html, &html)
+    }
+
+    /// Render agenda of site to `agenda.html` and `agenda.json`, which are removed if no TODO or
+    /// planning is found
+    fn render_agenda(&self, site: &Site) -> std::io::Result<()> {
+        let f_html = self.config.output_directory.join("agenda.html");
+        let f_json = self.config.output_directory.join("agenda.json");
+        let agenda = &site.agenda;
+        if agenda.headings.is_empty() {
+            let _ = fs::remove_file(&f_html);
+            let _ = fs::remove_file(&f_json);
+            return Ok(());
+        }
+
+        let feed = agenda.feed(site.config.agenda.span_days);
+        let json = serde_json::to_string_pretty(&feed)?;
+        fs::write(&f_json, json)?;
+
+        let mut ctx = tera::Context::new();
+        ctx.insert("agenda", &feed);
+        ctx.insert("is_home", &false);
+        ctx.insert("current_year", &Local::now().year());
+        ctx.insert("toc", &self.context.toc.to_html_nav(None));
+        ctx.insert("live_reload", &self.config.live_reload);
+
+        let html = self
+            .context
+            .tera
+            .render("agenda.tera.html", &ctx)
+            .unwrap_or_else(|err| format!("Template rendering page failed: {}", err));
+        fs::write(&f_html, &html)
+    }
+
+    /// Render clocked time of site to `clock.html`, which is removed if nothing is clocked
+    fn render_clock_report(&self, site: &Site) -> std::io::Result<()> {
+        let f_html = self.config.output_directory.join("clock.html");
+        let report = &site.clock_report;
+        if report.entries.is_empty() {
+            let _ = fs::remove_file(&f_html);
+            return Ok(());
+        }
+
+        let mut ctx = tera::Context::new();
+        let headings = report
+            .by_heading()
+            .into_iter()
+            .map(|e| {
+                (
+                    e.target.url,
+                    e.target.title,
+                    e.level,
+                    format_minutes(e.minutes),
+                )
+            })
+            .collect::<Vec<_>>();
+        ctx.insert("headings", &headings);
+        let tags = report
+            .by_tag()
+            .into_iter()
+            .map(|(tag, minutes)| (tag, format_minutes(minutes)))
+            .collect::<Vec<_>>();
+        ctx.insert("tags", &tags);
+        let dates = report
+            .by_date()
+            .into_iter()
+            .map(|(date, minutes)| (date.to_string(), format_minutes(minutes)))
+            .collect::<Vec<_>>();
+        ctx.insert("dates", &dates);
+        ctx.insert("total", &format_minutes(report.total()));
+        ctx.insert("is_home", &false);
+        ctx.insert("current_year", &Local::now().year());
+        ctx.insert("toc", &self.context.toc.to_html_nav(None));
+        ctx.insert("live_reload", &self.config.live_reload);
+
+        let html = self
+            .context
+            .tera
+            .render("clock.tera.html", &ctx)
+            .unwrap_or_else(|err| format!("Template rendering page failed: {}", err));
+        fs::write(&f_html, &html)
+    }
+
+    fn render_tag(&self, site: &Site, tag: &str, page_ids: &[PageId]) -> std::io::Result<()> {
+        let pages = page_ids
+            .iter()
+            .map(|e| site.pages.get(e).expect("get page"))
+            .collect::<Vec<_>>();
+        let mut ctx = tera::Context::new();
+        ctx.insert("tag", &tag);
+
+        let url_titles = pages
+            .iter()
+            .map(|p| (p.url.clone(), p.title.clone()))
+            .collect::<Vec<(String, String)>>();
+        ctx.insert("url_titles", &url_titles);
+        ctx.insert("is_home", &false);
+        let now = Local::now();
+        let current_year = now.year();
+        ctx.insert("current_year", &current_year);
+
+        ctx.insert("toc", &self.context.toc.to_html_nav(None));
+        ctx.insert("live_reload", &self.config.live_reload);
+
+        let html = self
+            .context
+            .tera
+            .render("tag.tera.html", &ctx)
+            .unwrap_or_else(|err| format!("Template rendering page failed: {}", err));
+
+        let f_html = self
+            .config
+            .output_directory
+            .join(format!("tags/{tag}.html").as_str());
+        let d_html = f_html.parent().expect("should have parent directory");
+        if !d_html.is_dir() {
+            fs::create_dir_all(d_html)?;
         }
+        fs::write(&f_html, &html)
     }
 
-    pub fn render_page_inner(&mut self, page: &Page) -> String {
-        self.context.figure_counter = 0;
-        self.context.table_counter = 0;
+    pub fn render_page_inner(&self, page: &Page) -> String {
+        let page_ctx = &mut PageContext {
+            equations: Equations::new(self.config.automatic_equaiton_numbering),
+            ..PageContext::default()
+        };
 
         let page_nav_context = PageNavContext::from_page(page, &self.context.pageid_to_url);
         let mut ctx = tera::Context::from_serialize(page_nav_context)
@@ -380,11 +664,36 @@ impl Renderer {
             "automatic_equaiton_numbering",
             &self.config.automatic_equaiton_numbering,
         );
+        ctx.insert("live_reload", &self.config.live_reload);
+        ctx.insert("mathjax", &(self.config.math == MathRenderer::Mathjax));
 
-        let content = self.render_org_file(&page.ast); // 7ms
+        let content = self.render_org_file(page_ctx, &page.ast); // 7ms
+        let content = page_ctx.equations.resolve_refs(&content);
+        let content = citation::resolve_citations(&content, page.ast.bibliography.as_ref());
         ctx.insert("content", &content);
+        ctx.insert(
+            "backlinks",
+            &id.map(|id| self.render_backlinks(id)).unwrap_or_default(),
+        );
+
+        // neighbourhood of roam nodes of page, skipped if nothing is connected
+        let roam_ids = page
+            .ast
+            .roam_nodes
+            .iter()
+            .map(|e| e.id.as_str())
+            .collect::<Vec<_>>();
+        let local_graph = (self.context.local_graph_depth > 0 && !roam_ids.is_empty())
+            .then(|| {
+                self.context
+                    .graph
+                    .neighbourhood(&roam_ids, self.context.local_graph_depth)
+            })
+            .filter(|e| !e.edges.is_empty())
+            .map(|e| e.to_script_json());
+        ctx.insert("local_graph", &local_graph);
 
-        let toc = self.get_toc_of_page(page).to_html_nav(None);
+        let toc = self.get_toc_of_page(page_ctx, page).to_html_nav(None);
         ctx.insert("toc_of_current_page", &toc);
 
         let n_color = self.config.bgcolor_for_white.len();
@@ -410,7 +719,7 @@ impl Renderer {
         html
     }
 
-    fn render_page(&mut self, page: &Page) -> std::io::Result<String> {
+    fn render_page(&self, page: &Page) -> std::io::Result<String> {
         let html = self.render_page_inner(page);
 
         let f_html = self.config.output_directory.join(page.html_path.as_str());
@@ -424,47 +733,50 @@ impl Renderer {
     }
 
     // todo: use tera template
-    fn render_org_file(&mut self, org_file: &OrgFile) -> String {
-        self.context.prev_head_level = vec![0];
+    fn render_org_file(&self, page_ctx: &mut PageContext, org_file: &OrgFile) -> String {
+        page_ctx.prev_head_level = vec![0];
 
-        self.footnote_defintions = org_file.footnote_definitions.clone();
+        page_ctx.footnote_definitions = org_file.footnote_definitions.clone();
 
         let mut output = String::new();
 
         if let Some(section) = &org_file.zeroth_section {
-            output.push_str(&self.render_section(section));
+            output.push_str(&self.render_section(page_ctx, section));
         }
 
         for subtree in &org_file.heading_subtrees {
-            output.push_str(&self.render_heading_subtree(subtree));
+            output.push_str(&self.render_heading_subtree(page_ctx, subtree));
         }
 
         output
     }
 
-    fn render_section(&mut self, section: &Section) -> String {
+    fn render_section(&self, page_ctx: &mut PageContext, section: &Section) -> String {
         section
             .elements
             .iter()
-            .map(|c| self.render_element(c))
+            .map(|c| self.render_element(page_ctx, c))
             .collect::<String>()
     }
 
-    fn render_heading_subtree(&mut self, heading: &HeadingSubtree) -> String {
-        if heading.level > self.context.prev_head_level.len() as u8 {
-            self.context.prev_head_level.push(1);
-        } else if heading.level == self.context.prev_head_level.len() as u8 {
-            let tmp = self.context.prev_head_level.pop().unwrap();
-            self.context.prev_head_level.push(tmp + 1);
+    fn render_heading_subtree(
+        &self,
+        page_ctx: &mut PageContext,
+        heading: &HeadingSubtree,
+    ) -> String {
+        if heading.level > page_ctx.prev_head_level.len() as u8 {
+            page_ctx.prev_head_level.push(1);
+        } else if heading.level == page_ctx.prev_head_level.len() as u8 {
+            let tmp = page_ctx.prev_head_level.pop().unwrap();
+            page_ctx.prev_head_level.push(tmp + 1);
         } else {
-            while heading.level < self.context.prev_head_level.len() as u8 {
-                let _ = self.context.prev_head_level.pop().unwrap();
+            while heading.level < page_ctx.prev_head_level.len() as u8 {
+                let _ = page_ctx.prev_head_level.pop().unwrap();
             }
-            let tmp = self.context.prev_head_level.pop().unwrap();
-            self.context.prev_head_level.push(tmp + 1);
+            let tmp = page_ctx.prev_head_level.pop().unwrap();
+            page_ctx.prev_head_level.push(tmp + 1);
         }
-        let index = self
-            .context
+        let index = page_ctx
             .prev_head_level
             .iter()
             .map(|n| n.to_string())
@@ -482,60 +794,41 @@ impl Renderer {
             return String::from("");
         }
 
-        let id_html = if let Some(id) = heading.properties.get("ID") {
-            format!(r##"id="{}""##, id)
-        } else {
-            format!(r##"id="{}""##, heading.id())
-        };
+        let id_html = format!(
+            r#"id="{}""#,
+            html_escape::encode_double_quoted_attribute(&heading.anchor)
+        );
 
-        let todo_html = if let Some(todo) = &heading.keyword {
-            let class_1 = match todo.as_str().to_uppercase().as_str() {
-                "DONE" => "done",
-                "TODO" => "todo",
-                _ => "todo",
-            };
-            format!(
-                r#"<span class="{} {}">{}</span> "#,
-                class_1,
-                escape_html(todo),
-                escape_html(todo)
-            )
-        } else {
-            String::new()
-        };
-
-        let tags_html = if !heading.tags.is_empty() {
-            let tags: Vec<String> = heading
-                .tags
-                .iter()
-                .map(|tag| format!(r#"<span class="tag">{}</span>"#, escape_html(tag)))
-                .collect();
-            format!(r#"<span class="tags">{}</span>"#, tags.join(" "))
-        } else {
-            String::new()
-        };
+        let todo_html = Self::render_todo_keyword(&heading.keyword);
+        let tags_html = Self::render_tags(&heading.tags);
 
         let section_html = if let Some(section) = &heading.section {
             if title == "Footnotes" {
-                let elements = self
-                    .footnote_defintions
+                let elements = page_ctx
+                    .footnote_definitions
                     .iter()
                     .map(|e| element::Element::FootnoteDefinition(e.clone()))
                     .collect::<Vec<_>>();
                 let section = Section { elements };
-                self.render_section(&section)
+                self.render_section(page_ctx, &section)
             } else {
-                self.render_section(&section)
+                self.render_section(page_ctx, &section)
             }
         } else {
             String::new()
         };
 
+        let backlinks_html = heading
+            .properties
+            .get("ID")
+            .map(|id| self.render_backlinks(id))
+            .unwrap_or_default();
+
         let content = if !heading.sub_heading_subtrees.is_empty() {
             let children_html: Vec<String> = heading
                 .sub_heading_subtrees
                 .iter()
-                .map(|child| self.render_heading_subtree(child))
+                .map(|child| self.render_heading_subtree(page_ctx, child))
                 .collect();
             format!("\n{}", children_html.join(""))
         } else {
@@ -546,6 +839,7 @@ impl Renderer {
             r##"<section class="outline-{level}">
   <h{level} {id_html}> {index} {todo} {title} {tags} </h{level}>
   {section}
+  {backlinks}
   {content}
 </section>
 "##,
@@ -555,51 +849,179 @@ impl Renderer {
             todo = todo_html,
             tags = tags_html,
             section = section_html,
+            backlinks = backlinks_html,
             content = content,
             id_html = id_html,
         )
     }
 
-    fn render_element(&mut self, element: &Element) -> String {
-        match element {
-            Element::Paragraph(paragraph) => self.render_paragraph(paragraph),
-            Element::Table(table) => self.render_table(table),
+    /// Backlinks and unlinked references of roam node `id`, empty if none
+    fn render_backlinks(&self, id: &str) -> String {
+        fn render_list(class: &str, title: &str, backlinks: Option<&Vec<Backlink>>) -> String {
+            let Some(backlinks) = backlinks.filter(|e| !e.is_empty()) else {
+                return String::new();
+            };
+            let items = backlinks
+                .iter()
+                .map(|backlink| {
+                    let breadcrumbs = backlink
+                        .breadcrumbs
+                        .iter()
+                        .map(|e| html_escape::encode_text(e).to_string())
+                        .collect::<Vec<_>>()
+                        .join(" › ");
+                    let context = backlink.context.as_ref().map_or(String::new(), |e| {
+                        format!(
+                            "\n    <blockquote>{}</blockquote>",
+                            html_escape::encode_text(e)
+                        )
+                    });
+                    format!(
+                        r#"  <li>
+    <a href="{}">{}</a> <span class="breadcrumbs">{}</span>{}
+  </li>"#,
+                        html_escape::encode_double_quoted_attribute(&backlink.url),
+                        html_escape::encode_text(&backlink.title),
+                        breadcrumbs,
+                        context
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join("\n");
+            format!(
+                r#"<div class="{class}">
+<p class="backlinks-title">{title} ({})</p>
+<ul>
+{items}
+</ul>
+</div>
+"#,
+                backlinks.len()
+            )
+        }
+
+        let backlinks = &self.context.backlinks;
+        format!(
+            "{}{}",
+            render_list("backlinks", "Backlinks", backlinks.linked.get(id)),
+            render_list(
+                "unlinked-references",
+                "Unlinked References",
+                backlinks.unlinked.get(id)
+            )
+        )
+    }
+
+    fn render_todo_keyword(keyword: &Option<String>) -> String {
+        if let Some(todo) = keyword {
+            let class_1 = match todo.as_str().to_uppercase().as_str() {
+                "DONE" => "done",
+                "TODO" => "todo",
+                _ => "todo",
+            };
+            format!(
+                r#"<span class="{} {}">{}</span> "#,
+                class_1,
+                escape_html(todo),
+                escape_html(todo)
+            )
+        } else {
+            String::new()
+        }
+    }
+
+    fn render_tags(tags: &[String]) -> String {
+        if !tags.is_empty() {
+            let tags: Vec<String> = tags
+                .iter()
+                .map(|tag| format!(r#"<span class="tag">{}</span>"#, escape_html(tag)))
+                .collect();
+            format!(r#"<span class="tags">{}</span>"#, tags.join(" "))
+        } else {
+            String::new()
+        }
+    }
+
+    // callout box, instead of heading which would break the outline
+    fn render_inline_task(&self, page_ctx: &mut PageContext, task: &InlineTask) -> String {
+        let priority_html = task.priority.as_ref().map_or(String::new(), |e| {
+            format!(r#"<span class="priority">[#{}]</span> "#, escape_html(e))
+        });
+        let title = task
+            .title
+            .iter()
+            .map(|e| self.render_object(e))
+            .collect::<String>();
+        let contents = task.contents.as_ref().map_or(String::new(), |contents| {
+            contents
+                .iter()
+                .map(|e| self.render_element(page_ctx, e))
+                .collect::<String>()
+        });
+
+        format!(
+            r##"<div class="inlinetask">
+<p class="inlinetask-title">{todo}{priority}{title} {tags}</p>
+{contents}</div>
+"##,
+            todo = Self::render_todo_keyword(&task.keyword),
+            priority = priority_html,
+            tags = Self::render_tags(&task.tags),
+        )
+    }
+
+    fn render_element(&self, page_ctx: &mut PageContext, element: &Element) -> String {
+        // target of internal links to `#+NAME:`
+        let anchor = element.name().map_or(String::new(), |name| {
+            format!(r#"<a id="{}"></a>"#, target_anchor(&name))
+        });
+        let html = match element {
+            Element::Paragraph(paragraph) => self.render_paragraph(page_ctx, paragraph),
+            Element::Table(table) => self.render_table(page_ctx, table),
             Element::Drawer(drawer) => self.render_drawer(drawer),
-            Element::CenterBlock(center_block) => self.render_center_block(center_block),
-            Element::QuoteBlock(quote_block) => self.render_quote_block(quote_block),
-            Element::SpecialBlock(special_block) => self.render_special_block(special_block),
+            Element::CenterBlock(center_block) => self.render_center_block(page_ctx, center_block),
+            Element::QuoteBlock(quote_block) => self.render_quote_block(page_ctx, quote_block),
+            Element::SpecialBlock(special_block) => {
+                self.render_special_block(page_ctx, special_block)
+            }
+            Element::DynamicBlock(dynamic_block) => {
+                self.render_dynamic_block(page_ctx, dynamic_block)
+            }
             Element::ExampleBlock(example_block) => self.render_example_block(example_block),
             Element::ExportBlock(export_block) => self.render_export_block(export_block),
             Element::CommentBlock(_comment_block) => Self::render_comment_block(),
-            Element::SrcBlock(src_block) => self.render_src_block(src_block),
+            Element::SrcBlock(src_block) => self.render_src_block(page_ctx, src_block),
+            Element::BabelCall(babel_call) => self.render_babel_call(page_ctx, babel_call),
             Element::VerseBlock(verse_block) => self.render_verse_block(verse_block),
 
-            Element::List(list) => self.render_list(list),
+            Element::List(list) => self.render_list(page_ctx, list),
             Element::Comment(_) => Self::render_comment(),
             Element::FixedWidth(fixed_width) => Self::render_fixed_width(fixed_width),
 
-            Element::Item(item) => self.render_item(item),
+            Element::Item(item) => self.render_item(page_ctx, item),
             Element::FootnoteDefinition(footnote_definition) => {
                 // String::from("")
-                self.render_footnote_definition(footnote_definition)
+                self.render_footnote_definition(page_ctx, footnote_definition)
             }
+            Element::InlineTask(task) => self.render_inline_task(page_ctx, task),
             Element::HorizontalRule(_) => Self::render_horizontal_rule(),
             Element::Keyword(keyword) => self.render_keyword(keyword),
-            Element::LatexEnvironment(env) => Self::render_latex_environment(env),
+            Element::LatexEnvironment(env) => self.render_latex_environment(page_ctx, env),
 
             _ => String::from(""),
             // AstElement::List(list) => self.render_list(list),
 
             // AstElement::HorizontalRule => "<hr/>\n".to_string(),
             // ... 其他元素渲染
-        }
+        };
+        format!("{anchor}{html}")
     }
 
-    pub fn get_table_vm(&mut self, table: &Table) -> TableViewModel {
+    pub fn get_table_vm(&self, page_ctx: &mut PageContext, table: &Table) -> TableViewModel {
         let has_caption = !table.caption.is_empty();
         let table_number = if has_caption {
-            self.context.table_counter += 1;
-            Some(self.context.table_counter)
+            page_ctx.table_counter += 1;
+            Some(page_ctx.table_counter)
         } else {
             None
         };
@@ -634,8 +1056,8 @@ impl Renderer {
         }
     }
 
-    fn render_table(&mut self, table: &Table) -> String {
-        let table_view_model = self.get_table_vm(table);
+    fn render_table(&self, page_ctx: &mut PageContext, table: &Table) -> String {
+        let table_view_model = self.get_table_vm(page_ctx, table);
         let ctx = tera::Context::from_serialize(&table_view_model)
             .expect("render_table: from serialize failed");
         self.context
@@ -705,6 +1127,7 @@ impl Renderer {
                 description,
                 path,
                 is_image,
+                href,
             }) => {
                 let desc = if description.len() == 0 {
                     path
@@ -715,8 +1138,22 @@ impl Renderer {
                         .collect::<String>()
                 };
 
-                if protocol == "fuzzy" {
+                if let Some(href) = href {
+                    format!(
+                        r##"<a href="{}">{}</a>"##,
+                        html_escape::encode_double_quoted_attribute(href),
+                        desc
+                    )
+                } else if protocol == "fuzzy" {
                     format!(r##"<a href="#{}">{}</a>"##, path, desc)
+                } else if protocol == "coderef" {
+                    let label = path.trim_start_matches('(').trim_end_matches(')');
+                    let desc = if description.is_empty() { label } else { desc };
+                    format!(
+                        r##"<a href="#{}" class="coderef">{}</a>"##,
+                        highlight::coderef_id(label),
+                        desc
+                    )
                 } else if description.len() == 0 && *is_image {
                     let path_html = if path.starts_with("file:") {
                         path.strip_prefix("file:").unwrap()
@@ -746,6 +1183,14 @@ impl Renderer {
                         format!("#{}", id)
                     };
                     format!(r##"<a href="{}">{}</a>"##, href, desc)
+                } else if let Some(template) = self.config.link_templates.get(protocol) {
+                    let tag = path.split_once(':').map_or(path.as_str(), |(_, tag)| tag);
+                    let href = expand_link_template(template, tag);
+                    format!(
+                        r##"<a href="{}">{}</a>"##,
+                        html_escape::encode_double_quoted_attribute(&href),
+                        desc
+                    )
                 } else {
                     format!(r##"<a href="{}">{}</a>"##, path, desc)
                 }
@@ -765,13 +1210,25 @@ impl Renderer {
             }
 
             Object::Target(text) => {
-                format!(r##"<a id="{text}"></a>"##)
+                format!(r#"<a id="{}"></a>"#, target_anchor(text))
             }
 
-            Object::Timestamp(text) => {
+            Object::Timestamp(timestamp) => {
+                let (start, end) = timestamp.html_datetime();
+                let contents = match timestamp.raw_parts() {
+                    (start_text, Some(end_text)) => format!(
+                        r##"<time datetime="{start}">{}</time>&ndash;<time datetime="{}">{}</time>"##,
+                        html_escape::encode_text(start_text),
+                        end.unwrap_or_default(),
+                        html_escape::encode_text(end_text)
+                    ),
+                    (text, None) => format!(
+                        r##"<time datetime="{start}">{}</time>"##,
+                        html_escape::encode_text(text)
+                    ),
+                };
                 format!(
-                    r##"<span class="timestamp-wrapper"><span class="timestamp">{}</span></span>"##,
-                    text.replace("--", "-")
+                    r##"<span class="timestamp-wrapper"><span class="timestamp">{contents}</span></span>"##
                 )
             }
 
@@ -821,6 +1278,13 @@ impl Renderer {
                 format!(r##"<sup>{}</sup>"##, inner)
             }
 
+            Object::LatexFragment {
+                content,
+                display_mode,
+            } if self.config.math == MathRenderer::Mathml => {
+                Self::render_latex_fragment_mathml(content, *display_mode)
+            }
+
             Object::LatexFragment {
                 content,
                 display_mode,
@@ -839,6 +1303,30 @@ impl Renderer {
                 format!("{}", value)
             }
 
+            Object::Citation {
+                global_prefix,
+                citestyle,
+                references,
+                global_suffix,
+            } => {
+                let render = |objects: &Vec<Object>| {
+                    objects
+                        .iter()
+                        .map(|o| self.render_object(o))
+                        .collect::<String>()
+                };
+                let references = references
+                    .iter()
+                    .map(|e| (render(&e.key_prefix), e.key.as_str(), render(&e.key_suffix)))
+                    .collect::<Vec<_>>();
+                citation::citation_placeholder(
+                    citestyle.as_deref(),
+                    &render(global_prefix),
+                    &references,
+                    &render(global_suffix),
+                )
+            }
+
             Object::ExportSnippet { backend, value } => {
                 if backend == "html" {
                     format!("{}", value)
@@ -848,10 +1336,53 @@ impl Renderer {
                 }
             }
 
-            _ => String::from(""), // AstInline::Link { url, text } => {
-                                   //     format!(r#"<a href="{}">{}</a>"#, escape_html(url), escape_html(text))
-                                   // }
-                                   // AstInline::Code(code) => {
+            Object::Link { url, text } => format!(
+                r#"<a href="{}">{}</a>"#,
+                html_escape::encode_double_quoted_attribute(url),
+                html_escape::encode_text(text.as_deref().unwrap_or(url))
+            ),
+
+            // results are in the following `{{{results(..)}}}` macro, expanded in parser
+            Object::InlineSourceBlock {
+                lang,
+                headers,
+                body,
+            } => match headers
+                .as_deref()
+                .and_then(|e| element::header_argument(e, "exports"))
+            {
+                Some("code" | "both") => format!(
+                    r#"<code class="src src-{}">{}</code>"#,
+                    lang,
+                    html_escape::encode_text(body)
+                ),
+                _ => String::new(),
+            },
+
+            Object::InlineBabelCall {
+                name,
+                header1,
+                arguments,
+                header2,
+            } => match [header2, header1]
+                .into_iter()
+                .flatten()
+                .find_map(|e| element::header_argument(e, "exports"))
+            {
+                Some("code" | "both") => {
+                    let header1 = header1.as_ref().map_or(String::new(), |e| format!("[{e}]"));
+                    let header2 = header2.as_ref().map_or(String::new(), |e| format!("[{e}]"));
+                    format!(
+                        r#"<code class="src src-org">{}</code>"#,
+                        html_escape::encode_text(&format!(
+                            "call_{name}{header1}({arguments}){header2}"
+                        ))
+                    )
+                }
+                _ => String::new(),
+            },
+
+            _ => String::from(""), // AstInline::Code(code) => {
                                    //     format!("<code>{}</code>", escape_html(code))
                                    // }
                                    // ... 其他内联元素渲染
@@ -880,11 +1411,25 @@ impl Renderer {
         )
     }
 
-    fn render_src_block(&self, block: &SrcBlock) -> String {
+    /// Code and/or cached results, as `:exports code|results|both|none` says
+    fn render_src_block(&self, page_ctx: &mut PageContext, block: &SrcBlock) -> String {
+        let exports = block.exports.as_deref().unwrap_or("code");
+        let results = match exports {
+            "results" | "both" => self.render_cached_results(page_ctx, &block.cached_results),
+            _ => String::new(),
+        };
+        if !matches!(exports, "code" | "both") {
+            return results;
+        }
+
+        // raw text, escaped by highlighter
         let s = block
             .contents
             .iter()
-            .map(|e| self.render_object(e))
+            .map(|e| match e {
+                Object::Text(text) => text.clone(),
+                _ => self.render_object(e),
+            })
             .collect::<String>();
 
         // strip common start spaces
@@ -903,24 +1448,59 @@ impl Renderer {
             s
         };
 
-        if let Some(v) = &block.exports {
-            match v.as_str() {
-                "code" | "both" => {
-                    format!(
-                        r##"<div class="code org-src-container"><pre class="src src-{}"><code class="language-{} block">{}</code></pre></div>"##,
-                        block.language, block.language, s
-                    )
-                }
-                _ => {
-                    format!("")
-                }
-            }
-        } else {
-            format!(
-                r##"<div class="code org-src-container"><pre class="src src-{}"><code class="language-{} block">{}</code></pre></div>"##,
-                block.language, block.language, s
-            )
+        let switches = SrcSwitches::parse(&block.switches);
+        let first_line_number = match switches.line_numbering {
+            Some(LineNumbering::Restart(n)) => n,
+            Some(LineNumbering::Continue(n)) => page_ctx.src_line_number + 1 + n,
+            None => 1,
+        };
+        if switches.line_numbering.is_some() {
+            page_ctx.src_line_number = first_line_number + s.split('\n').count() - 1;
         }
+        let code = highlight::render_code(&block.language, &s, &switches, first_line_number);
+
+        format!(
+            r##"<div class="code org-src-container"><pre class="src src-{}"><code class="language-{} block">{}</code></pre></div>{}"##,
+            block.language, block.language, code, results
+        )
+    }
+
+    /// The `#+CALL:` line and/or cached results, `:exports results` by default
+    fn render_babel_call(&self, page_ctx: &mut PageContext, call: &BabelCall) -> String {
+        let exports = call.exports().unwrap_or("results");
+        let code = match exports {
+            "code" | "both" => {
+                let line = OrgWriter::new().write_element(
+                    &Element::BabelCall(BabelCall {
+                        affiliated_keywords: vec![],
+                        cached_results: None,
+                        ..call.clone()
+                    }),
+                    "",
+                );
+                format!(
+                    r##"<div class="code org-src-container"><pre class="src src-org"><code class="language-org block">{}</code></pre></div>"##,
+                    html_escape::encode_text(line.trim_end())
+                )
+            }
+            _ => String::new(),
+        };
+        let results = match exports {
+            "results" | "both" => self.render_cached_results(page_ctx, &call.cached_results),
+            _ => String::new(),
+        };
+        format!("{code}{results}")
+    }
+
+    fn render_cached_results(
+        &self,
+        page_ctx: &mut PageContext,
+        cached_results: &Option<CachedResults>,
+    ) -> String {
+        cached_results
+            .as_ref()
+            .and_then(|e| e.element.as_deref())
+            .map_or(String::new(), |e| self.render_element(page_ctx, e))
     }
 
     // FIXME: only supoort html now
@@ -950,11 +1530,47 @@ impl Renderer {
         format!(r##"<hr>"##)
     }
 
-    fn render_latex_environment(latex_environment: &LatexEnvironment) -> String {
-        format!(r##"{}"##, latex_environment.text)
+    fn render_latex_environment(
+        &self,
+        page_ctx: &mut PageContext,
+        latex_environment: &LatexEnvironment,
+    ) -> String {
+        if self.config.math == MathRenderer::Mathjax {
+            return format!(r##"{}"##, latex_environment.text);
+        }
+
+        match math::environment_to_mathml(&latex_environment.text, &mut page_ctx.equations) {
+            Ok(mathml) => mathml,
+            Err(e) => Self::render_math_error(latex_environment.text.trim_end(), &e),
+        }
     }
 
-    fn render_drawer(&mut self, _drawer: &Drawer) -> String {
+    /// `\ref{..}`/`\eqref{..}` are placeholders resolved after the whole page is rendered
+    fn render_latex_fragment_mathml(content: &str, display_mode: Option<bool>) -> String {
+        let result = match display_mode {
+            Some(display) => math::fragment_to_mathml(content, display),
+            None => match math::parse_ref(content) {
+                Some((label, is_eqref)) => return math::ref_placeholder(label, is_eqref),
+                None => math::fragment_to_mathml(content, false),
+            },
+        };
+        match result {
+            Ok(mathml) => mathml,
+            // not math, e.g., `\cite{..}`
+            Err(_) if display_mode.is_none() => html_escape::encode_text(content).to_string(),
+            Err(e) => Self::render_math_error(content, &e),
+        }
+    }
+
+    fn render_math_error(latex: &str, error: &math::MathError) -> String {
+        format!(
+            r##"<code class="math-error" title="{}">{}</code>"##,
+            html_escape::encode_double_quoted_attribute(&error.message),
+            html_escape::encode_text(latex)
+        )
+    }
+
+    fn render_drawer(&self, _drawer: &Drawer) -> String {
         String::new()
         // drawer
         //     .contents
@@ -966,7 +1582,7 @@ impl Renderer {
     // <p class=?>?
     // image
     // table
-    fn render_paragraph(&mut self, paragraph: &Paragraph) -> String {
+    fn render_paragraph(&self, page_ctx: &mut PageContext, paragraph: &Paragraph) -> String {
         let caption = paragraph
             .affiliated_keywords
             .iter()
@@ -987,10 +1603,9 @@ impl Renderer {
                 .iter()
                 .filter(|e| match e {
                     Object::GeneralLink(GeneralLink {
-                        protocol: _,
-                        path: _,
                         description,
                         is_image,
+                        ..
                     }) if description.len() == 0 && *is_image => true,
                     _ => false,
                 })
@@ -1035,7 +1650,7 @@ impl Renderer {
                 .collect::<Vec<String>>()
                 .join(" ");
 
-            self.context.figure_counter = self.context.figure_counter + 1;
+            page_ctx.figure_counter = page_ctx.figure_counter + 1;
 
             let path = match &paragraph.objects[0] {
                 Object::GeneralLink(GeneralLink { path, .. }) => path,
@@ -1053,7 +1668,7 @@ impl Renderer {
                 path_html,
                 path.split("/").last().expect("todo"),
                 attr_html,
-                self.context.figure_counter,
+                page_ctx.figure_counter,
                 caption
             )
 
@@ -1063,7 +1678,7 @@ impl Renderer {
         // <p> <span class="figure-number">Figure {}: </span> {}</p>
         // </div>
         // "##,
-        //                 path_html, attr_html, self.context.figure_counter, caption,
+        //                 path_html, attr_html, page_ctx.figure_counter, caption,
         //             )
         } else {
             format!(r##"<p>{}</p>"##, contents)
@@ -1071,7 +1686,11 @@ impl Renderer {
     }
 
     // fixme: link: collect all footnotes into a div
-    fn render_footnote_definition(&mut self, footnote_definition: &FootnoteDefinition) -> String {
+    fn render_footnote_definition(
+        &self,
+        page_ctx: &mut PageContext,
+        footnote_definition: &FootnoteDefinition,
+    ) -> String {
         let c = if footnote_definition.rids.len() == 1 {
             format!(
                 r##"<sup> <a class="footnum" href="#fnr.{label}.{rid}" role="doc-backlink">^</a> </sup> "##,
@@ -1100,35 +1719,35 @@ impl Renderer {
             def = footnote_definition
                 .contents
                 .iter()
-                .map(|e| self.render_element(e))
+                .map(|e| self.render_element(page_ctx, e))
                 .collect::<String>()
                 .replace("<p>", r##"<p class="footpara">"##)
         )
     }
 
-    fn render_center_block(&mut self, block: &CenterBlock) -> String {
+    fn render_center_block(&self, page_ctx: &mut PageContext, block: &CenterBlock) -> String {
         format!(
             r##"<div class="org-center">{}</div>"##,
             block
                 .contents
                 .iter()
-                .map(|e| self.render_element(e))
+                .map(|e| self.render_element(page_ctx, e))
                 .collect::<String>()
         )
     }
 
-    fn render_quote_block(&mut self, block: &QuoteBlock) -> String {
+    fn render_quote_block(&self, page_ctx: &mut PageContext, block: &QuoteBlock) -> String {
         format!(
             r##"<blockquote>{}</blockquote>"##,
             block
                 .contents
                 .iter()
-                .map(|e| self.render_element(e))
+                .map(|e| self.render_element(page_ctx, e))
                 .collect::<String>()
         )
     }
 
-    fn render_special_block(&mut self, block: &SpecialBlock) -> String {
+    fn render_special_block(&self, page_ctx: &mut PageContext, block: &SpecialBlock) -> String {
         let maybe_note = if block.name == "note" {
             r##"<p class="note admonition-title" >Note</p>"##
         } else {
@@ -1142,7 +1761,19 @@ impl Renderer {
             block
                 .contents
                 .iter()
-                .map(|e| self.render_element(e))
+                .map(|e| self.render_element(page_ctx, e))
+                .collect::<String>()
+        )
+    }
+
+    fn render_dynamic_block(&self, page_ctx: &mut PageContext, block: &DynamicBlock) -> String {
+        format!(
+            r##"<div class="dynamic-block {}">{}</div>"##,
+            escape_html(&block.name),
+            block
+                .contents
+                .iter()
+                .map(|e| self.render_element(page_ctx, e))
                 .collect::<String>()
         )
     }
@@ -1151,18 +1782,19 @@ impl Renderer {
         // title has been rendered in render_org_file() with id
         match keyword.key.to_ascii_uppercase().as_str() {
             "TITLE" => format!(""),
+            "PRINT_BIBLIOGRAPHY" => citation::BIBLIOGRAPHY_PLACEHOLDER.to_string(),
             _ => format!(""),
         }
     }
 
-    fn render_list(&mut self, list: &List) -> String {
+    fn render_list(&self, page_ctx: &mut PageContext, list: &List) -> String {
         match list.list_type {
             ListType::Unordered => {
                 format!(
                     r##"<ul>{}</ul>"##,
                     list.items
                         .iter()
-                        .map(|i| self.render_item(&i))
+                        .map(|i| self.render_item(page_ctx, &i))
                         .collect::<String>()
                 )
             }
@@ -1172,7 +1804,7 @@ impl Renderer {
                     r##"<ol>{}</ol>"##,
                     list.items
                         .iter()
-                        .map(|i| self.render_item(&i))
+                        .map(|i| self.render_item(page_ctx, &i))
                         .collect::<String>()
                 )
             }
@@ -1182,14 +1814,14 @@ impl Renderer {
                     r##"<dl>{}</dl>"##,
                     list.items
                         .iter()
-                        .map(|i| self.render_item(&i))
+                        .map(|i| self.render_item(page_ctx, &i))
                         .collect::<String>()
                 )
             }
         }
     }
 
-    fn render_item(&mut self, item: &Item) -> String {
+    fn render_item(&self, page_ctx: &mut PageContext, item: &Item) -> String {
         let checkbox_html = match &item.checkbox {
             None => String::from(""),
             Some(e) => format!("<code>{e}</code>"),
@@ -1198,7 +1830,7 @@ impl Renderer {
         let contents_html = if item.contents.len() == 1 {
             item.contents
                 .iter()
-                .map(|i| self.render_element(&i))
+                .map(|i| self.render_element(page_ctx, &i))
                 .collect::<String>()
                 .as_str()
                 // .trim_prefix("<p>")
@@ -1209,7 +1841,7 @@ impl Renderer {
         } else {
             item.contents
                 .iter()
-                .map(|i| self.render_element(&i))
+                .map(|i| self.render_element(page_ctx, &i))
                 .collect::<String>()
         };
 
@@ -12,11 +12,20 @@ use rowan::GreenNode;
 use serde::Deserialize;
 use walkdir::WalkDir;
 
+use crate::compiler::agenda::{Agenda, AgendaConfig};
 use crate::compiler::ast_builder::element::{Id, OrgFile};
+use crate::compiler::clock::ClockReport;
 use crate::compiler::content::{Document, Section};
-use crate::compiler::parser::syntax::{OrgSyntaxKind, SyntaxNode};
+use crate::compiler::parser::syntax::OrgSyntaxKind;
+use crate::export::ssg::backlinks::Backlinks;
+use crate::export::ssg::graph::{GraphConfig, GraphFeed};
 use crate::export::ssg::toc::{TableOfContents, TocNode};
 
+/// builtin stylesheet, copied into output directory as `default.css`
+pub const DEFAULT_CSS: &str = include_str!("static/default.css");
+/// builtin script of knowledge graph, copied into output directory as `graph.js`
+pub const GRAPH_JS: &str = include_str!("static/graph.js");
+
 #[derive(Clone)]
 pub struct Page {
     pub id: PageId,
@@ -28,7 +37,8 @@ pub struct Page {
     pub html_path: String,
     pub metadata: PageMetadata,
     pub ast: OrgFile,
-    pub syntax_tree: SyntaxNode,
+    /// green tree, which is `Send + Sync` unlike `SyntaxNode`, so that pages can be rendered in parallel
+    pub syntax_tree: GreenNode,
 
     pub parent_id: Option<PageId>,
     pub children_ids: Vec<PageId>,
@@ -61,8 +71,9 @@ impl Page {
                 properties: BTreeMap::default(),
                 extracted_links: vec![],
                 roam_nodes: vec![],
+                bibliography: None,
             },
-            syntax_tree: SyntaxNode::new_root(GreenNode::new(OrgSyntaxKind::Root.into(), vec![])),
+            syntax_tree: GreenNode::new(OrgSyntaxKind::Root.into(), vec![]),
 
             parent_id: None,
 
@@ -101,6 +112,10 @@ impl fmt::Debug for Page {
 #[serde(default)]
 pub struct SiteConfig {
     pub output_directory: PathBuf,
+    pub agenda: AgendaConfig,
+    /// list plain-text mentions of title or aliases of roam nodes besides backlinks
+    pub unlinked_references: bool,
+    pub graph: GraphConfig,
     // pub base_url: String,
     // pub theme: String,
     // pub generate_search_index: bool,
@@ -109,6 +124,9 @@ impl Default for SiteConfig {
     fn default() -> Self {
         Self {
             output_directory: "public".into(),
+            agenda: AgendaConfig::default(),
+            unlinked_references: false,
+            graph: GraphConfig::default(),
         }
     }
 }
@@ -130,6 +148,14 @@ pub struct Site {
     _static_assets: Vec<(PathBuf, PathBuf)>,
 
     pub knowledge_graph: KnowledgeGraph,
+    /// clocked time of all documents, rendered to `clock.html`
+    pub clock_report: ClockReport,
+    /// scheduled, deadline and TODO headings, rendered to `agenda.html` and `agenda.json`
+    pub agenda: Agenda,
+    /// backlinks of roam nodes, rendered at the end of pages and headings with ID
+    pub backlinks: Backlinks,
+    /// knowledge graph rendered to `graph.html`, `graph.json` and local graph of pages
+    pub graph: GraphFeed,
     // roam_id, roamd_node, page_id
     // pub roam_nodes: Vec<String, >
     // todo: <roam_id> with page_id
@@ -199,6 +225,10 @@ impl Default for Site {
             flattened_pages: vec![],
             _static_assets: vec![],
             knowledge_graph: KnowledgeGraph::default(),
+            clock_report: ClockReport::default(),
+            agenda: Agenda::default(),
+            backlinks: Backlinks::default(),
+            graph: GraphFeed::default(),
         }
     }
 }
@@ -297,7 +327,7 @@ impl SiteBuilder {
         );
 
         let ast = document.ast.clone();
-        let syntax_tree = document.syntax_tree.clone();
+        let syntax_tree = document.syntax_tree.green().into_owned();
         let id = document.ast.id();
 
         let title = document
@@ -416,7 +446,7 @@ impl SiteBuilder {
             tracing::debug!(from=?static_directory_from.display(), to=?static_directory_to.display());
 
             let mut options = CopyOptions::new();
-            options.overwrite = false; // Overwrite existing files
+            options.overwrite = true; // Overwrite existing files
             options.copy_inside = false;
             options.content_only = true;
 
@@ -424,10 +454,8 @@ impl SiteBuilder {
                 .expect(format!("copy failed from {}", static_directory_from.display()).as_str());
             static_assets.push((static_directory_from, static_directory_to.to_path_buf()));
         }
-        std::fs::copy(
-            "src/export/ssg/static/default.css",
-            static_directory_to.join("default.css"),
-        )?;
+        std::fs::write(static_directory_to.join("default.css"), DEFAULT_CSS)?;
+        std::fs::write(static_directory_to.join("graph.js"), GRAPH_JS)?;
 
         // sass
 
@@ -597,6 +625,7 @@ impl SiteBuilder {
                                                 *target_index,
                                                 EdgeType::ExplicitReference {
                                                     source_path: extracted_link.source_path.clone(),
+                                                    context: extracted_link.context.clone(),
                                                 },
                                             );
                                         }
@@ -689,6 +718,10 @@ impl SiteBuilder {
                 }
             }
         }
+        // pages are stored in HashMap, sort by url for deterministic output
+        for page_ids in tag_index.values_mut() {
+            page_ids.sort_by(|a, b| self.pages[a].url.cmp(&self.pages[b].url));
+        }
         tracing::trace!("tag_index: {:?}", tag_index);
 
         tracing::debug!("  process static assets ...");
@@ -699,6 +732,15 @@ impl SiteBuilder {
             pageid_to_url.insert(id.clone(), page.url.clone());
         }
 
+        tracing::debug!("  build backlinks ...");
+        let backlinks = Backlinks::build(
+            &knowledge_graph,
+            &self.pages,
+            self.config.unlinked_references,
+        );
+
+        let graph = GraphFeed::from_knowledge_graph(&knowledge_graph);
+
         let site = Site {
             config: self.config.clone(),
             pages: self.pages.clone(),
@@ -707,6 +749,10 @@ impl SiteBuilder {
             knowledge_graph,
             tag_index,
             _static_assets: static_assets,
+            clock_report: ClockReport::from_section(root_section),
+            agenda: Agenda::from_section(root_section, &self.config.agenda),
+            backlinks,
+            graph,
             ..Site::default()
         };
 
@@ -0,0 +1,212 @@
+// Knowledge graph of site: force-directed layout in SVG, without external dependencies, so that
+// it works offline.
+//
+// Graph data is read from `<script type="application/json">` inside `.knowledge-graph`, see
+// `graph.json` for its format. Nodes can be filtered by tag and node type, and nodes of current
+// page are highlighted.
+(function () {
+    'use strict';
+
+    const SVG_NS = 'http://www.w3.org/2000/svg';
+    const NODE_TYPES = ['File', 'Headline'];
+
+    function element(name, attrs, ns) {
+        const e = ns ? document.createElementNS(ns, name) : document.createElement(name);
+        for (const key in attrs || {}) {
+            e.setAttribute(key, attrs[key]);
+        }
+        return e;
+    }
+
+    // Fruchterman-Reingold, starting from a circle so that layout is the same for every visit
+    function layout(nodes, links, width, height) {
+        const k = Math.sqrt((width * height) / Math.max(nodes.length, 1)) * 0.6;
+        nodes.forEach(function (node, i) {
+            const angle = (2 * Math.PI * i) / nodes.length;
+            node.x = width / 2 + (width / 3) * Math.cos(angle);
+            node.y = height / 2 + (height / 3) * Math.sin(angle);
+        });
+
+        const steps = 300;
+        for (let step = 0; step < steps; step++) {
+            nodes.forEach(function (node) {
+                node.dx = (width / 2 - node.x) * 0.05;
+                node.dy = (height / 2 - node.y) * 0.05;
+            });
+            for (let i = 0; i < nodes.length; i++) {
+                for (let j = i + 1; j < nodes.length; j++) {
+                    const a = nodes[i];
+                    const b = nodes[j];
+                    const x = a.x - b.x;
+                    const y = a.y - b.y;
+                    const d2 = Math.max(x * x + y * y, 0.01);
+                    const f = (k * k) / d2;
+                    a.dx += x * f;
+                    a.dy += y * f;
+                    b.dx -= x * f;
+                    b.dy -= y * f;
+                }
+            }
+            links.forEach(function (link) {
+                const [a, b] = link;
+                const x = a.x - b.x;
+                const y = a.y - b.y;
+                const d = Math.max(Math.sqrt(x * x + y * y), 0.1);
+                const f = d / k;
+                a.dx -= x * f;
+                a.dy -= y * f;
+                b.dx += x * f;
+                b.dy += y * f;
+            });
+
+            const temperature = (width / 10) * (1 - step / steps);
+            nodes.forEach(function (node) {
+                const d = Math.max(Math.sqrt(node.dx * node.dx + node.dy * node.dy), 0.1);
+                const move = Math.min(d, temperature);
+                node.x += (node.dx / d) * move;
+                node.y += (node.dy / d) * move;
+            });
+        }
+
+        // fit into view, leaving space for labels
+        const margin = 40;
+        const xs = nodes.map((node) => node.x);
+        const ys = nodes.map((node) => node.y);
+        const [x0, y0] = [Math.min(...xs), Math.min(...ys)];
+        const scale = Math.min(
+            (width - 2 * margin) / Math.max(Math.max(...xs) - x0, 1),
+            (height - 2 * margin) / Math.max(Math.max(...ys) - y0, 1),
+            1
+        );
+        nodes.forEach(function (node) {
+            node.x = margin + (node.x - x0) * scale;
+            node.y = margin + (node.y - y0) * scale;
+        });
+    }
+
+    function draw(svg, nodes, edges) {
+        while (svg.firstChild) {
+            svg.removeChild(svg.firstChild);
+        }
+        const width = svg.viewBox.baseVal.width;
+        const height = svg.viewBox.baseVal.height;
+        const index = new Map(nodes.map((node) => [node.id, node]));
+        const links = edges
+            .filter((edge) => index.has(edge.source) && index.has(edge.target))
+            .map((edge) => [index.get(edge.source), index.get(edge.target), edge.type]);
+        layout(nodes, links, width, height);
+
+        links.forEach(function (link) {
+            const [a, b, type] = link;
+            svg.appendChild(
+                element('line', { x1: a.x, y1: a.y, x2: b.x, y2: b.y, class: type }, SVG_NS)
+            );
+        });
+        nodes.forEach(function (node) {
+            const current = node.url.split('#')[0] === window.location.pathname;
+            const a = element('a', { href: node.url }, SVG_NS);
+            const circle = element(
+                'circle',
+                {
+                    cx: node.x,
+                    cy: node.y,
+                    r: node.node_type === 'File' ? 7 : 5,
+                    class: node.node_type + (current ? ' current' : ''),
+                },
+                SVG_NS
+            );
+            const title = element('title', {}, SVG_NS);
+            title.textContent = node.title;
+            circle.appendChild(title);
+            const label = element('text', { x: node.x + 9, y: node.y + 4 }, SVG_NS);
+            label.textContent = node.title;
+            a.appendChild(circle);
+            a.appendChild(label);
+            svg.appendChild(a);
+        });
+    }
+
+    // zoom by wheel and pan by dragging, through viewBox
+    function navigate(svg) {
+        let start = null;
+        svg.addEventListener('wheel', function (e) {
+            e.preventDefault();
+            const box = svg.viewBox.baseVal;
+            const scale = e.deltaY > 0 ? 1.1 : 1 / 1.1;
+            const rect = svg.getBoundingClientRect();
+            const x = box.x + ((e.clientX - rect.left) / rect.width) * box.width;
+            const y = box.y + ((e.clientY - rect.top) / rect.height) * box.height;
+            box.x = x - (x - box.x) * scale;
+            box.y = y - (y - box.y) * scale;
+            box.width *= scale;
+            box.height *= scale;
+        });
+        svg.addEventListener('pointerdown', function (e) {
+            if (e.target.closest('a')) {
+                return;
+            }
+            start = { x: e.clientX, y: e.clientY };
+            svg.setPointerCapture(e.pointerId);
+        });
+        svg.addEventListener('pointermove', function (e) {
+            if (!start) {
+                return;
+            }
+            const box = svg.viewBox.baseVal;
+            const rect = svg.getBoundingClientRect();
+            box.x -= ((e.clientX - start.x) / rect.width) * box.width;
+            box.y -= ((e.clientY - start.y) / rect.height) * box.height;
+            start = { x: e.clientX, y: e.clientY };
+        });
+        svg.addEventListener('pointerup', function () {
+            start = null;
+        });
+    }
+
+    function render(container) {
+        const data = JSON.parse(container.querySelector('script[type="application/json"]').text);
+        const width = Number(container.dataset.width || 800);
+        const height = Number(container.dataset.height || 600);
+
+        const filters = element('div', { class: 'graph-filters' });
+        const tagSelect = element('select', { 'aria-label': 'tag' });
+        const tags = [...new Set(data.nodes.flatMap((node) => node.tags))].sort();
+        ['', ...tags].forEach(function (tag) {
+            const option = element('option', { value: tag });
+            option.textContent = tag ? '🏷' + tag : 'all tags';
+            tagSelect.appendChild(option);
+        });
+        filters.appendChild(tagSelect);
+        const typeInputs = NODE_TYPES.map(function (type) {
+            const label = element('label');
+            const input = element('input', { type: 'checkbox', value: type });
+            input.checked = true;
+            label.appendChild(input);
+            label.appendChild(document.createTextNode(' ' + type));
+            filters.appendChild(label);
+            return input;
+        });
+
+        const svg = element('svg', { viewBox: `0 0 ${width} ${height}`, role: 'img' }, SVG_NS);
+        container.appendChild(filters);
+        container.appendChild(svg);
+        navigate(svg);
+
+        function update() {
+            const tag = tagSelect.value;
+            const types = typeInputs.filter((input) => input.checked).map((input) => input.value);
+            const nodes = data.nodes
+                .filter((node) => !tag || node.tags.includes(tag))
+                .filter((node) => types.includes(node.node_type))
+                .map((node) => Object.assign({}, node));
+            draw(svg, nodes, data.edges);
+        }
+        tagSelect.addEventListener('change', update);
+        typeInputs.forEach((input) => input.addEventListener('change', update));
+        update();
+    }
+
+    document.addEventListener('DOMContentLoaded', function () {
+        document.querySelectorAll('.knowledge-graph').forEach(render);
+    });
+})();
@@ -18,7 +18,7 @@ pub struct TocNode {
     pub children: Vec<TocNode>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, serde::Serialize)]
 pub struct TableOfContents {
     root_nodes: Vec<TocNode>, // not flatten
 }
@@ -0,0 +1,526 @@
+//! Tangle src blocks into source files, i.e., literate programming
+//!
+//! Src blocks with header argument `:tangle` are written to files:
+//! - `:tangle yes` writes to the org file with extension of language, e.g., `notes.py`,
+//!   `:tangle PATH` to PATH relative to the org file, and `:tangle no` is skipped
+//! - `:noweb yes|tangle|no-export|strip-export`: `<<NAME>>` is replaced with bodies of blocks
+//!   named NAME or with `:noweb-ref NAME` in the same org file
+//! - `:mkdirp yes`: parent directories of file are created if missing
+//! - `:shebang "#!/bin/sh"`: written as the first line, and file is made executable
+//! - `:comments link|yes|both`: each block is wrapped with comments linking back to org file
+//! - `:padline no`: no blank line is inserted before block
+//!
+//! Blocks in commented subtrees are skipped. Files are sorted by path and blocks of a file are in
+//! document order, so that tangling the same input always gives the same files.
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use walkdir::WalkDir;
+
+use crate::compiler::ast_builder::AstBuilder;
+use crate::compiler::ast_builder::element::{Element, HeadingSubtree, OrgFile, SrcBlock};
+use crate::compiler::ast_builder::object::Object;
+use crate::compiler::babel::{expand_noweb, unindent};
+use crate::compiler::diagnostic::Diagnostic;
+use crate::compiler::dynamic_block::{child_elements, plain_text};
+use crate::compiler::parser::OrgParser;
+use crate::compiler::parser::config::OrgParserConfig;
+use crate::export::org::OrgWriter;
+use crate::utils::normalize;
+
+/// A file to be written by tangling
+#[derive(Debug, Clone, PartialEq)]
+pub struct TangledFile {
+    pub path: PathBuf,
+    /// org file of src blocks
+    pub f_org: PathBuf,
+    pub content: String,
+    /// number of src blocks in file
+    pub n_blocks: usize,
+    /// made executable, i.e., with `:shebang`
+    pub executable: bool,
+    /// parent directories are created, i.e., with `:mkdirp yes`
+    pub mkdirp: bool,
+}
+
+impl TangledFile {
+    /// Write file if its content or mode is changed, return whether it's written.
+    pub fn write(&self) -> std::io::Result<bool> {
+        if let Some(parent) = self.path.parent().filter(|e| !e.as_os_str().is_empty()) {
+            if self.mkdirp {
+                fs::create_dir_all(parent)?;
+            } else if !parent.is_dir() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    format!("{} not found, use `:mkdirp yes`", parent.display()),
+                ));
+            }
+        }
+
+        let unchanged = fs::read_to_string(&self.path).is_ok_and(|e| e == self.content);
+        if unchanged && (!self.executable || is_executable(&self.path)) {
+            return Ok(false);
+        }
+        fs::write(&self.path, &self.content)?;
+        if self.executable {
+            set_executable(&self.path)?;
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(path).is_ok_and(|e| e.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(not(unix))]
+fn is_executable(_path: &Path) -> bool {
+    true
+}
+
+#[cfg(unix)]
+fn set_executable(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_mode(permissions.mode() | 0o111);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(not(unix))]
+fn set_executable(_path: &Path) -> std::io::Result<()> {
+    Ok(())
+}
+
+/// Src block with its position, used in link comments
+struct CollectedBlock<'a> {
+    block: &'a SrcBlock,
+    /// code without common indentation and comma escapes
+    body: String,
+    /// title of heading the block belongs to
+    heading: Option<String>,
+    /// 1-based index of block in its heading
+    index: usize,
+}
+
+pub struct Tangler {
+    parser: OrgParser,
+    ast_builder: AstBuilder,
+}
+
+impl Default for Tangler {
+    fn default() -> Self {
+        Self::new(OrgParserConfig::default())
+    }
+}
+
+impl Tangler {
+    pub fn new(config: OrgParserConfig) -> Self {
+        Self {
+            ast_builder: AstBuilder::new().with_link_abbrevs(config.org_link_abbrev_alist.clone()),
+            parser: OrgParser::new(config),
+        }
+    }
+
+    /// Tangled files of org files under `path`, which is a file or directory.
+    ///
+    /// A file tangled from more than one org file is reported, only the first is kept.
+    pub fn tangle_path<P: AsRef<Path>>(
+        &self,
+        path: P,
+    ) -> std::io::Result<(Vec<TangledFile>, Vec<Diagnostic>)> {
+        let mut files: BTreeMap<PathBuf, TangledFile> = BTreeMap::new();
+        let mut diagnostics = vec![];
+        for entry in WalkDir::new(path).sort_by_file_name() {
+            let entry = entry?;
+            let f_org = entry.path();
+            if !entry.file_type().is_file() || f_org.extension().is_none_or(|e| e != "org") {
+                continue;
+            }
+
+            let input = fs::read_to_string(f_org)?;
+            let (file_files, file_diagnostics) = self.tangle(f_org, &input);
+            diagnostics.extend(file_diagnostics);
+            for file in file_files {
+                match files.get(&file.path) {
+                    Some(first) => diagnostics.push(Diagnostic::warning(
+                        f_org,
+                        format!(
+                            "{} is already tangled from {}, skipped",
+                            file.path.display(),
+                            first.f_org.display()
+                        ),
+                    )),
+                    None => {
+                        files.insert(file.path.clone(), file);
+                    }
+                }
+            }
+        }
+        Ok((files.into_values().collect(), diagnostics))
+    }
+
+    /// Tangled files of `input` read from `f_org`, sorted by path.
+    ///
+    /// Files with unresolved noweb references are reported as diagnostics and not returned.
+    pub fn tangle<P: AsRef<Path>>(
+        &self,
+        f_org: P,
+        input: &str,
+    ) -> (Vec<TangledFile>, Vec<Diagnostic>) {
+        let f_org = f_org.as_ref();
+        let mut diagnostics = vec![];
+        if !input.to_ascii_lowercase().contains(":tangle") {
+            return (vec![], diagnostics);
+        }
+        let Some(syntax_tree) = self.parser.parse_source(f_org, input, &mut diagnostics) else {
+            return (vec![], diagnostics);
+        };
+        let org_file = match self.ast_builder.build(&syntax_tree, f_org) {
+            Ok(org_file) => org_file,
+            Err(e) => {
+                diagnostics.push(Diagnostic::error(f_org, e.to_string()));
+                return (vec![], diagnostics);
+            }
+        };
+
+        let blocks = collect_blocks(&org_file);
+        let bodies_of = |name: &str| {
+            blocks
+                .iter()
+                .filter(|e| {
+                    e.block.name.as_deref() == Some(name)
+                        || header(e.block, "noweb-ref") == Some(name)
+                })
+                .map(|e| e.body.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        let mut files: BTreeMap<PathBuf, TangledFile> = BTreeMap::new();
+        let mut failed: BTreeMap<PathBuf, String> = BTreeMap::new();
+        for collected in blocks.iter() {
+            let block = collected.block;
+            let path = match target(f_org, block) {
+                Ok(Some(path)) => path,
+                Ok(None) => continue,
+                Err(e) => {
+                    diagnostics.push(Diagnostic::warning(f_org, e));
+                    continue;
+                }
+            };
+            if failed.contains_key(&path) {
+                continue;
+            }
+
+            let body = match header(block, "noweb") {
+                Some("yes" | "tangle" | "no-export" | "strip-export") => {
+                    match expand_noweb(&collected.body, &bodies_of) {
+                        Ok(body) => body,
+                        Err(e) => {
+                            files.remove(&path);
+                            failed.insert(path, e);
+                            continue;
+                        }
+                    }
+                }
+                _ => collected.body.clone(),
+            };
+
+            let file = files.entry(path.clone()).or_insert_with(|| TangledFile {
+                path: path.clone(),
+                f_org: f_org.to_path_buf(),
+                content: String::new(),
+                n_blocks: 0,
+                executable: false,
+                mkdirp: false,
+            });
+            if let Some(shebang) = header(block, "shebang").map(unquote)
+                && !file.executable
+            {
+                file.content.insert_str(0, &format!("{shebang}\n"));
+                file.executable = true;
+            }
+            file.mkdirp |= header(block, "mkdirp") == Some("yes");
+            if file.n_blocks > 0 && header(block, "padline") != Some("no") {
+                file.content.push('\n');
+            }
+            match header(block, "comments") {
+                Some("link" | "yes" | "both") => {
+                    let (open, close) = link_comments(f_org, &path, collected);
+                    file.content.push_str(&format!("{open}\n{body}{close}\n"));
+                }
+                _ => file.content.push_str(&body),
+            }
+            file.n_blocks += 1;
+        }
+
+        for (path, e) in failed {
+            diagnostics.push(Diagnostic::warning(
+                f_org,
+                format!("{} is not tangled: {e}", path.display()),
+            ));
+        }
+        (files.into_values().collect(), diagnostics)
+    }
+}
+
+/// Src blocks of `org_file` in document order, except those in commented subtrees
+fn collect_blocks(org_file: &OrgFile) -> Vec<CollectedBlock<'_>> {
+    let mut blocks = vec![];
+    if let Some(section) = &org_file.zeroth_section {
+        collect_section(&section.elements, None, &mut blocks);
+    }
+    for heading in org_file.heading_subtrees.iter() {
+        collect_heading(heading, &mut blocks);
+    }
+    blocks
+}
+
+fn collect_heading<'a>(heading: &'a HeadingSubtree, blocks: &mut Vec<CollectedBlock<'a>>) {
+    if heading.is_commented {
+        return;
+    }
+    if let Some(section) = &heading.section {
+        let title = plain_text(&heading.title).trim().to_string();
+        collect_section(&section.elements, Some(title), blocks);
+    }
+    for subtree in heading.sub_heading_subtrees.iter() {
+        collect_heading(subtree, blocks);
+    }
+}
+
+fn collect_section<'a>(
+    elements: &'a [Element],
+    heading: Option<String>,
+    blocks: &mut Vec<CollectedBlock<'a>>,
+) {
+    let mut src_blocks = vec![];
+    collect_src_blocks(elements, &mut src_blocks);
+    let writer = OrgWriter::new();
+    for (i, block) in src_blocks.into_iter().enumerate() {
+        let text = block
+            .contents
+            .iter()
+            .map(|e| match e {
+                Object::Text(text) => text.clone(),
+                _ => writer.write_object(e, false),
+            })
+            .collect::<String>();
+        blocks.push(CollectedBlock {
+            block,
+            body: unindent(&text),
+            heading: heading.clone(),
+            index: i + 1,
+        });
+    }
+}
+
+fn collect_src_blocks<'a>(elements: &'a [Element], blocks: &mut Vec<&'a SrcBlock>) {
+    for element in elements {
+        match element {
+            Element::SrcBlock(block) => blocks.push(block),
+            Element::List(list) => {
+                for item in list.items.iter() {
+                    collect_src_blocks(&item.contents, blocks);
+                }
+            }
+            _ => {
+                if let Some(children) = child_elements(element) {
+                    collect_src_blocks(children, blocks);
+                }
+            }
+        }
+    }
+}
+
+/// Value of header argument `:key`, `:results` and `:exports` are not needed in tangling
+fn header<'a>(block: &'a SrcBlock, key: &str) -> Option<&'a str> {
+    block.other_args.get(key).map(|e| e.as_str())
+}
+
+fn unquote(s: &str) -> &str {
+    s.strip_prefix('"')
+        .and_then(|e| e.strip_suffix('"'))
+        .unwrap_or(s)
+}
+
+/// Target file of `block`, `None` if not tangled
+fn target(f_org: &Path, block: &SrcBlock) -> Result<Option<PathBuf>, String> {
+    let d_org = f_org.parent().unwrap_or(Path::new(""));
+    let path = match header(block, "tangle").map(unquote) {
+        None | Some("no" | "") => return Ok(None),
+        Some("yes") if block.language.is_empty() => {
+            return Err("src block without language can't be tangled by `:tangle yes`".into());
+        }
+        Some("yes") => f_org.with_extension(extension(&block.language)),
+        Some(path) => d_org.join(path),
+    };
+    Ok(Some(normalize(&path)))
+}
+
+/// File extension of language, the same as `org-babel-tangle-lang-exts`
+fn extension(language: &str) -> &str {
+    match language {
+        "emacs-lisp" | "elisp" => "el",
+        "python" | "python3" => "py",
+        "sh" | "shell" | "bash" | "zsh" => "sh",
+        "rust" => "rs",
+        "ruby" => "rb",
+        "perl" => "pl",
+        "haskell" => "hs",
+        "javascript" | "js" => "js",
+        "typescript" => "ts",
+        "c++" | "cpp" => "cpp",
+        "latex" => "tex",
+        "ocaml" => "ml",
+        "sqlite" | "sql" => "sql",
+        "clojure" => "clj",
+        "scheme" => "scm",
+        _ => language,
+    }
+}
+
+/// Line comment prefix of language
+fn comment_prefix(language: &str) -> &'static str {
+    match language {
+        "emacs-lisp" | "elisp" | "lisp" | "scheme" | "clojure" => ";;",
+        "c" | "c++" | "cpp" | "rust" | "go" | "java" | "javascript" | "js" | "typescript"
+        | "kotlin" | "swift" | "scala" | "css" => "//",
+        "sql" | "sqlite" | "lua" | "haskell" => "--",
+        "latex" | "tex" | "matlab" | "octave" => "%",
+        _ => "#",
+    }
+}
+
+/// Comments before and after block, linking back to the org file as org-babel does, e.g.,
+/// `# [[file:notes.org::*Heading][Heading:1]]` and `# Heading:1 ends here`
+fn link_comments(f_org: &Path, path: &Path, collected: &CollectedBlock) -> (String, String) {
+    let comment = comment_prefix(&collected.block.language);
+    let file = relative_path(path.parent().unwrap_or(Path::new("")), &normalize(f_org));
+    let file = file.to_string_lossy();
+    let (link, label) = match (&collected.block.name, &collected.heading) {
+        (Some(name), _) => (format!("file:{file}::{name}"), name.clone()),
+        (None, Some(heading)) => (
+            format!("file:{file}::*{heading}"),
+            format!("{heading}:{}", collected.index),
+        ),
+        (None, None) => (
+            format!("file:{file}"),
+            format!("No heading:{}", collected.index),
+        ),
+    };
+    (
+        format!("{comment} [[{link}][{label}]]"),
+        format!("{comment} {label} ends here"),
+    )
+}
+
+/// Path of `to` relative to directory `from`, both are relative to the same directory or absolute
+fn relative_path(from: &Path, to: &Path) -> PathBuf {
+    let from = normalize(from);
+    let from = from.components().collect::<Vec<_>>();
+    let to = to.components().collect::<Vec<_>>();
+    let n_common = from
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut path = PathBuf::new();
+    for _ in n_common..from.len() {
+        path.push("..");
+    }
+    for component in to[n_common..].iter() {
+        path.push(component);
+    }
+    path
+}
+
+#[cfg(test)]
+mod tests {
+    use pretty_assertions::assert_eq;
+
+    use super::*;
+
+    #[test]
+    fn test_tangle() {
+        let input = r##"#+begin_src python :tangle yes
+import os
+#+end_src
+
+* Main
+#+begin_src sh :tangle bin/run.sh :shebang "#!/bin/sh" :mkdirp yes :noweb yes :comments link
+set -e
+<<greet>> world
+#+end_src
+
+#+NAME: greet
+#+begin_src sh
+echo hello
+#+end_src
+
+- item
+  #+begin_src sh :tangle bin/run.sh :padline no
+  ,* not a heading
+  #+end_src
+
+* COMMENT Drafts
+#+begin_src python :tangle yes
+skipped()
+#+end_src
+"##;
+        let (files, diagnostics) = Tangler::default().tangle("notes/notes.org", input);
+        assert!(diagnostics.is_empty(), "{diagnostics:?}");
+        assert_eq!(
+            files
+                .iter()
+                .map(|e| (e.path.clone(), e.content.as_str(), e.executable, e.mkdirp))
+                .collect::<Vec<_>>(),
+            vec![
+                (
+                    PathBuf::from("notes/bin/run.sh"),
+                    "#!/bin/sh\n# [[file:../notes.org::*Main][Main:1]]\nset -e\necho hello world\n# Main:1 ends here\n* not a heading\n",
+                    true,
+                    true
+                ),
+                (PathBuf::from("notes/notes.py"), "import os\n", false, false),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tangle_unresolved_noweb() {
+        let input = "#+begin_src sh :tangle a.sh :noweb yes\n<<missing>>\n#+end_src\n\n#+begin_src sh :tangle b.sh :noweb no\n<<missing>>\n#+end_src\n";
+        let (files, diagnostics) = Tangler::default().tangle("test.org", input);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.contains("<<missing>>"));
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].path, PathBuf::from("b.sh"));
+        assert_eq!(files[0].content, "<<missing>>\n");
+    }
+
+    #[test]
+    fn test_write() {
+        let d_out =
+            std::env::temp_dir().join(format!("windancer_tangle_{}/out", std::process::id()));
+        let file = TangledFile {
+            path: d_out.join("run.sh"),
+            f_org: PathBuf::from("test.org"),
+            content: "#!/bin/sh\n".to_string(),
+            n_blocks: 1,
+            executable: true,
+            mkdirp: false,
+        };
+        assert!(file.write().is_err());
+
+        let file = TangledFile {
+            mkdirp: true,
+            ..file
+        };
+        assert!(file.write().expect("write"));
+        assert!(!file.write().expect("write"));
+        assert!(is_executable(&file.path));
+        fs::remove_dir_all(d_out.parent().unwrap()).expect("remove");
+    }
+}
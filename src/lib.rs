@@ -2,3 +2,4 @@ pub mod compiler; // ?
 pub mod config;
 pub mod constants;
 pub mod export;
+mod utils;
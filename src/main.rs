@@ -24,16 +24,7 @@ struct Cli {
 }
 
 fn load_config() -> Result<config::WindancerConfig, ::config::ConfigError> {
-    let builder =
-        ::config::Config::builder().add_source(::config::File::with_name("config").required(false));
-    let config = builder.build()?;
-
-    config
-        .try_deserialize()
-        .map(|mut e: config::WindancerConfig| {
-            e.update(true);
-            e
-        })
+    config::WindancerConfig::from_file("config.toml")
 }
 
 fn main() {
@@ -42,18 +33,22 @@ fn main() {
     if let Some(input_directory) = args.input_directory {
         config.update_input_directory(input_directory);
     }
+    if let Some(output) = args.output {
+        config.update_output_directory(output);
+    }
 
-    let max_level = match config.general.tracing_max_level.as_str() {
-        "error" => tracing::Level::ERROR,
-        "warn" => tracing::Level::WARN,
-        "info" => tracing::Level::INFO,
-        "debug" => tracing::Level::DEBUG,
-        _ => tracing::Level::TRACE,
-    };
+    let max_level = config.tracing_max_level();
     let subscriber = FmtSubscriber::builder().with_max_level(max_level).finish();
     tracing::subscriber::set_global_default(subscriber).expect("set global subscripber failed");
     tracing::info!("config={:#?}", config);
 
     let mut ssg = StaticSiteGenerator::new(config.compiler, config.ssg);
-    let _ = ssg.generate(config.general.input_directory);
+    match ssg.generate(config.general.input_directory) {
+        Ok(diagnostics) => {
+            for diagnostic in diagnostics {
+                eprintln!("{diagnostic}\n");
+            }
+        }
+        Err(e) => tracing::error!("generate failed: {}", e),
+    }
 }
@@ -0,0 +1,60 @@
+//! Helpers shared by compiler and exporters
+use std::path::{Component, Path, PathBuf};
+
+/// Slug used as html id, e.g., `Hello, World!` -> `hello-world`
+///
+/// Alphanumeric characters are kept in lowercase, and others are collapsed into `-`.
+pub(crate) fn anchor_slug(s: &str) -> String {
+    s.split(|c: char| !c.is_alphanumeric())
+        .filter(|e| !e.is_empty())
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// html id of `<<target>>` or element with `#+NAME:`, prefixed so that it differs from anchors of
+/// headings
+pub(crate) fn target_anchor(name: &str) -> String {
+    format!("target-{}", anchor_slug(name))
+}
+
+/// Remove `.` and resolve `..` lexically
+pub(crate) fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pretty_assertions::assert_eq;
+
+    #[test]
+    fn test_anchor_slug() {
+        assert_eq!(anchor_slug("Hello, World!"), "hello-world");
+        assert_eq!(anchor_slug("  TODO a_b  "), "todo-a-b");
+        assert_eq!(anchor_slug("中文 标题"), "中文-标题");
+        assert_eq!(anchor_slug("!?"), "");
+        assert_eq!(target_anchor("My <table>"), "target-my-table");
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(Path::new("a/./b/../c.org")), Path::new("a/c.org"));
+        assert_eq!(normalize(Path::new("../a/..")), Path::new(".."));
+    }
+}